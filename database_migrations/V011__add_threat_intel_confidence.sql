@@ -0,0 +1,4 @@
+-- V011: Threat intelligence indicator confidence
+-- Adds a per-indicator confidence (0-100) reported with IOC matches
+
+ALTER TABLE dev.threat_intel ADD COLUMN IF NOT EXISTS confidence UInt8 DEFAULT 50 AFTER source;
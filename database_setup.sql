@@ -344,6 +344,7 @@ CREATE TABLE IF NOT EXISTS dev.threat_intel (
     ioc_value String,
     ipv4 Nullable(String),
     source String,
+    confidence UInt8 DEFAULT 50,
     first_seen UInt32,
     created_at UInt32
 ) ENGINE = MergeTree()
@@ -81,6 +81,7 @@ pub static STATUS: Lazy<RwLock<PipeStatus>> = Lazy::new(Default::default);
 
 const DEFAULT_BATCH_SIZE: usize = 1000;
 const DEFAULT_BATCH_TIMEOUT_MS: u64 = 5000;
+const THREAT_INTEL_RELOAD_INTERVAL: Duration = Duration::from_secs(3600);
 
 struct Config {
     kafka_brokers: String,
@@ -140,7 +141,7 @@ struct CustomParserListResponse {
 type LogSourceCache = HashMap<String, String>;
 type TaxonomyCache = Vec<TaxonomyMapping>;
 type CustomParserCache = Vec<CustomParserDefinition>;
-type ThreatIntelCache = std::collections::HashSet<String>;
+type ThreatIntelCache = siem_ioc::IocStore;
 
 impl Config {
     fn from_env() -> Result<Self> {
@@ -336,16 +337,48 @@ fn apply_taxonomy_mappings(
 }
 
 fn enrich_with_threat_intel(event: &mut Event, threat_intel_cache: &ThreatIntelCache) {
-    // Check if the source IP matches any known threat indicators
-    if threat_intel_cache.contains(&event.source_ip) {
+    // Check every field that can carry an indicator; the most confident match wins
+    let candidates = [
+        ("source_ip", Some(&event.source_ip)),
+        ("dest_ip", event.dest_ip.as_ref()),
+        ("src_host", event.src_host.as_ref()),
+        ("dest_host", event.dest_host.as_ref()),
+        ("url", event.url.as_ref()),
+        ("file_hash", event.file_hash.as_ref()),
+    ];
+    let matched = threat_intel_cache.read(|index| {
+        candidates
+            .iter()
+            .filter_map(|(field, value)| Some((*field, index.match_value(value.as_ref()?)?)))
+            .min_by_key(|(_, hit)| std::cmp::Reverse(hit.confidence))
+    });
+
+    if let Some((field, hit)) = matched {
         event.is_threat = 1;
         info!(
-            "Threat detected! Event {} flagged as malicious based on source IP: {}",
-            event.event_id, event.source_ip
+            "Threat detected! Event {} flagged as malicious: {} matched {} indicator {} from {} (confidence {})",
+            event.event_id,
+            field,
+            hit.ioc_type.as_str(),
+            hit.indicator,
+            hit.source,
+            hit.confidence
         );
-    } else if event.is_threat == 0 {
-        // Only set to 0 if it wasn't already flagged as a threat by the parser
-        event.is_threat = 0;
+        event
+            .threat_category
+            .get_or_insert_with(|| "threat_intel".to_string());
+        event
+            .custom_fields
+            .insert("threat_indicator".to_string(), hit.indicator);
+        event
+            .custom_fields
+            .insert("threat_indicator_type".to_string(), hit.ioc_type.as_str().to_string());
+        event
+            .custom_fields
+            .insert("threat_source".to_string(), hit.source);
+        event
+            .custom_fields
+            .insert("threat_confidence".to_string(), hit.confidence.to_string());
     }
     // If is_threat is already 1 (from parser), preserve that value
 }
@@ -842,47 +875,6 @@ async fn lookup_source_type(
     }
 }
 
-async fn build_threat_intel_cache(client: &Client) -> Result<ThreatIntelCache> {
-    let mut cache = std::collections::HashSet::new();
-
-    let clickhouse_url = "http://localhost:8123";
-    let query = "SELECT ioc_value FROM dev.threat_intel WHERE ioc_type = 'ipv4' FORMAT JSON";
-    let url = format!("{}/?query={}", clickhouse_url, urlencoding::encode(query));
-
-    info!("Fetching threat intelligence from ClickHouse...");
-
-    match client.get(&url).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                let text = response.text().await?;
-                if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
-                    if let Some(data) = json.get("data").and_then(|d| d.as_array()) {
-                        for row in data {
-                            if let Some(ioc_value) = row.get("ioc_value").and_then(|v| v.as_str()) {
-                                cache.insert(ioc_value.to_string());
-                            }
-                        }
-                    }
-                }
-                info!("Loaded {} threat intelligence IOCs", cache.len());
-            } else {
-                warn!(
-                    "Failed to fetch threat intelligence: HTTP {}",
-                    response.status()
-                );
-            }
-        }
-        Err(e) => {
-            warn!(
-                "Failed to connect to ClickHouse for threat intelligence: {}",
-                e
-            );
-        }
-    }
-
-    Ok(cache)
-}
-
 /// HTTP endpoint to expose metrics as JSON
 async fn get_metrics(
     connection_manager: Arc<ConnectionManager>,
@@ -1060,7 +1052,17 @@ async fn main() -> Result<()> {
     let mut _custom_parser_cache = build_custom_parser_cache(&http_client, &config.api_url).await?;
 
     // Initialize threat intelligence cache
-    let mut threat_intel_cache = build_threat_intel_cache(&http_client).await?;
+    let threat_intel_feed = siem_ioc::ClickHouseFeed::new(
+        http_client.clone(),
+        config.clickhouse_url.clone(),
+        format!("{}.threat_intel", config.clickhouse_db),
+    );
+    let threat_intel_cache = ThreatIntelCache::new();
+    match threat_intel_cache.reload(&threat_intel_feed).await {
+        Ok(loaded) => info!("Loaded {} threat intelligence IOCs", loaded),
+        Err(e) => warn!("Failed to load threat intelligence: {}", e),
+    }
+    let mut threat_intel_reloaded_at = Instant::now();
 
     // Create interval for periodic flush checks
     let mut flush_interval = interval(Duration::from_secs(1));
@@ -1306,10 +1308,20 @@ async fn main() -> Result<()> {
                     }
                 }
                 info!("Refreshing threat intelligence cache...");
-                match build_threat_intel_cache(&http_client).await {
-                    Ok(new_cache) => {
-                        threat_intel_cache = new_cache;
-                        info!("Threat intelligence cache refreshed successfully");
+                // New indicators on every refresh; a full reload hourly drops deleted ones
+                let refreshed = if threat_intel_reloaded_at.elapsed() >= THREAT_INTEL_RELOAD_INTERVAL {
+                    threat_intel_reloaded_at = Instant::now();
+                    threat_intel_cache.reload(&threat_intel_feed).await
+                } else {
+                    threat_intel_cache.refresh(&threat_intel_feed).await
+                };
+                match refreshed {
+                    Ok(applied) => {
+                        info!(
+                            "Threat intelligence cache refreshed successfully ({} IOCs applied, {} total)",
+                            applied,
+                            threat_intel_cache.len()
+                        );
                     }
                     Err(e) => {
                         warn!("Failed to refresh threat intelligence cache: {}", e);
@@ -0,0 +1,388 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use serde::Serialize;
+
+/// Confidence given to indicators whose feed does not supply one
+pub const DEFAULT_CONFIDENCE: u8 = 50;
+
+/// Kinds of indicator the index matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IocType {
+    Ip,
+    Cidr,
+    Domain,
+    Url,
+    Hash,
+}
+
+impl IocType {
+    /// Maps the `ioc_type` names feeds use onto a kind
+    pub fn parse(ioc_type: &str) -> Option<Self> {
+        match ioc_type.trim().to_ascii_lowercase().as_str() {
+            "ip" | "ipv4" | "ipv6" | "ip-src" | "ip-dst" | "ipv4-addr" | "ipv6-addr" => Some(IocType::Ip),
+            "cidr" | "ipv4-cidr" | "ipv6-cidr" | "ip-range" | "network" => Some(IocType::Cidr),
+            "domain" | "hostname" | "fqdn" | "domain-name" => Some(IocType::Domain),
+            "url" | "uri" => Some(IocType::Url),
+            "hash" | "file_hash" | "md5" | "sha1" | "sha256" | "sha512" => Some(IocType::Hash),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IocType::Ip => "ip",
+            IocType::Cidr => "cidr",
+            IocType::Domain => "domain",
+            IocType::Url => "url",
+            IocType::Hash => "hash",
+        }
+    }
+}
+
+/// An indicator that an event value matched
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IocMatch {
+    /// The indicator in normalized form, e.g. `10.0.0.0/8` or `evil.example`
+    pub indicator: String,
+    pub ioc_type: IocType,
+    /// Feed the indicator was published by
+    pub source: String,
+    /// 0 to 100
+    pub confidence: u8,
+}
+
+#[derive(Debug, Clone)]
+struct Provenance {
+    source: Arc<str>,
+    confidence: u8,
+}
+
+/// In-memory indicator set. Every lookup is a handful of hash probes, so the
+/// cost does not grow with the number of indicators.
+#[derive(Debug, Default)]
+pub struct IocIndex {
+    ips: HashMap<IpAddr, Provenance>,
+    // Networks keyed by prefix length and masked address; a lookup probes
+    // only the prefix lengths in use, longest first
+    v4_networks: HashMap<(u8, u32), Provenance>,
+    v4_prefixes: BTreeSet<u8>,
+    v6_networks: HashMap<(u8, u128), Provenance>,
+    v6_prefixes: BTreeSet<u8>,
+    domains: HashMap<Box<str>, Provenance>,
+    urls: HashMap<Box<str>, Provenance>,
+    hashes: HashMap<Box<str>, Provenance>,
+    // Feed names are shared by every indicator they publish
+    sources: HashSet<Arc<str>>,
+}
+
+impl IocIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ips.len()
+            + self.v4_networks.len()
+            + self.v6_networks.len()
+            + self.domains.len()
+            + self.urls.len()
+            + self.hashes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds or replaces an indicator. Returns false, leaving the index
+    /// unchanged, for unknown types and values that are not valid for theirs.
+    pub fn insert(&mut self, ioc_type: &str, value: &str, source: &str, confidence: u8) -> bool {
+        let Some(key) = IocType::parse(ioc_type).and_then(|kind| Key::parse(kind, value.trim())) else {
+            return false;
+        };
+        let provenance = Provenance { source: self.intern(source), confidence: confidence.min(100) };
+
+        match key {
+            Key::Network(ip, prefix) if prefix == max_prefix(ip) => {
+                self.ips.insert(ip, provenance);
+            }
+            Key::Network(IpAddr::V4(network), prefix) => {
+                self.v4_networks.insert((prefix, mask_v4(network, prefix)), provenance);
+                self.v4_prefixes.insert(prefix);
+            }
+            Key::Network(IpAddr::V6(network), prefix) => {
+                self.v6_networks.insert((prefix, mask_v6(network, prefix)), provenance);
+                self.v6_prefixes.insert(prefix);
+            }
+            Key::Domain(domain) => { self.domains.insert(domain.into(), provenance); }
+            Key::Url(url) => { self.urls.insert(url.into(), provenance); }
+            Key::Hash(hash) => { self.hashes.insert(hash.into(), provenance); }
+        }
+        true
+    }
+
+    /// Exact address first, then the most specific containing range
+    pub fn match_ip(&self, ip: IpAddr) -> Option<IocMatch> {
+        let ip = canonical_ip(ip);
+        if let Some(provenance) = self.ips.get(&ip) {
+            return Some(hit(ip.to_string(), IocType::Ip, provenance));
+        }
+        match ip {
+            IpAddr::V4(ip) => self.v4_prefixes.iter().rev().find_map(|&prefix| {
+                let network = mask_v4(ip, prefix);
+                self.v4_networks.get(&(prefix, network)).map(|provenance| {
+                    hit(format!("{}/{}", Ipv4Addr::from(network), prefix), IocType::Cidr, provenance)
+                })
+            }),
+            IpAddr::V6(ip) => self.v6_prefixes.iter().rev().find_map(|&prefix| {
+                let network = mask_v6(ip, prefix);
+                self.v6_networks.get(&(prefix, network)).map(|provenance| {
+                    hit(format!("{}/{}", Ipv6Addr::from(network), prefix), IocType::Cidr, provenance)
+                })
+            }),
+        }
+    }
+
+    /// The domain itself or the closest listed parent domain
+    pub fn match_domain(&self, domain: &str) -> Option<IocMatch> {
+        let domain = normalize_domain(domain)?;
+        let mut candidate = domain.as_str();
+        loop {
+            if let Some(provenance) = self.domains.get(candidate) {
+                return Some(hit(candidate.to_string(), IocType::Domain, provenance));
+            }
+            candidate = candidate.split_once('.')?.1;
+        }
+    }
+
+    /// The exact URL, otherwise its host as an address or domain
+    pub fn match_url(&self, url: &str) -> Option<IocMatch> {
+        let url = normalize_url(url)?;
+        if let Some(provenance) = self.urls.get(url.as_str()) {
+            return Some(hit(url, IocType::Url, provenance));
+        }
+        let host = url_host(&url)?;
+        match host.parse::<IpAddr>() {
+            Ok(ip) => self.match_ip(ip),
+            Err(_) => self.match_domain(host),
+        }
+    }
+
+    pub fn match_hash(&self, hash: &str) -> Option<IocMatch> {
+        let hash = normalize_hash(hash)?;
+        self.hashes.get(hash.as_str()).map(|provenance| hit(hash, IocType::Hash, provenance))
+    }
+
+    /// Matches a value of unknown kind, judged by its shape
+    pub fn match_value(&self, value: &str) -> Option<IocMatch> {
+        let value = value.trim();
+        if let Ok(ip) = value.parse::<IpAddr>() {
+            return self.match_ip(ip);
+        }
+        if value.contains("://") {
+            return self.match_url(value);
+        }
+        if normalize_hash(value).is_some() {
+            return self.match_hash(value);
+        }
+        if value.contains('.') {
+            return self.match_domain(value);
+        }
+        None
+    }
+
+    fn intern(&mut self, source: &str) -> Arc<str> {
+        if let Some(shared) = self.sources.get(source) {
+            return shared.clone();
+        }
+        let shared: Arc<str> = Arc::from(source);
+        self.sources.insert(shared.clone());
+        shared
+    }
+}
+
+/// An indicator value in the form it is stored under
+enum Key {
+    Network(IpAddr, u8),
+    Domain(String),
+    Url(String),
+    Hash(String),
+}
+
+impl Key {
+    fn parse(kind: IocType, value: &str) -> Option<Self> {
+        match kind {
+            // Feeds often list ranges under their IP type
+            IocType::Ip | IocType::Cidr => parse_network(value).map(|(ip, prefix)| Key::Network(ip, prefix)),
+            IocType::Domain => normalize_domain(value.trim_start_matches("*.")).map(Key::Domain),
+            IocType::Url => normalize_url(value).map(Key::Url),
+            IocType::Hash => normalize_hash(value).map(Key::Hash),
+        }
+    }
+}
+
+fn hit(indicator: String, ioc_type: IocType, provenance: &Provenance) -> IocMatch {
+    IocMatch {
+        indicator,
+        ioc_type,
+        source: provenance.source.to_string(),
+        confidence: provenance.confidence,
+    }
+}
+
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+        v4 => v4,
+    }
+}
+
+fn max_prefix(ip: IpAddr) -> u8 {
+    if ip.is_ipv4() { 32 } else { 128 }
+}
+
+/// An address or `address/prefix`
+fn parse_network(value: &str) -> Option<(IpAddr, u8)> {
+    let (address, prefix) = match value.split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix.trim().parse::<u8>().ok()?)),
+        None => (value, None),
+    };
+    let mut ip: IpAddr = address.trim().parse().ok()?;
+    let mut prefix = prefix.unwrap_or(max_prefix(ip));
+    if prefix > max_prefix(ip) {
+        return None;
+    }
+    // ::ffff:a.b.c.d/n covers the same addresses as a.b.c.d/(n - 96)
+    if let IpAddr::V6(v6) = ip {
+        if let Some(v4) = v6.to_ipv4_mapped().filter(|_| prefix >= 96) {
+            ip = IpAddr::V4(v4);
+            prefix -= 96;
+        }
+    }
+    Some((ip, prefix))
+}
+
+fn mask_v4(ip: Ipv4Addr, prefix: u8) -> u32 {
+    let mask = if prefix == 0 { 0 } else { u32::MAX << (32 - u32::from(prefix)) };
+    u32::from(ip) & mask
+}
+
+fn mask_v6(ip: Ipv6Addr, prefix: u8) -> u128 {
+    let mask = if prefix == 0 { 0 } else { u128::MAX << (128 - u32::from(prefix)) };
+    u128::from(ip) & mask
+}
+
+fn normalize_domain(domain: &str) -> Option<String> {
+    let domain = domain.trim().trim_start_matches('.').trim_end_matches('.').to_ascii_lowercase();
+    let valid = !domain.is_empty()
+        && domain.split('.').all(|label| {
+            !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        });
+    valid.then_some(domain)
+}
+
+/// Lowercases the scheme and host and drops credentials, the fragment and a
+/// bare trailing slash; the path and query are compared as given
+fn normalize_url(url: &str) -> Option<String> {
+    let (scheme, rest) = url.trim().split_once("://")?;
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (authority, path) = rest.split_at(rest.find(['/', '?']).unwrap_or(rest.len()));
+    let host = authority.rsplit('@').next().unwrap_or_default().trim_end_matches('.');
+    if scheme.is_empty() || host.is_empty() {
+        return None;
+    }
+    let path = if path == "/" { "" } else { path };
+    Some(format!("{}://{}{}", scheme.to_ascii_lowercase(), host.to_ascii_lowercase(), path))
+}
+
+/// Host of a normalized URL, without its port
+fn url_host(url: &str) -> Option<&str> {
+    let rest = url.split_once("://")?.1;
+    let authority = &rest[..rest.find(['/', '?']).unwrap_or(rest.len())];
+    if let Some(bracketed) = authority.strip_prefix('[') {
+        return bracketed.split(']').next();
+    }
+    match authority.rsplit_once(':') {
+        Some((host, port)) if port.chars().all(|c| c.is_ascii_digit()) => Some(host),
+        _ => Some(authority),
+    }
+}
+
+fn normalize_hash(hash: &str) -> Option<String> {
+    let hash = hash.trim();
+    let valid = matches!(hash.len(), 32 | 40 | 64 | 128) && hash.chars().all(|c| c.is_ascii_hexdigit());
+    valid.then(|| hash.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index() -> IocIndex {
+        let mut index = IocIndex::new();
+        assert!(index.insert("ipv4", "198.51.100.7", "abuse.ch", 90));
+        assert!(index.insert("cidr", "203.0.113.0/24", "spamhaus", 70));
+        assert!(index.insert("ipv4-cidr", "203.0.0.0/16", "spamhaus", 40));
+        assert!(index.insert("ipv6", "2001:db8:bad::/48", "internal", 60));
+        assert!(index.insert("domain", "*.Evil.Example.", "urlhaus", 80));
+        assert!(index.insert("url", "HTTP://Phish.Example/login?next=/", "openphish", 95));
+        assert!(index.insert("sha256", &"AB".repeat(32), "malwarebazaar", 100));
+        assert!(!index.insert("ipv4", "not-an-ip", "feed", 50));
+        assert!(!index.insert("cidr", "10.0.0.0/33", "feed", 50));
+        assert!(!index.insert("md5", "xyz", "feed", 50));
+        assert!(!index.insert("email", "a@b.example", "feed", 50));
+        index
+    }
+
+    #[test]
+    fn test_ip_and_cidr_matching() {
+        let index = index();
+        assert_eq!(index.len(), 7);
+
+        let exact = index.match_ip("198.51.100.7".parse().unwrap()).unwrap();
+        assert_eq!((exact.indicator.as_str(), exact.ioc_type, exact.confidence), ("198.51.100.7", IocType::Ip, 90));
+        assert_eq!(exact.source, "abuse.ch");
+
+        // The most specific range wins
+        let narrow = index.match_value("203.0.113.200").unwrap();
+        assert_eq!((narrow.indicator.as_str(), narrow.ioc_type), ("203.0.113.0/24", IocType::Cidr));
+        let wide = index.match_value("::ffff:203.0.5.1").unwrap();
+        assert_eq!(wide.indicator, "203.0.0.0/16");
+        assert_eq!(index.match_value("2001:db8:bad:1::9").unwrap().indicator, "2001:db8:bad::/48");
+        assert_eq!(index.match_value("198.51.100.8"), None);
+        assert_eq!(index.match_value("2001:db8:bae::1"), None);
+    }
+
+    #[test]
+    fn test_domain_url_and_hash_matching() {
+        let index = index();
+
+        assert_eq!(index.match_domain("evil.example").unwrap().indicator, "evil.example");
+        assert_eq!(index.match_value("CDN.a.evil.example.").unwrap().indicator, "evil.example");
+        assert_eq!(index.match_value("notevil.example"), None);
+        assert_eq!(index.match_value("example"), None);
+
+        let url = index.match_value("http://phish.example/login?next=/#top").unwrap();
+        assert_eq!((url.indicator.as_str(), url.ioc_type), ("http://phish.example/login?next=/", IocType::Url));
+        assert_eq!(index.match_value("http://phish.example/other"), None);
+        // Unlisted URLs still match through their host
+        let host = index.match_url("https://user@dl.evil.example:8443/payload.bin").unwrap();
+        assert_eq!((host.indicator.as_str(), host.ioc_type), ("evil.example", IocType::Domain));
+        assert_eq!(index.match_url("http://[::ffff:198.51.100.7]:80/").unwrap().indicator, "198.51.100.7");
+
+        let hash = index.match_value(&"ab".repeat(32)).unwrap();
+        assert_eq!((hash.ioc_type, hash.source.as_str()), (IocType::Hash, "malwarebazaar"));
+        assert_eq!(index.match_hash(&"ab".repeat(16)), None);
+    }
+
+    #[test]
+    fn test_reinsert_replaces_provenance() {
+        let mut index = index();
+        assert!(index.insert("ip", "198.51.100.7", "otx", 20));
+        assert_eq!(index.len(), 7);
+        let hit = index.match_value("198.51.100.7").unwrap();
+        assert_eq!((hit.source.as_str(), hit.confidence), ("otx", 20));
+        assert_eq!(index.sources.len(), 7);
+    }
+}
@@ -0,0 +1,34 @@
+//! Indicator of compromise matching shared by the unified pipeline and the
+//! consumer.
+//!
+//! Indicators are loaded from the ClickHouse `threat_intel` table that
+//! `siem_threat_intel` populates and are matched against event values:
+//!
+//! - IP addresses, exactly or by the longest containing CIDR range
+//! - domains, including any subdomain of a listed domain
+//! - URLs, exactly or through their host
+//! - MD5, SHA-1, SHA-256 and SHA-512 file hashes
+//!
+//! ```rust,no_run
+//! use siem_ioc::{ClickHouseFeed, IocStore};
+//!
+//! # async fn run() -> Result<(), siem_ioc::IocError> {
+//! let feed = ClickHouseFeed::new(reqwest::Client::new(), "http://localhost:8123", "dev.threat_intel");
+//! let store = IocStore::new();
+//! store.reload(&feed).await?;
+//!
+//! if let Some(hit) = store.match_value("198.51.100.7") {
+//!     println!("{} from {} ({}%)", hit.indicator, hit.source, hit.confidence);
+//! }
+//!
+//! // Later, pick up indicators added since the last load
+//! store.refresh(&feed).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+mod index;
+mod store;
+
+pub use index::{IocIndex, IocMatch, IocType, DEFAULT_CONFIDENCE};
+pub use store::{ClickHouseFeed, IocError, IocStore, ThreatIntelRow};
@@ -0,0 +1,250 @@
+use std::sync::{Mutex, PoisonError, RwLock};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::index::{IocIndex, IocMatch, DEFAULT_CONFIDENCE};
+
+#[derive(Error, Debug)]
+pub enum IocError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("ClickHouse returned {status}: {body}")]
+    ClickHouse { status: u16, body: String },
+
+    #[error("Invalid threat_intel row: {0}")]
+    Row(#[from] serde_json::Error),
+}
+
+/// A row of the `threat_intel` table. Columns other than these are ignored.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ThreatIntelRow {
+    pub ioc_type: String,
+    pub ioc_value: String,
+    #[serde(default)]
+    pub source: String,
+    #[serde(default = "default_confidence")]
+    pub confidence: u8,
+    #[serde(default)]
+    pub created_at: u32,
+}
+
+fn default_confidence() -> u8 {
+    DEFAULT_CONFIDENCE
+}
+
+/// Reads indicators from a ClickHouse table over the HTTP interface
+pub struct ClickHouseFeed {
+    client: reqwest::Client,
+    url: String,
+    table: String,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl ClickHouseFeed {
+    pub fn new(client: reqwest::Client, url: impl Into<String>, table: impl Into<String>) -> Self {
+        ClickHouseFeed {
+            client,
+            url: url.into(),
+            table: table.into(),
+            username: None,
+            password: None,
+        }
+    }
+
+    pub fn with_credentials(mut self, username: impl Into<String>, password: Option<String>) -> Self {
+        self.username = Some(username.into());
+        self.password = password;
+        self
+    }
+
+    /// Streams rows created at or after `since` (all rows when `None`) into
+    /// `on_row` without holding the whole result in memory, and returns the
+    /// latest `created_at` seen.
+    pub async fn fetch(&self, since: Option<u32>, mut on_row: impl FnMut(ThreatIntelRow)) -> Result<Option<u32>, IocError> {
+        // `SELECT *` keeps tables created before the confidence column loadable
+        let mut query = format!("SELECT * FROM {}", self.table);
+        if let Some(since) = since {
+            query.push_str(&format!(" WHERE created_at >= {}", since));
+        }
+        query.push_str(" FORMAT JSONEachRow");
+
+        let mut request = self.client.post(&self.url).body(query);
+        if let Some(username) = &self.username {
+            request = request.basic_auth(username, self.password.as_ref());
+        }
+        let mut response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let body = response.text().await.unwrap_or_default();
+            return Err(IocError::ClickHouse { status, body });
+        }
+
+        let mut latest: Option<u32> = None;
+        let mut handle = |line: &[u8]| -> Result<(), IocError> {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                return Ok(());
+            }
+            let row: ThreatIntelRow = serde_json::from_slice(line)?;
+            latest = Some(latest.map_or(row.created_at, |latest| latest.max(row.created_at)));
+            on_row(row);
+            Ok(())
+        };
+
+        let mut pending = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            pending.extend_from_slice(&chunk);
+            let mut consumed = 0;
+            while let Some(end) = pending[consumed..].iter().position(|&b| b == b'\n') {
+                handle(&pending[consumed..consumed + end])?;
+                consumed += end + 1;
+            }
+            pending.drain(..consumed);
+        }
+        handle(&pending)?;
+        Ok(latest)
+    }
+}
+
+/// Shared, refreshable indicator index
+#[derive(Default)]
+pub struct IocStore {
+    index: RwLock<IocIndex>,
+    // `created_at` of the newest row loaded; `None` until the first load
+    watermark: Mutex<Option<u32>>,
+}
+
+impl IocStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.read(IocIndex::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Runs `f` under one read lock, e.g. to check several fields of an event
+    pub fn read<R>(&self, f: impl FnOnce(&IocIndex) -> R) -> R {
+        f(&self.index.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    pub fn match_value(&self, value: &str) -> Option<IocMatch> {
+        self.read(|index| index.match_value(value))
+    }
+
+    /// Adds the rows created since the last load, or loads everything when
+    /// nothing has been loaded yet. Returns the number of indicators applied.
+    ///
+    /// Rows from the second of the last load are fetched again, since more
+    /// may have been inserted within it; applying a row twice is harmless.
+    pub async fn refresh(&self, feed: &ClickHouseFeed) -> Result<usize, IocError> {
+        let Some(since) = *self.watermark.lock().unwrap_or_else(PoisonError::into_inner) else {
+            return self.reload(feed).await;
+        };
+
+        let mut rows = Vec::new();
+        let latest = feed.fetch(Some(since), |row| rows.push(row)).await?;
+        let applied = {
+            let mut index = self.index.write().unwrap_or_else(PoisonError::into_inner);
+            rows.iter().filter(|row| index.insert(&row.ioc_type, &row.ioc_value, &row.source, row.confidence)).count()
+        };
+        self.advance_watermark(latest.unwrap_or(since));
+        Ok(applied)
+    }
+
+    /// Replaces the index with a fresh load of the whole table, dropping
+    /// indicators that have since been deleted. Lookups keep using the old
+    /// index until the new one is complete.
+    pub async fn reload(&self, feed: &ClickHouseFeed) -> Result<usize, IocError> {
+        let mut fresh = IocIndex::new();
+        let mut applied = 0;
+        let latest = feed.fetch(None, |row| {
+            if fresh.insert(&row.ioc_type, &row.ioc_value, &row.source, row.confidence) {
+                applied += 1;
+            }
+        }).await?;
+
+        *self.index.write().unwrap_or_else(PoisonError::into_inner) = fresh;
+        *self.watermark.lock().unwrap_or_else(PoisonError::into_inner) = Some(latest.unwrap_or(0));
+        Ok(applied)
+    }
+
+    fn advance_watermark(&self, latest: u32) {
+        let mut watermark = self.watermark.lock().unwrap_or_else(PoisonError::into_inner);
+        *watermark = Some(watermark.map_or(latest, |current| current.max(latest)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers each connection with the next body, recording the queries
+    async fn serve(bodies: Vec<&'static str>) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut queries = Vec::new();
+            for body in bodies {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buffer = [0u8; 4096];
+                loop {
+                    let read = socket.read(&mut buffer).await.unwrap();
+                    request.extend_from_slice(&buffer[..read]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some((head, query)) = text.split_once("\r\n\r\n") {
+                        let length = head.lines()
+                            .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                            .unwrap_or(0);
+                        if query.len() >= length {
+                            queries.push(query.to_string());
+                            break;
+                        }
+                    }
+                }
+                let response = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+            queries
+        });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn test_reload_then_incremental_refresh() {
+        let (url, server) = serve(vec![
+            concat!(
+                r#"{"ioc_id":"1","ioc_type":"ipv4","ioc_value":"198.51.100.7","source":"abuse.ch","first_seen":1,"created_at":100}"#, "\n",
+                r#"{"ioc_id":"2","ioc_type":"domain","ioc_value":"evil.example","source":"urlhaus","confidence":85,"created_at":120}"#, "\n",
+                r#"{"ioc_id":"3","ioc_type":"email","ioc_value":"a@b.example","source":"misc","created_at":130}"#,
+            ),
+            concat!(
+                r#"{"ioc_type":"cidr","ioc_value":"203.0.113.0/24","source":"spamhaus","confidence":70,"created_at":140}"#, "\n",
+            ),
+        ]).await;
+        let feed = ClickHouseFeed::new(reqwest::Client::new(), url, "dev.threat_intel");
+        let store = IocStore::new();
+
+        assert_eq!(store.refresh(&feed).await.unwrap(), 2);
+        assert_eq!(store.len(), 2);
+        let hit = store.match_value("www.evil.example").unwrap();
+        assert_eq!((hit.source.as_str(), hit.confidence), ("urlhaus", 85));
+        assert_eq!(store.match_value("198.51.100.7").unwrap().confidence, DEFAULT_CONFIDENCE);
+
+        assert_eq!(store.refresh(&feed).await.unwrap(), 1);
+        assert_eq!(store.match_value("203.0.113.9").unwrap().indicator, "203.0.113.0/24");
+        assert_eq!(store.len(), 3);
+
+        let queries = server.await.unwrap();
+        assert_eq!(queries[0], "SELECT * FROM dev.threat_intel FORMAT JSONEachRow");
+        assert_eq!(queries[1], "SELECT * FROM dev.threat_intel WHERE created_at >= 130 FORMAT JSONEachRow");
+    }
+}
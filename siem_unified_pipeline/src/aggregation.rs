@@ -0,0 +1,478 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::config::AggregateFunction;
+use crate::error::{Result, PipelineError};
+use crate::mapping::set_path;
+use crate::pipeline::{PipelineEvent, ProcessingStage};
+
+/// Metadata key marking a summary event with the `pipeline#step` that produced it,
+/// so the transformation manager resumes it after that step.
+pub const AGGREGATED_BY_METADATA_KEY: &str = "aggregated_by";
+
+// A sliding window places each event in size/slide windows; keep that bounded
+const MAX_WINDOWS_PER_EVENT: i64 = 60;
+const MAX_OPEN_BUCKETS: usize = 100_000;
+const DEFAULT_TOP_K: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowSpec {
+    size: Duration,
+    slide: Duration,
+}
+
+impl WindowSpec {
+    /// Parse a window such as `"5m"`, optionally sliding every `slide`.
+    pub fn parse(window: &str, slide: Option<&str>) -> Result<Self> {
+        let parse = |value: &str| {
+            crate::utils::parse_duration(value)
+                .filter(|d| !d.is_zero())
+                .and_then(|d| Duration::from_std(d).ok())
+                .ok_or_else(|| PipelineError::config(format!("Invalid aggregation window duration '{}'", value)))
+        };
+
+        let size = parse(window)?;
+        let slide = match slide {
+            Some(slide_text) => {
+                let slide = parse(slide_text)?;
+                if slide > size {
+                    return Err(PipelineError::config(format!(
+                        "Aggregation slide '{}' exceeds window '{}'", slide_text, window
+                    )));
+                }
+                slide
+            }
+            None => size,
+        };
+
+        if size.num_milliseconds() / slide.num_milliseconds() > MAX_WINDOWS_PER_EVENT {
+            return Err(PipelineError::config(format!(
+                "Aggregation window '{}' overlaps more than {} slides", window, MAX_WINDOWS_PER_EVENT
+            )));
+        }
+
+        Ok(WindowSpec { size, slide })
+    }
+
+    pub fn is_sliding(&self) -> bool {
+        self.slide < self.size
+    }
+
+    /// Start times (epoch ms) of every window containing `timestamp`.
+    fn window_starts(&self, timestamp: DateTime<Utc>) -> Vec<i64> {
+        let ts = timestamp.timestamp_millis();
+        let slide = self.slide.num_milliseconds();
+        let size = self.size.num_milliseconds();
+
+        let mut starts = Vec::new();
+        let mut start = ts.div_euclid(slide) * slide;
+        while start > ts - size {
+            starts.push(start);
+            start -= slide;
+        }
+        starts
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum AggregateKind {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+    DistinctCount,
+    TopK(usize),
+}
+
+impl AggregateKind {
+    fn parse(function: &str) -> Result<Self> {
+        let function = function.trim().to_lowercase();
+        let kind = match function.as_str() {
+            "count" => AggregateKind::Count,
+            "sum" => AggregateKind::Sum,
+            "min" => AggregateKind::Min,
+            "max" => AggregateKind::Max,
+            "avg" | "mean" => AggregateKind::Avg,
+            "distinct_count" | "count_distinct" | "distinct" => AggregateKind::DistinctCount,
+            "top_k" | "topk" => AggregateKind::TopK(DEFAULT_TOP_K),
+            other => {
+                // `top_5`, `top_k:5`
+                let k = other.strip_prefix("top_k:")
+                    .or_else(|| other.strip_prefix("top_"))
+                    .and_then(|k| k.parse::<usize>().ok())
+                    .filter(|k| *k > 0)
+                    .ok_or_else(|| PipelineError::config(format!("Unknown aggregate function '{}'", other)))?;
+                AggregateKind::TopK(k)
+            }
+        };
+        Ok(kind)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CompiledFunction {
+    kind: AggregateKind,
+    // None counts every event rather than events carrying a field
+    field: Option<String>,
+    output_field: String,
+}
+
+#[derive(Debug, Clone)]
+enum Accumulator {
+    Count(u64),
+    Sum(f64),
+    Min(Option<f64>),
+    Max(Option<f64>),
+    Avg { sum: f64, count: u64 },
+    Distinct(HashSet<String>),
+    TopK(HashMap<String, u64>),
+}
+
+impl Accumulator {
+    fn new(kind: &AggregateKind) -> Self {
+        match kind {
+            AggregateKind::Count => Accumulator::Count(0),
+            AggregateKind::Sum => Accumulator::Sum(0.0),
+            AggregateKind::Min => Accumulator::Min(None),
+            AggregateKind::Max => Accumulator::Max(None),
+            AggregateKind::Avg => Accumulator::Avg { sum: 0.0, count: 0 },
+            AggregateKind::DistinctCount => Accumulator::Distinct(HashSet::new()),
+            AggregateKind::TopK(_) => Accumulator::TopK(HashMap::new()),
+        }
+    }
+
+    fn add(&mut self, value: Option<&Value>, counts_all: bool) {
+        let value = value.filter(|v| !v.is_null());
+        match self {
+            Accumulator::Count(count) => {
+                if counts_all || value.is_some() {
+                    *count += 1;
+                }
+            }
+            Accumulator::Sum(sum) => {
+                if let Some(n) = value.and_then(as_number) {
+                    *sum += n;
+                }
+            }
+            Accumulator::Min(min) => {
+                if let Some(n) = value.and_then(as_number) {
+                    *min = Some(min.map_or(n, |m| m.min(n)));
+                }
+            }
+            Accumulator::Max(max) => {
+                if let Some(n) = value.and_then(as_number) {
+                    *max = Some(max.map_or(n, |m| m.max(n)));
+                }
+            }
+            Accumulator::Avg { sum, count } => {
+                if let Some(n) = value.and_then(as_number) {
+                    *sum += n;
+                    *count += 1;
+                }
+            }
+            Accumulator::Distinct(values) => {
+                if let Some(value) = value {
+                    values.insert(as_key(value));
+                }
+            }
+            Accumulator::TopK(counts) => {
+                if let Some(value) = value {
+                    *counts.entry(as_key(value)).or_insert(0) += 1;
+                }
+            }
+        }
+    }
+
+    fn finish(&self, kind: &AggregateKind) -> Value {
+        match self {
+            Accumulator::Count(count) => json!(count),
+            Accumulator::Sum(sum) => json!(sum),
+            Accumulator::Min(min) => min.map_or(Value::Null, |n| json!(n)),
+            Accumulator::Max(max) => max.map_or(Value::Null, |n| json!(n)),
+            Accumulator::Avg { sum, count } => {
+                if *count == 0 { Value::Null } else { json!(sum / *count as f64) }
+            }
+            Accumulator::Distinct(values) => json!(values.len()),
+            Accumulator::TopK(counts) => {
+                let k = match kind {
+                    AggregateKind::TopK(k) => *k,
+                    _ => DEFAULT_TOP_K,
+                };
+                let mut ranked: Vec<(&String, &u64)> = counts.iter().collect();
+                ranked.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+                Value::Array(ranked.into_iter().take(k)
+                    .map(|(value, count)| json!({ "value": value, "count": count }))
+                    .collect())
+            }
+        }
+    }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+fn as_key(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Bucket {
+    start_ms: i64,
+    group: Vec<Value>,
+    accumulators: Vec<Accumulator>,
+    event_count: u64,
+    sources: BTreeSet<String>,
+}
+
+/// Windowed state for one `aggregate` step.
+///
+/// Events are bucketed by their own timestamp and a window is emitted once the
+/// wall clock passes its end. An event arriving after its window was emitted
+/// opens a fresh bucket and produces a second, partial summary.
+#[derive(Debug)]
+pub struct Aggregator {
+    name: String,
+    window: WindowSpec,
+    group_by: Vec<String>,
+    functions: Vec<CompiledFunction>,
+    buckets: HashMap<(i64, String), Bucket>,
+    overflow_count: u64,
+}
+
+impl Aggregator {
+    pub fn new(
+        name: &str,
+        window: &str,
+        slide: Option<&str>,
+        group_by: &[String],
+        functions: &[AggregateFunction],
+    ) -> Result<Self> {
+        let window = WindowSpec::parse(window, slide)?;
+
+        let mut compiled = Vec::new();
+        for function in functions {
+            let kind = AggregateKind::parse(&function.function)?;
+            let field = Some(function.field.trim())
+                .filter(|f| !f.is_empty() && *f != "*")
+                .map(str::to_string);
+            if field.is_none() && kind != AggregateKind::Count {
+                return Err(PipelineError::config(format!(
+                    "Aggregate function '{}' requires a field", function.function
+                )));
+            }
+            if function.output_field.trim().is_empty() {
+                return Err(PipelineError::config(format!(
+                    "Aggregate function '{}' has no output field", function.function
+                )));
+            }
+            compiled.push(CompiledFunction { kind, field, output_field: function.output_field.clone() });
+        }
+
+        if compiled.is_empty() {
+            return Err(PipelineError::config("Aggregate step has no functions"));
+        }
+
+        Ok(Aggregator {
+            name: name.to_string(),
+            window,
+            group_by: group_by.to_vec(),
+            functions: compiled,
+            buckets: HashMap::new(),
+            overflow_count: 0,
+        })
+    }
+
+    pub fn window(&self) -> &WindowSpec {
+        &self.window
+    }
+
+    pub fn open_buckets(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Events discarded because too many windows/groups were open at once.
+    pub fn overflow_count(&self) -> u64 {
+        self.overflow_count
+    }
+
+    pub fn add(&mut self, event: &PipelineEvent) {
+        let group: Vec<Value> = self.group_by.iter()
+            .map(|field| event.get_field(field).map(|v| v.into_owned()).unwrap_or(Value::Null))
+            .collect();
+        let group_key = serde_json::to_string(&group).unwrap_or_default();
+
+        let values: Vec<Option<Value>> = self.functions.iter()
+            .map(|f| f.field.as_ref().and_then(|field| event.get_field(field)).map(|v| v.into_owned()))
+            .collect();
+
+        for start_ms in self.window.window_starts(event.timestamp) {
+            let key = (start_ms, group_key.clone());
+            if !self.buckets.contains_key(&key) && self.buckets.len() >= MAX_OPEN_BUCKETS {
+                self.overflow_count += 1;
+                continue;
+            }
+
+            let functions = &self.functions;
+            let bucket = self.buckets.entry(key).or_insert_with(|| Bucket {
+                start_ms,
+                group: group.clone(),
+                accumulators: functions.iter().map(|f| Accumulator::new(&f.kind)).collect(),
+                event_count: 0,
+                sources: BTreeSet::new(),
+            });
+
+            for ((function, accumulator), value) in functions.iter().zip(bucket.accumulators.iter_mut()).zip(&values) {
+                accumulator.add(value.as_ref(), function.field.is_none());
+            }
+            bucket.event_count += 1;
+            bucket.sources.insert(event.source.clone());
+        }
+    }
+
+    /// Emit summaries for every window that has ended by `now`, or all of them when `force` is set.
+    pub fn flush(&mut self, now: DateTime<Utc>, force: bool) -> Vec<PipelineEvent> {
+        let size_ms = self.window.size.num_milliseconds();
+        let now_ms = now.timestamp_millis();
+
+        let closed: Vec<(i64, String)> = self.buckets.iter()
+            .filter(|(_, bucket)| force || bucket.start_ms + size_ms <= now_ms)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        let buckets: Vec<Bucket> = closed.into_iter()
+            .filter_map(|key| self.buckets.remove(&key))
+            .collect();
+        let mut summaries: Vec<PipelineEvent> = buckets.into_iter()
+            .map(|bucket| self.summarize(bucket))
+            .collect();
+        summaries.sort_by_key(|event| event.timestamp);
+        summaries
+    }
+
+    fn summarize(&self, bucket: Bucket) -> PipelineEvent {
+        let start = Utc.timestamp_millis_opt(bucket.start_ms).single().unwrap_or_else(Utc::now);
+        let end = start + self.window.size;
+
+        let mut data = json!({
+            "event_type": "aggregate",
+            "event_count": bucket.event_count,
+            "window": {
+                "start": start.to_rfc3339(),
+                "end": end.to_rfc3339(),
+                "size_seconds": self.window.size.num_seconds(),
+                "sliding": self.window.is_sliding(),
+            },
+        });
+        for (field, value) in self.group_by.iter().zip(bucket.group) {
+            set_path(&mut data, field, value);
+        }
+        for (function, accumulator) in self.functions.iter().zip(&bucket.accumulators) {
+            set_path(&mut data, &function.output_field, accumulator.finish(&function.kind));
+        }
+
+        let source = if bucket.sources.len() == 1 {
+            bucket.sources.into_iter().next().unwrap_or_default()
+        } else {
+            "aggregate".to_string()
+        };
+
+        let mut metadata = HashMap::new();
+        metadata.insert(AGGREGATED_BY_METADATA_KEY.to_string(), self.name.clone());
+
+        PipelineEvent {
+            id: Uuid::new_v4(),
+            timestamp: end,
+            source,
+            data,
+            metadata,
+            processing_stage: ProcessingStage::Ingested,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(ts: i64, data: Value) -> PipelineEvent {
+        PipelineEvent {
+            id: Uuid::new_v4(),
+            timestamp: Utc.timestamp_opt(ts, 0).unwrap(),
+            source: "netflow".to_string(),
+            data,
+            metadata: HashMap::new(),
+            processing_stage: ProcessingStage::Ingested,
+        }
+    }
+
+    fn function(function: &str, field: &str, output_field: &str) -> AggregateFunction {
+        AggregateFunction { function: function.into(), field: field.into(), output_field: output_field.into() }
+    }
+
+    #[test]
+    fn test_tumbling_window_grouped_summary() {
+        let mut aggregator = Aggregator::new(
+            "flows#1",
+            "5m",
+            None,
+            &["src_ip".to_string()],
+            &[
+                function("count", "", "flows"),
+                function("sum", "bytes", "bytes.total"),
+                function("max", "bytes", "bytes.max"),
+                function("avg", "bytes", "bytes.avg"),
+                function("distinct_count", "dst_port", "ports"),
+                function("top_k:1", "dst_ip", "top_destinations"),
+            ],
+        ).unwrap();
+
+        aggregator.add(&event(1_700_000_000, json!({"src_ip": "10.0.0.1", "dst_ip": "8.8.8.8", "dst_port": 53, "bytes": 100})));
+        aggregator.add(&event(1_700_000_010, json!({"src_ip": "10.0.0.1", "dst_ip": "8.8.8.8", "dst_port": 443, "bytes": "300"})));
+        aggregator.add(&event(1_700_000_020, json!({"src_ip": "10.0.0.1", "dst_ip": "1.1.1.1", "dst_port": 443, "bytes": 200})));
+        aggregator.add(&event(1_700_000_030, json!({"src_ip": "10.0.0.2", "bytes": 5})));
+
+        // Window [1699999800, 1700000100) is still open
+        assert!(aggregator.flush(Utc.timestamp_opt(1_700_000_050, 0).unwrap(), false).is_empty());
+
+        let summaries = aggregator.flush(Utc.timestamp_opt(1_700_000_100, 0).unwrap(), false);
+        assert_eq!(summaries.len(), 2);
+        let summary = summaries.iter().find(|e| e.data["src_ip"] == "10.0.0.1").unwrap();
+        assert_eq!(summary.data["flows"], json!(3));
+        assert_eq!(summary.data["bytes"]["total"], json!(600.0));
+        assert_eq!(summary.data["bytes"]["max"], json!(300.0));
+        assert_eq!(summary.data["bytes"]["avg"], json!(200.0));
+        assert_eq!(summary.data["ports"], json!(2));
+        assert_eq!(summary.data["top_destinations"], json!([{"value": "8.8.8.8", "count": 2}]));
+        assert_eq!(summary.metadata[AGGREGATED_BY_METADATA_KEY], "flows#1");
+        assert_eq!(summary.source, "netflow");
+        assert_eq!(aggregator.open_buckets(), 0);
+    }
+
+    #[test]
+    fn test_sliding_window_overlap() {
+        let mut aggregator = Aggregator::new("flows#0", "10m", Some("5m"), &[], &[function("count", "*", "events")]).unwrap();
+        aggregator.add(&event(1_700_000_000, json!({})));
+        assert_eq!(aggregator.open_buckets(), 2);
+
+        let summaries = aggregator.flush(Utc::now(), true);
+        assert_eq!(summaries.len(), 2);
+        assert!(summaries.iter().all(|e| e.data["events"] == json!(1)));
+        assert!(summaries[0].data["window"]["sliding"].as_bool().unwrap());
+
+        assert!(WindowSpec::parse("1m", Some("5m")).is_err());
+        assert!(WindowSpec::parse("soon", None).is_err());
+        assert!(Aggregator::new("x#0", "1m", None, &[], &[function("median", "bytes", "m")]).is_err());
+    }
+}
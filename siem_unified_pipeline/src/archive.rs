@@ -0,0 +1,488 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use arrow_array::builder::{MapBuilder, MapFieldNames, StringBuilder, TimestampMillisecondBuilder};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Fields, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, GzipLevel, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use tracing::{debug, info, warn};
+
+use crate::config::{CompressionType, FileRotation, PartitioningConfig, PartitioningStrategy};
+use crate::error::{Result, PipelineError};
+use crate::pipeline::PipelineEvent;
+
+/// Event fields with a column of their own; everything else in the event
+/// data lands in `additional_fields`.
+pub const CANONICAL_FIELDS: [&str; 6] = ["severity", "facility", "hostname", "process", "message", "raw_message"];
+
+const DEFAULT_TENANT: &str = "default";
+
+/// Arrow schema of archived events: the canonical `ParsedEvent` fields,
+/// the remaining event data as a string map and the pipeline metadata.
+pub fn schema() -> SchemaRef {
+    let string_map = |name: &str| {
+        let entries = Fields::from(vec![
+            Field::new("key", DataType::Utf8, false),
+            Field::new("value", DataType::Utf8, true),
+        ]);
+        Field::new(name, DataType::Map(Arc::new(Field::new("key_value", DataType::Struct(entries), false)), false), false)
+    };
+
+    let mut fields = vec![
+        Field::new("id", DataType::Utf8, false),
+        Field::new("timestamp", DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())), false),
+        Field::new("tenant", DataType::Utf8, false),
+        Field::new("source", DataType::Utf8, false),
+        Field::new("source_type", DataType::Utf8, true),
+    ];
+    fields.extend(CANONICAL_FIELDS.iter().map(|name| Field::new(*name, DataType::Utf8, true)));
+    fields.push(Field::new("processing_stage", DataType::Utf8, false));
+    fields.push(string_map("additional_fields"));
+    fields.push(string_map("metadata"));
+    Arc::new(Schema::new(fields))
+}
+
+/// Parquet writer settings for a destination's `compression`.
+pub fn writer_properties(compression: Option<&CompressionType>) -> WriterProperties {
+    let compression = match compression {
+        None => Compression::UNCOMPRESSED,
+        Some(CompressionType::Gzip) => Compression::GZIP(GzipLevel::default()),
+        Some(CompressionType::Zstd) => Compression::ZSTD(ZstdLevel::default()),
+        Some(CompressionType::Lz4) => Compression::LZ4_RAW,
+        Some(CompressionType::Snappy) => Compression::SNAPPY,
+    };
+    WriterProperties::builder().set_compression(compression).build()
+}
+
+/// Tenant an event belongs to, from its metadata or data, `default` otherwise.
+pub fn tenant(event: &PipelineEvent) -> &str {
+    event.metadata.get("tenant_id").map(String::as_str)
+        .or_else(|| event.data.get("tenant_id").and_then(|t| t.as_str()))
+        .filter(|t| !t.is_empty())
+        .unwrap_or(DEFAULT_TENANT)
+}
+
+/// Hive-style directory of an event, e.g. `tenant=acme/date=2024-05-01/hour=13`.
+///
+/// `fields` are laid out in order: `tenant`, `date` and `hour` are derived
+/// from the event, any other name is read from the event data. The strategy
+/// then adds a `Time` path (a chrono format), a `Field` value or a `Hash`
+/// bucket of the event id. Without partitioning, events are laid out by
+/// tenant, date and hour.
+pub fn partition_path(event: &PipelineEvent, partitioning: Option<&PartitioningConfig>) -> String {
+    let Some(partitioning) = partitioning else {
+        return partition_path(event, Some(&PartitioningConfig {
+            strategy: PartitioningStrategy::Time { format: String::new() },
+            fields: vec!["tenant".to_string(), "date".to_string(), "hour".to_string()],
+        }));
+    };
+
+    let field_segment = |name: &str| {
+        let value = match name {
+            "tenant" => tenant(event).to_string(),
+            "date" => event.timestamp.format("%Y-%m-%d").to_string(),
+            "hour" => event.timestamp.format("%H").to_string(),
+            _ => match event.data.get(name) {
+                Some(serde_json::Value::String(value)) => value.clone(),
+                Some(serde_json::Value::Null) | None => String::new(),
+                Some(value) => value.to_string(),
+            },
+        };
+        format!("{}={}", sanitize(name), sanitize(&value))
+    };
+
+    let mut segments: Vec<String> = partitioning.fields.iter().map(|name| field_segment(name)).collect();
+    match &partitioning.strategy {
+        PartitioningStrategy::Time { format } if !format.is_empty() => {
+            segments.extend(event.timestamp.format(format).to_string().split('/').map(sanitize));
+        }
+        PartitioningStrategy::Time { .. } => {}
+        PartitioningStrategy::Field { field } => {
+            if !partitioning.fields.contains(field) {
+                segments.push(field_segment(field));
+            }
+        }
+        PartitioningStrategy::Hash { buckets } => {
+            let bucket = crc32fast::hash(event.id.as_bytes()) % (*buckets).max(1);
+            segments.push(format!("bucket={}", bucket));
+        }
+    }
+    segments.join("/")
+}
+
+// Keeps partition values to a single, harmless path segment
+pub(crate) fn sanitize(value: &str) -> String {
+    let cleaned: String = value.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    match cleaned.trim_matches('.') {
+        "" => "unknown".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+/// Convert events to a record batch of [`schema`].
+pub fn record_batch(events: &[&PipelineEvent]) -> Result<RecordBatch> {
+    let strings = || StringBuilder::with_capacity(events.len(), events.len() * 16);
+    let string_map = || MapBuilder::new(
+        Some(MapFieldNames { entry: "key_value".to_string(), key: "key".to_string(), value: "value".to_string() }),
+        StringBuilder::new(),
+        StringBuilder::new(),
+    );
+
+    let mut ids = strings();
+    let mut timestamps = TimestampMillisecondBuilder::with_capacity(events.len()).with_timezone("UTC");
+    let mut tenants = strings();
+    let mut sources = strings();
+    let mut source_types = strings();
+    let mut canonical: Vec<StringBuilder> = CANONICAL_FIELDS.iter().map(|_| strings()).collect();
+    let mut stages = strings();
+    let mut additional = string_map();
+    let mut metadata = string_map();
+
+    for event in events {
+        ids.append_value(event.id.to_string());
+        timestamps.append_value(event.timestamp.timestamp_millis());
+        tenants.append_value(tenant(event));
+        sources.append_value(&event.source);
+        source_types.append_option(event.metadata.get("source_type"));
+        stages.append_value(format!("{:?}", event.processing_stage));
+
+        match &event.data {
+            serde_json::Value::Object(data) => {
+                for (builder, name) in canonical.iter_mut().zip(CANONICAL_FIELDS) {
+                    builder.append_option(data.get(name).and_then(text));
+                }
+                for (key, value) in data {
+                    if !CANONICAL_FIELDS.contains(&key.as_str()) {
+                        additional.keys().append_value(key);
+                        additional.values().append_option(text(value));
+                    }
+                }
+            }
+            // Unstructured events keep their payload as the message
+            other => {
+                for (builder, name) in canonical.iter_mut().zip(CANONICAL_FIELDS) {
+                    builder.append_option((name == "message").then(|| text(other)).flatten());
+                }
+            }
+        }
+        additional.append(true).map_err(arrow_error)?;
+
+        let sorted: BTreeMap<_, _> = event.metadata.iter().collect();
+        for (key, value) in sorted {
+            metadata.keys().append_value(key);
+            metadata.values().append_value(value);
+        }
+        metadata.append(true).map_err(arrow_error)?;
+    }
+
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(ids.finish()),
+        Arc::new(timestamps.finish()),
+        Arc::new(tenants.finish()),
+        Arc::new(sources.finish()),
+        Arc::new(source_types.finish()),
+    ];
+    columns.extend(canonical.iter_mut().map(|builder| Arc::new(builder.finish()) as ArrayRef));
+    columns.push(Arc::new(stages.finish()));
+    columns.push(Arc::new(additional.finish()));
+    columns.push(Arc::new(metadata.finish()));
+
+    RecordBatch::try_new(schema(), columns).map_err(arrow_error)
+}
+
+// Strings as they are, other values as JSON text
+fn text(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn arrow_error(e: impl std::fmt::Display) -> PipelineError {
+    PipelineError::serialization(format!("Failed to build Parquet rows: {}", e))
+}
+
+fn parquet_error(e: impl std::fmt::Display) -> PipelineError {
+    PipelineError::storage(format!("Failed to write Parquet part: {}", e))
+}
+
+/// When an open part is finished and a new one started.
+#[derive(Debug, Clone)]
+pub struct RollPolicy {
+    pub max_bytes: u64,
+    pub max_age: Duration,
+}
+
+impl RollPolicy {
+    pub fn from_rotation(rotation: &FileRotation) -> Self {
+        Self {
+            max_bytes: rotation.size_mb.max(1).saturating_mul(1024 * 1024),
+            max_age: Duration::from_secs(rotation.time_hours.max(1).saturating_mul(3600)),
+        }
+    }
+}
+
+impl Default for RollPolicy {
+    fn default() -> Self {
+        Self { max_bytes: 128 * 1024 * 1024, max_age: Duration::from_secs(3600) }
+    }
+}
+
+/// Where Parquet parts are written while open and published once finished.
+#[async_trait::async_trait]
+pub trait PartStore: Send + 'static {
+    type Writer: Write + Send;
+
+    /// Start the part `name` under `partition`.
+    fn create(&mut self, partition: &str, name: &str) -> Result<Self::Writer>;
+
+    /// Make a part whose footer has been written visible to readers.
+    async fn publish(&mut self, partition: &str, name: &str, writer: Self::Writer) -> Result<()>;
+}
+
+/// Parts under a local directory. Open parts carry an `.inprogress` suffix
+/// and are renamed once their footer is written.
+pub struct DirectoryStore {
+    root: PathBuf,
+}
+
+impl DirectoryStore {
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)
+            .map_err(|e| PipelineError::io(format!("Failed to create archive directory {}: {}", root.display(), e)))?;
+        Ok(Self { root })
+    }
+
+    fn path(&self, partition: &str, name: &str) -> PathBuf {
+        self.root.join(partition).join(name)
+    }
+}
+
+#[async_trait::async_trait]
+impl PartStore for DirectoryStore {
+    type Writer = File;
+
+    fn create(&mut self, partition: &str, name: &str) -> Result<File> {
+        let path = self.path(partition, &format!("{}.inprogress", name));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        File::create(&path)
+            .map_err(|e| PipelineError::io(format!("Failed to create {}: {}", path.display(), e)))
+    }
+
+    async fn publish(&mut self, partition: &str, name: &str, file: File) -> Result<()> {
+        file.sync_all()?;
+        let path = self.path(partition, name);
+        std::fs::rename(self.path(partition, &format!("{}.inprogress", name)), &path)
+            .map_err(|e| PipelineError::io(format!("Failed to publish {}: {}", path.display(), e)))
+    }
+}
+
+struct OpenPart<W: Write + Send> {
+    name: String,
+    writer: ArrowWriter<W>,
+    opened: Instant,
+}
+
+/// Parquet parts, one open per partition, rolled by size and age.
+///
+/// Every batch is flushed as a row group so its rows are on their way to
+/// the store when the write returns, but a part only becomes readable once
+/// it is finished; parts still open when the process dies are lost.
+pub struct ParquetArchive<S: PartStore> {
+    store: S,
+    partitioning: Option<PartitioningConfig>,
+    policy: RollPolicy,
+    properties: WriterProperties,
+    open: HashMap<String, OpenPart<S::Writer>>,
+}
+
+impl<S: PartStore> ParquetArchive<S> {
+    pub fn new(
+        store: S,
+        partitioning: Option<PartitioningConfig>,
+        policy: RollPolicy,
+        compression: Option<&CompressionType>,
+    ) -> Self {
+        Self { store, partitioning, policy, properties: writer_properties(compression), open: HashMap::new() }
+    }
+
+    /// Append events to the open part of each of their partitions, returning
+    /// the Parquet bytes written.
+    pub async fn write(&mut self, events: &[PipelineEvent]) -> Result<u64> {
+        let mut partitions: BTreeMap<String, Vec<&PipelineEvent>> = BTreeMap::new();
+        for event in events {
+            partitions.entry(partition_path(event, self.partitioning.as_ref())).or_default().push(event);
+        }
+
+        let mut bytes_written = 0;
+        for (partition, partition_events) in partitions {
+            let batch = record_batch(&partition_events)?;
+            let mut part = match self.open.remove(&partition) {
+                Some(part) => part,
+                None => self.start(&partition)?,
+            };
+            let before = part.writer.bytes_written();
+            if let Err(e) = part.writer.write(&batch).and_then(|_| part.writer.flush()) {
+                // Keep the row groups that did make it out; the retry starts a fresh part
+                if let Err(finish_error) = self.finish(&partition, part).await {
+                    warn!("Discarding Parquet part in {}: {}", partition, finish_error);
+                }
+                return Err(parquet_error(e));
+            }
+            bytes_written += (part.writer.bytes_written() - before) as u64;
+            self.open.insert(partition, part);
+        }
+
+        self.roll(false).await?;
+        Ok(bytes_written)
+    }
+
+    /// Finish parts that reached their size or age, or every part when `all`.
+    pub async fn roll(&mut self, all: bool) -> Result<()> {
+        let due: Vec<String> = self.open.iter()
+            .filter(|(_, part)| all
+                || part.writer.bytes_written() as u64 >= self.policy.max_bytes
+                || part.opened.elapsed() >= self.policy.max_age)
+            .map(|(partition, _)| partition.clone())
+            .collect();
+
+        let mut result = Ok(());
+        for partition in due {
+            if let Some(part) = self.open.remove(&partition) {
+                if let Err(e) = self.finish(&partition, part).await {
+                    warn!("Failed to finish Parquet part in {}: {}", partition, e);
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    fn start(&mut self, partition: &str) -> Result<OpenPart<S::Writer>> {
+        let name = format!(
+            "part-{}-{}.parquet",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        let writer = self.store.create(partition, &name)?;
+        let writer = ArrowWriter::try_new(writer, schema(), Some(self.properties.clone())).map_err(parquet_error)?;
+        debug!("Started Parquet part {}/{}", partition, name);
+        Ok(OpenPart { name, writer, opened: Instant::now() })
+    }
+
+    async fn finish(&mut self, partition: &str, part: OpenPart<S::Writer>) -> Result<()> {
+        let writer = part.writer.into_inner().map_err(parquet_error)?;
+        self.store.publish(partition, &part.name, writer).await?;
+        info!("Finished Parquet part {}/{}", partition, part.name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ProcessingStage;
+    use arrow_array::{Array, MapArray, StringArray};
+    use chrono::TimeZone;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn event(tenant: &str, hour: u32, data: serde_json::Value) -> PipelineEvent {
+        PipelineEvent {
+            id: uuid::Uuid::new_v4(),
+            timestamp: chrono::Utc.with_ymd_and_hms(2024, 5, 1, hour, 30, 0).unwrap(),
+            source: "fw".to_string(),
+            data,
+            metadata: HashMap::from([
+                ("tenant_id".to_string(), tenant.to_string()),
+                ("source_type".to_string(), "syslog".to_string()),
+            ]),
+            processing_stage: ProcessingStage::Routed,
+        }
+    }
+
+    fn parts(dir: &std::path::Path) -> Vec<PathBuf> {
+        let mut found: Vec<PathBuf> = walkdir::WalkDir::new(dir).into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_partition_paths() {
+        let e = event("acme", 13, serde_json::json!({ "hostname": "gw/1" }));
+        assert_eq!(partition_path(&e, None), "tenant=acme/date=2024-05-01/hour=13");
+
+        let by_host = PartitioningConfig {
+            strategy: PartitioningStrategy::Time { format: "%Y/%m".to_string() },
+            fields: vec!["hostname".to_string()],
+        };
+        assert_eq!(partition_path(&e, Some(&by_host)), "hostname=gw_1/2024/05");
+
+        let hashed = PartitioningConfig { strategy: PartitioningStrategy::Hash { buckets: 4 }, fields: vec![] };
+        let bucket = partition_path(&e, Some(&hashed));
+        assert!(["bucket=0", "bucket=1", "bucket=2", "bucket=3"].contains(&bucket.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_parts_are_partitioned_and_readable() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DirectoryStore::open(dir.path()).unwrap();
+        let mut archive = ParquetArchive::new(store, None, RollPolicy::default(), Some(&CompressionType::Zstd));
+
+        let events = vec![
+            event("acme", 13, serde_json::json!({ "message": "denied", "severity": "high", "src_port": 443 })),
+            event("acme", 14, serde_json::json!({ "message": "allowed" })),
+            event("globex", 13, serde_json::json!("raw line")),
+        ];
+        archive.write(&events).await.unwrap();
+        // Open parts are not visible yet
+        assert!(parts(dir.path()).iter().all(|p| p.extension().unwrap() == "inprogress"));
+
+        archive.roll(true).await.unwrap();
+        let found = parts(dir.path());
+        assert_eq!(found.len(), 3);
+        assert!(found[0].starts_with(dir.path().join("tenant=acme/date=2024-05-01/hour=13")));
+        assert!(found[2].starts_with(dir.path().join("tenant=globex/date=2024-05-01/hour=13")));
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&found[0]).unwrap()).unwrap().build().unwrap();
+        let batch = reader.map(|b| b.unwrap()).next().unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        let column = |name: &str| batch.column_by_name(name).unwrap().as_any().downcast_ref::<StringArray>().unwrap().value(0).to_string();
+        assert_eq!(column("message"), "denied");
+        assert_eq!(column("severity"), "high");
+        assert_eq!(column("tenant"), "acme");
+        let additional = batch.column_by_name("additional_fields").unwrap().as_any().downcast_ref::<MapArray>().unwrap();
+        let entries = additional.value(0);
+        assert_eq!(entries.column(0).as_any().downcast_ref::<StringArray>().unwrap().value(0), "src_port");
+        assert_eq!(entries.column(1).as_any().downcast_ref::<StringArray>().unwrap().value(0), "443");
+    }
+
+    #[tokio::test]
+    async fn test_parts_roll_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = DirectoryStore::open(dir.path()).unwrap();
+        let policy = RollPolicy { max_bytes: 1, max_age: Duration::from_secs(3600) };
+        let mut archive = ParquetArchive::new(store, None, policy, None);
+
+        archive.write(&[event("acme", 13, serde_json::json!({ "message": "one" }))]).await.unwrap();
+        archive.write(&[event("acme", 13, serde_json::json!({ "message": "two" }))]).await.unwrap();
+        let found = parts(dir.path());
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|p| p.extension().unwrap() == "parquet"));
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use rand::Rng;
+
+use crate::config::{LoadBalancingStrategy, RoutingConfig};
+
+struct GroupState {
+    members: Vec<String>,
+    strategy: LoadBalancingStrategy,
+    cursor: AtomicUsize,
+}
+
+/// Picks the member of a destination group that receives an event.
+pub struct GroupBalancer {
+    groups: HashMap<String, GroupState>,
+}
+
+impl GroupBalancer {
+    /// Members `enabled` rejects are left out; the configuration is fixed
+    /// for the balancer's lifetime, so they could never take events.
+    pub fn new(config: &RoutingConfig, enabled: impl Fn(&str) -> bool) -> Self {
+        let groups = config.destination_groups.iter()
+            .map(|(name, group)| (name.clone(), GroupState {
+                members: group.members.iter().filter(|member| enabled(member)).cloned().collect(),
+                strategy: group.strategy.clone().unwrap_or_else(|| config.load_balancing.clone()),
+                cursor: AtomicUsize::new(0),
+            }))
+            .collect();
+        Self { groups }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.groups.contains_key(name)
+    }
+
+    /// Field whose value keeps a `hash` group's events on one member
+    pub fn hash_field(&self, group: &str) -> Option<&str> {
+        match &self.groups.get(group)?.strategy {
+            LoadBalancingStrategy::Hash { field } => Some(field),
+            _ => None,
+        }
+    }
+
+    /// Chooses among the members `available` accepts. When none are, every
+    /// member is a candidate: the write then fails and is retried rather than
+    /// the event being dropped. `advance` is false for dry runs, which must
+    /// not shift round-robin positions.
+    pub fn select(&self, group: &str, key: Option<&str>, available: impl Fn(&str) -> bool, advance: bool) -> Option<&str> {
+        let state = self.groups.get(group)?;
+        let mut candidates: Vec<&str> = state.members.iter()
+            .map(String::as_str)
+            .filter(|member| available(member))
+            .collect();
+        if candidates.is_empty() {
+            candidates = state.members.iter().map(String::as_str).collect();
+        }
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let tick = || if advance {
+            state.cursor.fetch_add(1, Ordering::Relaxed)
+        } else {
+            state.cursor.load(Ordering::Relaxed)
+        };
+
+        let chosen = match &state.strategy {
+            LoadBalancingStrategy::RoundRobin | LoadBalancingStrategy::LeastConnections => {
+                candidates[tick() % candidates.len()]
+            }
+            LoadBalancingStrategy::Random => candidates[rand::thread_rng().gen_range(0..candidates.len())],
+            LoadBalancingStrategy::Weighted(weights) => {
+                let weight = |member: &str| u64::from(weights.get(member).copied().unwrap_or(1));
+                let total: u64 = candidates.iter().map(|member| weight(member)).sum();
+                if total == 0 {
+                    candidates[tick() % candidates.len()]
+                } else {
+                    let mut slot = tick() as u64 % total;
+                    candidates.iter().copied()
+                        .find(|member| {
+                            let w = weight(member);
+                            if slot < w {
+                                true
+                            } else {
+                                slot -= w;
+                                false
+                            }
+                        })
+                        .unwrap_or(candidates[0])
+                }
+            }
+            // Rendezvous hashing: losing a member only moves the keys it held
+            LoadBalancingStrategy::Hash { .. } => {
+                let key = key.unwrap_or_default();
+                candidates.iter().copied()
+                    .max_by_key(|member| {
+                        let mut hasher = crc32fast::Hasher::new();
+                        hasher.update(member.as_bytes());
+                        hasher.update(&[0]);
+                        hasher.update(key.as_bytes());
+                        hasher.finalize()
+                    })
+                    .unwrap_or(candidates[0])
+            }
+            LoadBalancingStrategy::Failover => candidates[0],
+        };
+        Some(chosen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{DestinationGroup, PipelineConfig};
+
+    fn balancer(strategy: LoadBalancingStrategy) -> GroupBalancer {
+        let mut config = PipelineConfig::default().routing;
+        config.destination_groups.insert("ch".to_string(), DestinationGroup {
+            members: ["ch_a", "ch_disabled", "ch_b", "ch_c"].map(String::from).to_vec(),
+            strategy: Some(strategy),
+        });
+        GroupBalancer::new(&config, |member| member != "ch_disabled")
+    }
+
+    #[test]
+    fn test_failover_and_round_robin() {
+        let failover = balancer(LoadBalancingStrategy::Failover);
+        assert_eq!(failover.select("ch", None, |_| true, true), Some("ch_a"));
+        assert_eq!(failover.select("ch", None, |m| m != "ch_a", true), Some("ch_b"));
+        // Nothing healthy: stay on the primary
+        assert_eq!(failover.select("ch", None, |_| false, true), Some("ch_a"));
+        assert_eq!(failover.select("other", None, |_| true, true), None);
+
+        let round_robin = balancer(LoadBalancingStrategy::RoundRobin);
+        let picks: Vec<_> = (0..4).map(|_| round_robin.select("ch", None, |m| m != "ch_b", true).unwrap()).collect();
+        assert_eq!(picks, ["ch_a", "ch_c", "ch_a", "ch_c"]);
+        // A dry run does not move the cursor
+        assert_eq!(round_robin.select("ch", None, |_| true, false), round_robin.select("ch", None, |_| true, false));
+    }
+
+    #[test]
+    fn test_weighted_and_hash() {
+        let weighted = balancer(LoadBalancingStrategy::Weighted(HashMap::from([
+            ("ch_a".to_string(), 3),
+            ("ch_c".to_string(), 0),
+        ])));
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for _ in 0..40 {
+            *counts.entry(weighted.select("ch", None, |_| true, true).unwrap()).or_default() += 1;
+        }
+        assert_eq!(counts.get("ch_a"), Some(&30));
+        assert_eq!(counts.get("ch_b"), Some(&10));
+        assert_eq!(counts.get("ch_c"), None);
+
+        let hashed = balancer(LoadBalancingStrategy::Hash { field: "tenant_id".to_string() });
+        assert_eq!(hashed.hash_field("ch"), Some("tenant_id"));
+        let home = hashed.select("ch", Some("acme"), |_| true, true).unwrap();
+        assert!((0..10).all(|_| hashed.select("ch", Some("acme"), |_| true, true) == Some(home)));
+        // Only keys on the lost member move
+        let moved = hashed.select("ch", Some("acme"), |m| m != home, true).unwrap();
+        assert_ne!(moved, home);
+        for tenant in ["t1", "t2", "t3", "t4", "t5", "t6"] {
+            let before = hashed.select("ch", Some(tenant), |_| true, true).unwrap();
+            if before != home {
+                assert_eq!(hashed.select("ch", Some(tenant), |m| m != home, true), Some(before));
+            }
+        }
+    }
+}
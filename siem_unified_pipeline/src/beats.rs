@@ -0,0 +1,321 @@
+use std::collections::VecDeque;
+use std::io::Read;
+use flate2::read::ZlibDecoder;
+use serde_json::{Map, Value};
+
+use crate::error::{Result, PipelineError};
+
+const PROTOCOL_VERSION: u8 = b'2';
+const FRAME_WINDOW: u8 = b'W';
+const FRAME_COMPRESSED: u8 = b'C';
+const FRAME_JSON: u8 = b'J';
+const FRAME_DATA: u8 = b'D';
+const FRAME_ACK: u8 = b'A';
+
+/// A single event received from a beat, with its Lumberjack sequence number.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatsEvent {
+    pub sequence: u32,
+    pub fields: Value,
+    /// Size of the encoded event payload in bytes
+    pub size: usize,
+}
+
+/// All events announced by one window frame. The client expects a single ACK
+/// carrying `last_sequence` once the whole batch has been accepted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BeatsBatch {
+    pub events: Vec<BeatsEvent>,
+    pub last_sequence: u32,
+}
+
+enum LumberjackFrame {
+    Window(u32),
+    Compressed(Vec<u8>),
+    Event(BeatsEvent),
+}
+
+/// Incremental decoder for the Lumberjack v2 protocol spoken by Elastic Beats.
+///
+/// Bytes read from the connection are appended to a buffer which `decode`
+/// drains frame by frame; compressed frames are inflated and decoded in place.
+pub struct LumberjackDecoder {
+    max_payload_size: usize,
+    window_size: Option<u32>,
+    events: Vec<BeatsEvent>,
+    ready: VecDeque<BeatsBatch>,
+}
+
+impl LumberjackDecoder {
+    pub fn new(max_payload_size: usize) -> Self {
+        Self {
+            max_payload_size,
+            window_size: None,
+            events: Vec::new(),
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Consume complete frames from `buffer`, returning the next batch whose
+    /// window has been filled. Incomplete trailing frames stay in the buffer.
+    pub fn decode(&mut self, buffer: &mut Vec<u8>) -> Result<Option<BeatsBatch>> {
+        loop {
+            if let Some(batch) = self.ready.pop_front() {
+                return Ok(Some(batch));
+            }
+
+            match parse_frame(buffer, self.max_payload_size)? {
+                Some((frame, consumed)) => {
+                    buffer.drain(..consumed);
+                    self.apply(frame)?;
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+
+    /// Events received since the last window frame that have not formed a
+    /// complete batch yet.
+    pub fn pending_events(&self) -> usize {
+        self.events.len()
+    }
+
+    fn apply(&mut self, frame: LumberjackFrame) -> Result<()> {
+        match frame {
+            LumberjackFrame::Window(size) => {
+                if !self.events.is_empty() {
+                    return Err(PipelineError::parsing(format!(
+                        "Lumberjack window frame received with {} events of the previous window outstanding",
+                        self.events.len()
+                    )));
+                }
+                self.window_size = (size > 0).then_some(size);
+            }
+            LumberjackFrame::Compressed(data) => {
+                let payload = inflate(&data, self.max_payload_size)?;
+                let mut offset = 0;
+                while offset < payload.len() {
+                    match parse_frame(&payload[offset..], self.max_payload_size)? {
+                        Some((frame, consumed)) => {
+                            offset += consumed;
+                            self.apply(frame)?;
+                        }
+                        None => {
+                            return Err(PipelineError::parsing("Truncated frame inside Lumberjack compressed frame"));
+                        }
+                    }
+                }
+            }
+            LumberjackFrame::Event(event) => {
+                let window_size = self.window_size.ok_or_else(|| {
+                    PipelineError::parsing("Lumberjack event received before a window frame")
+                })?;
+                self.events.push(event);
+
+                if self.events.len() >= window_size as usize {
+                    let events = std::mem::take(&mut self.events);
+                    let last_sequence = events.last().map(|e| e.sequence).unwrap_or_default();
+                    self.ready.push_back(BeatsBatch { events, last_sequence });
+                    self.window_size = None;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Encode the ACK frame acknowledging every event up to `sequence`.
+pub fn encode_ack(sequence: u32) -> [u8; 6] {
+    let seq = sequence.to_be_bytes();
+    [PROTOCOL_VERSION, FRAME_ACK, seq[0], seq[1], seq[2], seq[3]]
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    buf.get(offset..offset + 4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn check_size(len: u32, max_payload_size: usize) -> Result<usize> {
+    let len = len as usize;
+    if len > max_payload_size {
+        return Err(PipelineError::parsing(format!(
+            "Lumberjack payload of {} bytes exceeds limit of {} bytes",
+            len, max_payload_size
+        )));
+    }
+    Ok(len)
+}
+
+// Returns the frame and the number of bytes it occupied, or None when `buf`
+// does not yet hold the complete frame.
+fn parse_frame(buf: &[u8], max_payload_size: usize) -> Result<Option<(LumberjackFrame, usize)>> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    if buf[0] != PROTOCOL_VERSION {
+        return Err(PipelineError::parsing(format!(
+            "Unsupported Lumberjack protocol version {:?}",
+            buf[0] as char
+        )));
+    }
+
+    match buf[1] {
+        FRAME_WINDOW => Ok(read_u32(buf, 2).map(|size| (LumberjackFrame::Window(size), 6))),
+        FRAME_COMPRESSED => {
+            let Some(len) = read_u32(buf, 2) else { return Ok(None) };
+            let len = check_size(len, max_payload_size)?;
+            Ok(buf.get(6..6 + len)
+                .map(|data| (LumberjackFrame::Compressed(data.to_vec()), 6 + len)))
+        }
+        FRAME_JSON => {
+            let (Some(sequence), Some(len)) = (read_u32(buf, 2), read_u32(buf, 6)) else { return Ok(None) };
+            let len = check_size(len, max_payload_size)?;
+            let Some(payload) = buf.get(10..10 + len) else { return Ok(None) };
+
+            let fields = serde_json::from_slice(payload).map_err(|e| {
+                PipelineError::parsing(format!("Invalid JSON in Lumberjack event {}: {}", sequence, e))
+            })?;
+            Ok(Some((LumberjackFrame::Event(BeatsEvent { sequence, fields, size: len }), 10 + len)))
+        }
+        FRAME_DATA => parse_data_frame(buf, max_payload_size),
+        other => Err(PipelineError::parsing(format!(
+            "Unknown Lumberjack frame type {:?}",
+            other as char
+        ))),
+    }
+}
+
+// Key/value data frames predate JSON frames; values are always strings.
+fn parse_data_frame(buf: &[u8], max_payload_size: usize) -> Result<Option<(LumberjackFrame, usize)>> {
+    let (Some(sequence), Some(pairs)) = (read_u32(buf, 2), read_u32(buf, 6)) else { return Ok(None) };
+
+    let mut offset = 10;
+    let mut fields = Map::new();
+    for _ in 0..pairs {
+        let mut strings = [String::new(), String::new()];
+        for slot in strings.iter_mut() {
+            let Some(len) = read_u32(buf, offset) else { return Ok(None) };
+            let len = check_size(len, max_payload_size)?;
+            let Some(bytes) = buf.get(offset + 4..offset + 4 + len) else { return Ok(None) };
+            *slot = String::from_utf8_lossy(bytes).into_owned();
+            offset += 4 + len;
+            if offset - 10 > max_payload_size {
+                return Err(PipelineError::parsing(format!(
+                    "Lumberjack data frame exceeds limit of {} bytes",
+                    max_payload_size
+                )));
+            }
+        }
+        let [key, value] = strings;
+        fields.insert(key, Value::String(value));
+    }
+
+    let event = BeatsEvent { sequence, fields: Value::Object(fields), size: offset - 10 };
+    Ok(Some((LumberjackFrame::Event(event), offset)))
+}
+
+fn inflate(data: &[u8], max_payload_size: usize) -> Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    ZlibDecoder::new(data)
+        .take(max_payload_size as u64 + 1)
+        .read_to_end(&mut payload)
+        .map_err(|e| PipelineError::compression(format!("Failed to inflate Lumberjack frame: {}", e)))?;
+
+    if payload.len() > max_payload_size {
+        return Err(PipelineError::parsing(format!(
+            "Inflated Lumberjack frame exceeds limit of {} bytes",
+            max_payload_size
+        )));
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use serde_json::json;
+    use std::io::Write;
+
+    fn window(size: u32) -> Vec<u8> {
+        let mut frame = vec![b'2', b'W'];
+        frame.extend_from_slice(&size.to_be_bytes());
+        frame
+    }
+
+    fn json_frame(sequence: u32, event: &Value) -> Vec<u8> {
+        let payload = serde_json::to_vec(event).unwrap();
+        let mut frame = vec![b'2', b'J'];
+        frame.extend_from_slice(&sequence.to_be_bytes());
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    fn compressed(frames: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(frames).unwrap();
+        let data = encoder.finish().unwrap();
+        let mut frame = vec![b'2', b'C'];
+        frame.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&data);
+        frame
+    }
+
+    #[test]
+    fn test_window_batches_across_compressed_and_split_reads() {
+        let first = json!({"message": "one", "agent": {"type": "filebeat"}, "@metadata": {"beat": "filebeat"}});
+        let second = json!({"message": "two", "host": {"name": "web-1"}});
+
+        let mut inner = json_frame(1, &first);
+        inner.extend(json_frame(2, &second));
+        let mut stream = window(2);
+        stream.extend(compressed(&inner));
+        stream.extend(window(1));
+        stream.extend(json_frame(3, &json!({"message": "three"})));
+
+        let mut decoder = LumberjackDecoder::new(1024 * 1024);
+        let mut buffer = Vec::new();
+        let mut batches = Vec::new();
+        for chunk in stream.chunks(7) {
+            buffer.extend_from_slice(chunk);
+            while let Some(batch) = decoder.decode(&mut buffer).unwrap() {
+                batches.push(batch);
+            }
+        }
+
+        assert!(buffer.is_empty());
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].last_sequence, 2);
+        assert_eq!(batches[0].events[0].fields, first);
+        assert_eq!(batches[0].events[1].fields["host"]["name"], "web-1");
+        assert_eq!(batches[1].last_sequence, 3);
+        assert_eq!(encode_ack(3), [b'2', b'A', 0, 0, 0, 3]);
+    }
+
+    #[test]
+    fn test_data_frames_and_protocol_errors() {
+        let mut stream = window(1);
+        stream.extend_from_slice(&[b'2', b'D', 0, 0, 0, 9, 0, 0, 0, 1]);
+        stream.extend_from_slice(&[0, 0, 0, 4]);
+        stream.extend_from_slice(b"line");
+        stream.extend_from_slice(&[0, 0, 0, 2]);
+        stream.extend_from_slice(b"hi");
+
+        let mut decoder = LumberjackDecoder::new(1024);
+        let batch = decoder.decode(&mut stream).unwrap().unwrap();
+        assert_eq!(batch.last_sequence, 9);
+        assert_eq!(batch.events[0].fields, json!({"line": "hi"}));
+
+        let mut oversized = window(1);
+        oversized.extend(json_frame(1, &json!({"message": "x".repeat(2048)})));
+        assert!(LumberjackDecoder::new(1024).decode(&mut oversized).is_err());
+
+        let mut no_window = json_frame(1, &json!({}));
+        assert!(LumberjackDecoder::new(1024).decode(&mut no_window).is_err());
+
+        let mut wrong_version = vec![b'1', b'W', 0, 0, 0, 1];
+        assert!(LumberjackDecoder::new(1024).decode(&mut wrong_version).is_err());
+    }
+}
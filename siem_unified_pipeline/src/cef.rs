@@ -0,0 +1,423 @@
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+
+use crate::error::{Result, PipelineError};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CefRecord {
+    pub version: String,
+    pub device_vendor: String,
+    pub device_product: String,
+    pub device_version: String,
+    pub signature_id: String,
+    pub name: String,
+    pub severity: String,
+    /// Text before `CEF:`, usually a syslog header
+    pub prefix: String,
+    pub extensions: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeefRecord {
+    pub version: String,
+    pub vendor: String,
+    pub product: String,
+    pub product_version: String,
+    pub event_id: String,
+    pub delimiter: char,
+    pub prefix: String,
+    pub attributes: Vec<(String, String)>,
+}
+
+impl CefRecord {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.extensions.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+impl LeefRecord {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.attributes.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Split `input` on unescaped `|`, returning at most `count` header fields and the rest.
+fn split_header(input: &str, count: usize) -> Option<(Vec<String>, &str)> {
+    let bytes = input.as_bytes();
+    let mut fields = Vec::with_capacity(count);
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() && fields.len() < count {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'|' => {
+                fields.push(unescape_header(&input[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if fields.len() < count {
+        return None;
+    }
+    Some((fields, input.get(start..).unwrap_or("")))
+}
+
+fn unescape_header(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(next @ ('|' | '\\')) => out.push(next),
+                Some(next) => {
+                    out.push('\\');
+                    out.push(next);
+                }
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out.trim().to_string()
+}
+
+fn unescape_extension(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => out.push('\n'),
+                Some('r') => out.push('\r'),
+                Some(next @ ('=' | '\\' | '|')) => out.push(next),
+                Some(next) => {
+                    out.push('\\');
+                    out.push(next);
+                }
+                None => out.push('\\'),
+            }
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn is_key_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'-' | b'[' | b']')
+}
+
+/// Parse a CEF extension string into ordered key/value pairs.
+///
+/// Pairs are space separated and values may themselves contain spaces, so a new
+/// pair starts only at an unescaped `=` preceded by a bare key and a space.
+pub fn parse_cef_extensions(input: &str) -> Vec<(String, String)> {
+    let bytes = input.as_bytes();
+
+    // (key start, '=' position) for every unescaped '=' that follows a bare key
+    let mut boundaries = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'=' => {
+                let mut start = i;
+                while start > 0 && is_key_byte(bytes[start - 1]) {
+                    start -= 1;
+                }
+                if start < i && (start == 0 || bytes[start - 1] == b' ') {
+                    boundaries.push((start, i));
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    boundaries.iter().enumerate().map(|(index, &(start, eq))| {
+        let end = boundaries.get(index + 1).map(|&(next, _)| next).unwrap_or(bytes.len());
+        let key = input[start..eq].to_string();
+        let value = unescape_extension(input[eq + 1..end].trim_end_matches(' '));
+        (key, value)
+    }).collect()
+}
+
+/// Parse a CEF record, tolerating a syslog header before `CEF:`.
+pub fn parse_cef(line: &str) -> Result<CefRecord> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let offset = line.find("CEF:")
+        .ok_or_else(|| PipelineError::parsing("Message is not CEF: missing 'CEF:' marker"))?;
+    let prefix = line[..offset].trim().to_string();
+
+    let (header, extension) = split_header(&line[offset + 4..], 7)
+        .ok_or_else(|| PipelineError::parsing("CEF header must have 7 pipe-delimited fields"))?;
+
+    let mut header = header.into_iter();
+    let mut next = || header.next().unwrap_or_default();
+
+    Ok(CefRecord {
+        version: next(),
+        device_vendor: next(),
+        device_product: next(),
+        device_version: next(),
+        signature_id: next(),
+        name: next(),
+        severity: next(),
+        prefix,
+        extensions: parse_cef_extensions(extension),
+    })
+}
+
+/// Decode a LEEF 2.0 delimiter: a literal character or a hex code such as `x09` / `0x5E`.
+fn parse_leef_delimiter(value: &str) -> Result<char> {
+    let hex = value.strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .or_else(|| value.strip_prefix("\\x"))
+        .or_else(|| value.strip_prefix('x'))
+        .or_else(|| value.strip_prefix('X'));
+
+    match (hex, value.chars().count()) {
+        (_, 0) => Ok('\t'),
+        (_, 1) => Ok(value.chars().next().unwrap_or('\t')),
+        (None, _) if value == "\\t" => Ok('\t'),
+        (Some(hex), _) => u32::from_str_radix(hex, 16).ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| PipelineError::parsing(format!("Invalid LEEF delimiter '{}'", value))),
+        (None, _) => Err(PipelineError::parsing(format!("Invalid LEEF delimiter '{}'", value))),
+    }
+}
+
+/// Parse a LEEF 1.0 or 2.0 record, tolerating a syslog header before `LEEF:`.
+///
+/// Attributes are split on tab for LEEF 1.0, or on the delimiter declared in
+/// the sixth LEEF 2.0 header field.
+pub fn parse_leef(line: &str) -> Result<LeefRecord> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let offset = line.find("LEEF:")
+        .ok_or_else(|| PipelineError::parsing("Message is not LEEF: missing 'LEEF:' marker"))?;
+    let prefix = line[..offset].trim().to_string();
+    let body = &line[offset + 5..];
+
+    let version = body.split('|').next().unwrap_or_default().trim().to_string();
+    let header_fields = if version.starts_with('2') { 6 } else { 5 };
+
+    let (header, attributes) = split_header(body, header_fields)
+        .ok_or_else(|| PipelineError::parsing(format!("LEEF {} header must have {} pipe-delimited fields", version, header_fields)))?;
+
+    let delimiter = match header.get(5) {
+        Some(raw) => parse_leef_delimiter(raw)?,
+        None => '\t',
+    };
+
+    let mut pairs = Vec::new();
+    let mut current = String::new();
+    let mut chars = attributes.chars().peekable();
+    let mut flush = |current: &mut String| {
+        if let Some((key, value)) = current.split_once('=') {
+            let key = key.trim();
+            if !key.is_empty() {
+                pairs.push((key.to_string(), value.replace("\\=", "=").replace("\\|", "|")));
+            }
+        }
+        current.clear();
+    };
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek() == Some(&delimiter) {
+            current.push(delimiter);
+            chars.next();
+        } else if c == delimiter {
+            flush(&mut current);
+        } else {
+            current.push(c);
+        }
+    }
+    flush(&mut current);
+
+    let mut header = header.into_iter();
+    Ok(LeefRecord {
+        version: header.next().unwrap_or_default(),
+        vendor: header.next().unwrap_or_default(),
+        product: header.next().unwrap_or_default(),
+        product_version: header.next().unwrap_or_default(),
+        event_id: header.next().unwrap_or_default(),
+        delimiter,
+        prefix,
+        attributes: pairs,
+    })
+}
+
+/// Canonical (`parsed_event.rs`) name for a well-known CEF or LEEF attribute key.
+pub fn canonical_field(key: &str) -> Option<&'static str> {
+    let field = match key {
+        "src" | "sourceAddress" | "srcPreNAT" => "source.ip",
+        "dst" | "destinationAddress" | "dstPreNAT" => "destination.ip",
+        "spt" | "sourcePort" | "srcPort" => "source.port",
+        "dpt" | "destinationPort" | "dstPort" => "destination.port",
+        "proto" | "transportProtocol" => "network.protocol",
+        "suser" | "sourceUserName" | "usrName" | "accountName" => "user.name",
+        "dvchost" | "deviceHostName" | "identHostName" => "host.name",
+        "sproc" | "sourceProcessName" | "dproc" | "destinationProcessName" => "process.name",
+        "spid" | "sourceProcessId" | "dpid" | "destinationProcessId" => "process.pid",
+        "act" | "deviceAction" | "action" => "event.action",
+        "externalId" => "event.id",
+        "outcome" | "eventOutcome" => "event.outcome",
+        "request" | "requestUrl" | "url" => "url.original",
+        "requestMethod" => "http.request.method",
+        "requestClientApplication" | "userAgent" => "user_agent.original",
+        "fname" | "fileName" => "file.name",
+        "filePath" => "file.path",
+        "msg" | "message" => "message",
+        _ => return None,
+    };
+    Some(field)
+}
+
+/// Bucket a CEF (0-10, or Low/Medium/High/Very-High) or LEEF (1-10) severity.
+pub fn normalize_severity(severity: &str) -> String {
+    let severity = severity.trim();
+    match severity.parse::<u8>() {
+        Ok(0..=3) => "low".to_string(),
+        Ok(4..=6) => "medium".to_string(),
+        Ok(7..=8) => "high".to_string(),
+        Ok(_) => "critical".to_string(),
+        Err(_) => match severity.to_lowercase().as_str() {
+            "" | "unknown" => "info".to_string(),
+            "very-high" | "very high" | "veryhigh" => "critical".to_string(),
+            other => other.to_string(),
+        },
+    }
+}
+
+/// Translate the Java `SimpleDateFormat` patterns devices put in `devTimeFormat`.
+fn java_to_chrono_format(format: &str) -> String {
+    const TOKENS: [(&str, &str); 13] = [
+        ("yyyy", "%Y"), ("yy", "%y"), ("MMM", "%b"), ("MM", "%m"), ("dd", "%d"),
+        ("HH", "%H"), ("hh", "%I"), ("mm", "%M"), ("ss", "%S"), ("SSS", "%3f"),
+        ("a", "%p"), ("Z", "%z"), ("z", "%Z"),
+    ];
+
+    let mut out = String::new();
+    let mut rest = format;
+    'outer: while !rest.is_empty() {
+        for (java, chrono) in TOKENS {
+            if let Some(tail) = rest.strip_prefix(java) {
+                out.push_str(chrono);
+                rest = tail;
+                continue 'outer;
+            }
+        }
+        let c = rest.chars().next().unwrap_or_default();
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+/// Parse device timestamps: epoch milliseconds/seconds, RFC 3339, the CEF
+/// `MMM dd yyyy HH:mm:ss` family, or an explicit Java-style format.
+pub fn parse_device_time(value: &str, java_format: Option<&str>) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+
+    if let Some(format) = java_format {
+        let format = java_to_chrono_format(format);
+        if let Ok(ts) = DateTime::parse_from_str(value, &format) {
+            return Some(ts.with_timezone(&Utc));
+        }
+        if let Ok(naive) = NaiveDateTime::parse_from_str(value, &format) {
+            return Some(Utc.from_utc_datetime(&naive));
+        }
+    }
+
+    if let Ok(number) = value.parse::<i64>() {
+        return if number.abs() >= 100_000_000_000 {
+            Utc.timestamp_millis_opt(number).single()
+        } else {
+            Utc.timestamp_opt(number, 0).single()
+        };
+    }
+
+    if let Ok(ts) = DateTime::parse_from_rfc3339(value) {
+        return Some(ts.with_timezone(&Utc));
+    }
+
+    for format in ["%b %d %Y %H:%M:%S%.f", "%b %d %Y %H:%M:%S", "%b %d %H:%M:%S %Y", "%Y-%m-%d %H:%M:%S"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(Utc.from_utc_datetime(&naive));
+        }
+    }
+    // Trailing zone name, e.g. "Nov 19 2019 22:05:02 UTC"
+    let (without_zone, _) = value.rsplit_once(' ')?;
+    NaiveDateTime::parse_from_str(without_zone, "%b %d %Y %H:%M:%S").ok()
+        .map(|naive| Utc.from_utc_datetime(&naive))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arcsight_cef_with_escapes_and_multiword_values() {
+        let record = parse_cef(
+            "Sep 19 08:26:10 host CEF:0|Security|threatmanager|1.0|100|detected a \\| in message|10|src=10.0.0.1 act=blocked a \\= dst=1.1.1.1"
+        ).unwrap();
+        assert_eq!(record.prefix, "Sep 19 08:26:10 host");
+        assert_eq!(record.device_vendor, "Security");
+        assert_eq!(record.name, "detected a | in message");
+        assert_eq!(record.severity, "10");
+        assert_eq!(record.get("act"), Some("blocked a ="));
+        assert_eq!(record.get("dst"), Some("1.1.1.1"));
+
+        let record = parse_cef(
+            "CEF:0|ArcSight|ArcSight|7.4.0.8166.0|agent:030|Agent [cef_syslog] type [syslog] started|Low|\
+             rt=1574201102245 dvchost=arcsight-01 suser=jdoe cs1Label=Policy cs1=Default Web Policy \
+             request=https://example.com/a?b=c msg=Path C:\\\\Temp\\\\x.exe\\nsecond line"
+        ).unwrap();
+        assert_eq!(record.signature_id, "agent:030");
+        assert_eq!(record.get("cs1"), Some("Default Web Policy"));
+        assert_eq!(record.get("request"), Some("https://example.com/a?b=c"));
+        assert_eq!(record.get("msg"), Some("Path C:\\Temp\\x.exe\nsecond line"));
+        assert_eq!(normalize_severity(&record.severity), "low");
+        assert_eq!(
+            parse_device_time(record.get("rt").unwrap(), None).unwrap().to_rfc3339(),
+            "2019-11-19T22:05:02.245+00:00"
+        );
+        assert_eq!(canonical_field("suser"), Some("user.name"));
+        // CEF does not say which algorithm produced a hash, so it keeps its own key
+        assert_eq!(canonical_field("fileHash"), None);
+        assert_eq!(canonical_field("reason"), None);
+
+        assert!(parse_cef("CEF:0|only|three").is_err());
+    }
+
+    #[test]
+    fn test_qradar_leef_1_and_2() {
+        let record = parse_leef(
+            "LEEF:1.0|Microsoft|MSExchange|4.0 SP1|15345|src=192.0.2.0\tdst=172.50.123.1\tsev=5\tcat=anomaly\tsrcPort=81\tdstPort=21\tusrName=joe.black"
+        ).unwrap();
+        assert_eq!(record.version, "1.0");
+        assert_eq!(record.event_id, "15345");
+        assert_eq!(record.delimiter, '\t');
+        assert_eq!(record.get("usrName"), Some("joe.black"));
+        assert_eq!(record.attributes.len(), 7);
+
+        let record = parse_leef(
+            "Jan 18 11:07:53 192.0.2.1 LEEF:2.0|Lancope|StealthWatch|1.0|41|^|src=10.0.1.8^dst=10.0.0.5^sev=5^srcPort=81^dstPort=21^url=http://x/?a=b^devTime=Oct 12 2022 10:22:00^devTimeFormat=MMM dd yyyy HH:mm:ss"
+        ).unwrap();
+        assert_eq!(record.delimiter, '^');
+        assert_eq!(record.get("url"), Some("http://x/?a=b"));
+        assert_eq!(
+            parse_device_time(record.get("devTime").unwrap(), record.get("devTimeFormat")).unwrap().to_rfc3339(),
+            "2022-10-12T10:22:00+00:00"
+        );
+
+        let record = parse_leef("LEEF:2.0|Vendor|Product|1|ID|x7C|a=1|b=2 3").unwrap();
+        assert_eq!(record.delimiter, '|');
+        assert_eq!(record.attributes, vec![("a".to_string(), "1".to_string()), ("b".to_string(), "2 3".to_string())]);
+
+        assert!(parse_leef("LEEF:2.0|Vendor|Product|1|ID|xZZ|a=1").is_err());
+    }
+}
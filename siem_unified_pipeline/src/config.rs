@@ -17,6 +17,14 @@ pub struct PipelineConfig {
     pub security: SecurityConfig,
     pub performance: PerformanceConfig,
     pub rate_limiting: RateLimitingConfig,
+    #[serde(default)]
+    pub detection: DetectionConfig,
+    #[serde(default)]
+    pub wal: WalConfig,
+    #[serde(default)]
+    pub geoip: GeoIpConfig,
+    #[serde(default)]
+    pub threat_intel: ThreatIntelConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,17 +54,134 @@ pub struct DataSource {
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum SourceType {
-    Syslog { port: u16, protocol: String },
-    Http { endpoint: String, method: String },
+    Syslog {
+        port: u16,
+        /// `udp` (default), `tcp`, or `tls` using `SecurityConfig.tls`
+        protocol: String,
+        /// Largest single message accepted on stream transports, in bytes
+        #[serde(default)]
+        max_message_size: Option<usize>,
+    },
+    Http {
+        /// URL fetched in `poll` mode, or the `host:port` listened on in `receive` mode
+        endpoint: String,
+        method: String,
+        /// `poll` (default) or `receive`
+        #[serde(default)]
+        mode: Option<String>,
+        /// Request path accepted in receive mode; defaults to `/`
+        #[serde(default)]
+        path: Option<String>,
+        /// Credentials required from senders in receive mode
+        #[serde(default)]
+        auth: Option<HttpSourceAuth>,
+        /// Largest request body accepted after decompression, in bytes
+        #[serde(default)]
+        max_body_size: Option<usize>,
+        /// Events buffered ahead of the pipeline before senders get 429
+        #[serde(default)]
+        queue_size: Option<usize>,
+        /// Poll interval such as `30s`; defaults to one minute
+        #[serde(default)]
+        poll_interval: Option<String>,
+        /// Extra headers sent with each poll request
+        #[serde(default)]
+        headers: HashMap<String, String>,
+        /// JSONPath selecting the records in a polled response, e.g. `$.data[*]`
+        #[serde(default)]
+        records_path: Option<String>,
+        #[serde(default)]
+        pagination: Option<HttpPagination>,
+    },
     Kafka { topic: String, brokers: Vec<String> },
-    File { path: String, watch: bool },
-    Database { connection_string: String, query: String },
-    S3 { bucket: String, prefix: String, region: String },
+    File {
+        /// File path or glob, e.g. `/var/log/app/**/*.log`
+        path: String,
+        /// React to filesystem notifications as well as polling
+        watch: bool,
+        /// Regex matching the first line of an event; other lines are
+        /// appended to the event before them
+        #[serde(default)]
+        multiline_start: Option<String>,
+        /// Where read offsets are kept; defaults to `data/checkpoints/<source>.json`
+        #[serde(default)]
+        checkpoint_path: Option<String>,
+    },
+    Database {
+        /// `postgres://...` or `sqlite://<path>`
+        connection_string: String,
+        query: String,
+        /// Increasing column (id or timestamp) whose last delivered value
+        /// is kept as the watermark; without it the whole query is re-read
+        /// on every poll
+        #[serde(default)]
+        tracking_column: Option<String>,
+        /// Poll interval such as `30s`; defaults to one minute
+        #[serde(default)]
+        poll_interval: Option<String>,
+        /// Rows fetched per page
+        #[serde(default)]
+        batch_size: Option<usize>,
+        /// Where the watermark is kept; defaults to `data/checkpoints/<source>.watermark.json`
+        #[serde(default)]
+        checkpoint_path: Option<String>,
+    },
+    S3 {
+        bucket: String,
+        prefix: String,
+        region: String,
+        /// Endpoint override for S3-compatible stores such as MinIO
+        #[serde(default)]
+        endpoint: Option<String>,
+        /// How often the prefix is listed; defaults to five minutes
+        #[serde(default)]
+        poll_interval: Option<String>,
+        /// Where processed keys and ETags are kept; defaults to `data/checkpoints/<source>.s3.json`
+        #[serde(default)]
+        checkpoint_path: Option<String>,
+    },
     Beats { port: u16 },
-    Fluentd { port: u16 },
+    Fluentd {
+        port: u16,
+        /// Require the Forward protocol shared-key handshake
+        #[serde(default)]
+        shared_key: Option<String>,
+        /// Hostname announced in the handshake; defaults to `$HOSTNAME`
+        #[serde(default)]
+        self_hostname: Option<String>,
+    },
     Custom { plugin: String, config: HashMap<String, String> },
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum HttpSourceAuth {
+    /// `Authorization: Bearer <token>` with any of the listed tokens
+    Bearer { tokens: Vec<String> },
+    /// Hex HMAC-SHA256 of the raw body, optionally prefixed with `sha256=`
+    Hmac {
+        secret: String,
+        /// Header carrying the signature; defaults to `X-Signature`
+        #[serde(default)]
+        header: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct HttpPagination {
+    /// JSONPath of the next-page cursor in each response
+    pub cursor_path: String,
+    /// Query parameter the cursor is sent in; without one the cursor is
+    /// followed as the next page URL
+    #[serde(default)]
+    pub cursor_param: Option<String>,
+    /// Upper bound on pages fetched per poll; defaults to 100
+    #[serde(default)]
+    pub max_pages: Option<usize>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct SourceConfig {
@@ -66,9 +191,12 @@ pub struct SourceConfig {
     pub timestamp_field: Option<String>,
     pub timestamp_format: Option<String>,
     pub fields: HashMap<String, FieldConfig>,
+    /// UTC offset (`+05:30`, `-0800`, `UTC`) for device timestamps sent without one
+    #[serde(default)]
+    pub timezone: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum DataFormat {
     Json,
@@ -157,6 +285,12 @@ pub enum TransformationStep {
     Aggregate {
         window: String,
         functions: Vec<AggregateFunction>,
+        /// Fields whose values partition each window into separate summaries
+        #[serde(default)]
+        group_by: Vec<String>,
+        /// Hop between window starts; windows are tumbling when unset
+        #[serde(default)]
+        slide: Option<String>,
     },
     Custom {
         plugin: String,
@@ -198,6 +332,25 @@ pub struct DataDestination {
     pub batch_size: usize,
     pub flush_interval: u64,
     pub retry_attempts: u32,
+    /// Always on for Splunk HEC and webhook destinations, with defaults when unset
+    #[serde(default)]
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+/// Stops writes to a destination after `failure_threshold` consecutive
+/// failures, then lets one batch through every `reset_timeout_secs` to
+/// probe whether it has recovered.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default)]
+pub struct CircuitBreakerConfig {
+    pub failure_threshold: u32,
+    pub reset_timeout_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self { failure_threshold: 5, reset_timeout_secs: 30 }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -222,6 +375,9 @@ pub enum DestinationType {
         bucket: String,
         prefix: String,
         region: String,
+        /// When Parquet parts are rolled; 128 MB or one hour when unset
+        #[serde(default)]
+        rotation: Option<FileRotation>,
     },
 
     File {
@@ -233,12 +389,69 @@ pub enum DestinationType {
         method: String,
         headers: HashMap<String, String>,
     },
+    /// Splunk HTTP Event Collector
+    SplunkHec {
+        /// Base URL of the collector, e.g. `https://splunk:8088`
+        endpoint: String,
+        token: String,
+        #[serde(default)]
+        index: Option<String>,
+        /// Defaults to the event's `source_type`
+        #[serde(default)]
+        sourcetype: Option<String>,
+        /// Index per event `source_type`, overriding `index`
+        #[serde(default)]
+        indexes: HashMap<String, String>,
+        /// Sourcetype per event `source_type`, overriding `sourcetype`
+        #[serde(default)]
+        sourcetypes: HashMap<String, String>,
+        /// Channel GUID for indexer acknowledgement; when set, a batch only
+        /// counts as written once Splunk acknowledges it
+        #[serde(default)]
+        ack_channel: Option<String>,
+    },
+    /// HTTP endpoint receiving payloads rendered from a template
+    Webhook {
+        url: String,
+        #[serde(default = "default_webhook_method")]
+        method: String,
+        #[serde(default)]
+        headers: HashMap<String, String>,
+        /// Payload for one event; `{{field}}` is replaced with the event's
+        /// field (dot notation, then metadata), `{{event}}` with its data
+        template: String,
+        #[serde(default = "default_webhook_content_type")]
+        content_type: String,
+        #[serde(default)]
+        batch_format: WebhookBatchFormat,
+    },
     Custom {
         plugin: String,
         config: HashMap<String, String>,
     },
 }
 
+fn default_webhook_method() -> String {
+    "POST".to_string()
+}
+
+fn default_webhook_content_type() -> String {
+    "application/json".to_string()
+}
+
+/// How the rendered payloads of a batch are sent to a webhook.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookBatchFormat {
+    /// One request whose body is a JSON array of the payloads
+    #[default]
+    JsonArray,
+    /// One request with a payload per line
+    Lines,
+    /// One request per payload, for endpoints that take a single message
+    Single,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct DestinationConfig {
@@ -275,7 +488,28 @@ pub enum PartitioningStrategy {
 pub struct RoutingConfig {
     pub rules: Vec<RoutingRule>,
     pub default_destination: String,
+    /// Strategy for destination groups that do not set their own
     pub load_balancing: LoadBalancingStrategy,
+    /// Named sets of destinations, usable wherever a destination name is;
+    /// each event goes to one member
+    #[serde(default)]
+    pub destination_groups: HashMap<String, DestinationGroup>,
+    /// Seconds between destination health probes; 0 disables them
+    #[serde(default = "default_health_check_interval")]
+    pub health_check_interval_secs: u64,
+}
+
+fn default_health_check_interval() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct DestinationGroup {
+    /// Member destinations; `failover` prefers them in this order
+    pub members: Vec<String>,
+    #[serde(default)]
+    pub strategy: Option<LoadBalancingStrategy>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -293,8 +527,15 @@ pub struct RoutingRule {
 pub enum LoadBalancingStrategy {
     RoundRobin,
     Random,
+    /// Relative weight per member; members left out weigh 1
     Weighted(HashMap<String, u32>),
+    /// Connection counts are not tracked per destination, so this balances
+    /// like `round_robin`
     LeastConnections,
+    /// Sticky by the value of `field` (e.g. `tenant_id` or `hostname`)
+    Hash { field: String },
+    /// Everything goes to the first member that is not unhealthy
+    Failover,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -428,9 +669,21 @@ pub struct WorkersConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct BuffersConfig {
+    /// Capacity of the queue between the sources and the processing loop
     pub event_buffer_size: usize,
+    /// Events held in batches waiting for parallel workers
     pub batch_buffer_size: usize,
     pub flush_interval_ms: u64,
+    /// Overflow file used before sources are made to wait
+    #[serde(default)]
+    pub spill: Option<SpillConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct SpillConfig {
+    pub path: String,
+    pub max_size_mb: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -457,6 +710,144 @@ pub struct RateLimitingConfig {
     pub burst_size: u32,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default)]
+pub struct DetectionConfig {
+    pub enabled: bool,
+    /// Directory of Sigma `.yml` rules loaded at startup
+    pub sigma_rules_path: Option<String>,
+    pub max_recent_alerts: usize,
+    /// File where correlation window state is snapshotted between restarts
+    pub correlation_state_path: Option<String>,
+    pub correlation_snapshot_interval_secs: u64,
+    pub max_correlation_groups: usize,
+    pub max_events_per_group: usize,
+}
+
+impl Default for DetectionConfig {
+    fn default() -> Self {
+        DetectionConfig {
+            enabled: true,
+            sigma_rules_path: None,
+            max_recent_alerts: 1000,
+            correlation_state_path: None,
+            correlation_snapshot_interval_secs: 60,
+            max_correlation_groups: 100000,
+            max_events_per_group: 1000,
+        }
+    }
+}
+
+/// Write-ahead log of ingested events, acknowledged once every routed
+/// destination has stored them and replayed on startup otherwise.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default)]
+pub struct WalConfig {
+    pub enabled: bool,
+    /// Directory holding the segment files
+    pub path: String,
+    pub fsync: FsyncPolicy,
+    /// How often the active segment is synced under the `interval` policy
+    pub fsync_interval_ms: u64,
+    pub segment_size_mb: u64,
+    /// Sources are made to wait once unacknowledged events reach this size
+    pub max_size_mb: u64,
+}
+
+impl Default for WalConfig {
+    fn default() -> Self {
+        WalConfig {
+            enabled: false,
+            path: "data/wal".to_string(),
+            fsync: FsyncPolicy::Interval,
+            fsync_interval_ms: 1000,
+            segment_size_mb: 64,
+            max_size_mb: 4096,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// Sync after every appended event
+    Always,
+    /// Sync the active segment every `fsync_interval_ms`
+    Interval,
+    /// Leave flushing to the operating system
+    Never,
+}
+
+/// Offline location and ASN lookups for the `geoip` enricher, backed by
+/// MaxMind `.mmdb` files that are reloaded when they change on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default)]
+pub struct GeoIpConfig {
+    /// GeoLite2 or GeoIP2 City database
+    pub city_database: Option<String>,
+    /// GeoLite2 or GeoIP2 ASN database
+    pub asn_database: Option<String>,
+    /// Addresses whose results, including misses, are kept in memory
+    pub cache_size: usize,
+    /// How often the database files are checked for a newer version
+    pub reload_check_interval_secs: u64,
+    /// Event fields holding the source address, tried in order
+    pub source_ip_fields: Vec<String>,
+    /// Event fields holding the destination address, tried in order
+    pub destination_ip_fields: Vec<String>,
+}
+
+impl Default for GeoIpConfig {
+    fn default() -> Self {
+        GeoIpConfig {
+            city_database: None,
+            asn_database: None,
+            cache_size: 10000,
+            reload_check_interval_secs: 60,
+            source_ip_fields: ["source_ip", "src_ip", "source.ip"].map(String::from).to_vec(),
+            destination_ip_fields: ["destination_ip", "dst_ip", "dest_ip", "destination.ip"].map(String::from).to_vec(),
+        }
+    }
+}
+
+/// Indicators of compromise for the `threat_intel` enricher, loaded from
+/// the ClickHouse table `siem_threat_intel` populates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", default)]
+pub struct ThreatIntelConfig {
+    pub enabled: bool,
+    /// ClickHouse HTTP interface
+    pub url: String,
+    pub table: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// How often indicators added since the last load are fetched
+    pub refresh_interval_secs: u64,
+    /// How often the whole table is reloaded, dropping deleted indicators
+    pub full_reload_interval_secs: u64,
+    /// Event fields checked against the indicators, by their value's shape
+    pub fields: Vec<String>,
+}
+
+impl Default for ThreatIntelConfig {
+    fn default() -> Self {
+        ThreatIntelConfig {
+            enabled: false,
+            url: "http://localhost:8123".to_string(),
+            table: "dev.threat_intel".to_string(),
+            username: None,
+            password: None,
+            refresh_interval_secs: 60,
+            full_reload_interval_secs: 3600,
+            fields: [
+                "source_ip", "src_ip", "destination_ip", "dst_ip", "dest_ip",
+                "domain", "hostname", "dns.question.name", "url", "url.original",
+                "file_hash", "hash.md5", "hash.sha1", "hash.sha256",
+            ].map(String::from).to_vec(),
+        }
+    }
+}
+
 impl PipelineConfig {
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
         let content = std::fs::read_to_string(path)
@@ -556,17 +947,56 @@ impl PipelineConfig {
     }
     
     pub fn validate(&self) -> Result<()> {
+        let known = |name: &str| self.destinations.contains_key(name)
+            || self.routing.destination_groups.contains_key(name);
+        
         // Validate that referenced destinations exist
-        if !self.destinations.contains_key(&self.routing.default_destination) {
+        if !known(&self.routing.default_destination) {
             return Err(PipelineError::ConfigError(
                 format!("Default destination '{}' not found", self.routing.default_destination)
             ));
         }
         
+        // Groups hold concrete destinations only, so they never nest
+        for (name, group) in &self.routing.destination_groups {
+            if self.destinations.contains_key(name) {
+                return Err(PipelineError::ConfigError(
+                    format!("Destination group '{}' has the same name as a destination", name)
+                ));
+            }
+            if group.members.is_empty() {
+                return Err(PipelineError::ConfigError(
+                    format!("Destination group '{}' has no members", name)
+                ));
+            }
+            for member in &group.members {
+                if !self.destinations.contains_key(member) {
+                    return Err(PipelineError::ConfigError(
+                        format!("Member '{}' of destination group '{}' is not a destination", member, name)
+                    ));
+                }
+            }
+            match group.strategy.as_ref().unwrap_or(&self.routing.load_balancing) {
+                LoadBalancingStrategy::Weighted(weights) => {
+                    if let Some(stray) = weights.keys().find(|member| !group.members.contains(member)) {
+                        return Err(PipelineError::ConfigError(
+                            format!("Weight for '{}' in destination group '{}' names a non-member", stray, name)
+                        ));
+                    }
+                }
+                LoadBalancingStrategy::Hash { field } if field.is_empty() => {
+                    return Err(PipelineError::ConfigError(
+                        format!("Destination group '{}' hashes on an empty field name", name)
+                    ));
+                }
+                _ => {}
+            }
+        }
+        
         // Validate routing rules
         for rule in &self.routing.rules {
             for dest in &rule.destinations {
-                if !self.destinations.contains_key(dest) {
+                if !known(dest) {
                     return Err(PipelineError::ConfigError(
                         format!("Destination '{}' in rule '{}' not found", dest, rule.name)
                     ));
@@ -608,6 +1038,8 @@ impl Default for PipelineConfig {
                 rules: Vec::new(),
                 default_destination: "default".to_string(),
                 load_balancing: LoadBalancingStrategy::RoundRobin,
+                destination_groups: HashMap::new(),
+                health_check_interval_secs: default_health_check_interval(),
             },
             storage: StorageConfig {
                 data_lake: DataLakeConfig {
@@ -661,6 +1093,7 @@ impl Default for PipelineConfig {
                     event_buffer_size: 100000,
                     batch_buffer_size: 10000,
                     flush_interval_ms: 1000,
+                    spill: None,
                 },
                 memory: MemoryConfig {
                     max_memory_usage: "8GB".to_string(),
@@ -678,6 +1111,10 @@ impl Default for PipelineConfig {
                 requests_per_second: 10000,
                 burst_size: 50000,
             },
+            detection: DetectionConfig::default(),
+            wal: WalConfig::default(),
+            geoip: GeoIpConfig::default(),
+            threat_intel: ThreatIntelConfig::default(),
         }
     }
 }
\ No newline at end of file
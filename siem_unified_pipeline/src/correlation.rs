@@ -0,0 +1,663 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+use uuid::Uuid;
+
+use crate::error::{Result, PipelineError};
+use crate::models::{Alert, AlertSeverity, DetectionRule, RuleType};
+use crate::pipeline::PipelineEvent;
+use crate::sigma;
+use crate::utils;
+
+/// Sigma-style correlation rule that aggregates matches of other rules.
+///
+/// ```yaml
+/// title: Brute force from single source
+/// level: high
+/// correlation:
+///   type: event_count
+///   rules: [Failed logon]
+///   group-by: [source_ip]
+///   timespan: 5m
+///   condition:
+///     gte: 10
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrelationRule {
+    pub title: String,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub level: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub references: Vec<String>,
+    pub correlation: CorrelationSpec,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CorrelationSpec {
+    #[serde(rename = "type")]
+    pub correlation_type: CorrelationType,
+    /// Titles or IDs of the rules whose matches feed this correlation
+    pub rules: Vec<String>,
+    #[serde(default, rename = "group-by")]
+    pub group_by: Vec<String>,
+    pub timespan: String,
+    #[serde(default)]
+    pub condition: CorrelationCondition,
+    /// Whether the referenced rules still raise their own alerts
+    #[serde(default)]
+    pub generate: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CorrelationType {
+    /// N matching events within the timespan
+    EventCount,
+    /// N distinct values of `condition.field` within the timespan
+    ValueCount,
+    /// All referenced rules matched within the timespan, in any order
+    Temporal,
+    /// All referenced rules matched within the timespan, in the listed order
+    TemporalOrdered,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CorrelationCondition {
+    pub gt: Option<u64>,
+    pub gte: Option<u64>,
+    pub eq: Option<u64>,
+    pub field: Option<String>,
+}
+
+impl CorrelationCondition {
+    fn is_met(&self, count: u64) -> bool {
+        if let Some(v) = self.gt {
+            return count > v;
+        }
+        if let Some(v) = self.gte {
+            return count >= v;
+        }
+        if let Some(v) = self.eq {
+            return count == v;
+        }
+        count > 0
+    }
+}
+
+impl CorrelationRule {
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        let rule: CorrelationRule = serde_yaml::from_str(yaml)
+            .map_err(|e| PipelineError::validation(format!("Invalid correlation rule: {}", e)))?;
+        rule.validate()?;
+        Ok(rule)
+    }
+
+    /// Whether a YAML document is a correlation rule rather than a plain Sigma rule.
+    pub fn is_correlation_yaml(yaml: &str) -> bool {
+        serde_yaml::from_str::<serde_yaml::Value>(yaml)
+            .ok()
+            .and_then(|v| v.get("correlation").cloned())
+            .is_some()
+    }
+
+    fn validate(&self) -> Result<()> {
+        let spec = &self.correlation;
+        if spec.rules.is_empty() {
+            return Err(PipelineError::validation(format!("Correlation '{}' references no rules", self.title)));
+        }
+        if utils::parse_duration(&spec.timespan).is_none() {
+            return Err(PipelineError::validation(format!("Invalid timespan '{}'", spec.timespan)));
+        }
+        if spec.correlation_type == CorrelationType::ValueCount && spec.condition.field.is_none() {
+            return Err(PipelineError::validation("value_count correlations require condition.field"));
+        }
+        Ok(())
+    }
+
+    pub fn timespan(&self) -> chrono::Duration {
+        utils::parse_duration(&self.correlation.timespan)
+            .and_then(|d| chrono::Duration::from_std(d).ok())
+            .unwrap_or_else(|| chrono::Duration::seconds(0))
+    }
+
+    pub fn severity(&self) -> AlertSeverity {
+        sigma::level_to_severity(self.level.as_deref())
+    }
+
+    pub fn to_detection_rule(&self, yaml: &str, author: &str) -> DetectionRule {
+        let now = Utc::now();
+        DetectionRule {
+            id: self.id.as_deref()
+                .and_then(|id| Uuid::parse_str(id).ok())
+                .unwrap_or_else(Uuid::new_v4),
+            name: self.title.clone(),
+            description: self.description.clone().unwrap_or_default(),
+            severity: self.severity(),
+            rule_type: match self.correlation.correlation_type {
+                CorrelationType::ValueCount => RuleType::Statistical,
+                _ => RuleType::Correlation,
+            },
+            query: yaml.to_string(),
+            conditions: serde_json::to_value(&self.correlation).unwrap_or_default(),
+            enabled: true,
+            author: self.author.clone().unwrap_or_else(|| author.to_string()),
+            version: "1".to_string(),
+            mitre_tactics: sigma::mitre_tactics(&self.tags),
+            mitre_techniques: sigma::mitre_techniques(&self.tags),
+            tags: self.tags.clone(),
+            references: self.references.clone(),
+            false_positive_rate: 0.0,
+            last_triggered: None,
+            trigger_count: 0,
+            suppression_rules: Vec::new(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Hit {
+    timestamp: DateTime<Utc>,
+    event_id: Uuid,
+    rule_index: usize,
+    value: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupState {
+    last_seen: DateTime<Utc>,
+    // Sample of recent hits, capped at the per-group limit
+    hits: VecDeque<Hit>,
+    // Events in the window per second, uncapped so counts can exceed the sample
+    #[serde(default)]
+    counts: VecDeque<(i64, u64)>,
+}
+
+impl GroupState {
+    fn record(&mut self, timestamp: DateTime<Utc>) {
+        let second = timestamp.timestamp();
+        match self.counts.iter_mut().rev().find(|(s, _)| *s == second) {
+            Some((_, count)) => *count += 1,
+            None => self.counts.push_back((second, 1)),
+        }
+    }
+
+    fn event_count(&self) -> u64 {
+        self.counts.iter().map(|(_, count)| count).sum()
+    }
+}
+
+/// Serializable copy of all in-flight correlation state, keyed by rule title.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CorrelationSnapshot {
+    pub taken_at: Option<DateTime<Utc>>,
+    pub rules: HashMap<String, HashMap<String, GroupState>>,
+}
+
+struct ActiveCorrelation {
+    rule: DetectionRule,
+    correlation: CorrelationRule,
+    groups: HashMap<String, GroupState>,
+}
+
+/// Keeps per-group sliding windows for every loaded correlation rule.
+pub struct CorrelationEngine {
+    rules: HashMap<String, ActiveCorrelation>,
+    // State restored from a snapshot for rules that have not been loaded yet
+    pending: HashMap<String, HashMap<String, GroupState>>,
+    max_groups: usize,
+    max_hits_per_group: usize,
+}
+
+impl CorrelationEngine {
+    pub fn new(max_groups: usize, max_hits_per_group: usize) -> Self {
+        CorrelationEngine {
+            rules: HashMap::new(),
+            pending: HashMap::new(),
+            max_groups,
+            max_hits_per_group,
+        }
+    }
+
+    pub fn set_limits(&mut self, max_groups: usize, max_hits_per_group: usize) {
+        self.max_groups = max_groups;
+        self.max_hits_per_group = max_hits_per_group;
+    }
+
+    pub fn add_rule(&mut self, rule: DetectionRule, correlation: CorrelationRule) {
+        let groups = self.rules.remove(&correlation.title)
+            .map(|existing| existing.groups)
+            .or_else(|| self.pending.remove(&correlation.title))
+            .unwrap_or_default();
+
+        self.rules.insert(correlation.title.clone(), ActiveCorrelation { rule, correlation, groups });
+    }
+
+    pub fn remove_rule(&mut self, rule_id: &Uuid) -> bool {
+        let title = self.rules.iter()
+            .find(|(_, r)| r.rule.id == *rule_id)
+            .map(|(title, _)| title.clone());
+        title.map(|t| self.rules.remove(&t).is_some()).unwrap_or(false)
+    }
+
+    pub fn get_rules(&self) -> Vec<DetectionRule> {
+        self.rules.values().map(|r| r.rule.clone()).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn group_count(&self) -> usize {
+        self.rules.values().map(|r| r.groups.len()).sum()
+    }
+
+    /// Whether alerts from a base rule are swallowed by a correlation that consumes it.
+    pub fn suppresses(&self, rule_names: &HashSet<String>) -> bool {
+        self.rules.values().any(|r| {
+            r.rule.enabled
+                && !r.correlation.correlation.generate
+                && r.correlation.correlation.rules.iter().any(|name| rule_names.contains(name))
+        })
+    }
+
+    /// Feed an event that matched the given base rules (by title and ID) through every correlation.
+    pub fn process(&mut self, event: &PipelineEvent, matched_rules: &HashSet<String>) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        let max_groups = self.max_groups;
+        let max_hits = self.max_hits_per_group;
+
+        for active in self.rules.values_mut() {
+            if !active.rule.enabled {
+                continue;
+            }
+
+            let spec = &active.correlation.correlation;
+            let matched_indices: Vec<usize> = spec.rules.iter()
+                .enumerate()
+                .filter(|(_, name)| matched_rules.contains(*name))
+                .map(|(i, _)| i)
+                .collect();
+            if matched_indices.is_empty() {
+                continue;
+            }
+
+            let group_values = group_values(event, &spec.group_by);
+            let group_key = group_key(&group_values);
+            let value = spec.condition.field.as_ref()
+                .and_then(|f| event.get_field(f))
+                .map(|v| match v.as_ref() {
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                });
+
+            // Counting correlations count an event once; temporal ones need a hit per rule
+            let indices = match spec.correlation_type {
+                CorrelationType::EventCount | CorrelationType::ValueCount => vec![matched_indices[0]],
+                _ => matched_indices,
+            };
+
+            if !active.groups.contains_key(&group_key) && active.groups.len() >= max_groups {
+                evict_oldest_group(&mut active.groups);
+            }
+
+            let window = active.correlation.timespan();
+            let group = active.groups.entry(group_key.clone()).or_insert_with(|| GroupState {
+                last_seen: event.timestamp,
+                hits: VecDeque::new(),
+                counts: VecDeque::new(),
+            });
+            if event.timestamp > group.last_seen {
+                group.last_seen = event.timestamp;
+            }
+
+            group.record(event.timestamp);
+            for rule_index in indices {
+                group.hits.push_back(Hit {
+                    timestamp: event.timestamp,
+                    event_id: event.id,
+                    rule_index,
+                    value: value.clone(),
+                });
+            }
+
+            let cutoff = window_start(group.last_seen, window);
+            group.hits.retain(|h| h.timestamp >= cutoff);
+            group.counts.retain(|(second, _)| *second >= cutoff.timestamp());
+            while group.hits.len() > max_hits {
+                group.hits.pop_front();
+            }
+
+            if let Some(event_ids) = evaluate_group(&active.correlation, group) {
+                debug!("Correlation '{}' fired for group '{}'", active.correlation.title, group_key);
+                alerts.push(build_alert(&active.rule, &active.correlation, &group_values, event_ids));
+                group.hits.clear();
+                group.counts.clear();
+            }
+        }
+
+        alerts
+    }
+
+    /// Drop groups whose newest hit has fallen out of the window.
+    pub fn expire(&mut self, now: DateTime<Utc>) {
+        for active in self.rules.values_mut() {
+            let cutoff = window_start(now, active.correlation.timespan());
+            active.groups.retain(|_, g| g.last_seen >= cutoff && !g.hits.is_empty());
+        }
+    }
+
+    pub fn snapshot(&self) -> CorrelationSnapshot {
+        let mut rules: HashMap<String, HashMap<String, GroupState>> = self.pending.clone();
+        for (title, active) in &self.rules {
+            rules.insert(title.clone(), active.groups.clone());
+        }
+        CorrelationSnapshot {
+            taken_at: Some(Utc::now()),
+            rules,
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: CorrelationSnapshot) {
+        for (title, groups) in snapshot.rules {
+            match self.rules.get_mut(&title) {
+                Some(active) => active.groups = groups,
+                None => {
+                    self.pending.insert(title, groups);
+                }
+            }
+        }
+    }
+}
+
+fn group_values(event: &PipelineEvent, group_by: &[String]) -> Vec<String> {
+    group_by.iter()
+        .map(|field| match event.get_field(field).as_deref() {
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(serde_json::Value::Null) | None => String::new(),
+            Some(other) => other.to_string(),
+        })
+        .collect()
+}
+
+// Groups are keyed by the JSON array of their values, so values containing
+// any separator stay apart and snapshots remain plain JSON objects
+fn group_key(values: &[String]) -> String {
+    serde_json::to_string(values).unwrap_or_default()
+}
+
+// Very long timespans reach back past the earliest representable time
+fn window_start(end: DateTime<Utc>, window: chrono::Duration) -> DateTime<Utc> {
+    end.checked_sub_signed(window).unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+fn evict_oldest_group(groups: &mut HashMap<String, GroupState>) {
+    let oldest = groups.iter()
+        .min_by_key(|(_, g)| g.last_seen)
+        .map(|(k, _)| k.clone());
+    if let Some(key) = oldest {
+        groups.remove(&key);
+    }
+}
+
+// Returns the contributing event IDs when the group satisfies the rule
+fn evaluate_group(rule: &CorrelationRule, group: &GroupState) -> Option<Vec<Uuid>> {
+    let spec = &rule.correlation;
+    match spec.correlation_type {
+        CorrelationType::EventCount => {
+            if spec.condition.is_met(group.event_count()) {
+                Some(unique_event_ids(group.hits.iter()))
+            } else {
+                None
+            }
+        }
+        CorrelationType::ValueCount => {
+            let distinct: HashSet<&str> = group.hits.iter()
+                .filter_map(|h| h.value.as_deref())
+                .collect();
+            if spec.condition.is_met(distinct.len() as u64) {
+                Some(unique_event_ids(group.hits.iter()))
+            } else {
+                None
+            }
+        }
+        CorrelationType::Temporal => {
+            let seen: HashSet<usize> = group.hits.iter().map(|h| h.rule_index).collect();
+            if seen.len() == spec.rules.len() {
+                Some(unique_event_ids(group.hits.iter()))
+            } else {
+                None
+            }
+        }
+        CorrelationType::TemporalOrdered => {
+            let mut next = 0;
+            let mut contributing = Vec::new();
+            for hit in &group.hits {
+                if hit.rule_index == next {
+                    contributing.push(hit);
+                    next += 1;
+                    if next == spec.rules.len() {
+                        return Some(unique_event_ids(contributing.into_iter()));
+                    }
+                }
+            }
+            None
+        }
+    }
+}
+
+fn unique_event_ids<'a>(hits: impl Iterator<Item = &'a Hit>) -> Vec<Uuid> {
+    let mut seen = HashSet::new();
+    hits.filter(|h| seen.insert(h.event_id)).map(|h| h.event_id).collect()
+}
+
+fn build_alert(rule: &DetectionRule, correlation: &CorrelationRule, group_values: &[String], event_ids: Vec<Uuid>) -> Alert {
+    let spec = &correlation.correlation;
+
+    let description = if spec.group_by.is_empty() {
+        format!("{} ({} events within {})", rule.description, event_ids.len(), spec.timespan)
+    } else {
+        format!(
+            "{} ({} events within {} for {} = {})",
+            rule.description, event_ids.len(), spec.timespan, spec.group_by.join(", "), group_values.join(", ")
+        )
+    };
+
+    let mut alert = Alert::new(
+        rule.name.clone(),
+        description.trim_start().to_string(),
+        rule.severity.clone(),
+        rule.name.clone(),
+        rule.id,
+        event_ids,
+    );
+    alert.source_events_count = alert.event_ids.len() as i32;
+    alert.mitre_tactics = rule.mitre_tactics.clone();
+    alert.mitre_techniques = rule.mitre_techniques.clone();
+    alert.indicators = spec.group_by.iter()
+        .zip(group_values)
+        .filter(|(_, v)| !v.is_empty())
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect();
+    alert
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ProcessingStage;
+
+    fn event(ip: &str, user: &str, seconds: i64) -> PipelineEvent {
+        PipelineEvent {
+            id: Uuid::new_v4(),
+            timestamp: DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap(),
+            source: "test".to_string(),
+            data: serde_json::json!({"source_ip": ip, "user_name": user}),
+            metadata: HashMap::new(),
+            processing_stage: ProcessingStage::Normalized,
+        }
+    }
+
+    fn engine_with(yaml: &str) -> CorrelationEngine {
+        let correlation = CorrelationRule::from_yaml(yaml).unwrap();
+        let rule = correlation.to_detection_rule(yaml, "test");
+        let mut engine = CorrelationEngine::new(100, 100);
+        engine.add_rule(rule, correlation);
+        engine
+    }
+
+    fn matched(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_event_count_within_window() {
+        let mut engine = engine_with(r#"
+title: Brute force
+correlation:
+  type: event_count
+  rules: [Failed logon]
+  group-by: [source_ip]
+  timespan: 1m
+  condition:
+    gte: 3
+"#);
+        let failed = matched(&["Failed logon"]);
+
+        assert!(engine.process(&event("10.0.0.1", "a", 0), &failed).is_empty());
+        assert!(engine.process(&event("10.0.0.2", "a", 1), &failed).is_empty());
+        // Falls outside the one-minute window of the first event
+        assert!(engine.process(&event("10.0.0.1", "a", 90), &failed).is_empty());
+        assert!(engine.process(&event("10.0.0.1", "a", 100), &failed).is_empty());
+        let alerts = engine.process(&event("10.0.0.1", "a", 110), &failed);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].event_ids.len(), 3);
+        assert_eq!(alerts[0].indicators, vec!["source_ip=10.0.0.1".to_string()]);
+        assert!(engine.suppresses(&failed));
+    }
+
+    #[test]
+    fn test_event_count_above_hit_limit() {
+        let mut engine = engine_with(r#"
+title: Password spray
+correlation:
+  type: event_count
+  rules: [Failed logon]
+  group-by: [source_ip]
+  timespan: 5m
+  condition:
+    gte: 5
+"#);
+        engine.set_limits(100, 2);
+        let failed = matched(&["Failed logon"]);
+
+        for second in 0..4 {
+            assert!(engine.process(&event("10.0.0.1", "a", second), &failed).is_empty());
+        }
+        // Only the newest hits are kept as a sample once the count passes the limit
+        let alerts = engine.process(&event("10.0.0.1", "a", 4), &failed);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].event_ids.len(), 2);
+
+        // Events that slid out of the window no longer count
+        for second in [400, 401, 402, 403] {
+            assert!(engine.process(&event("10.0.0.1", "a", second), &failed).is_empty());
+        }
+        assert!(engine.process(&event("10.0.0.1", "a", 800), &failed).is_empty());
+    }
+
+    #[test]
+    fn test_timespan_overflow_is_rejected() {
+        let yaml = "title: x\ncorrelation:\n  type: event_count\n  rules: [a]\n  timespan: 99999999999999999w\n  condition:\n    gte: 1\n";
+        assert!(CorrelationRule::from_yaml(yaml).is_err());
+    }
+
+    #[test]
+    fn test_group_values_containing_separators_stay_apart() {
+        let mut engine = engine_with(r#"
+title: Repeated failures
+correlation:
+  type: event_count
+  rules: [Failed logon]
+  group-by: [user_name, source_ip]
+  timespan: 5m
+  condition:
+    gte: 2
+"#);
+        let failed = matched(&["Failed logon"]);
+
+        assert!(engine.process(&event("c", "a|b", 0), &failed).is_empty());
+        assert!(engine.process(&event("b|c", "a", 1), &failed).is_empty());
+        assert_eq!(engine.group_count(), 2);
+
+        let alerts = engine.process(&event("b|c", "a", 2), &failed);
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].indicators, vec!["user_name=a".to_string(), "source_ip=b|c".to_string()]);
+    }
+
+    #[test]
+    fn test_ordered_sequence_and_snapshot() {
+        let yaml = r#"
+title: Logon then privilege change
+correlation:
+  type: temporal_ordered
+  rules: [Logon, Add to admins]
+  group-by: [user_name]
+  timespan: 10m
+  generate: true
+"#;
+        let mut engine = engine_with(yaml);
+
+        // B before A does not count
+        assert!(engine.process(&event("h", "bob", 0), &matched(&["Add to admins"])).is_empty());
+        assert!(engine.process(&event("h", "bob", 10), &matched(&["Logon"])).is_empty());
+        assert!(!engine.suppresses(&matched(&["Logon"])));
+
+        // State survives a snapshot round trip into a fresh engine
+        let snapshot = serde_json::to_string(&engine.snapshot()).unwrap();
+        let mut restored = CorrelationEngine::new(100, 100);
+        restored.restore(serde_json::from_str(&snapshot).unwrap());
+        let correlation = CorrelationRule::from_yaml(yaml).unwrap();
+        restored.add_rule(correlation.to_detection_rule(yaml, "test"), correlation);
+
+        let alerts = restored.process(&event("h", "bob", 20), &matched(&["Add to admins"]));
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].event_ids.len(), 2);
+    }
+
+    #[test]
+    fn test_group_limit_evicts_oldest() {
+        let mut engine = engine_with(r#"
+title: Spray
+correlation:
+  type: value_count
+  rules: [Failed logon]
+  group-by: [source_ip]
+  timespan: 5m
+  condition:
+    field: user_name
+    gte: 2
+"#);
+        engine.set_limits(2, 100);
+        let failed = matched(&["Failed logon"]);
+
+        engine.process(&event("10.0.0.1", "a", 0), &failed);
+        engine.process(&event("10.0.0.2", "a", 1), &failed);
+        engine.process(&event("10.0.0.3", "a", 2), &failed);
+        assert_eq!(engine.group_count(), 2);
+
+        // 10.0.0.1 was evicted, so a second user from it starts over
+        assert!(engine.process(&event("10.0.0.1", "b", 3), &failed).is_empty());
+        assert_eq!(engine.process(&event("10.0.0.3", "b", 4), &failed).len(), 1);
+    }
+}
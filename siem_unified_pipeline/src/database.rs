@@ -456,6 +456,17 @@ impl DatabaseManager {
         Ok(())
     }
 
+    /// Returns whether a rule with this ID existed
+    pub async fn delete_detection_rule(&self, rule_id: Uuid) -> Result<bool, PipelineError> {
+        let result = sqlx::query("DELETE FROM detection_rules WHERE id = $1")
+            .bind(rule_id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| PipelineError::database(format!("Failed to delete detection rule: {}", e)))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
     // Data source operations
     pub async fn insert_data_source(&self, source: &DataSource) -> Result<(), PipelineError> {
         let query = r#"
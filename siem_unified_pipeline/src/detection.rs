@@ -0,0 +1,551 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::{info, warn, error, debug};
+use uuid::Uuid;
+use serde::{Deserialize, Serialize};
+
+use crate::config::PipelineConfig;
+use crate::correlation::{CorrelationEngine, CorrelationRule, CorrelationSnapshot};
+use crate::database::DatabaseManager;
+use crate::error::{Result, PipelineError};
+use crate::models::{Alert, DetectionRule, RuleType};
+use crate::pipeline::PipelineEvent;
+use crate::sigma::CompiledSigmaRule;
+use crate::utils;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DetectionStats {
+    pub events_evaluated: u64,
+    pub rules_matched: u64,
+    pub alerts_raised: u64,
+    pub alert_store_errors: u64,
+    pub last_alert: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+struct LoadedRule {
+    rule: DetectionRule,
+    compiled: CompiledSigmaRule,
+}
+
+pub struct DetectionEngine {
+    config: Arc<RwLock<PipelineConfig>>,
+    rules: Arc<RwLock<HashMap<Uuid, LoadedRule>>>,
+    database: Arc<RwLock<Option<Arc<DatabaseManager>>>>,
+    correlation: Arc<RwLock<CorrelationEngine>>,
+    recent_alerts: Arc<RwLock<VecDeque<Alert>>>,
+    stats: Arc<RwLock<DetectionStats>>,
+}
+
+impl DetectionEngine {
+    pub async fn new(config: &PipelineConfig) -> Result<Self> {
+        info!("Initializing detection engine");
+
+        let engine = DetectionEngine {
+            config: Arc::new(RwLock::new(config.clone())),
+            rules: Arc::new(RwLock::new(HashMap::new())),
+            database: Arc::new(RwLock::new(None)),
+            correlation: Arc::new(RwLock::new(CorrelationEngine::new(
+                config.detection.max_correlation_groups,
+                config.detection.max_events_per_group,
+            ))),
+            recent_alerts: Arc::new(RwLock::new(VecDeque::new())),
+            stats: Arc::new(RwLock::new(DetectionStats::default())),
+        };
+
+        // Restore before rules load so each rule picks its windows back up as it is registered
+        if let Some(path) = &config.detection.correlation_state_path {
+            engine.restore_state(path).await;
+        }
+
+        if let Some(path) = &config.detection.sigma_rules_path {
+            engine.load_rules_from_directory(path).await?;
+        }
+
+        Ok(engine)
+    }
+
+    /// Attach the alert store and pull enabled Sigma rules from it.
+    pub async fn set_database(&self, database: Arc<DatabaseManager>) -> Result<()> {
+        *self.database.write().await = Some(database);
+        self.load_rules_from_database().await
+    }
+
+    pub async fn load_rules_from_database(&self) -> Result<()> {
+        let database = match self.database.read().await.clone() {
+            Some(db) => db,
+            None => return Ok(()),
+        };
+
+        let rules = database.get_enabled_detection_rules().await?;
+        let mut loaded = 0;
+        for rule in rules {
+            let (name, id) = (rule.name.clone(), rule.id);
+            let result = match rule.rule_type {
+                RuleType::Sigma => self.register_sigma(rule).await,
+                RuleType::Correlation | RuleType::Statistical => self.register_correlation(rule).await,
+                _ => continue,
+            };
+
+            match result {
+                Ok(_) => loaded += 1,
+                Err(e) => warn!("Skipping detection rule {} ({}): {}", name, id, e),
+            }
+        }
+
+        info!("Loaded {} detection rules from database", loaded);
+        Ok(())
+    }
+
+    async fn register_sigma(&self, rule: DetectionRule) -> Result<()> {
+        let compiled = CompiledSigmaRule::from_yaml(&rule.query)?;
+        self.rules.write().await.insert(rule.id, LoadedRule { rule, compiled });
+        Ok(())
+    }
+
+    async fn register_correlation(&self, rule: DetectionRule) -> Result<()> {
+        let correlation = CorrelationRule::from_yaml(&rule.query)?;
+        self.correlation.write().await.add_rule(rule, correlation);
+        Ok(())
+    }
+
+    pub async fn load_rules_from_directory(&self, path: &str) -> Result<()> {
+        let mut entries = tokio::fs::read_dir(path).await
+            .map_err(|e| PipelineError::config(format!("Failed to read Sigma rules directory {}: {}", path, e)))?;
+
+        let mut loaded = 0;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_path = entry.path();
+            let is_yaml = file_path.extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| ext == "yml" || ext == "yaml")
+                .unwrap_or(false);
+            if !is_yaml {
+                continue;
+            }
+
+            match self.load_rule_file(&file_path).await {
+                Ok(_) => loaded += 1,
+                Err(e) => warn!("Skipping Sigma rule {}: {}", file_path.display(), e),
+            }
+        }
+
+        info!("Loaded {} Sigma rules from {}", loaded, path);
+        Ok(())
+    }
+
+    async fn load_rule_file(&self, path: &Path) -> Result<DetectionRule> {
+        let content = tokio::fs::read_to_string(path).await?;
+        self.add_sigma_rule(&content, "file").await
+    }
+
+    /// Compile a Sigma or Sigma correlation rule and register it for streaming evaluation.
+    pub async fn add_sigma_rule(&self, yaml: &str, author: &str) -> Result<DetectionRule> {
+        let rule = if CorrelationRule::is_correlation_yaml(yaml) {
+            CorrelationRule::from_yaml(yaml)?.to_detection_rule(yaml, author)
+        } else {
+            Self::to_detection_rule(&CompiledSigmaRule::from_yaml(yaml)?, yaml, author)
+        };
+
+        if let Some(database) = self.database.read().await.clone() {
+            database.insert_detection_rule(&rule).await?;
+        }
+
+        match rule.rule_type {
+            RuleType::Sigma => self.register_sigma(rule.clone()).await?,
+            _ => self.register_correlation(rule.clone()).await?,
+        }
+
+        info!("Registered {:?} rule: {} ({})", rule.rule_type, rule.name, rule.id);
+        Ok(rule)
+    }
+
+    /// Unregister a rule and delete it from the alert store so it is not
+    /// loaded again on restart. Rules read from the Sigma rules directory
+    /// come back whenever that directory is next loaded.
+    pub async fn remove_rule(&self, rule_id: &Uuid) -> Result<()> {
+        let removed = self.rules.write().await.remove(rule_id).is_some()
+            || self.correlation.write().await.remove_rule(rule_id);
+        let deleted = match self.database.read().await.clone() {
+            Some(database) => database.delete_detection_rule(*rule_id).await?,
+            None => false,
+        };
+
+        if removed || deleted {
+            return Ok(());
+        }
+        Err(PipelineError::not_found(format!("Detection rule '{}' not found", rule_id)))
+    }
+
+    pub async fn get_rules(&self) -> Vec<DetectionRule> {
+        let mut rules: Vec<DetectionRule> = {
+            let rules = self.rules.read().await;
+            rules.values().map(|r| r.rule.clone()).collect()
+        };
+        rules.extend(self.correlation.read().await.get_rules());
+        rules
+    }
+
+    /// Evaluate an event against every loaded rule, persisting any alerts raised.
+    pub async fn evaluate(&self, event: &PipelineEvent) -> Result<Vec<Alert>> {
+        if !self.config.read().await.detection.enabled {
+            return Ok(Vec::new());
+        }
+
+        // Matching rules are known by title, rule ID and Sigma ID so correlations can reference any of them
+        let mut matched_names = HashSet::new();
+        let mut matches = Vec::new();
+        {
+            let rules = self.rules.read().await;
+            for loaded in rules.values().filter(|r| r.rule.enabled && r.compiled.matches(event)) {
+                let mut names: HashSet<String> = HashSet::new();
+                names.insert(loaded.rule.name.clone());
+                names.insert(loaded.rule.id.to_string());
+                if let Some(id) = loaded.compiled.id() {
+                    names.insert(id.to_string());
+                }
+                matched_names.extend(names.iter().cloned());
+                matches.push((names, Self::build_alert(&loaded.rule, &loaded.compiled, event)));
+            }
+        }
+
+        let matched_count = matches.len() as u64;
+        let alerts: Vec<Alert> = if matches.is_empty() {
+            Vec::new()
+        } else {
+            let mut correlation = self.correlation.write().await;
+            if correlation.is_empty() {
+                matches.into_iter().map(|(_, alert)| alert).collect()
+            } else {
+                let mut raised: Vec<Alert> = matches.into_iter()
+                    .filter(|(names, _)| !correlation.suppresses(names))
+                    .map(|(_, alert)| alert)
+                    .collect();
+                raised.extend(correlation.process(event, &matched_names));
+                raised
+            }
+        };
+
+        {
+            let mut stats = self.stats.write().await;
+            stats.events_evaluated += 1;
+            stats.rules_matched += matched_count;
+        }
+
+        if alerts.is_empty() {
+            return Ok(alerts);
+        }
+
+        self.raise_alerts(&alerts).await;
+        Ok(alerts)
+    }
+
+    /// Persist and record alerts produced by any detection source.
+    pub async fn raise_alerts(&self, alerts: &[Alert]) {
+        let database = self.database.read().await.clone();
+        let mut store_errors = 0;
+
+        for alert in alerts {
+            debug!("Rule '{}' raised alert {}", alert.rule_name, alert.id);
+            if let Some(database) = &database {
+                if let Err(e) = database.insert_alert(alert).await {
+                    error!("Failed to store alert {}: {}", alert.id, e);
+                    store_errors += 1;
+                    continue;
+                }
+                if let Err(e) = database.update_rule_trigger_count(alert.rule_id).await {
+                    warn!("Failed to update trigger count for rule {}: {}", alert.rule_id, e);
+                }
+            }
+        }
+
+        let max_recent = self.config.read().await.detection.max_recent_alerts;
+        {
+            let mut recent = self.recent_alerts.write().await;
+            for alert in alerts {
+                recent.push_back(alert.clone());
+            }
+            while recent.len() > max_recent {
+                recent.pop_front();
+            }
+        }
+
+        {
+            let mut rules = self.rules.write().await;
+            for alert in alerts {
+                if let Some(loaded) = rules.get_mut(&alert.rule_id) {
+                    loaded.rule.trigger_count += 1;
+                    loaded.rule.last_triggered = Some(alert.created_at);
+                }
+            }
+        }
+
+        let mut stats = self.stats.write().await;
+        stats.alerts_raised += alerts.len() as u64;
+        stats.alert_store_errors += store_errors;
+        stats.last_alert = Some(chrono::Utc::now());
+    }
+
+    /// Run a rule that has not been registered against a set of sample events.
+    pub fn test_sigma_rule(yaml: &str, events: &[PipelineEvent]) -> Result<Vec<Uuid>> {
+        let compiled = CompiledSigmaRule::from_yaml(yaml)?;
+        Ok(events.iter().filter(|e| compiled.matches(e)).map(|e| e.id).collect())
+    }
+
+    pub async fn get_recent_alerts(&self, limit: usize) -> Vec<Alert> {
+        let recent = self.recent_alerts.read().await;
+        recent.iter().rev().take(limit).cloned().collect()
+    }
+
+    pub async fn get_stats(&self) -> DetectionStats {
+        self.stats.read().await.clone()
+    }
+
+    /// Write the correlation windows to the configured snapshot file.
+    pub async fn persist_state(&self) -> Result<()> {
+        let path = match self.config.read().await.detection.correlation_state_path.clone() {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+
+        let snapshot = self.correlation.read().await.snapshot();
+        let content = serde_json::to_vec(&snapshot)?;
+        utils::write_file_atomic(Path::new(&path), &content).await?;
+
+        debug!("Persisted correlation state to {}", path);
+        Ok(())
+    }
+
+    async fn restore_state(&self, path: &str) {
+        let content = match tokio::fs::read(path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                warn!("Failed to read correlation state from {}: {}", path, e);
+                return;
+            }
+        };
+
+        match serde_json::from_slice::<CorrelationSnapshot>(&content) {
+            Ok(snapshot) => {
+                info!("Restored correlation state for {} rules from {}", snapshot.rules.len(), path);
+                self.correlation.write().await.restore(snapshot);
+            }
+            Err(e) => warn!("Ignoring unreadable correlation state in {}: {}", path, e),
+        }
+    }
+
+    /// Expire idle correlation groups and snapshot what remains.
+    pub async fn run_maintenance(&self) {
+        self.correlation.write().await.expire(chrono::Utc::now());
+        if let Err(e) = self.persist_state().await {
+            warn!("Failed to persist correlation state: {}", e);
+        }
+    }
+
+    pub async fn get_health(&self) -> serde_json::Value {
+        let stats = self.get_stats().await;
+        let rule_count = self.rules.read().await.len();
+        let (correlation_rules, correlation_groups) = {
+            let correlation = self.correlation.read().await;
+            (correlation.get_rules().len(), correlation.group_count())
+        };
+        let database_attached = self.database.read().await.is_some();
+
+        serde_json::json!({
+            "status": if stats.alert_store_errors == 0 { "healthy" } else { "degraded" },
+            "rules_loaded": rule_count,
+            "correlation_rules_loaded": correlation_rules,
+            "correlation_groups": correlation_groups,
+            "alert_store_attached": database_attached,
+            "events_evaluated": stats.events_evaluated,
+            "alerts_raised": stats.alerts_raised,
+            "alert_store_errors": stats.alert_store_errors,
+            "last_alert": stats.last_alert.map(|t| t.to_rfc3339())
+        })
+    }
+
+    pub async fn reload_config(&self, new_config: &PipelineConfig) -> Result<()> {
+        info!("Reloading detection configuration");
+
+        // Correlation windows are kept in memory across reloads; only limits change
+        let path_changed = {
+            let current = self.config.read().await;
+            current.detection.sigma_rules_path != new_config.detection.sigma_rules_path
+        };
+        *self.config.write().await = new_config.clone();
+        self.correlation.write().await.set_limits(
+            new_config.detection.max_correlation_groups,
+            new_config.detection.max_events_per_group,
+        );
+
+        if path_changed {
+            if let Some(path) = &new_config.detection.sigma_rules_path {
+                self.load_rules_from_directory(path).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn shutdown(&self) -> Result<()> {
+        info!("Shutting down detection engine");
+        self.persist_state().await
+    }
+
+    fn build_alert(rule: &DetectionRule, compiled: &CompiledSigmaRule, event: &PipelineEvent) -> Alert {
+        let mut alert = Alert::new(
+            rule.name.clone(),
+            rule.description.clone(),
+            rule.severity.clone(),
+            rule.name.clone(),
+            rule.id,
+            vec![event.id],
+        );
+        alert.source_events_count = 1;
+        alert.mitre_tactics = compiled.mitre_tactics();
+        alert.mitre_techniques = compiled.mitre_techniques();
+        alert.affected_assets = ["host_name", "hostname", "source_ip"].iter()
+            .filter_map(|f| event.get_field(f))
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect();
+        alert.affected_users = ["user_name", "user", "username"].iter()
+            .filter_map(|f| event.get_field(f))
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect();
+        alert
+    }
+
+    fn to_detection_rule(compiled: &CompiledSigmaRule, yaml: &str, author: &str) -> DetectionRule {
+        let sigma = &compiled.rule;
+        let now = chrono::Utc::now();
+
+        DetectionRule {
+            id: sigma.id.as_deref()
+                .and_then(|id| Uuid::parse_str(id).ok())
+                .unwrap_or_else(Uuid::new_v4),
+            name: sigma.title.clone(),
+            description: sigma.description.clone().unwrap_or_default(),
+            severity: compiled.severity(),
+            rule_type: RuleType::Sigma,
+            query: yaml.to_string(),
+            conditions: serde_json::to_value(&sigma.logsource).unwrap_or_default(),
+            enabled: sigma.status.as_deref() != Some("deprecated"),
+            author: sigma.author.clone().unwrap_or_else(|| author.to_string()),
+            version: "1".to_string(),
+            mitre_tactics: compiled.mitre_tactics(),
+            mitre_techniques: compiled.mitre_techniques(),
+            tags: sigma.tags.clone(),
+            references: sigma.references.clone(),
+            false_positive_rate: 0.0,
+            last_triggered: None,
+            trigger_count: 0,
+            suppression_rules: sigma.falsepositives.clone(),
+            created_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ProcessingStage;
+
+    const FAILED_LOGON: &str = r#"
+title: Failed logon
+level: medium
+tags: [attack.credential_access, attack.t1110]
+detection:
+  selection:
+    event_id: 4625
+  condition: selection
+"#;
+
+    fn event(data: serde_json::Value) -> PipelineEvent {
+        PipelineEvent {
+            id: Uuid::new_v4(),
+            timestamp: chrono::Utc::now(),
+            source: "test".to_string(),
+            data,
+            metadata: HashMap::new(),
+            processing_stage: ProcessingStage::Normalized,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sigma_rule_raises_alert() {
+        let engine = DetectionEngine::new(&PipelineConfig::default()).await.unwrap();
+        let rule = engine.add_sigma_rule(FAILED_LOGON, "test").await.unwrap();
+
+        let logon = event(serde_json::json!({"event_id": 4624, "user_name": "alice"}));
+        assert!(engine.evaluate(&logon).await.unwrap().is_empty());
+
+        let failed = event(serde_json::json!({"event_id": 4625, "user_name": "alice", "source_ip": "10.0.0.5"}));
+        let alerts = engine.evaluate(&failed).await.unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].rule_id, rule.id);
+        assert_eq!(alerts[0].event_ids, vec![failed.id]);
+        assert_eq!(alerts[0].mitre_techniques, vec!["T1110".to_string()]);
+        assert_eq!(alerts[0].affected_users, vec!["alice".to_string()]);
+        assert_eq!(alerts[0].affected_assets, vec!["10.0.0.5".to_string()]);
+
+        let stats = engine.get_stats().await;
+        assert_eq!((stats.events_evaluated, stats.rules_matched, stats.alerts_raised), (2, 1, 1));
+        assert_eq!(engine.get_recent_alerts(10).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_rules_load_from_directory_and_can_be_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("failed_logon.yml"), FAILED_LOGON).unwrap();
+        std::fs::write(dir.path().join("broken.yaml"), "title: Broken\ndetection:\n  condition: missing\n").unwrap();
+        std::fs::write(dir.path().join("README.md"), "not a rule").unwrap();
+        let mut config = PipelineConfig::default();
+        config.detection.sigma_rules_path = Some(dir.path().display().to_string());
+
+        let engine = DetectionEngine::new(&config).await.unwrap();
+        let rules = engine.get_rules().await;
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].name, "Failed logon");
+        assert_eq!(rules[0].author, "file");
+
+        let failed = serde_json::json!({"event_id": 4625, "user_name": "carol"});
+        assert_eq!(engine.evaluate(&event(failed.clone())).await.unwrap().len(), 1);
+
+        engine.remove_rule(&rules[0].id).await.unwrap();
+        assert!(engine.evaluate(&event(failed)).await.unwrap().is_empty());
+        assert!(engine.remove_rule(&rules[0].id).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_correlation_state_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = PipelineConfig::default();
+        config.detection.correlation_state_path = Some(dir.path().join("correlation.json").display().to_string());
+        let correlation = r#"
+title: Repeated failed logons
+correlation:
+  type: event_count
+  rules: [Failed logon]
+  group-by: [user_name]
+  timespan: 10m
+  condition:
+    gte: 2
+"#;
+
+        let engine = DetectionEngine::new(&config).await.unwrap();
+        engine.add_sigma_rule(FAILED_LOGON, "test").await.unwrap();
+        engine.add_sigma_rule(correlation, "test").await.unwrap();
+        assert!(engine.evaluate(&event(serde_json::json!({"event_id": 4625, "user_name": "bob"}))).await.unwrap().is_empty());
+        engine.shutdown().await.unwrap();
+
+        let restarted = DetectionEngine::new(&config).await.unwrap();
+        restarted.add_sigma_rule(FAILED_LOGON, "test").await.unwrap();
+        restarted.add_sigma_rule(correlation, "test").await.unwrap();
+        let alerts = restarted.evaluate(&event(serde_json::json!({"event_id": 4625, "user_name": "bob"}))).await.unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].rule_name, "Repeated failed logons");
+        assert_eq!(alerts[0].event_ids.len(), 2);
+    }
+}
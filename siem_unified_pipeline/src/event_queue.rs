@@ -0,0 +1,321 @@
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, Notify};
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+use tracing::{debug, error, info, warn};
+
+use crate::config::{BuffersConfig, SpillConfig};
+use crate::error::{Result, PipelineError};
+use crate::pipeline::PipelineEvent;
+use crate::wal::WriteAheadLog;
+
+/// Sending half of the queue between the sources and the processing loop.
+///
+/// `send` waits once both the in-memory queue and the spill file are full,
+/// which is what pauses TCP reads, file tailing and pollers; `try_send`
+/// hands the event back instead for callers that answer the sender
+/// themselves (HTTP 429) or pause elsewhere (Kafka partitions). With a
+/// write-ahead log configured every event is logged before it is queued.
+#[derive(Clone)]
+pub struct EventSender {
+    tx: mpsc::Sender<PipelineEvent>,
+    spill: Option<Arc<SpillBuffer>>,
+    wal: Option<Arc<WriteAheadLog>>,
+}
+
+impl EventSender {
+    pub async fn send(&self, event: PipelineEvent) -> std::result::Result<(), SendError<PipelineEvent>> {
+        if let Some(wal) = &self.wal {
+            // A full or failing log holds the source back like a full queue
+            while let Err(e) = wal.append(&event) {
+                if self.tx.is_closed() {
+                    return Err(SendError(event));
+                }
+                debug!("Waiting on write-ahead log: {}", e);
+                wal.wait_for_space().await;
+            }
+        }
+        self.enqueue(event).await
+    }
+
+    // Hands the event back like mpsc::Sender::try_send
+    #[allow(clippy::result_large_err)]
+    pub fn try_send(&self, event: PipelineEvent) -> std::result::Result<(), TrySendError<PipelineEvent>> {
+        let Some(wal) = &self.wal else { return self.try_enqueue(event) };
+        if wal.append(&event).is_err() {
+            return Err(TrySendError::Full(event));
+        }
+        let result = self.try_enqueue(event);
+        // The caller keeps the event, so it must not be replayed as well
+        if let Err(TrySendError::Full(event) | TrySendError::Closed(event)) = &result {
+            wal.ack(event.id);
+        }
+        result
+    }
+
+    /// Queue an event recovered from the write-ahead log without logging it again.
+    pub async fn replay(&self, event: PipelineEvent) -> std::result::Result<(), SendError<PipelineEvent>> {
+        self.enqueue(event).await
+    }
+
+    /// Events waiting to be processed, in memory and spilled.
+    pub fn depth(&self) -> usize {
+        let queued = self.tx.max_capacity() - self.tx.capacity();
+        queued + self.spill.as_ref().map(|spill| spill.len()).unwrap_or_default()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    async fn enqueue(&self, event: PipelineEvent) -> std::result::Result<(), SendError<PipelineEvent>> {
+        match self.try_enqueue(event) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(event)) => self.tx.send(event).await,
+            Err(TrySendError::Closed(event)) => Err(SendError(event)),
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn try_enqueue(&self, event: PipelineEvent) -> std::result::Result<(), TrySendError<PipelineEvent>> {
+        let event = match self.tx.try_send(event) {
+            Err(TrySendError::Full(event)) => event,
+            other => return other,
+        };
+        match &self.spill {
+            Some(spill) => spill.push(event).map_err(TrySendError::Full),
+            None => Err(TrySendError::Full(event)),
+        }
+    }
+}
+
+/// Create the bounded event queue described by `BuffersConfig`, with the
+/// optional spill file and the task that feeds spilled events back.
+pub fn event_queue(
+    config: &BuffersConfig,
+    wal: Option<Arc<WriteAheadLog>>,
+) -> Result<(EventSender, mpsc::Receiver<PipelineEvent>)> {
+    let (tx, rx) = mpsc::channel(config.event_buffer_size.max(1));
+
+    let spill = match &config.spill {
+        Some(spill_config) => {
+            let spill = Arc::new(SpillBuffer::open(spill_config)?);
+            tokio::spawn(drain_spill(spill.clone(), tx.clone()));
+            Some(spill)
+        }
+        None => None,
+    };
+
+    Ok((EventSender { tx, spill, wal }, rx))
+}
+
+// Moves spilled events into the queue as room frees up. Events that
+// overflowed are delivered after ones queued in the meantime, so ordering
+// across an overflow is not preserved.
+async fn drain_spill(spill: Arc<SpillBuffer>, tx: mpsc::Sender<PipelineEvent>) {
+    loop {
+        if spill.is_empty() {
+            spill.pushed.notified().await;
+            continue;
+        }
+        let permit = match tx.reserve().await {
+            Ok(permit) => permit,
+            Err(_) => return,
+        };
+        match spill.pop() {
+            Ok(Some(event)) => permit.send(event),
+            Ok(None) => {}
+            Err(e) => {
+                error!("Failed to read spilled event: {}", e);
+                drop(permit);
+                tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            }
+        }
+    }
+}
+
+struct SpillFile {
+    writer: BufWriter<File>,
+    reader: BufReader<File>,
+    write_pos: u64,
+    read_pos: u64,
+    count: usize,
+}
+
+/// Length-prefixed JSON events appended to a single file and read back in
+/// order. The file is truncated whenever it has been fully drained.
+pub struct SpillBuffer {
+    path: PathBuf,
+    max_bytes: u64,
+    file: Mutex<SpillFile>,
+    pushed: Notify,
+}
+
+impl SpillBuffer {
+    pub fn open(config: &SpillConfig) -> Result<Self> {
+        let path = PathBuf::from(&config.path);
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Spilled events do not survive a restart; they were never acknowledged to anyone
+        let file = OpenOptions::new().create(true).read(true).write(true).truncate(true).open(&path)
+            .map_err(|e| PipelineError::io(format!("Failed to open spill file {}: {}", path.display(), e)))?;
+        let reader = file.try_clone()?;
+        info!("Event queue overflow spills to {} (up to {} MB)", path.display(), config.max_size_mb);
+
+        Ok(Self {
+            path,
+            max_bytes: config.max_size_mb.saturating_mul(1024 * 1024),
+            file: Mutex::new(SpillFile {
+                writer: BufWriter::new(file),
+                reader: BufReader::new(reader),
+                write_pos: 0,
+                read_pos: 0,
+                count: 0,
+            }),
+            pushed: Notify::new(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.file.lock().map(|file| file.count).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Append an event, handing it back when the file is at its size limit
+    /// or cannot be written.
+    #[allow(clippy::result_large_err)]
+    pub fn push(&self, event: PipelineEvent) -> std::result::Result<(), PipelineEvent> {
+        let encoded = match serde_json::to_vec(&event) {
+            Ok(encoded) => encoded,
+            Err(_) => return Err(event),
+        };
+        let Ok(mut file) = self.file.lock() else { return Err(event) };
+        let record_len = 4 + encoded.len() as u64;
+        if file.write_pos + record_len > self.max_bytes {
+            return Err(event);
+        }
+
+        let written = file.writer.write_all(&(encoded.len() as u32).to_be_bytes())
+            .and_then(|_| file.writer.write_all(&encoded))
+            .and_then(|_| file.writer.flush());
+        if let Err(e) = written {
+            warn!("Failed to spill event to {}: {}", self.path.display(), e);
+            // Drop whatever part of the record made it out so the next read stays aligned
+            let write_pos = file.write_pos;
+            let _ = file.writer.get_mut().set_len(write_pos);
+            let _ = file.writer.seek(SeekFrom::Start(write_pos));
+            return Err(event);
+        }
+
+        file.write_pos += record_len;
+        file.count += 1;
+        drop(file);
+        self.pushed.notify_one();
+        Ok(())
+    }
+
+    pub fn pop(&self) -> Result<Option<PipelineEvent>> {
+        let mut file = self.file.lock()
+            .map_err(|_| PipelineError::internal("Spill buffer lock poisoned"))?;
+        if file.count == 0 {
+            return Ok(None);
+        }
+
+        let read_pos = file.read_pos;
+        let record = file.reader.seek(SeekFrom::Start(read_pos)).and_then(|_| {
+            let mut len = [0u8; 4];
+            file.reader.read_exact(&mut len)?;
+            let mut encoded = vec![0u8; u32::from_be_bytes(len) as usize];
+            file.reader.read_exact(&mut encoded)?;
+            Ok(encoded)
+        });
+        let decoded = record.map_err(PipelineError::from).and_then(|encoded| {
+            file.read_pos += 4 + encoded.len() as u64;
+            file.count -= 1;
+            Ok(serde_json::from_slice::<PipelineEvent>(&encoded)?)
+        });
+
+        if decoded.is_err() {
+            // Nothing after a bad record can be trusted to be aligned
+            warn!("Discarding {} spilled events after a read failure", file.count);
+            file.count = 0;
+        }
+        if file.count == 0 {
+            file.writer.get_mut().set_len(0)?;
+            file.writer.seek(SeekFrom::Start(0))?;
+            file.write_pos = 0;
+            file.read_pos = 0;
+        }
+
+        decoded.map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ProcessingStage;
+    use std::collections::HashMap;
+
+    fn event(n: u64) -> PipelineEvent {
+        PipelineEvent {
+            id: uuid::Uuid::new_v4(),
+            timestamp: chrono::Utc::now(),
+            source: "test".to_string(),
+            data: serde_json::json!({ "n": n }),
+            metadata: HashMap::new(),
+            processing_stage: ProcessingStage::Ingested,
+        }
+    }
+
+    fn buffers(spill: Option<SpillConfig>) -> BuffersConfig {
+        BuffersConfig { event_buffer_size: 2, batch_buffer_size: 10, flush_interval_ms: 100, spill }
+    }
+
+    #[tokio::test]
+    async fn test_bounded_queue_applies_backpressure() {
+        let (tx, mut rx) = event_queue(&buffers(None), None).unwrap();
+        tx.send(event(1)).await.unwrap();
+        tx.send(event(2)).await.unwrap();
+        assert!(matches!(tx.try_send(event(3)), Err(TrySendError::Full(_))));
+        assert_eq!(tx.depth(), 2);
+
+        let blocked = tokio::spawn({
+            let tx = tx.clone();
+            async move { tx.send(event(3)).await }
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!blocked.is_finished());
+
+        assert_eq!(rx.recv().await.unwrap().data["n"], 1);
+        blocked.await.unwrap().unwrap();
+        assert_eq!(tx.depth(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_spill_overflow_is_drained_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let spill = SpillConfig { path: dir.path().join("spill/events.spill").display().to_string(), max_size_mb: 1 };
+        let (tx, mut rx) = event_queue(&buffers(Some(spill)), None).unwrap();
+
+        for n in 0..10 {
+            tx.try_send(event(n)).unwrap();
+        }
+        assert_eq!(tx.depth(), 10);
+
+        let mut received = Vec::new();
+        for _ in 0..10 {
+            received.push(rx.recv().await.unwrap().data["n"].as_u64().unwrap());
+        }
+        received.sort();
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+        assert_eq!(tx.depth(), 0);
+        assert_eq!(std::fs::metadata(dir.path().join("spill/events.spill")).unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,467 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use globset::GlobBuilder;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+use walkdir::WalkDir;
+
+use crate::archive::{self, RollPolicy};
+use crate::config::{CompressionType, FileRotation};
+use crate::error::{Result, PipelineError};
+use crate::pipeline::PipelineEvent;
+
+// Rendered per event; the time placeholders are left out of retention series
+const PLACEHOLDERS: [&str; 5] = ["{tenant}", "{source}", "{source_type}", "{date}", "{hour}"];
+const TIME_PLACEHOLDERS: [&str; 2] = ["{date}", "{hour}"];
+
+/// A destination path such as `/var/log/siem/{tenant}/{date}.ndjson`.
+///
+/// `{tenant}`, `{source}`, `{source_type}`, `{date}` (`%Y-%m-%d`) and
+/// `{hour}` are filled in from each event, in UTC.
+#[derive(Debug, Clone)]
+pub struct PathTemplate {
+    template: String,
+}
+
+impl PathTemplate {
+    pub fn new(template: &str) -> Self {
+        Self { template: template.to_string() }
+    }
+
+    pub fn render(&self, event: &PipelineEvent) -> PathBuf {
+        PathBuf::from(self.fill(|placeholder| value(event, placeholder)))
+    }
+
+    /// Directory above the first placeholder, where every rendered path lives.
+    pub fn root(&self) -> PathBuf {
+        let fixed = match PLACEHOLDERS.iter().filter_map(|p| self.template.find(p)).min() {
+            Some(index) => &self.template[..index],
+            None => self.template.as_str(),
+        };
+        match Path::new(fixed).parent() {
+            Some(parent) if !fixed.ends_with('/') => parent.to_path_buf(),
+            _ => PathBuf::from(fixed),
+        }
+    }
+
+    // Glob over the closed segments of every file sharing the event's
+    // non-time placeholders, so `{tenant}/{date}.ndjson` keeps files per tenant
+    fn series(&self, event: &PipelineEvent) -> String {
+        let pattern = self.fill(|placeholder| match TIME_PLACEHOLDERS.contains(&placeholder) {
+            true => "*".to_string(),
+            false => escape_glob(&value(event, placeholder)),
+        });
+        format!("{}.*", pattern)
+    }
+
+    fn fill(&self, value: impl Fn(&str) -> String) -> String {
+        let mut rendered = String::with_capacity(self.template.len());
+        let mut rest = self.template.as_str();
+        while let Some((index, placeholder)) = PLACEHOLDERS.iter()
+            .filter_map(|p| rest.find(p).map(|i| (i, *p)))
+            .min()
+        {
+            rendered.push_str(&rest[..index]);
+            rendered.push_str(&value(placeholder));
+            rest = &rest[index + placeholder.len()..];
+        }
+        rendered.push_str(rest);
+        rendered
+    }
+}
+
+fn value(event: &PipelineEvent, placeholder: &str) -> String {
+    match placeholder {
+        "{tenant}" => archive::sanitize(archive::tenant(event)),
+        "{source}" => archive::sanitize(&event.source),
+        "{source_type}" => archive::sanitize(event.metadata.get("source_type").map(String::as_str).unwrap_or("unknown")),
+        "{date}" => event.timestamp.format("%Y-%m-%d").to_string(),
+        "{hour}" => event.timestamp.format("%H").to_string(),
+        _ => String::new(),
+    }
+}
+
+fn escape_glob(literal: &str) -> String {
+    literal.chars().map(|c| match c {
+        '*' | '?' | '[' | ']' | '{' | '}' => format!("[{}]", c),
+        _ => c.to_string(),
+    }).collect()
+}
+
+/// Check that closed segments can be written with `compression`.
+pub fn validate_compression(compression: Option<&CompressionType>) -> Result<()> {
+    match compression {
+        Some(CompressionType::Snappy) => Err(PipelineError::configuration(
+            "File destinations support gzip, zstd or lz4 compression, not snappy",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Bumped on SIGHUP so file destinations reopen their paths, which lets
+/// logrotate move files out from under the pipeline.
+pub fn watch_hangup(generation: Arc<AtomicU64>) {
+    #[cfg(unix)]
+    tokio::spawn(async move {
+        let mut hangups = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(e) => {
+                warn!("Failed to install SIGHUP handler, files will not be reopened: {}", e);
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            info!("Received SIGHUP, reopening destination files");
+            generation.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+    #[cfg(not(unix))]
+    let _ = generation;
+}
+
+struct ActiveFile {
+    file: File,
+    size: u64,
+    opened: Instant,
+    series: String,
+}
+
+/// NDJSON files for a file destination, one open per rendered path.
+///
+/// A file is rotated once it reaches `size_mb` or has been open for
+/// `time_hours`: it is renamed with a timestamp suffix, compressed in the
+/// background and the oldest closed segments of its series beyond
+/// `keep_files` are deleted (0 keeps them all).
+pub struct RotatingFiles {
+    template: PathTemplate,
+    policy: RollPolicy,
+    keep_files: u32,
+    compression: Option<CompressionType>,
+    reopen: Arc<AtomicU64>,
+    reopened: u64,
+    open: HashMap<PathBuf, ActiveFile>,
+    // Compression and retention of rotated segments, one at a time
+    closing: Option<JoinHandle<()>>,
+}
+
+impl RotatingFiles {
+    pub fn new(
+        template: &str,
+        rotation: &FileRotation,
+        compression: Option<&CompressionType>,
+        reopen: Arc<AtomicU64>,
+    ) -> Result<Self> {
+        validate_compression(compression)?;
+        let template = PathTemplate::new(template);
+        let root = template.root();
+        if !root.as_os_str().is_empty() {
+            std::fs::create_dir_all(&root)
+                .map_err(|e| PipelineError::io(format!("Failed to create directory {}: {}", root.display(), e)))?;
+        }
+
+        let reopened = reopen.load(Ordering::SeqCst);
+        Ok(Self {
+            template,
+            policy: RollPolicy::from_rotation(rotation),
+            keep_files: rotation.keep_files,
+            compression: compression.cloned(),
+            reopen,
+            reopened,
+            open: HashMap::new(),
+            closing: None,
+        })
+    }
+
+    /// Append events to their files, returning the bytes written.
+    pub async fn write(&mut self, events: &[PipelineEvent]) -> Result<u64> {
+        self.reopen_if_signalled().await;
+
+        let mut files: BTreeMap<PathBuf, (String, Vec<u8>)> = BTreeMap::new();
+        for event in events {
+            let (_, lines) = files.entry(self.template.render(event))
+                .or_insert_with(|| (self.template.series(event), Vec::new()));
+            serde_json::to_writer(&mut *lines, event)?;
+            lines.push(b'\n');
+        }
+
+        let mut bytes_written = 0;
+        for (path, (series, lines)) in files {
+            let mut active = match self.open.remove(&path) {
+                Some(active) => active,
+                None => Self::open_file(&path, series).await?,
+            };
+            let written = match active.file.write_all(&lines).await {
+                Ok(()) => active.file.flush().await,
+                Err(e) => Err(e),
+            };
+            // A failed file is dropped so the retry reopens it rather than append after a partial write
+            if let Err(e) = written {
+                return Err(PipelineError::io(format!("Failed to write to {}: {}", path.display(), e)));
+            }
+            active.size += lines.len() as u64;
+            bytes_written += lines.len() as u64;
+            self.open.insert(path, active);
+        }
+
+        self.rotate(false).await?;
+        Ok(bytes_written)
+    }
+
+    /// Rotate files that are due, or flush every file and wait for pending
+    /// compression when `closing`.
+    pub async fn rotate(&mut self, closing: bool) -> Result<()> {
+        if closing {
+            for (path, mut active) in self.open.drain() {
+                if let Err(e) = active.file.flush().await {
+                    warn!("Failed to flush {}: {}", path.display(), e);
+                }
+            }
+            if let Some(task) = self.closing.take() {
+                let _ = task.await;
+            }
+            return Ok(());
+        }
+
+        self.reopen_if_signalled().await;
+        let due: Vec<PathBuf> = self.open.iter()
+            .filter(|(_, active)| active.size >= self.policy.max_bytes || active.opened.elapsed() >= self.policy.max_age)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for path in due {
+            let Some(mut active) = self.open.remove(&path) else { continue };
+            active.file.flush().await?;
+            drop(active.file);
+
+            let segment = segment_path(&path);
+            tokio::fs::rename(&path, &segment).await
+                .map_err(|e| PipelineError::io(format!("Failed to rotate {}: {}", path.display(), e)))?;
+            debug!("Rotated {} to {}", path.display(), segment.display());
+
+            let compression = self.compression.clone();
+            let root = self.template.root();
+            let keep_files = self.keep_files;
+            let previous = self.closing.take();
+            self.closing = Some(tokio::spawn(async move {
+                if let Some(previous) = previous {
+                    let _ = previous.await;
+                }
+                let _ = tokio::task::spawn_blocking(move || {
+                    if let Err(e) = compress_segment(&segment, compression.as_ref()) {
+                        warn!("Failed to compress {}: {}", segment.display(), e);
+                    }
+                    if let Err(e) = prune(&root, &active.series, keep_files) {
+                        warn!("Failed to apply retention to {}: {}", active.series, e);
+                    }
+                }).await;
+            }));
+        }
+        Ok(())
+    }
+
+    async fn open_file(path: &Path, series: String) -> Result<ActiveFile> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path).await
+            .map_err(|e| PipelineError::io(format!("Failed to open file {}: {}", path.display(), e)))?;
+        let size = file.metadata().await?.len();
+        Ok(ActiveFile { file, size, opened: Instant::now(), series })
+    }
+
+    // Files are reopened lazily by the next write to their path
+    async fn reopen_if_signalled(&mut self) {
+        let generation = self.reopen.load(Ordering::SeqCst);
+        if generation == self.reopened {
+            return;
+        }
+        self.reopened = generation;
+        for (path, mut active) in self.open.drain() {
+            if let Err(e) = active.file.flush().await {
+                warn!("Failed to flush {} before reopening: {}", path.display(), e);
+            }
+        }
+    }
+}
+
+// `events.ndjson` -> `events.ndjson.20240501T130000`, numbered if taken
+fn segment_path(path: &Path) -> PathBuf {
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S");
+    let mut segment = PathBuf::from(format!("{}.{}", path.display(), stamp));
+    let mut n = 1;
+    while segment.exists() || has_compressed(&segment) {
+        segment = PathBuf::from(format!("{}.{}-{}", path.display(), stamp, n));
+        n += 1;
+    }
+    segment
+}
+
+fn has_compressed(segment: &Path) -> bool {
+    [".gz", ".zst", ".lz4"].iter().any(|ext| PathBuf::from(format!("{}{}", segment.display(), ext)).exists())
+}
+
+fn compress_segment(segment: &Path, compression: Option<&CompressionType>) -> Result<()> {
+    let Some(compression) = compression else { return Ok(()) };
+    let target = PathBuf::from(format!("{}{}", segment.display(), crate::sink::extension(Some(compression))));
+    let mut input = std::fs::File::open(segment)?;
+    // Retention orders segments by when they were last written, compressed or not
+    let last_written = input.metadata()?.modified()?;
+    let output = std::io::BufWriter::new(std::fs::File::create(&target)?);
+
+    let written: std::io::Result<std::fs::File> = match compression {
+        CompressionType::Gzip => {
+            let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+            std::io::copy(&mut input, &mut encoder).and_then(|_| encoder.finish())
+                .and_then(|output| output.into_inner().map_err(|e| e.into_error()))
+        }
+        CompressionType::Zstd => {
+            let mut encoder = zstd::stream::write::Encoder::new(output, 3)?;
+            std::io::copy(&mut input, &mut encoder).and_then(|_| encoder.finish())
+                .and_then(|output| output.into_inner().map_err(|e| e.into_error()))
+        }
+        CompressionType::Lz4 => {
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(output);
+            std::io::copy(&mut input, &mut encoder)
+                .and_then(|_| encoder.finish().map_err(std::io::Error::from))
+                .and_then(|output| output.into_inner().map_err(|e| e.into_error()))
+        }
+        CompressionType::Snappy => return validate_compression(Some(compression)),
+    };
+
+    // `File::set_modified` needs Rust 1.75, newer than the crate's MSRV
+    let last_written = filetime::FileTime::from_system_time(last_written);
+    let finished = written.and_then(|mut file| {
+        file.flush()?;
+        filetime::set_file_handle_times(&file, None, Some(last_written))?;
+        file.sync_all()
+    });
+    match finished {
+        Ok(()) => {
+            std::fs::remove_file(segment)?;
+            Ok(())
+        }
+        Err(e) => {
+            let _ = std::fs::remove_file(&target);
+            Err(PipelineError::compression(format!("Failed to compress {}: {}", segment.display(), e)))
+        }
+    }
+}
+
+// Delete the oldest closed segments matching `series` beyond `keep_files`
+fn prune(root: &Path, series: &str, keep_files: u32) -> Result<()> {
+    if keep_files == 0 {
+        return Ok(());
+    }
+    let matcher = GlobBuilder::new(series)
+        .literal_separator(true)
+        .build()
+        .map_err(|e| PipelineError::config(format!("Invalid file path pattern '{}': {}", series, e)))?
+        .compile_matcher();
+
+    let mut segments: Vec<(std::time::SystemTime, PathBuf)> = WalkDir::new(root).into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file() && matcher.is_match(entry.path()))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.into_path())))
+        .collect();
+    segments.sort();
+
+    let excess = segments.len().saturating_sub(keep_files as usize);
+    for (_, path) in segments.into_iter().take(excess) {
+        std::fs::remove_file(&path)?;
+        info!("Removed expired file segment {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ProcessingStage;
+    use chrono::TimeZone;
+    use std::io::Read;
+
+    fn event(tenant: &str, day: u32) -> PipelineEvent {
+        PipelineEvent {
+            id: uuid::Uuid::new_v4(),
+            timestamp: chrono::Utc.with_ymd_and_hms(2024, 5, day, 13, 0, 0).unwrap(),
+            source: "fw".to_string(),
+            data: serde_json::json!({ "message": "x".repeat(100) }),
+            metadata: HashMap::from([("tenant_id".to_string(), tenant.to_string())]),
+            processing_stage: ProcessingStage::Routed,
+        }
+    }
+
+    fn rotation(size_mb: u64, keep_files: u32) -> FileRotation {
+        FileRotation { size_mb, time_hours: 24, keep_files }
+    }
+
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = WalkDir::new(dir).into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.path().strip_prefix(dir).unwrap().display().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_templates() {
+        let template = PathTemplate::new("/var/log/siem/{tenant}/{date}.ndjson");
+        assert_eq!(template.render(&event("acme", 1)), PathBuf::from("/var/log/siem/acme/2024-05-01.ndjson"));
+        assert_eq!(template.render(&event("../x", 1)), PathBuf::from("/var/log/siem/_x/2024-05-01.ndjson"));
+        assert_eq!(template.root(), PathBuf::from("/var/log/siem"));
+        assert_eq!(template.series(&event("acme", 1)), "/var/log/siem/acme/*.ndjson.*");
+        assert_eq!(PathTemplate::new("/var/log/events.ndjson").root(), PathBuf::from("/var/log"));
+    }
+
+    #[tokio::test]
+    async fn test_rotation_compression_and_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = format!("{}/{{tenant}}/{{date}}.ndjson", dir.path().display());
+        let mut files = RotatingFiles::new(&template, &rotation(1, 2), Some(&CompressionType::Gzip), Arc::default()).unwrap();
+        // Rotate after every write
+        files.policy.max_bytes = 1;
+
+        for day in 1..=4 {
+            files.write(&[event("acme", day)]).await.unwrap();
+        }
+        files.write(&[event("globex", 1)]).await.unwrap();
+        files.rotate(true).await.unwrap();
+
+        let found = names(dir.path());
+        assert_eq!(found.len(), 3, "{:?}", found);
+        assert!(found[0].starts_with("acme/2024-05-03.ndjson.") && found[0].ends_with(".gz"), "{:?}", found);
+        assert!(found[1].starts_with("acme/2024-05-04.ndjson."));
+        assert!(found[2].starts_with("globex/2024-05-01.ndjson."));
+
+        let mut text = String::new();
+        flate2::read::MultiGzDecoder::new(std::fs::File::open(dir.path().join(&found[0])).unwrap())
+            .read_to_string(&mut text).unwrap();
+        assert_eq!(text.lines().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reopens_after_hangup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.ndjson");
+        let reopen = Arc::new(AtomicU64::new(0));
+        let mut files = RotatingFiles::new(&path.display().to_string(), &rotation(1024, 0), None, reopen.clone()).unwrap();
+
+        files.write(&[event("acme", 1)]).await.unwrap();
+        // logrotate moves the file away and signals
+        std::fs::rename(&path, dir.path().join("events.ndjson.1")).unwrap();
+        reopen.fetch_add(1, Ordering::SeqCst);
+        files.write(&[event("acme", 2)]).await.unwrap();
+        files.rotate(true).await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+        assert_eq!(std::fs::read_to_string(dir.path().join("events.ndjson.1")).unwrap().lines().count(), 1);
+    }
+}
@@ -0,0 +1,518 @@
+use std::collections::HashMap;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tracing::{debug, info, warn};
+use walkdir::WalkDir;
+
+use crate::error::{Result, PipelineError};
+use crate::utils;
+
+// Files shorter than this have no fingerprint and are identified by inode alone
+const FINGERPRINT_BYTES: usize = 256;
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+// Keeps one busy file from starving the others within a poll
+const MAX_READ_PER_POLL: usize = 4 * 1024 * 1024;
+// Lines and multi-line events are cut at this size instead of buffering without bound
+const MAX_EVENT_BYTES: usize = 1024 * 1024;
+const MULTILINE_FLUSH_AFTER: Duration = Duration::from_secs(2);
+
+/// Persisted read position for one file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileCheckpoint {
+    pub path: String,
+    pub fingerprint: Option<u64>,
+    pub offset: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CheckpointFile {
+    files: HashMap<String, FileCheckpoint>,
+}
+
+/// Local JSON store of per-file offsets, keyed by file identity.
+pub struct CheckpointStore {
+    path: PathBuf,
+    files: HashMap<String, FileCheckpoint>,
+    dirty: bool,
+}
+
+impl CheckpointStore {
+    /// Load the store at `path`; a missing or unreadable file starts empty.
+    pub async fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let files = match tokio::fs::read(&path).await {
+            Ok(content) => match serde_json::from_slice::<CheckpointFile>(&content) {
+                Ok(checkpoints) => {
+                    info!("Loaded {} file checkpoints from {}", checkpoints.files.len(), path.display());
+                    checkpoints.files
+                }
+                Err(e) => {
+                    warn!("Ignoring unreadable checkpoint file {}: {}", path.display(), e);
+                    HashMap::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                warn!("Failed to read checkpoint file {}: {}", path.display(), e);
+                HashMap::new()
+            }
+        };
+
+        Self { path, files, dirty: false }
+    }
+
+    pub fn get(&self, id: &str) -> Option<&FileCheckpoint> {
+        self.files.get(id)
+    }
+
+    fn update(&mut self, id: &str, checkpoint: FileCheckpoint) {
+        if self.files.get(id) != Some(&checkpoint) {
+            self.files.insert(id.to_string(), checkpoint);
+            self.dirty = true;
+        }
+    }
+
+    fn retain<F: FnMut(&str) -> bool>(&mut self, mut keep: F) {
+        let before = self.files.len();
+        self.files.retain(|id, _| keep(id));
+        self.dirty |= self.files.len() != before;
+    }
+
+    /// Write the store if anything changed since the last save.
+    pub async fn save(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let content = serde_json::to_vec(&CheckpointFile { files: self.files.clone() })?;
+        utils::write_file_atomic(&self.path, &content).await?;
+
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// A complete line, or joined multi-line event, read from a tailed file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TailedEvent {
+    pub path: String,
+    pub message: String,
+    /// Byte offset just past the event in its file
+    pub offset: u64,
+}
+
+struct PendingEvent {
+    message: String,
+    end_offset: u64,
+}
+
+struct TailedFile {
+    path: PathBuf,
+    file: File,
+    fingerprint: Option<u64>,
+    read_offset: u64,
+    // Everything before this offset has been handed out as events
+    committed_offset: u64,
+    line_start: u64,
+    partial: Vec<u8>,
+    pending: Option<PendingEvent>,
+    last_activity: Instant,
+    seen: bool,
+}
+
+impl TailedFile {
+    fn path_string(&self) -> String {
+        self.path.to_string_lossy().into_owned()
+    }
+
+    async fn read_new(&mut self, multiline: Option<&Regex>, events: &mut Vec<TailedEvent>) -> std::io::Result<bool> {
+        let len = self.file.metadata().await?.len();
+        if len < self.read_offset {
+            info!("{} was truncated, reading from the start", self.path.display());
+            self.flush_pending(events);
+            self.file.seek(SeekFrom::Start(0)).await?;
+            self.partial.clear();
+            self.read_offset = 0;
+            self.committed_offset = 0;
+            self.line_start = 0;
+            self.fingerprint = None;
+        }
+
+        let mut buf = vec![0u8; READ_CHUNK_SIZE];
+        let mut budget = MAX_READ_PER_POLL;
+        let at_eof = loop {
+            if budget == 0 {
+                break false;
+            }
+            let n = self.file.read(&mut buf[..READ_CHUNK_SIZE.min(budget)]).await?;
+            if n == 0 {
+                break true;
+            }
+            budget -= n;
+            self.last_activity = Instant::now();
+            self.consume(&buf[..n], multiline, events);
+        };
+
+        if self.fingerprint.is_none() && self.read_offset >= FINGERPRINT_BYTES as u64 {
+            self.file.seek(SeekFrom::Start(0)).await?;
+            self.fingerprint = fingerprint(&mut self.file, self.read_offset).await?;
+            self.file.seek(SeekFrom::Start(self.read_offset)).await?;
+        }
+
+        Ok(at_eof)
+    }
+
+    fn consume(&mut self, bytes: &[u8], multiline: Option<&Regex>, events: &mut Vec<TailedEvent>) {
+        let mut start = 0;
+        for (i, byte) in bytes.iter().enumerate() {
+            if *byte == b'\n' {
+                self.partial.extend_from_slice(&bytes[start..i]);
+                let end = self.line_start + self.partial.len() as u64 + 1;
+                self.take_line(end, multiline, events);
+                start = i + 1;
+            }
+        }
+        self.partial.extend_from_slice(&bytes[start..]);
+        self.read_offset += bytes.len() as u64;
+
+        if self.partial.len() >= MAX_EVENT_BYTES {
+            let end = self.line_start + self.partial.len() as u64;
+            self.take_line(end, multiline, events);
+        }
+    }
+
+    fn take_line(&mut self, end: u64, multiline: Option<&Regex>, events: &mut Vec<TailedEvent>) {
+        if self.partial.last() == Some(&b'\r') {
+            self.partial.pop();
+        }
+        let line = String::from_utf8_lossy(&self.partial).into_owned();
+        let start = self.line_start;
+        self.partial.clear();
+        self.line_start = end;
+
+        let Some(start_pattern) = multiline else {
+            events.push(TailedEvent { path: self.path_string(), message: line, offset: end });
+            self.committed_offset = end;
+            return;
+        };
+
+        match &mut self.pending {
+            Some(pending) if !start_pattern.is_match(&line) => {
+                pending.message.push('\n');
+                pending.message.push_str(&line);
+                pending.end_offset = end;
+                if pending.message.len() >= MAX_EVENT_BYTES {
+                    self.flush_pending(events);
+                }
+            }
+            _ => {
+                self.flush_pending(events);
+                self.pending = Some(PendingEvent { message: line, end_offset: end });
+                self.committed_offset = start;
+            }
+        }
+    }
+
+    fn flush_pending(&mut self, events: &mut Vec<TailedEvent>) {
+        if let Some(pending) = self.pending.take() {
+            events.push(TailedEvent { path: self.path_string(), message: pending.message, offset: pending.end_offset });
+            self.committed_offset = pending.end_offset;
+        }
+    }
+}
+
+// FNV-1a, so fingerprints stay comparable across builds
+async fn fingerprint(file: &mut File, len: u64) -> std::io::Result<Option<u64>> {
+    if len < FINGERPRINT_BYTES as u64 {
+        return Ok(None);
+    }
+    let mut head = [0u8; FINGERPRINT_BYTES];
+    file.read_exact(&mut head).await?;
+    Ok(Some(head.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })))
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &std::fs::Metadata, _path: &Path) -> String {
+    use std::os::unix::fs::MetadataExt;
+    format!("{}:{}", metadata.dev(), metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &std::fs::Metadata, path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// Follows every file matching a glob, surviving renames, truncation and
+/// restarts.
+///
+/// Files are identified by device and inode, so a logrotate rename keeps
+/// reading the old file to its end while the replacement is picked up from
+/// its first byte. Offsets are only committed past complete events, so a
+/// partially written multi-line event is read again after a restart.
+pub struct FileTailer {
+    base_dir: PathBuf,
+    max_depth: usize,
+    matcher: GlobMatcher,
+    multiline: Option<Regex>,
+    files: HashMap<String, TailedFile>,
+    checkpoints: CheckpointStore,
+}
+
+impl FileTailer {
+    pub fn new(pattern: &str, multiline_start: Option<&str>, checkpoints: CheckpointStore) -> Result<Self> {
+        let matcher = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| PipelineError::config(format!("Invalid file path pattern '{}': {}", pattern, e)))?
+            .compile_matcher();
+
+        let multiline = multiline_start
+            .map(|start| {
+                Regex::new(start)
+                    .map_err(|e| PipelineError::config(format!("Invalid multiline start pattern '{}': {}", start, e)))
+            })
+            .transpose()?;
+
+        let (base_dir, max_depth) = glob_base(pattern);
+        Ok(Self {
+            base_dir,
+            max_depth,
+            matcher,
+            multiline,
+            files: HashMap::new(),
+            checkpoints,
+        })
+    }
+
+    /// Directory that contains every file the pattern can match.
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// Whether matches can lie below the immediate children of `base_dir`.
+    pub fn is_recursive(&self) -> bool {
+        self.max_depth > 1
+    }
+
+    pub fn tracked_files(&self) -> usize {
+        self.files.len()
+    }
+
+    fn discover(&self) -> Vec<PathBuf> {
+        WalkDir::new(&self.base_dir)
+            .follow_links(true)
+            .max_depth(self.max_depth)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .filter(|path| self.matcher.is_match(path.strip_prefix(".").unwrap_or(path)))
+            .collect()
+    }
+
+    async fn open(&self, id: &str, path: &Path, len: u64) -> std::io::Result<TailedFile> {
+        let mut file = File::open(path).await?;
+        let fingerprint = fingerprint(&mut file, len).await?;
+
+        let offset = match self.checkpoints.get(id) {
+            Some(checkpoint) => {
+                let reused = matches!((checkpoint.fingerprint, fingerprint), (Some(a), Some(b)) if a != b);
+                if reused || checkpoint.offset > len {
+                    info!("Checkpoint for {} no longer matches the file, reading from the start", path.display());
+                    0
+                } else {
+                    checkpoint.offset
+                }
+            }
+            None => 0,
+        };
+        file.seek(SeekFrom::Start(offset)).await?;
+        debug!("Tailing {} from offset {}", path.display(), offset);
+
+        Ok(TailedFile {
+            path: path.to_path_buf(),
+            file,
+            fingerprint,
+            read_offset: offset,
+            committed_offset: offset,
+            line_start: offset,
+            partial: Vec::new(),
+            pending: None,
+            last_activity: Instant::now(),
+            seen: true,
+        })
+    }
+
+    /// Pick up new and rotated files and return every event written since
+    /// the last poll.
+    pub async fn poll(&mut self) -> Vec<TailedEvent> {
+        for tailed in self.files.values_mut() {
+            tailed.seen = false;
+        }
+
+        for path in self.discover() {
+            let metadata = match tokio::fs::metadata(&path).await {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    debug!("Skipping {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            let id = file_id(&metadata, &path);
+
+            if let Some(tailed) = self.files.get_mut(&id) {
+                if tailed.path != path {
+                    info!("{} was renamed to {}", tailed.path.display(), path.display());
+                    tailed.path = path;
+                }
+                tailed.seen = true;
+                continue;
+            }
+
+            match self.open(&id, &path, metadata.len()).await {
+                Ok(tailed) => {
+                    self.files.insert(id, tailed);
+                }
+                Err(e) => warn!("Failed to open {}: {}", path.display(), e),
+            }
+        }
+
+        let mut events = Vec::new();
+        let mut finished = Vec::new();
+        for (id, tailed) in self.files.iter_mut() {
+            match tailed.read_new(self.multiline.as_ref(), &mut events).await {
+                Ok(at_eof) => {
+                    if tailed.pending.is_some() && tailed.last_activity.elapsed() >= MULTILINE_FLUSH_AFTER {
+                        tailed.flush_pending(&mut events);
+                    }
+                    // Rotated away or deleted: drain what was written, then let go
+                    if at_eof && !tailed.seen {
+                        tailed.flush_pending(&mut events);
+                        finished.push(id.clone());
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to read {}: {}", tailed.path.display(), e);
+                    if !tailed.seen {
+                        finished.push(id.clone());
+                    }
+                }
+            }
+        }
+
+        for id in finished {
+            if let Some(tailed) = self.files.remove(&id) {
+                debug!("Stopped tailing {}", tailed.path.display());
+            }
+        }
+
+        events
+    }
+
+    /// Record the committed offsets of every tracked file and persist them.
+    pub async fn save_checkpoints(&mut self) -> Result<()> {
+        for (id, tailed) in &self.files {
+            self.checkpoints.update(id, FileCheckpoint {
+                path: tailed.path_string(),
+                fingerprint: tailed.fingerprint,
+                offset: tailed.committed_offset,
+            });
+        }
+        let files = &self.files;
+        self.checkpoints.retain(|id| files.contains_key(id));
+        self.checkpoints.save().await
+    }
+}
+
+// Split a glob into the literal directory it starts from and how deep below
+// that directory matches can be
+fn glob_base(pattern: &str) -> (PathBuf, usize) {
+    let is_glob = |component: &str| component.contains(['*', '?', '[', '{']);
+    let components: Vec<&str> = pattern.split('/').collect();
+
+    let literal = components.iter().take_while(|c| !is_glob(c)).count();
+    let (base, depth) = if literal == components.len() {
+        // Plain path: watch the single file from its parent directory
+        (components[..literal - 1].join("/"), 1)
+    } else if components[literal..].iter().any(|c| c.contains("**")) {
+        (components[..literal].join("/"), usize::MAX)
+    } else {
+        (components[..literal].join("/"), components.len() - literal)
+    };
+
+    let base = match base.as_str() {
+        "" if pattern.starts_with('/') => PathBuf::from("/"),
+        "" => PathBuf::from("."),
+        _ => PathBuf::from(base),
+    };
+    (base, depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write(path: &Path, content: &str) {
+        std::fs::OpenOptions::new().create(true).append(true).open(path).unwrap()
+            .write_all(content.as_bytes()).unwrap();
+    }
+
+    fn messages(events: &[TailedEvent]) -> Vec<&str> {
+        events.iter().map(|e| e.message.as_str()).collect()
+    }
+
+    #[test]
+    fn test_glob_base() {
+        assert_eq!(glob_base("/var/log/*.log"), (PathBuf::from("/var/log"), 1));
+        assert_eq!(glob_base("/var/log/**/*.log"), (PathBuf::from("/var/log"), usize::MAX));
+        assert_eq!(glob_base("/var/log/app.log"), (PathBuf::from("/var/log"), 1));
+        assert_eq!(glob_base("logs/*/app.log"), (PathBuf::from("logs"), 2));
+        assert_eq!(glob_base("*.log"), (PathBuf::from("."), 1));
+    }
+
+    #[tokio::test]
+    async fn test_rotation_multiline_and_checkpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = dir.path().join("app.log");
+        let checkpoint_path = dir.path().join("state/checkpoints.json");
+        let pattern = format!("{}/*.log", dir.path().display());
+
+        write(&log, "2024 first\n  at frame\n2024 second\npartial");
+        let mut tailer = FileTailer::new(&pattern, Some(r"^\d{4} "), CheckpointStore::load(&checkpoint_path).await).unwrap();
+        let events = tailer.poll().await;
+        assert_eq!(messages(&events), vec!["2024 first\n  at frame"]);
+        tailer.save_checkpoints().await.unwrap();
+
+        // Restart: the pending "second" event is read again, nothing is duplicated
+        let mut tailer = FileTailer::new(&pattern, Some(r"^\d{4} "), CheckpointStore::load(&checkpoint_path).await).unwrap();
+        write(&log, " line\n2024 third\n");
+        let events = tailer.poll().await;
+        assert_eq!(messages(&events), vec!["2024 second\npartial line"]);
+
+        // logrotate: the renamed file is drained, the new one starts at byte 0
+        std::fs::rename(&log, dir.path().join("app.log.1")).unwrap();
+        write(&dir.path().join("app.log.1"), "2024 late write\n");
+        write(&log, "2024 new file\n");
+        let events = tailer.poll().await;
+        assert_eq!(messages(&events), vec!["2024 third", "2024 late write"]);
+        assert_eq!(tailer.tracked_files(), 1);
+
+        // copytruncate releases the event pending from the old contents
+        std::fs::write(&log, "2024 x\n").unwrap();
+        let events = tailer.poll().await;
+        assert_eq!(messages(&events), vec!["2024 new file"]);
+        tailer.save_checkpoints().await.unwrap();
+
+        let saved: CheckpointFile = serde_json::from_slice(&std::fs::read(&checkpoint_path).unwrap()).unwrap();
+        assert_eq!(saved.files.len(), 1);
+        assert_eq!(saved.files.values().next().unwrap().offset, 0);
+    }
+}
@@ -0,0 +1,535 @@
+use std::borrow::Cow;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::error::{Result, PipelineError};
+use crate::pipeline::PipelineEvent;
+use crate::transformation::ParsedEvent;
+use crate::utils;
+
+/// Compiled filter condition, e.g.
+/// `severity in ['high', 'critical'] and not (src_ip cidr '10.0.0.0/8' or message matches '^health')`.
+///
+/// Fields are dotted paths looked up in `PipelineEvent.data`, then `metadata`, then the parsed
+/// event; a `parsed.` prefix reads the parsed event only. String comparisons ignore case.
+/// A bare field is true when it is present and not `false`, `null`, `0` or empty.
+#[derive(Debug, Clone)]
+pub struct FilterExpression {
+    source: String,
+    root: Expr,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Truthy(String),
+    Compare { field: String, op: CompareOp, value: Value },
+    In { field: String, values: Vec<Value> },
+    Matches { field: String, regex: Regex },
+    Cidr { field: String, blocks: Vec<String> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Symbol(&'static str),
+}
+
+impl FilterExpression {
+    pub fn compile(source: &str) -> Result<Self> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let root = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(PipelineError::validation(format!(
+                "Unexpected {:?} in filter condition '{}'", token, source
+            )));
+        }
+        Ok(FilterExpression { source: source.to_string(), root })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn matches(&self, event: &PipelineEvent, parsed: Option<&ParsedEvent>) -> bool {
+        eval(&self.root, event, parsed)
+    }
+}
+
+fn eval(expr: &Expr, event: &PipelineEvent, parsed: Option<&ParsedEvent>) -> bool {
+    match expr {
+        Expr::And(a, b) => eval(a, event, parsed) && eval(b, event, parsed),
+        Expr::Or(a, b) => eval(a, event, parsed) || eval(b, event, parsed),
+        Expr::Not(inner) => !eval(inner, event, parsed),
+        Expr::Truthy(field) => match lookup(field, event, parsed).as_deref() {
+            None | Some(Value::Null) | Some(Value::Bool(false)) => false,
+            Some(Value::String(s)) => !s.is_empty(),
+            Some(Value::Number(n)) => n.as_f64().map(|f| f != 0.0).unwrap_or(true),
+            Some(Value::Array(items)) => !items.is_empty(),
+            Some(_) => true,
+        },
+        Expr::Compare { field, op, value } => match lookup(field, event, parsed) {
+            // A missing field is only ever "not equal"
+            None => *op == CompareOp::Ne,
+            Some(actual) => {
+                let hit = any_element(&actual, |v| compare(v, *op, value));
+                if *op == CompareOp::Ne {
+                    // For arrays, != means no element equals the value
+                    !any_element(&actual, |v| compare(v, CompareOp::Eq, value))
+                } else {
+                    hit
+                }
+            }
+        },
+        Expr::In { field, values } => lookup(field, event, parsed)
+            .map(|actual| any_element(&actual, |v| values.iter().any(|c| compare(v, CompareOp::Eq, c))))
+            .unwrap_or(false),
+        Expr::Matches { field, regex } => lookup(field, event, parsed)
+            .map(|actual| any_element(&actual, |v| regex.is_match(&as_text(v))))
+            .unwrap_or(false),
+        Expr::Cidr { field, blocks } => lookup(field, event, parsed)
+            .map(|actual| any_element(&actual, |v| {
+                let ip = as_text(v);
+                blocks.iter().any(|b| utils::ip_in_cidr(&ip, b).unwrap_or(false))
+            }))
+            .unwrap_or(false),
+    }
+}
+
+fn any_element(value: &Value, mut check: impl FnMut(&Value) -> bool) -> bool {
+    match value {
+        Value::Array(items) => items.iter().any(check),
+        other => check(other),
+    }
+}
+
+fn lookup<'a>(field: &str, event: &'a PipelineEvent, parsed: Option<&'a ParsedEvent>) -> Option<Cow<'a, Value>> {
+    if let Some(rest) = field.strip_prefix("parsed.") {
+        return parsed.and_then(|p| parsed_field(p, rest));
+    }
+    if let Some(rest) = field.strip_prefix("metadata.") {
+        if let Some(value) = event.metadata.get(rest) {
+            return Some(Cow::Owned(Value::String(value.clone())));
+        }
+    }
+    event.get_field(field).or_else(|| parsed.and_then(|p| parsed_field(p, field)))
+}
+
+fn parsed_field<'a>(parsed: &'a ParsedEvent, field: &str) -> Option<Cow<'a, Value>> {
+    let text = match field {
+        "timestamp" => parsed.timestamp.to_rfc3339(),
+        "severity" => parsed.severity.clone(),
+        "facility" => parsed.facility.clone(),
+        "hostname" => parsed.hostname.clone(),
+        "process" => parsed.process.clone(),
+        "message" => parsed.message.clone(),
+        "source_country" => parsed.source_country.clone()?,
+        "destination_country" => parsed.destination_country.clone()?,
+        _ => return parsed.fields.get(field).map(Cow::Borrowed),
+    };
+    Some(Cow::Owned(Value::String(text)))
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
+
+fn compare(actual: &Value, op: CompareOp, expected: &Value) -> bool {
+    match op {
+        CompareOp::Eq | CompareOp::Ne => {
+            let equal = match (actual, expected) {
+                (_, Value::Null) => actual.is_null(),
+                (Value::Bool(a), Value::Bool(b)) => a == b,
+                (Value::String(a), Value::Bool(b)) => a.eq_ignore_ascii_case(&b.to_string()),
+                (_, Value::Number(_)) => match (as_number(actual), as_number(expected)) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => false,
+                },
+                _ => as_text(actual).to_lowercase() == as_text(expected).to_lowercase(),
+            };
+            if op == CompareOp::Eq { equal } else { !equal }
+        }
+        CompareOp::Gt | CompareOp::Ge | CompareOp::Lt | CompareOp::Le => {
+            let ordering = match (as_number(actual), as_number(expected)) {
+                (Some(a), Some(b)) => a.partial_cmp(&b),
+                _ => Some(as_text(actual).cmp(&as_text(expected))),
+            };
+            match ordering {
+                Some(std::cmp::Ordering::Greater) => matches!(op, CompareOp::Gt | CompareOp::Ge),
+                Some(std::cmp::Ordering::Less) => matches!(op, CompareOp::Lt | CompareOp::Le),
+                Some(std::cmp::Ordering::Equal) => matches!(op, CompareOp::Ge | CompareOp::Le),
+                None => false,
+            }
+        }
+        CompareOp::Contains | CompareOp::StartsWith | CompareOp::EndsWith => {
+            let haystack = as_text(actual).to_lowercase();
+            let needle = as_text(expected).to_lowercase();
+            match op {
+                CompareOp::Contains => haystack.contains(&needle),
+                CompareOp::StartsWith => haystack.starts_with(&needle),
+                _ => haystack.ends_with(&needle),
+            }
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+
+        if c == '\'' || c == '"' {
+            let quote = c;
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(PipelineError::validation(format!("Unterminated string in '{}'", source))),
+                    Some('\\') if i + 1 < chars.len() => {
+                        text.push(chars[i + 1]);
+                        i += 2;
+                    }
+                    Some(&ch) if ch == quote => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&ch) => {
+                        text.push(ch);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(Token::Str(text));
+            continue;
+        }
+
+        let starts_number = c.is_ascii_digit()
+            || (c == '-' && chars.get(i + 1).map(|n| n.is_ascii_digit()).unwrap_or(false));
+        if starts_number {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = text.parse()
+                .map_err(|_| PipelineError::validation(format!("Invalid number '{}' in filter condition", text)))?;
+            tokens.push(Token::Num(number));
+            continue;
+        }
+
+        if c.is_alphabetic() || c == '_' || c == '@' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || matches!(chars[i], '_' | '.' | '@' | '-')) {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+            continue;
+        }
+
+        let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+        let symbol = match two.as_str() {
+            "==" => Some("=="),
+            "!=" => Some("!="),
+            ">=" => Some(">="),
+            "<=" => Some("<="),
+            "=~" => Some("=~"),
+            "!~" => Some("!~"),
+            "&&" => Some("and"),
+            "||" => Some("or"),
+            _ => None,
+        };
+        if let Some(symbol) = symbol {
+            tokens.push(Token::Symbol(symbol));
+            i += 2;
+            continue;
+        }
+
+        let symbol = match c {
+            '(' => "(",
+            ')' => ")",
+            '[' => "[",
+            ']' => "]",
+            ',' => ",",
+            '>' => ">",
+            '<' => "<",
+            '=' => "==",
+            '!' => "not",
+            _ => return Err(PipelineError::validation(format!("Unexpected character '{}' in filter condition", c))),
+        };
+        tokens.push(Token::Symbol(symbol));
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    // Keywords and symbolic operators are interchangeable (`and`/`&&`, `not`/`!`)
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Ident(word)) => word.eq_ignore_ascii_case(keyword),
+            Some(Token::Symbol(symbol)) => *symbol == keyword,
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<()> {
+        match self.next() {
+            Some(Token::Symbol(s)) if s == symbol => Ok(()),
+            other => Err(PipelineError::validation(format!("Expected '{}' but found {:?}", symbol, other))),
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr> {
+        let mut left = self.parse_and()?;
+        while self.peek_keyword("or") {
+            self.pos += 1;
+            left = Expr::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr> {
+        let mut left = self.parse_not()?;
+        while self.peek_keyword("and") {
+            self.pos += 1;
+            left = Expr::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expr> {
+        if self.peek_keyword("not") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Symbol("(")) => {
+                let inner = self.parse_or()?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Some(Token::Ident(field)) => self.parse_comparison(field),
+            other => Err(PipelineError::validation(format!("Expected a field name but found {:?}", other))),
+        }
+    }
+
+    fn parse_comparison(&mut self, field: String) -> Result<Expr> {
+        let op = match self.peek().cloned() {
+            Some(Token::Symbol(symbol)) => symbol.to_string(),
+            Some(Token::Ident(word)) => word.to_lowercase(),
+            _ => return Ok(Expr::Truthy(field)),
+        };
+
+        let compare = |op| -> Option<CompareOp> {
+            Some(match op {
+                "==" | "eq" => CompareOp::Eq,
+                "!=" | "ne" => CompareOp::Ne,
+                ">" | "gt" => CompareOp::Gt,
+                ">=" | "gte" => CompareOp::Ge,
+                "<" | "lt" => CompareOp::Lt,
+                "<=" | "lte" => CompareOp::Le,
+                "contains" => CompareOp::Contains,
+                "startswith" | "starts_with" => CompareOp::StartsWith,
+                "endswith" | "ends_with" => CompareOp::EndsWith,
+                _ => return None,
+            })
+        };
+
+        if let Some(op) = compare(op.as_str()) {
+            self.pos += 1;
+            let value = self.parse_value()?;
+            return Ok(Expr::Compare { field, op, value });
+        }
+
+        match op.as_str() {
+            "in" => {
+                self.pos += 1;
+                Ok(Expr::In { field, values: self.parse_list()? })
+            }
+            "not" => {
+                // `field not in [...]` / `field not contains '...'`
+                self.pos += 1;
+                let inner = self.parse_comparison(field)?;
+                Ok(Expr::Not(Box::new(inner)))
+            }
+            "=~" | "!~" | "matches" => {
+                self.pos += 1;
+                let pattern = match self.parse_value()? {
+                    Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                let regex = Regex::new(&pattern)
+                    .map_err(|e| PipelineError::validation(format!("Invalid regex '{}': {}", pattern, e)))?;
+                let expr = Expr::Matches { field, regex };
+                Ok(if op == "!~" { Expr::Not(Box::new(expr)) } else { expr })
+            }
+            "cidr" | "in_cidr" => {
+                self.pos += 1;
+                let blocks: Vec<String> = if matches!(self.peek(), Some(Token::Symbol("["))) {
+                    self.parse_list()?.iter().map(as_text).collect()
+                } else {
+                    vec![as_text(&self.parse_value()?)]
+                };
+                for block in &blocks {
+                    utils::parse_cidr(block)?;
+                }
+                Ok(Expr::Cidr { field, blocks })
+            }
+            // `and`, `or`, `)` etc. end a bare field reference
+            _ => Ok(Expr::Truthy(field)),
+        }
+    }
+
+    fn parse_list(&mut self) -> Result<Vec<Value>> {
+        self.expect("[")?;
+        let mut values = Vec::new();
+        if matches!(self.peek(), Some(Token::Symbol("]"))) {
+            self.pos += 1;
+            return Ok(values);
+        }
+        loop {
+            values.push(self.parse_value()?);
+            match self.next() {
+                Some(Token::Symbol(",")) => continue,
+                Some(Token::Symbol("]")) => break,
+                other => return Err(PipelineError::validation(format!("Expected ',' or ']' but found {:?}", other))),
+            }
+        }
+        Ok(values)
+    }
+
+    fn parse_value(&mut self) -> Result<Value> {
+        match self.next() {
+            Some(Token::Str(s)) => Ok(Value::String(s)),
+            Some(Token::Num(n)) => Ok(serde_json::Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null)),
+            Some(Token::Ident(word)) => Ok(match word.to_lowercase().as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                "null" => Value::Null,
+                // Unquoted words are accepted as string literals
+                _ => Value::String(word),
+            }),
+            other => Err(PipelineError::validation(format!("Expected a value but found {:?}", other))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ProcessingStage;
+    use std::collections::HashMap;
+
+    fn event(data: Value) -> PipelineEvent {
+        PipelineEvent {
+            id: uuid::Uuid::new_v4(),
+            timestamp: chrono::Utc::now(),
+            source: "test".to_string(),
+            data,
+            metadata: HashMap::from([("source_type".to_string(), "syslog".to_string())]),
+            processing_stage: ProcessingStage::Parsed,
+        }
+    }
+
+    fn check(condition: &str, data: Value) -> bool {
+        FilterExpression::compile(condition).unwrap().matches(&event(data), None)
+    }
+
+    #[test]
+    fn test_boolean_logic_and_comparisons() {
+        let data = serde_json::json!({
+            "message": "Possible ATTACK detected",
+            "severity": 7,
+            "user": {"name": "root"},
+            "tags": ["auth", "linux"]
+        });
+
+        assert!(check("message CONTAINS 'attack' OR message CONTAINS 'malware'", data.clone()));
+        assert!(check("severity >= 5 and user.name == 'ROOT'", data.clone()));
+        assert!(check("not (severity > 9) && tags in ['auth']", data.clone()));
+        assert!(check("source_type == 'syslog' and metadata.source_type != 'file'", data.clone()));
+        assert!(check("missing != 'x' and not missing", data.clone()));
+        assert!(!check("severity < 5 || user.name not in ['root', 'admin']", data));
+    }
+
+    #[test]
+    fn test_regex_and_cidr() {
+        let data = serde_json::json!({"src_ip": "192.168.4.20", "path": "/admin/login.php"});
+
+        assert!(check("src_ip cidr ['10.0.0.0/8', '192.168.0.0/16']", data.clone()));
+        assert!(!check("src_ip in_cidr '10.0.0.0/8'", data.clone()));
+        assert!(check(r"path =~ '^/admin/.*\.php$'", data.clone()));
+        assert!(check("path !~ 'wp-login'", data));
+
+        // A bare address is a single-host block and IPv4-mapped IPv6 addresses match IPv4 blocks
+        assert!(check("src_ip cidr '192.168.4.20'", serde_json::json!({"src_ip": "192.168.4.20"})));
+        assert!(!check("src_ip cidr '192.168.4.21'", serde_json::json!({"src_ip": "192.168.4.20"})));
+        assert!(check("src_ip cidr '10.0.0.0/8'", serde_json::json!({"src_ip": "::ffff:10.1.2.3"})));
+    }
+
+    #[test]
+    fn test_invalid_conditions_are_rejected() {
+        assert!(FilterExpression::compile("severity >").is_err());
+        assert!(FilterExpression::compile("(a == 1").is_err());
+        assert!(FilterExpression::compile("ip cidr 'not-a-cidr'").is_err());
+        assert!(FilterExpression::compile("msg =~ '('").is_err());
+    }
+}
@@ -0,0 +1,557 @@
+use std::io::Read;
+use chrono::{DateTime, TimeZone, Utc};
+use flate2::read::MultiGzDecoder;
+use rand::RngCore;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha512};
+
+use crate::error::{Result, PipelineError};
+
+// Nesting deeper than this is rejected rather than risking stack exhaustion
+const MAX_DEPTH: usize = 64;
+const EVENT_TIME_EXT: i8 = 0;
+
+/// A decoded MessagePack value, as used on the wire by the Forward protocol.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MsgPack {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Str(String),
+    Bin(Vec<u8>),
+    Array(Vec<MsgPack>),
+    Map(Vec<(MsgPack, MsgPack)>),
+    Ext(i8, Vec<u8>),
+}
+
+impl MsgPack {
+    /// Text content of a string value; older clients send strings as bin.
+    pub fn as_text(&self) -> Option<String> {
+        match self {
+            MsgPack::Str(s) => Some(s.clone()),
+            MsgPack::Bin(b) => Some(String::from_utf8_lossy(b).into_owned()),
+            _ => None,
+        }
+    }
+
+    /// Look up a string key in a map value.
+    pub fn get(&self, key: &str) -> Option<&MsgPack> {
+        match self {
+            MsgPack::Map(entries) => entries.iter()
+                .find(|(k, _)| k.as_text().as_deref() == Some(key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        match self {
+            MsgPack::Nil => Value::Null,
+            MsgPack::Bool(b) => Value::Bool(*b),
+            MsgPack::Int(i) => Value::from(*i),
+            MsgPack::UInt(u) => Value::from(*u),
+            MsgPack::Float(f) => serde_json::Number::from_f64(*f).map(Value::Number).unwrap_or(Value::Null),
+            MsgPack::Str(_) | MsgPack::Bin(_) => Value::String(self.as_text().unwrap_or_default()),
+            MsgPack::Array(items) => Value::Array(items.iter().map(MsgPack::to_json).collect()),
+            MsgPack::Map(entries) => {
+                let mut object = Map::new();
+                for (key, value) in entries {
+                    let key = key.as_text().unwrap_or_else(|| key.to_json().to_string());
+                    object.insert(key, value.to_json());
+                }
+                Value::Object(object)
+            }
+            MsgPack::Ext(..) => match event_time(self) {
+                Some(time) => Value::String(time.to_rfc3339()),
+                None => Value::Null,
+            },
+        }
+    }
+}
+
+enum DecodeError {
+    Incomplete,
+    Invalid(String),
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> std::result::Result<&'a [u8], DecodeError> {
+        let end = self.pos.checked_add(n).ok_or(DecodeError::Incomplete)?;
+        let bytes = self.buf.get(self.pos..end).ok_or(DecodeError::Incomplete)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, width: usize) -> std::result::Result<u64, DecodeError> {
+        Ok(self.take(width)?.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64))
+    }
+
+    fn value(&mut self, depth: usize) -> std::result::Result<MsgPack, DecodeError> {
+        if depth > MAX_DEPTH {
+            return Err(DecodeError::Invalid("MessagePack nesting too deep".to_string()));
+        }
+
+        let marker = self.take(1)?[0];
+        let value = match marker {
+            0x00..=0x7f => MsgPack::UInt(marker as u64),
+            0x80..=0x8f => self.map((marker & 0x0f) as usize, depth)?,
+            0x90..=0x9f => self.array((marker & 0x0f) as usize, depth)?,
+            0xa0..=0xbf => self.str((marker & 0x1f) as usize)?,
+            0xc0 => MsgPack::Nil,
+            0xc2 => MsgPack::Bool(false),
+            0xc3 => MsgPack::Bool(true),
+            0xc4..=0xc6 => {
+                let len = self.uint(1 << (marker - 0xc4))? as usize;
+                MsgPack::Bin(self.take(len)?.to_vec())
+            }
+            0xc7..=0xc9 => {
+                let len = self.uint(1 << (marker - 0xc7))? as usize;
+                self.ext(len)?
+            }
+            0xca => MsgPack::Float(f32::from_bits(self.uint(4)? as u32) as f64),
+            0xcb => MsgPack::Float(f64::from_bits(self.uint(8)?)),
+            0xcc..=0xcf => MsgPack::UInt(self.uint(1 << (marker - 0xcc))?),
+            0xd0 => MsgPack::Int(self.uint(1)? as u8 as i8 as i64),
+            0xd1 => MsgPack::Int(self.uint(2)? as u16 as i16 as i64),
+            0xd2 => MsgPack::Int(self.uint(4)? as u32 as i32 as i64),
+            0xd3 => MsgPack::Int(self.uint(8)? as i64),
+            0xd4..=0xd8 => self.ext(1 << (marker - 0xd4))?,
+            0xd9..=0xdb => {
+                let len = self.uint(1 << (marker - 0xd9))? as usize;
+                self.str(len)?
+            }
+            0xdc | 0xdd => {
+                let len = self.uint(2 << (marker - 0xdc))? as usize;
+                self.array(len, depth)?
+            }
+            0xde | 0xdf => {
+                let len = self.uint(2 << (marker - 0xde))? as usize;
+                self.map(len, depth)?
+            }
+            0xe0..=0xff => MsgPack::Int(marker as i8 as i64),
+            0xc1 => return Err(DecodeError::Invalid("Invalid MessagePack marker 0xc1".to_string())),
+        };
+        Ok(value)
+    }
+
+    fn str(&mut self, len: usize) -> std::result::Result<MsgPack, DecodeError> {
+        Ok(MsgPack::Str(String::from_utf8_lossy(self.take(len)?).into_owned()))
+    }
+
+    fn ext(&mut self, len: usize) -> std::result::Result<MsgPack, DecodeError> {
+        let ext_type = self.take(1)?[0] as i8;
+        Ok(MsgPack::Ext(ext_type, self.take(len)?.to_vec()))
+    }
+
+    // Lengths come from the peer, so capacity is bounded by the bytes present
+    fn array(&mut self, len: usize, depth: usize) -> std::result::Result<MsgPack, DecodeError> {
+        let mut items = Vec::with_capacity(len.min(self.buf.len() - self.pos));
+        for _ in 0..len {
+            items.push(self.value(depth + 1)?);
+        }
+        Ok(MsgPack::Array(items))
+    }
+
+    fn map(&mut self, len: usize, depth: usize) -> std::result::Result<MsgPack, DecodeError> {
+        let mut entries = Vec::with_capacity(len.min(self.buf.len() - self.pos));
+        for _ in 0..len {
+            let key = self.value(depth + 1)?;
+            let value = self.value(depth + 1)?;
+            entries.push((key, value));
+        }
+        Ok(MsgPack::Map(entries))
+    }
+}
+
+/// Decode one MessagePack value from the start of `buf`, returning it with
+/// the number of bytes consumed, or None if `buf` holds only part of it.
+pub fn decode_value(buf: &[u8]) -> Result<Option<(MsgPack, usize)>> {
+    let mut reader = Reader { buf, pos: 0 };
+    match reader.value(0) {
+        Ok(value) => Ok(Some((value, reader.pos))),
+        Err(DecodeError::Incomplete) => Ok(None),
+        Err(DecodeError::Invalid(message)) => Err(PipelineError::parsing(message)),
+    }
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize, fix: (u8, usize), markers: [u8; 3]) {
+    if len < fix.1 {
+        out.push(fix.0 | len as u8);
+    } else if len <= u8::MAX as usize && markers[0] != 0 {
+        out.extend_from_slice(&[markers[0], len as u8]);
+    } else if len <= u16::MAX as usize {
+        out.push(markers[1]);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        out.push(markers[2]);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_len(out, s.len(), (0xa0, 32), [0xd9, 0xda, 0xdb]);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_bin(out: &mut Vec<u8>, b: &[u8]) {
+    write_len(out, b.len(), (0xc4, 0), [0xc4, 0xc5, 0xc6]);
+    out.extend_from_slice(b);
+}
+
+fn write_array_len(out: &mut Vec<u8>, len: usize) {
+    write_len(out, len, (0x90, 16), [0, 0xdc, 0xdd]);
+}
+
+fn write_map_len(out: &mut Vec<u8>, len: usize) {
+    write_len(out, len, (0x80, 16), [0, 0xde, 0xdf]);
+}
+
+/// Encode the `{"ack": chunk}` response for a message sent with the `chunk` option.
+pub fn encode_ack(chunk: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(chunk.len() + 8);
+    write_map_len(&mut out, 1);
+    write_str(&mut out, "ack");
+    write_str(&mut out, chunk);
+    out
+}
+
+/// Interpret a Forward event time: integer seconds or the EventTime extension.
+pub fn event_time(value: &MsgPack) -> Option<DateTime<Utc>> {
+    match value {
+        MsgPack::UInt(secs) => Utc.timestamp_opt(i64::try_from(*secs).ok()?, 0).single(),
+        MsgPack::Int(secs) => Utc.timestamp_opt(*secs, 0).single(),
+        MsgPack::Float(secs) => {
+            Utc.timestamp_opt(secs.trunc() as i64, (secs.fract() * 1e9) as u32).single()
+        }
+        MsgPack::Ext(EVENT_TIME_EXT, data) if data.len() == 8 => {
+            let secs = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+            let nanos = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+            Utc.timestamp_opt(secs as i64, nanos).single()
+        }
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForwardEntry {
+    pub time: DateTime<Utc>,
+    pub record: Value,
+}
+
+/// One Forward protocol message in any of its four modes, flattened to entries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForwardMessage {
+    pub tag: String,
+    pub entries: Vec<ForwardEntry>,
+    /// Chunk id to echo back in an ACK once the entries are accepted
+    pub chunk: Option<String>,
+}
+
+fn parse_entry(entry: &MsgPack) -> Result<ForwardEntry> {
+    match entry {
+        MsgPack::Array(items) if items.len() == 2 => {
+            let time = event_time(&items[0])
+                .ok_or_else(|| PipelineError::parsing("Invalid Forward entry time"))?;
+            Ok(ForwardEntry { time, record: items[1].to_json() })
+        }
+        _ => Err(PipelineError::parsing("Forward entry must be a [time, record] array")),
+    }
+}
+
+// PackedForward carries a concatenated stream of msgpack-encoded entries
+fn parse_packed_entries(data: &[u8]) -> Result<Vec<ForwardEntry>> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    while offset < data.len() {
+        let (entry, consumed) = decode_value(&data[offset..])?
+            .ok_or_else(|| PipelineError::parsing("Truncated entry in PackedForward stream"))?;
+        entries.push(parse_entry(&entry)?);
+        offset += consumed;
+    }
+    Ok(entries)
+}
+
+fn gunzip(data: &[u8], max_size: usize) -> Result<Vec<u8>> {
+    let mut payload = Vec::new();
+    MultiGzDecoder::new(data)
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut payload)
+        .map_err(|e| PipelineError::compression(format!("Failed to decompress CompressedPackedForward entries: {}", e)))?;
+
+    if payload.len() > max_size {
+        return Err(PipelineError::parsing(format!(
+            "Decompressed Forward entries exceed limit of {} bytes",
+            max_size
+        )));
+    }
+    Ok(payload)
+}
+
+/// Flatten a decoded Forward message (Message, Forward, PackedForward or
+/// CompressedPackedForward mode) into its tag, entries and options.
+pub fn parse_forward_message(message: &MsgPack, max_size: usize) -> Result<ForwardMessage> {
+    let items = match message {
+        MsgPack::Array(items) if items.len() >= 2 => items,
+        _ => return Err(PipelineError::parsing("Forward message must be an array of at least two elements")),
+    };
+    let tag = items[0].as_text()
+        .ok_or_else(|| PipelineError::parsing("Forward message tag must be a string"))?;
+
+    let (entries, option) = match &items[1] {
+        MsgPack::Array(entries) => {
+            let entries = entries.iter().map(parse_entry).collect::<Result<Vec<_>>>()?;
+            (entries, items.get(2))
+        }
+        MsgPack::Str(_) | MsgPack::Bin(_) => {
+            let option = items.get(2);
+            let data = match &items[1] {
+                MsgPack::Str(s) => s.as_bytes(),
+                MsgPack::Bin(b) => b.as_slice(),
+                _ => unreachable!(),
+            };
+            let compression = option.and_then(|o| o.get("compressed")).and_then(MsgPack::as_text);
+            let entries = match compression.as_deref() {
+                Some("gzip") => parse_packed_entries(&gunzip(data, max_size)?)?,
+                Some("text") | None => parse_packed_entries(data)?,
+                Some(other) => {
+                    return Err(PipelineError::parsing(format!("Unsupported Forward compression '{}'", other)));
+                }
+            };
+            (entries, option)
+        }
+        time => {
+            let time = event_time(time)
+                .ok_or_else(|| PipelineError::parsing("Invalid Forward message time"))?;
+            let record = items.get(2)
+                .ok_or_else(|| PipelineError::parsing("Forward message is missing its record"))?;
+            (vec![ForwardEntry { time, record: record.to_json() }], items.get(3))
+        }
+    };
+
+    let chunk = option.and_then(|o| o.get("chunk")).and_then(MsgPack::as_text);
+    Ok(ForwardMessage { tag, entries, chunk })
+}
+
+fn sha512_hex(parts: &[&[u8]]) -> String {
+    let mut hasher = Sha512::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Server side of the Forward shared-key handshake (HELO, PING, PONG).
+pub struct SharedKeyAuth {
+    shared_key: String,
+    hostname: String,
+    nonce: [u8; 16],
+}
+
+impl SharedKeyAuth {
+    pub fn new(shared_key: &str, hostname: &str) -> Self {
+        let mut nonce = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        Self {
+            shared_key: shared_key.to_string(),
+            hostname: hostname.to_string(),
+            nonce,
+        }
+    }
+
+    /// The HELO message sent as soon as a client connects.
+    pub fn helo(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_array_len(&mut out, 2);
+        write_str(&mut out, "HELO");
+        write_map_len(&mut out, 3);
+        write_str(&mut out, "nonce");
+        write_bin(&mut out, &self.nonce);
+        write_str(&mut out, "auth");
+        write_bin(&mut out, b"");
+        write_str(&mut out, "keepalive");
+        out.push(0xc3);
+        out
+    }
+
+    /// Check a client's PING and build the PONG reply. The boolean is whether
+    /// the client proved knowledge of the shared key.
+    pub fn pong(&self, ping: &MsgPack) -> (Vec<u8>, bool) {
+        let fields = match ping {
+            MsgPack::Array(items) if items.len() >= 4 => items.iter().map(MsgPack::as_text).collect::<Vec<_>>(),
+            _ => Vec::new(),
+        };
+
+        let (authenticated, reason, salt) = match fields.as_slice() {
+            [Some(kind), Some(client_hostname), Some(salt), Some(digest), ..] if kind == "PING" => {
+                let expected = sha512_hex(&[
+                    salt.as_bytes(),
+                    client_hostname.as_bytes(),
+                    &self.nonce,
+                    self.shared_key.as_bytes(),
+                ]);
+                if expected.eq_ignore_ascii_case(digest) {
+                    (true, "", salt.clone())
+                } else {
+                    (false, "shared_key mismatch", salt.clone())
+                }
+            }
+            _ => (false, "malformed PING", String::new()),
+        };
+
+        let mut out = Vec::new();
+        write_array_len(&mut out, 5);
+        write_str(&mut out, "PONG");
+        out.push(if authenticated { 0xc3 } else { 0xc2 });
+        write_str(&mut out, reason);
+        write_str(&mut out, &self.hostname);
+        if authenticated {
+            let digest = sha512_hex(&[
+                salt.as_bytes(),
+                self.hostname.as_bytes(),
+                &self.nonce,
+                self.shared_key.as_bytes(),
+            ]);
+            write_str(&mut out, &digest);
+        } else {
+            write_str(&mut out, "");
+        }
+        (out, authenticated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use serde_json::json;
+    use std::io::Write;
+
+    fn entry(out: &mut Vec<u8>, secs: u32, message: &str) {
+        write_array_len(out, 2);
+        out.push(0xd7);
+        out.push(0x00);
+        out.extend_from_slice(&secs.to_be_bytes());
+        out.extend_from_slice(&500_000_000u32.to_be_bytes());
+        write_map_len(out, 1);
+        write_str(out, "log");
+        write_str(out, message);
+    }
+
+    fn decode(bytes: &[u8]) -> MsgPack {
+        let (value, consumed) = decode_value(bytes).unwrap().unwrap();
+        assert_eq!(consumed, bytes.len());
+        value
+    }
+
+    #[test]
+    fn test_forward_modes() {
+        // Message mode with integer time and chunk option
+        let mut message = Vec::new();
+        write_array_len(&mut message, 4);
+        write_str(&mut message, "kube.app");
+        message.extend_from_slice(&[0xce, 0x65, 0x92, 0x00, 0x80]);
+        write_map_len(&mut message, 2);
+        write_str(&mut message, "log");
+        write_str(&mut message, "hello");
+        write_str(&mut message, "count");
+        message.extend_from_slice(&[0xd0, 0xfe]);
+        write_map_len(&mut message, 1);
+        write_str(&mut message, "chunk");
+        write_str(&mut message, "abc==");
+
+        assert!(decode_value(&message[..message.len() - 3]).unwrap().is_none());
+        let parsed = parse_forward_message(&decode(&message), 1024).unwrap();
+        assert_eq!(parsed.tag, "kube.app");
+        assert_eq!(parsed.chunk.as_deref(), Some("abc=="));
+        assert_eq!(parsed.entries[0].record, json!({"log": "hello", "count": -2}));
+        assert_eq!(parsed.entries[0].time.timestamp(), 1_704_067_200);
+
+        // Forward mode
+        let mut forward = Vec::new();
+        write_array_len(&mut forward, 2);
+        write_str(&mut forward, "app");
+        write_array_len(&mut forward, 2);
+        entry(&mut forward, 10, "a");
+        entry(&mut forward, 11, "b");
+        let parsed = parse_forward_message(&decode(&forward), 1024).unwrap();
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.entries[1].time.timestamp_subsec_millis(), 500);
+        assert!(parsed.chunk.is_none());
+
+        // PackedForward and CompressedPackedForward
+        let mut stream = Vec::new();
+        entry(&mut stream, 20, "x");
+        entry(&mut stream, 21, "y");
+        let mut packed = Vec::new();
+        write_array_len(&mut packed, 2);
+        write_str(&mut packed, "app");
+        write_bin(&mut packed, &stream);
+        let parsed = parse_forward_message(&decode(&packed), 1024).unwrap();
+        assert_eq!(parsed.entries[1].record, json!({"log": "y"}));
+
+        let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+        gz.write_all(&stream).unwrap();
+        let mut compressed = Vec::new();
+        write_array_len(&mut compressed, 3);
+        write_str(&mut compressed, "app");
+        write_bin(&mut compressed, &gz.finish().unwrap());
+        write_map_len(&mut compressed, 2);
+        write_str(&mut compressed, "compressed");
+        write_str(&mut compressed, "gzip");
+        write_str(&mut compressed, "chunk");
+        write_str(&mut compressed, "c1");
+        let parsed = parse_forward_message(&decode(&compressed), 1024).unwrap();
+        assert_eq!(parsed.entries.len(), 2);
+        assert_eq!(parsed.chunk.as_deref(), Some("c1"));
+        assert!(parse_forward_message(&decode(&compressed), 8).is_err());
+
+        assert_eq!(decode(&encode_ack("c1")).get("ack").and_then(MsgPack::as_text).as_deref(), Some("c1"));
+    }
+
+    #[test]
+    fn test_shared_key_handshake() {
+        let auth = SharedKeyAuth::new("secret", "pipeline");
+        let helo = decode(&auth.helo());
+        let nonce = match &helo {
+            MsgPack::Array(items) => match items[1].get("nonce") {
+                Some(MsgPack::Bin(nonce)) => nonce.clone(),
+                other => panic!("unexpected nonce {:?}", other),
+            },
+            other => panic!("unexpected HELO {:?}", other),
+        };
+
+        let ping = |key: &str| {
+            let digest = sha512_hex(&[b"salt", b"fluent-bit", &nonce, key.as_bytes()]);
+            MsgPack::Array(vec![
+                MsgPack::Str("PING".to_string()),
+                MsgPack::Str("fluent-bit".to_string()),
+                MsgPack::Str("salt".to_string()),
+                MsgPack::Str(digest),
+                MsgPack::Str(String::new()),
+                MsgPack::Str(String::new()),
+            ])
+        };
+
+        let (reply, ok) = auth.pong(&ping("secret"));
+        assert!(ok);
+        match decode(&reply) {
+            MsgPack::Array(items) => {
+                assert_eq!(items[1], MsgPack::Bool(true));
+                let expected = sha512_hex(&[b"salt", b"pipeline", &nonce, b"secret"]);
+                assert_eq!(items[4].as_text(), Some(expected));
+            }
+            other => panic!("unexpected PONG {:?}", other),
+        }
+
+        let (_, ok) = auth.pong(&ping("wrong"));
+        assert!(!ok);
+    }
+}
@@ -0,0 +1,370 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use reqwest::{Client, Method};
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::config::{DestinationType, WebhookBatchFormat};
+use crate::error::{Result, PipelineError};
+use crate::pipeline::PipelineEvent;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const ACK_TIMEOUT: Duration = Duration::from_secs(60);
+const ACK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// HTTP client shared by a forwarding destination's requests.
+pub fn http_client() -> Result<Client> {
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| PipelineError::http(format!("Failed to build HTTP client: {}", e)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct HecReply {
+    #[serde(default)]
+    text: String,
+    #[serde(rename = "ackId")]
+    ack_id: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct HecAckReply {
+    #[serde(default)]
+    acks: HashMap<String, bool>,
+}
+
+/// Sends batches to a Splunk HTTP Event Collector as one request of
+/// concatenated event envelopes.
+pub struct SplunkHecWriter {
+    client: Client,
+    events_url: String,
+    ack_url: String,
+    authorization: String,
+    index: Option<String>,
+    sourcetype: Option<String>,
+    indexes: HashMap<String, String>,
+    sourcetypes: HashMap<String, String>,
+    ack_channel: Option<String>,
+}
+
+impl SplunkHecWriter {
+    pub fn from_destination(client: Client, destination: &DestinationType) -> Result<Self> {
+        let DestinationType::SplunkHec { endpoint, token, index, sourcetype, indexes, sourcetypes, ack_channel } = destination else {
+            return Err(PipelineError::configuration("Invalid destination type for Splunk HEC"));
+        };
+        let base = endpoint.trim_end_matches('/');
+        Ok(Self {
+            client,
+            events_url: format!("{}/services/collector/event", base),
+            ack_url: format!("{}/services/collector/ack", base),
+            authorization: format!("Splunk {}", token),
+            index: index.clone(),
+            sourcetype: sourcetype.clone(),
+            indexes: indexes.clone(),
+            sourcetypes: sourcetypes.clone(),
+            ack_channel: ack_channel.clone(),
+        })
+    }
+
+    /// HEC envelope of an event; metadata goes out as indexed fields.
+    pub fn envelope(&self, event: &PipelineEvent) -> serde_json::Value {
+        let source_type = event.metadata.get("source_type");
+        let mut envelope = serde_json::json!({
+            "time": event.timestamp.timestamp_millis() as f64 / 1000.0,
+            "source": event.source,
+            "event": event.data,
+            "fields": event.metadata,
+        });
+
+        let host = event.data.get("hostname").and_then(|h| h.as_str())
+            .or_else(|| event.metadata.get("source_ip").map(String::as_str));
+        if let Some(host) = host {
+            envelope["host"] = host.into();
+        }
+        if let Some(index) = source_type.and_then(|t| self.indexes.get(t)).or(self.index.as_ref()) {
+            envelope["index"] = index.as_str().into();
+        }
+        let sourcetype = source_type.and_then(|t| self.sourcetypes.get(t))
+            .or(self.sourcetype.as_ref())
+            .or(source_type);
+        if let Some(sourcetype) = sourcetype {
+            envelope["sourcetype"] = sourcetype.as_str().into();
+        }
+        envelope
+    }
+
+    pub async fn write(&mut self, events: &[PipelineEvent]) -> Result<u64> {
+        let mut body = Vec::new();
+        for event in events {
+            serde_json::to_writer(&mut body, &self.envelope(event))?;
+            body.push(b'\n');
+        }
+        let bytes_sent = body.len() as u64;
+
+        let mut request = self.client.post(&self.events_url)
+            .header("Authorization", &self.authorization)
+            .body(body);
+        if let Some(channel) = &self.ack_channel {
+            request = request.header("X-Splunk-Request-Channel", channel);
+        }
+        let response = request.send().await
+            .map_err(|e| PipelineError::http(format!("Splunk HEC request failed: {}", e)))?;
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            return Err(PipelineError::storage(format!("Splunk HEC returned {}: {}", status, text)));
+        }
+
+        let reply: HecReply = serde_json::from_str(&text).unwrap_or_default();
+        if let (Some(channel), Some(ack_id)) = (&self.ack_channel, reply.ack_id) {
+            self.wait_for_ack(channel, ack_id).await?;
+        }
+        debug!("Splunk HEC accepted {} events: {}", events.len(), reply.text);
+        Ok(bytes_sent)
+    }
+
+    // Polls the ack endpoint until the batch is indexed; a timeout fails the
+    // batch so it is sent again
+    async fn wait_for_ack(&self, channel: &str, ack_id: u64) -> Result<()> {
+        let deadline = Instant::now() + ACK_TIMEOUT;
+        loop {
+            let response = self.client.post(&self.ack_url)
+                .header("Authorization", &self.authorization)
+                .header("X-Splunk-Request-Channel", channel)
+                .json(&serde_json::json!({ "acks": [ack_id] }))
+                .send()
+                .await
+                .map_err(|e| PipelineError::http(format!("Splunk HEC ack request failed: {}", e)))?;
+            if response.status().is_success() {
+                let reply: HecAckReply = response.json().await.unwrap_or_default();
+                if reply.acks.get(&ack_id.to_string()) == Some(&true) {
+                    return Ok(());
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(PipelineError::storage(format!(
+                    "Splunk did not acknowledge batch {} within {:?}", ack_id, ACK_TIMEOUT
+                )));
+            }
+            tokio::time::sleep(ACK_POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplatePart {
+    Text(String),
+    Field(String),
+}
+
+/// A webhook payload with `{{field}}` placeholders.
+///
+/// Fields are looked up with dot notation in the event data, then in its
+/// metadata; `{{id}}`, `{{timestamp}}` and `{{source}}` are the event's own
+/// and `{{event}}` is its whole data as JSON. Missing fields render empty.
+/// For JSON payloads string values are escaped so they can sit inside quotes.
+#[derive(Debug, Clone)]
+pub struct PayloadTemplate {
+    parts: Vec<TemplatePart>,
+    json: bool,
+}
+
+impl PayloadTemplate {
+    pub fn parse(template: &str, json: bool) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("{{") {
+            let end = rest[start..].find("}}")
+                .ok_or_else(|| PipelineError::configuration(format!("Unclosed '{{{{' in webhook template: {}", template)))?;
+            let field = rest[start + 2..start + end].trim();
+            if field.is_empty() {
+                return Err(PipelineError::configuration(format!("Empty placeholder in webhook template: {}", template)));
+            }
+            if start > 0 {
+                parts.push(TemplatePart::Text(rest[..start].to_string()));
+            }
+            parts.push(TemplatePart::Field(field.to_string()));
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Text(rest.to_string()));
+        }
+        Ok(Self { parts, json })
+    }
+
+    pub fn render(&self, event: &PipelineEvent) -> String {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Text(text) => rendered.push_str(text),
+                TemplatePart::Field(field) => rendered.push_str(&self.value(event, field)),
+            }
+        }
+        rendered
+    }
+
+    fn value(&self, event: &PipelineEvent, field: &str) -> String {
+        let text = match field {
+            "event" => return event.data.to_string(),
+            "id" => event.id.to_string(),
+            "timestamp" => event.timestamp.to_rfc3339(),
+            "source" => event.source.clone(),
+            _ => match event.get_field(field) {
+                Some(value) => match value.as_ref() {
+                    serde_json::Value::String(s) => s.clone(),
+                    serde_json::Value::Null => String::new(),
+                    other => return other.to_string(),
+                },
+                None => String::new(),
+            },
+        };
+        match self.json {
+            true => {
+                let quoted = serde_json::Value::String(text).to_string();
+                quoted[1..quoted.len() - 1].to_string()
+            }
+            false => text,
+        }
+    }
+}
+
+/// Sends rendered payloads to an HTTP endpoint, a batch per request unless
+/// the format is `single`. A failed `single` batch is retried whole, so
+/// payloads sent before the failure may arrive twice.
+pub struct WebhookWriter {
+    client: Client,
+    url: String,
+    method: Method,
+    headers: HashMap<String, String>,
+    content_type: String,
+    template: PayloadTemplate,
+    batch_format: WebhookBatchFormat,
+}
+
+impl WebhookWriter {
+    pub fn from_destination(client: Client, destination: &DestinationType) -> Result<Self> {
+        let DestinationType::Webhook { url, method, headers, template, content_type, batch_format } = destination else {
+            return Err(PipelineError::configuration("Invalid destination type for webhook"));
+        };
+        let method = Method::from_bytes(method.to_uppercase().as_bytes())
+            .map_err(|_| PipelineError::configuration(format!("Unsupported HTTP method: {}", method)))?;
+        let content_type = match batch_format {
+            WebhookBatchFormat::JsonArray => "application/json".to_string(),
+            _ => content_type.clone(),
+        };
+        let json = content_type.contains("json");
+        Ok(Self {
+            client,
+            url: url.clone(),
+            method,
+            headers: headers.clone(),
+            content_type,
+            template: PayloadTemplate::parse(template, json)?,
+            batch_format: batch_format.clone(),
+        })
+    }
+
+    pub async fn write(&mut self, events: &[PipelineEvent]) -> Result<u64> {
+        let payloads: Vec<String> = events.iter().map(|event| self.template.render(event)).collect();
+        match self.batch_format {
+            WebhookBatchFormat::JsonArray => {
+                let values = payloads.iter()
+                    .map(|payload| serde_json::from_str::<serde_json::Value>(payload).map_err(|e| {
+                        PipelineError::serialization(format!("Webhook template did not render valid JSON: {}", e))
+                    }))
+                    .collect::<Result<Vec<_>>>()?;
+                self.send(serde_json::to_vec(&values)?).await
+            }
+            WebhookBatchFormat::Lines => {
+                let mut body = payloads.join("\n");
+                body.push('\n');
+                self.send(body.into_bytes()).await
+            }
+            WebhookBatchFormat::Single => {
+                let mut bytes_sent = 0;
+                for payload in payloads {
+                    bytes_sent += self.send(payload.into_bytes()).await?;
+                }
+                Ok(bytes_sent)
+            }
+        }
+    }
+
+    async fn send(&self, body: Vec<u8>) -> Result<u64> {
+        let bytes_sent = body.len() as u64;
+        let mut request = self.client.request(self.method.clone(), &self.url)
+            .header("Content-Type", &self.content_type);
+        for (key, value) in &self.headers {
+            request = request.header(key, value);
+        }
+        let response = request.body(body).send().await
+            .map_err(|e| PipelineError::http(format!("Webhook request failed: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(PipelineError::storage(format!(
+                "Webhook returned error status: {} - {}",
+                response.status(),
+                response.text().await.unwrap_or_default()
+            )));
+        }
+        Ok(bytes_sent)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ProcessingStage;
+
+    fn event() -> PipelineEvent {
+        PipelineEvent {
+            id: uuid::Uuid::new_v4(),
+            timestamp: chrono::Utc::now(),
+            source: "fw-01".to_string(),
+            data: serde_json::json!({ "message": "denied \"admin\"", "hostname": "gw", "src": { "port": 443 } }),
+            metadata: HashMap::from([("source_type".to_string(), "cisco_asa".to_string())]),
+            processing_stage: ProcessingStage::Routed,
+        }
+    }
+
+    #[test]
+    fn test_templates_render_fields() {
+        let template = PayloadTemplate::parse(r#"{"text": "{{message}} on {{ hostname }}", "port": {{src.port}}, "type": "{{source_type}}{{missing}}"}"#, true).unwrap();
+        let rendered: serde_json::Value = serde_json::from_str(&template.render(&event())).unwrap();
+        assert_eq!(rendered["text"], "denied \"admin\" on gw");
+        assert_eq!(rendered["port"], 443);
+        assert_eq!(rendered["type"], "cisco_asa");
+
+        let text = PayloadTemplate::parse("{{source}}: {{message}}", false).unwrap();
+        assert_eq!(text.render(&event()), "fw-01: denied \"admin\"");
+        assert!(PayloadTemplate::parse("{{message", false).is_err());
+        assert!(PayloadTemplate::parse("{{ }}", false).is_err());
+    }
+
+    #[test]
+    fn test_hec_envelope_mapping() {
+        let destination = DestinationType::SplunkHec {
+            endpoint: "https://splunk:8088/".to_string(),
+            token: "secret".to_string(),
+            index: Some("main".to_string()),
+            sourcetype: None,
+            indexes: HashMap::from([("cisco_asa".to_string(), "network".to_string())]),
+            sourcetypes: HashMap::from([("cisco_asa".to_string(), "cisco:asa".to_string())]),
+            ack_channel: None,
+        };
+        let writer = SplunkHecWriter::from_destination(Client::new(), &destination).unwrap();
+        assert_eq!(writer.events_url, "https://splunk:8088/services/collector/event");
+
+        let envelope = writer.envelope(&event());
+        assert_eq!(envelope["index"], "network");
+        assert_eq!(envelope["sourcetype"], "cisco:asa");
+        assert_eq!(envelope["host"], "gw");
+        assert_eq!(envelope["fields"]["source_type"], "cisco_asa");
+
+        let mut other = event();
+        other.metadata.clear();
+        let envelope = writer.envelope(&other);
+        assert_eq!(envelope["index"], "main");
+        assert!(envelope.get("sourcetype").is_none());
+    }
+}
@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, warn};
+
+use crate::config::GeoIpConfig;
+use crate::error::{Result, PipelineError};
+use crate::transformation::GeoLocation;
+
+/// Placeholder for the parts of a location the databases do not know
+pub const UNKNOWN_LOCATION: &str = "Unknown";
+
+/// Whether `ip` is globally routable, and so worth looking up. Private,
+/// loopback, link-local, documentation and other reserved ranges are not.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_public_v4(v4),
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_v4(v4),
+            None => is_public_v6(v6),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || a == 0
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64)
+        // IETF protocol assignments, 192.0.0.0/24
+        || (a == 192 && b == 0 && c == 0)
+        // Benchmarking, 198.18.0.0/15
+        || (a == 198 && (b & 0xfe) == 18)
+        // Reserved for future use, 240.0.0.0/4
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local, fc00::/7
+        || (segments[0] & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10
+        || (segments[0] & 0xffc0) == 0xfe80
+        // Documentation, 2001:db8::/32
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8))
+}
+
+/// Fixed-size map that evicts the least recently used entry
+struct LruCache<K, V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<K, (V, u64)>,
+    // Last use tick of each key, oldest first
+    order: BTreeMap<u64, K>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruCache<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self { capacity, tick: 0, entries: HashMap::new(), order: BTreeMap::new() }
+    }
+
+    fn get(&mut self, key: &K) -> Option<V> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.1);
+        entry.1 = self.tick;
+        self.order.insert(self.tick, key.clone());
+        Some(entry.0.clone())
+    }
+
+    fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, previous)) = self.entries.insert(key.clone(), (value, self.tick)) {
+            self.order.remove(&previous);
+        }
+        self.order.insert(self.tick, key);
+        while self.entries.len() > self.capacity {
+            match self.order.pop_first() {
+                Some((_, oldest)) => { self.entries.remove(&oldest); }
+                None => break,
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(feature = "geoip")]
+type Reader = maxminddb::Reader<Vec<u8>>;
+
+#[cfg(not(feature = "geoip"))]
+struct Reader;
+
+/// One `.mmdb` file and the version of it currently in use
+struct Database {
+    path: PathBuf,
+    reader: RwLock<Option<Arc<Reader>>>,
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl Database {
+    fn new(path: &str) -> Self {
+        let database = Database {
+            path: PathBuf::from(path),
+            reader: RwLock::new(None),
+            modified: Mutex::new(None),
+        };
+        if let Err(e) = database.reload_if_changed() {
+            warn!("GeoIP database {} is unavailable: {}", path, e);
+        }
+        database
+    }
+
+    #[cfg(feature = "geoip")]
+    fn reader(&self) -> Option<Arc<Reader>> {
+        self.reader.read().ok()?.clone()
+    }
+
+    /// Reopens the file when its modification time moved. A file that fails
+    /// to load, such as one still being written, leaves the current version in
+    /// place and is retried on the next check.
+    fn reload_if_changed(&self) -> Result<bool> {
+        let modified = std::fs::metadata(&self.path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| PipelineError::io(format!("Failed to stat {}: {}", self.path.display(), e)))?;
+        let mut current = self.modified.lock()
+            .map_err(|_| PipelineError::internal("GeoIP database lock poisoned"))?;
+        if *current == Some(modified) {
+            return Ok(false);
+        }
+
+        let reader = Self::open(&self.path)?;
+        if let Ok(mut slot) = self.reader.write() {
+            *slot = Some(Arc::new(reader));
+        }
+        *current = Some(modified);
+        info!("Loaded GeoIP database {}", self.path.display());
+        Ok(true)
+    }
+
+    #[cfg(feature = "geoip")]
+    fn open(path: &std::path::Path) -> Result<Reader> {
+        maxminddb::Reader::open_readfile(path)
+            .map_err(|e| PipelineError::config(format!("Failed to open GeoIP database {}: {}", path.display(), e)))
+    }
+
+    #[cfg(not(feature = "geoip"))]
+    fn open(path: &std::path::Path) -> Result<Reader> {
+        Err(PipelineError::config(format!(
+            "Cannot open GeoIP database {}: built without the geoip feature", path.display()
+        )))
+    }
+}
+
+/// Looks up public addresses in the City and ASN databases
+pub struct GeoIpResolver {
+    city: Option<Database>,
+    asn: Option<Database>,
+    cache: Mutex<LruCache<IpAddr, Option<GeoLocation>>>,
+    reload_check_interval: Duration,
+    last_reload_check: Mutex<Instant>,
+}
+
+impl GeoIpResolver {
+    /// Databases that cannot be opened are logged and retried on later
+    /// reload checks; until then their part of each result is missing.
+    pub fn new(config: &GeoIpConfig) -> Self {
+        GeoIpResolver {
+            city: config.city_database.as_deref().map(Database::new),
+            asn: config.asn_database.as_deref().map(Database::new),
+            cache: Mutex::new(LruCache::new(config.cache_size)),
+            reload_check_interval: Duration::from_secs(config.reload_check_interval_secs),
+            last_reload_check: Mutex::new(Instant::now()),
+        }
+    }
+
+    pub fn is_configured(&self) -> bool {
+        self.city.is_some() || self.asn.is_some()
+    }
+
+    /// Location of `ip`, or `None` for non-public addresses and addresses
+    /// neither database knows
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoLocation> {
+        if !is_public(ip) || !self.is_configured() {
+            return None;
+        }
+        self.reload_if_due();
+
+        if let Some(cached) = self.cache.lock().ok().and_then(|mut cache| cache.get(&ip)) {
+            return cached;
+        }
+        let location = self.query(ip);
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(ip, location.clone());
+        }
+        location
+    }
+
+    fn reload_if_due(&self) {
+        {
+            let Ok(mut last) = self.last_reload_check.lock() else { return };
+            if last.elapsed() < self.reload_check_interval {
+                return;
+            }
+            *last = Instant::now();
+        }
+
+        let mut reloaded = false;
+        for database in self.city.iter().chain(self.asn.iter()) {
+            match database.reload_if_changed() {
+                Ok(changed) => reloaded |= changed,
+                Err(e) => warn!("GeoIP database reload skipped: {}", e),
+            }
+        }
+        // Cached results may be stale, including misses the new version knows
+        if reloaded {
+            if let Ok(mut cache) = self.cache.lock() {
+                cache.clear();
+            }
+        }
+    }
+
+    #[cfg(feature = "geoip")]
+    fn query(&self, ip: IpAddr) -> Option<GeoLocation> {
+        use maxminddb::geoip2;
+
+        let city_reader = self.city.as_ref().and_then(Database::reader);
+        let city = city_reader.as_ref().and_then(|reader| reader.lookup::<geoip2::City>(ip).ok());
+        let asn_reader = self.asn.as_ref().and_then(Database::reader);
+        let asn = asn_reader.as_ref().and_then(|reader| reader.lookup::<geoip2::Asn>(ip).ok());
+        if city.is_none() && asn.is_none() {
+            return None;
+        }
+
+        let english = |names: Option<&BTreeMap<&str, &str>>| {
+            names.and_then(|names| names.get("en")).map(|name| name.to_string())
+        };
+        let country = city.as_ref()
+            .and_then(|record| record.country.as_ref())
+            .and_then(|country| english(country.names.as_ref()).or_else(|| country.iso_code.map(String::from)));
+        let city_name = city.as_ref()
+            .and_then(|record| record.city.as_ref())
+            .and_then(|city| english(city.names.as_ref()));
+        let location = city.as_ref().and_then(|record| record.location.as_ref());
+
+        Some(GeoLocation {
+            country: country.unwrap_or_else(|| UNKNOWN_LOCATION.to_string()),
+            city: city_name.unwrap_or_else(|| UNKNOWN_LOCATION.to_string()),
+            latitude: location.and_then(|location| location.latitude).unwrap_or(0.0),
+            longitude: location.and_then(|location| location.longitude).unwrap_or(0.0),
+            asn: asn.as_ref().and_then(|asn| asn.autonomous_system_number).map(|number| format!("AS{}", number)),
+            organization: asn.as_ref().and_then(|asn| asn.autonomous_system_organization).map(String::from),
+        })
+    }
+
+    #[cfg(not(feature = "geoip"))]
+    fn query(&self, _ip: IpAddr) -> Option<GeoLocation> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_addresses() {
+        for ip in ["8.8.8.8", "1.1.1.1", "100.128.0.1", "2606:4700::1111", "::ffff:8.8.4.4"] {
+            assert!(is_public(ip.parse().unwrap()), "{} should be public", ip);
+        }
+        for ip in [
+            "10.1.2.3", "172.16.0.1", "192.168.1.1", "127.0.0.1", "169.254.1.1", "0.0.0.0",
+            "100.64.0.1", "192.0.0.8", "192.0.2.1", "198.18.0.1", "224.0.0.1", "240.0.0.1",
+            "255.255.255.255", "::1", "::", "fd00::1", "fe80::1", "2001:db8::1", "ff02::1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} should be skipped", ip);
+        }
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        assert_eq!(cache.get(&"a"), Some(1));
+        cache.insert("c", 3);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(1));
+        assert_eq!(cache.get(&"c"), Some(3));
+
+        cache.insert("a", 10);
+        cache.insert("d", 4);
+        assert_eq!(cache.get(&"c"), None);
+        assert_eq!(cache.get(&"a"), Some(10));
+        assert_eq!(cache.entries.len(), cache.order.len());
+
+        cache.clear();
+        assert_eq!(cache.get(&"a"), None);
+    }
+
+    #[test]
+    fn test_unconfigured_resolver_skips_lookups() {
+        let resolver = GeoIpResolver::new(&GeoIpConfig::default());
+        assert!(!resolver.is_configured());
+        assert!(resolver.lookup("8.8.8.8".parse().unwrap()).is_none());
+
+        let missing = GeoIpResolver::new(&GeoIpConfig {
+            city_database: Some("/nonexistent/GeoLite2-City.mmdb".to_string()),
+            ..GeoIpConfig::default()
+        });
+        assert!(missing.lookup("8.8.8.8".parse().unwrap()).is_none());
+    }
+}
@@ -249,6 +249,9 @@ pub fn create_router(state: AppState) -> Router {
         // Routing management endpoints
         .route("/routing/rules", get(get_routing_rules))
         .route("/routing/rules", post(create_routing_rule))
+        .route("/routing/validate", post(validate_routing_rule))
+        .route("/routing/explain", get(explain_routing_rules))
+        .route("/routing/dry-run", post(dry_run_routing))
         .route("/routing/rules/:name", get(get_routing_rule))
         .route("/routing/rules/:name", put(update_routing_rule))
         .route("/routing/rules/:name", delete(delete_routing_rule))
@@ -1195,29 +1198,13 @@ pub async fn create_routing_rule(
     }
     
     // Create new routing rule
-    let new_rule = crate::routing::RoutingRule {
-        id: Uuid::new_v4().to_string(),
-        name: request.name.clone(),
-        description: request.description.unwrap_or_default(),
-        enabled: request.enabled.unwrap_or(true),
-        priority: request.priority.unwrap_or(100),
-        conditions: match serde_json::from_value(request.conditions) {
-            Ok(conditions) => conditions,
-            Err(e) => {
-                warn!("Invalid conditions format: {}", e);
-                return Err(PipelineError::bad_request(format!("Invalid conditions format: {}", e)));
-            }
-        },
-        destinations: match serde_json::from_value::<Vec<String>>(request.actions) {
-            Ok(destinations) => destinations,
-            Err(e) => {
-                warn!("Invalid actions format: {}", e);
-                return Err(PipelineError::bad_request(format!("Invalid actions format: {}", e)));
-            }
-        },
-        tags: request.tags.unwrap_or_default(),
-        metadata: HashMap::new(),
-    };
+    let request_name = request.name.clone();
+    let new_rule = build_routing_rule(request)?;
+    
+    if let Err(e) = routing_manager.validate_rule(&new_rule) {
+        warn!("Routing rule '{}' is invalid: {}", new_rule.name, e);
+        return Err(PipelineError::bad_request(e.to_string()));
+    }
     
     // Add rule to routing manager
     let add_result = routing_manager.add_rule(new_rule.clone()).await;
@@ -1229,12 +1216,12 @@ pub async fn create_routing_rule(
             
             Ok((
                 StatusCode::CREATED,
-                [("Location", format!("/api/v1/routing/rules/{}", request.name))],
+                [("Location", format!("/api/v1/routing/rules/{}", request_name))],
                 Json(response),
             ))
         }
         Err(e) => {
-            error!("Failed to create routing rule '{}': {}", request.name, e);
+            error!("Failed to create routing rule '{}': {}", request_name, e);
             Err(PipelineError::internal(format!("Failed to create rule: {}", e)))
         }
     }
@@ -1300,14 +1287,17 @@ pub async fn update_routing_rule(
     if let Some(priority) = request.priority {
         existing_rule.priority = priority;
     }
+    if let Some(stop_processing) = request.stop_processing {
+        existing_rule.stop_processing = stop_processing;
+    }
+    if let Some(sample_percent) = request.sample_percent {
+        // 100% is the same as not sampling, so store it as such
+        existing_rule.sample_percent = (sample_percent < 100.0).then_some(sample_percent);
+    }
     if let Some(conditions) = request.conditions {
-        existing_rule.conditions = match serde_json::from_value(conditions) {
-            Ok(conditions) => conditions,
-            Err(e) => {
-                warn!("Invalid conditions format: {}", e);
-                return Err(PipelineError::bad_request(format!("Invalid conditions format: {}", e)));
-            }
-        };
+        let (conditions, condition_group) = parse_routing_conditions(conditions)?;
+        existing_rule.conditions = conditions;
+        existing_rule.condition_group = condition_group;
     }
     if let Some(actions) = request.actions {
         existing_rule.destinations = match serde_json::from_value::<Vec<String>>(actions) {
@@ -1322,6 +1312,11 @@ pub async fn update_routing_rule(
         existing_rule.tags = tags;
     }
     
+    if let Err(e) = routing_manager.validate_rule(&existing_rule) {
+        warn!("Updated routing rule '{}' is invalid: {}", name, e);
+        return Err(PipelineError::bad_request(e.to_string()));
+    }
+    
     // Update rule in routing manager
     let update_result = routing_manager.update_rule_by_name(&name, existing_rule.clone()).await;
     match update_result {
@@ -1337,6 +1332,246 @@ pub async fn update_routing_rule(
     }
 }
 
+/// Accepts either the flat condition list (ANDed) or a single nested
+/// `all`/`any`/`not` group.
+fn parse_routing_conditions(
+    conditions: serde_json::Value,
+) -> Result<(Vec<crate::routing::RoutingCondition>, Option<crate::routing::ConditionGroup>)> {
+    let parsed = match conditions {
+        serde_json::Value::Null => Ok((Vec::new(), None)),
+        serde_json::Value::Array(_) => serde_json::from_value(conditions).map(|list| (list, None)),
+        group => serde_json::from_value(group).map(|group| (Vec::new(), Some(group))),
+    };
+    parsed.map_err(|e| {
+        warn!("Invalid conditions format: {}", e);
+        PipelineError::bad_request(format!("Invalid conditions format: {}", e))
+    })
+}
+
+fn build_routing_rule(request: crate::schemas::CreateRoutingRuleRequest) -> Result<crate::routing::RoutingRule> {
+    let (conditions, condition_group) = parse_routing_conditions(request.conditions)?;
+    let destinations = match serde_json::from_value::<Vec<String>>(request.actions) {
+        Ok(destinations) => destinations,
+        Err(e) => {
+            warn!("Invalid actions format: {}", e);
+            return Err(PipelineError::bad_request(format!("Invalid actions format: {}", e)));
+        }
+    };
+    
+    Ok(crate::routing::RoutingRule {
+        id: Uuid::new_v4().to_string(),
+        name: request.name,
+        description: request.description.unwrap_or_default(),
+        enabled: request.enabled.unwrap_or(true),
+        priority: request.priority.unwrap_or(100),
+        conditions,
+        condition_group,
+        destinations,
+        stop_processing: request.stop_processing.unwrap_or(false),
+        sample_percent: request.sample_percent.filter(|percent| *percent < 100.0),
+        tags: request.tags.unwrap_or_default(),
+        metadata: HashMap::new(),
+    })
+}
+
+/// Checks a rule definition without saving it and explains how it would route
+pub async fn validate_routing_rule(
+    State(state): State<AppState>,
+    Json(request): Json<crate::schemas::CreateRoutingRuleRequest>,
+) -> Result<impl IntoResponse> {
+    info!("Validating routing rule: {}", request.name);
+    
+    let mut errors = Vec::new();
+    if let Err(validation_errors) = request.validate() {
+        errors.push(format!("Validation failed: {:?}", validation_errors));
+    }
+    
+    let routing_manager = state.pipeline.get_routing_manager();
+    let explanation = match build_routing_rule(request) {
+        Ok(rule) => {
+            if let Err(e) = routing_manager.validate_rule(&rule) {
+                errors.push(e.to_string());
+            }
+            Some(rule.explain())
+        }
+        Err(e) => {
+            errors.push(e.to_string());
+            None
+        }
+    };
+    
+    Ok(Json(serde_json::json!({
+        "valid": errors.is_empty(),
+        "errors": errors,
+        "explanation": explanation,
+    })))
+}
+
+/// Lists enabled and disabled rules in evaluation order with a plain-language
+/// description of each, noting where `stop_processing` ends evaluation
+pub async fn explain_routing_rules(State(state): State<AppState>) -> Result<impl IntoResponse> {
+    let routing_manager = state.pipeline.get_routing_manager();
+    let rules = routing_manager.get_rules_in_evaluation_order().await;
+    
+    let mut stopped_by: Option<String> = None;
+    let explained: Vec<serde_json::Value> = rules.iter().enumerate().map(|(position, rule)| {
+        let entry = serde_json::json!({
+            "position": position + 1,
+            "name": rule.name,
+            "priority": rule.priority,
+            "enabled": rule.enabled,
+            "stop_processing": rule.stop_processing,
+            "sample_percent": rule.sample_percent,
+            "explanation": rule.explain(),
+            "shadowed_by": stopped_by,
+        });
+        // An unconditional, unsampled stop rule swallows everything below it
+        if rule.enabled && rule.stop_processing && rule.sample_percent.is_none()
+            && rule.conditions.is_empty() && rule.condition_group.is_none()
+        {
+            stopped_by.get_or_insert_with(|| rule.name.clone());
+        }
+        entry
+    }).collect();
+    
+    Ok(Json(serde_json::json!({
+        "semantics": "Rules run from highest to lowest priority; every match adds its destinations until a matching rule with stop_processing ends evaluation",
+        "rules": explained,
+    })))
+}
+
+/// Stored rows carry the parsed columns separately from `fields`; fold them
+/// back into `data` so routing sees the same shape it saw at ingest
+fn stored_event_to_pipeline_event(event: crate::models::Event) -> PipelineEvent {
+    let mut data = match event.fields {
+        serde_json::Value::Object(map) => map,
+        _ => serde_json::Map::new(),
+    };
+    for (key, value) in [
+        ("source_type", serde_json::json!(event.source_type)),
+        ("severity", serde_json::json!(event.severity)),
+        ("facility", serde_json::json!(event.facility)),
+        ("hostname", serde_json::json!(event.hostname)),
+        ("process", serde_json::json!(event.process)),
+        ("message", serde_json::json!(event.message)),
+        ("raw_message", serde_json::json!(event.raw_message)),
+        ("source_ip", serde_json::json!(event.source_ip)),
+        ("source_port", serde_json::json!(event.source_port)),
+        ("protocol", serde_json::json!(event.protocol)),
+        ("tags", serde_json::json!(event.tags)),
+    ] {
+        data.entry(key.to_string()).or_insert(value);
+    }
+    
+    PipelineEvent {
+        id: event.id,
+        timestamp: event.timestamp,
+        source: event.source,
+        data: serde_json::Value::Object(data),
+        metadata: HashMap::from([("source_type".to_string(), event.source_type)]),
+        processing_stage: ProcessingStage::Stored,
+    }
+}
+
+/// Shows how sample or recently stored events route under the current rules
+/// and, if `proposed_rules` is given, how the proposal would change that
+pub async fn dry_run_routing(
+    State(state): State<AppState>,
+    Json(request): Json<crate::schemas::RoutingDryRunRequest>,
+) -> Result<impl IntoResponse> {
+    if let Err(validation_errors) = request.validate() {
+        warn!("Routing dry run validation failed: {:?}", validation_errors);
+        return Err(PipelineError::bad_request(format!("Validation failed: {:?}", validation_errors)));
+    }
+    
+    let routing_manager = state.pipeline.get_routing_manager();
+    
+    let mut events = Vec::new();
+    for value in request.events.unwrap_or_default() {
+        // Anything that is not a full PipelineEvent is taken as its `data`
+        let event = serde_json::from_value::<PipelineEvent>(value.clone()).unwrap_or_else(|_| PipelineEvent {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            source: "dry_run".to_string(),
+            data: value,
+            metadata: HashMap::new(),
+            processing_stage: ProcessingStage::Parsed,
+        });
+        events.push(event);
+    }
+    if let Some(last_n) = request.last_n {
+        let search_query = crate::models::SearchQuery {
+            query: String::new(),
+            filters: HashMap::new(),
+            time_range: crate::models::TimeRange {
+                start: Utc::now() - chrono::Duration::days(7),
+                end: Utc::now(),
+            },
+            sort_by: Some("timestamp".to_string()),
+            sort_order: crate::models::SortOrder::Desc,
+            limit: last_n,
+            offset: 0,
+            include_metadata: true,
+        };
+        let stored = search_events_from_sqlite(&search_query).await
+            .map_err(|e| PipelineError::internal(format!("Failed to load stored events: {}", e)))?;
+        events.extend(stored.into_iter().map(stored_event_to_pipeline_event));
+    }
+    if events.is_empty() {
+        return Err(PipelineError::bad_request("Provide sample events or last_n"));
+    }
+    
+    let proposed = match request.proposed_rules {
+        Some(requests) => {
+            let current = routing_manager.get_rules().await;
+            let mut rules = Vec::with_capacity(requests.len());
+            for rule_request in requests {
+                if let Err(validation_errors) = rule_request.validate() {
+                    return Err(PipelineError::bad_request(format!("Proposed rule '{}' failed validation: {:?}", rule_request.name, validation_errors)));
+                }
+                let mut rule = build_routing_rule(rule_request)?;
+                // Keep IDs of existing rules so sampling decisions line up
+                if let Some(existing) = current.iter().find(|r| r.name == rule.name) {
+                    rule.id = existing.id.clone();
+                }
+                if let Err(e) = routing_manager.validate_rule(&rule) {
+                    return Err(PipelineError::bad_request(format!("Proposed rule '{}' is invalid: {}", rule.name, e)));
+                }
+                rules.push(rule);
+            }
+            Some(rules)
+        }
+        None => None,
+    };
+    
+    let mut results = Vec::with_capacity(events.len());
+    let mut changed_events = 0;
+    for event in &events {
+        let current = routing_manager.trace_event(event, None).await?;
+        let mut result = serde_json::json!({ "event": event, "current": current });
+        
+        if let Some(rules) = &proposed {
+            let after = routing_manager.trace_event(event, Some(rules)).await?;
+            let added: Vec<&String> = after.destinations.iter().filter(|d| !current.destinations.contains(d)).collect();
+            let removed: Vec<&String> = current.destinations.iter().filter(|d| !after.destinations.contains(d)).collect();
+            let changed = !added.is_empty() || !removed.is_empty();
+            if changed {
+                changed_events += 1;
+            }
+            result["diff"] = serde_json::json!({ "changed": changed, "added": added, "removed": removed });
+            result["proposed"] = serde_json::json!(after);
+        }
+        results.push(result);
+    }
+    
+    info!("Routing dry run over {} events, {} changed by proposal", events.len(), changed_events);
+    Ok(Json(serde_json::json!({
+        "events_evaluated": events.len(),
+        "events_changed": proposed.as_ref().map(|_| changed_events),
+        "results": results,
+    })))
+}
+
 pub async fn delete_routing_rule(
     State(state): State<AppState>,
     Path(name): Path<String>,
@@ -2132,10 +2367,11 @@ pub async fn update_case(State(_state): State<AppState>, Path(_id): Path<String>
 }
 
 // Rule Management Handlers
-pub async fn get_rules(State(_state): State<AppState>) -> Result<impl IntoResponse> {
+pub async fn get_rules(State(state): State<AppState>) -> Result<impl IntoResponse> {
+    let detection_rules = state.pipeline.get_detection_engine().get_rules().await;
     let rules = serde_json::json!({
-        "rules": [],
-        "total_count": 0
+        "total_count": detection_rules.len(),
+        "rules": detection_rules
     });
     Ok(Json(rules))
 }
@@ -2201,20 +2437,63 @@ pub async fn delete_rule(State(_state): State<AppState>, Path(_id): Path<String>
     Ok(Json(response))
 }
 
-pub async fn create_sigma_rule(State(_state): State<AppState>, Json(_request): Json<serde_json::Value>) -> Result<impl IntoResponse> {
+pub async fn create_sigma_rule(State(state): State<AppState>, Json(request): Json<serde_json::Value>) -> Result<impl IntoResponse> {
+    let sigma_yaml = request.get("sigma_yaml")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| PipelineError::bad_request("Missing required field: sigma_yaml"))?;
+    
+    let rule = state.pipeline.get_detection_engine()
+        .add_sigma_rule(sigma_yaml, "api")
+        .await
+        .map_err(|e| PipelineError::bad_request(e.to_string()))?;
+    
+    info!("Created Sigma rule: {} ({})", rule.name, rule.id);
+    
     let response = serde_json::json!({
-        "rule_id": Uuid::new_v4().to_string(),
+        "rule_id": rule.id.to_string(),
+        "rule_name": rule.name,
         "status": "created",
         "message": "Sigma rule created successfully"
     });
     Ok((StatusCode::CREATED, Json(response)))
 }
 
-pub async fn test_rule(State(_state): State<AppState>, Json(_request): Json<serde_json::Value>) -> Result<impl IntoResponse> {
+pub async fn test_rule(State(_state): State<AppState>, Json(request): Json<serde_json::Value>) -> Result<impl IntoResponse> {
+    let start = std::time::Instant::now();
+    
+    let sigma_yaml = request.get("sigma_yaml")
+        .or_else(|| request.get("query"))
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| PipelineError::bad_request("Missing required field: sigma_yaml"))?;
+    
+    // Sample events are raw event payloads, evaluated as if freshly ingested
+    let events: Vec<PipelineEvent> = request.get("events")
+        .and_then(|v| v.as_array())
+        .map(|items| items.iter().map(|data| PipelineEvent {
+            id: Uuid::new_v4(),
+            timestamp: Utc::now(),
+            source: "rule_test".to_string(),
+            data: data.clone(),
+            metadata: HashMap::new(),
+            processing_stage: ProcessingStage::Normalized,
+        }).collect())
+        .unwrap_or_default();
+    
+    let matched = crate::detection::DetectionEngine::test_sigma_rule(sigma_yaml, &events)
+        .map_err(|e| PipelineError::bad_request(e.to_string()))?;
+    
+    let matched_indices: Vec<usize> = events.iter()
+        .enumerate()
+        .filter(|(_, e)| matched.contains(&e.id))
+        .map(|(i, _)| i)
+        .collect();
+    
     let response = serde_json::json!({
-        "test_result": "passed",
-        "matches": 0,
-        "execution_time_ms": 15
+        "test_result": if matched_indices.is_empty() { "no_match" } else { "matched" },
+        "events_tested": events.len(),
+        "matches": matched_indices.len(),
+        "matched_events": matched_indices,
+        "execution_time_ms": start.elapsed().as_millis() as u64
     });
     Ok(Json(response))
 }
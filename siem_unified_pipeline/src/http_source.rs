@@ -0,0 +1,291 @@
+use std::io::Read;
+use axum::http::{header, HeaderMap};
+use ring::hmac;
+use serde_json::Value;
+
+use crate::config::HttpSourceAuth;
+use crate::error::{Result, PipelineError};
+
+pub const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+pub const DEFAULT_QUEUE_SIZE: usize = 10_000;
+pub const DEFAULT_MAX_PAGES: usize = 100;
+const DEFAULT_SIGNATURE_HEADER: &str = "x-signature";
+
+/// Credential check for pushed requests.
+pub enum RequestAuth {
+    None,
+    Bearer(Vec<String>),
+    Hmac { key: hmac::Key, header: String },
+}
+
+impl RequestAuth {
+    pub fn new(auth: Option<&HttpSourceAuth>) -> Self {
+        match auth {
+            None => RequestAuth::None,
+            Some(HttpSourceAuth::Bearer { tokens }) => RequestAuth::Bearer(tokens.clone()),
+            Some(HttpSourceAuth::Hmac { secret, header }) => RequestAuth::Hmac {
+                key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()),
+                header: header.clone().unwrap_or_else(|| DEFAULT_SIGNATURE_HEADER.to_string()),
+            },
+        }
+    }
+
+    pub fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<()> {
+        let authorized = match self {
+            RequestAuth::None => true,
+            RequestAuth::Bearer(tokens) => {
+                let presented = headers.get(header::AUTHORIZATION)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.strip_prefix("Bearer "))
+                    .map(str::trim);
+                // Check every token so timing does not reveal which one matched
+                presented.is_some_and(|presented| {
+                    tokens.iter().fold(false, |found, token| {
+                        constant_time_eq(presented.as_bytes(), token.as_bytes()) | found
+                    })
+                })
+            }
+            RequestAuth::Hmac { key, header } => {
+                let presented = headers.get(header.as_str())
+                    .and_then(|value| value.to_str().ok())
+                    .map(|value| value.trim().trim_start_matches("sha256=").to_ascii_lowercase());
+                presented.is_some_and(|presented| {
+                    let expected: String = hmac::sign(key, body).as_ref()
+                        .iter()
+                        .map(|b| format!("{:02x}", b))
+                        .collect();
+                    constant_time_eq(presented.as_bytes(), expected.as_bytes())
+                })
+            }
+        };
+
+        if authorized {
+            Ok(())
+        } else {
+            Err(PipelineError::authentication("Missing or invalid credentials"))
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Undo a `Content-Encoding` of gzip or zstd, refusing bodies that expand
+/// past `limit` bytes.
+pub fn decode_body(body: &[u8], content_encoding: Option<&str>, limit: usize) -> Result<Vec<u8>> {
+    let encoding = content_encoding.map(|e| e.trim().to_ascii_lowercase()).unwrap_or_default();
+    let too_large = || PipelineError::bad_request(format!("Request body exceeds {} bytes", limit));
+
+    let mut output = Vec::new();
+    let result = match encoding.as_str() {
+        "" | "identity" => {
+            if body.len() > limit {
+                return Err(too_large());
+            }
+            return Ok(body.to_vec());
+        }
+        "gzip" | "x-gzip" => {
+            flate2::read::MultiGzDecoder::new(body).take(limit as u64 + 1).read_to_end(&mut output)
+        }
+        "zstd" => zstd::stream::read::Decoder::new(body)
+            .and_then(|decoder| decoder.take(limit as u64 + 1).read_to_end(&mut output)),
+        other => return Err(PipelineError::bad_request(format!("Unsupported Content-Encoding '{}'", other))),
+    };
+
+    result.map_err(|e| PipelineError::bad_request(format!("Failed to decode {} body: {}", encoding, e)))?;
+    if output.len() > limit {
+        return Err(too_large());
+    }
+    Ok(output)
+}
+
+/// Split a body into records: the elements of a JSON array, a single JSON
+/// document, or one record per non-empty line (NDJSON or raw text). Lines
+/// that are not JSON are returned as strings.
+pub fn split_body(body: &[u8]) -> Vec<Value> {
+    let text = String::from_utf8_lossy(body);
+    let trimmed = text.trim_start_matches('\u{feff}').trim();
+
+    if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        match serde_json::from_str::<Value>(trimmed) {
+            Ok(Value::Array(items)) => return items,
+            Ok(value) => return vec![value],
+            Err(_) => {}
+        }
+    }
+
+    trimmed.lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| match serde_json::from_str::<Value>(line) {
+            Ok(value @ Value::Object(_)) => value,
+            _ => Value::String(line.to_string()),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+/// Subset of JSONPath used to pick records and cursors out of API responses:
+/// `$`, `.name`, `['name']`, `[0]`, `[*]` and `.*`.
+#[derive(Debug, Clone)]
+pub struct JsonPath {
+    segments: Vec<PathSegment>,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<Self> {
+        let invalid = |reason: &str| PipelineError::config(format!("Invalid JSONPath '{}': {}", path, reason));
+        let rest = path.trim().strip_prefix('$').ok_or_else(|| invalid("must start with '$'"))?;
+        let chars: Vec<char> = rest.chars().collect();
+
+        let mut segments = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '.' => {
+                    let start = i + 1;
+                    let mut end = start;
+                    while end < chars.len() && chars[end] != '.' && chars[end] != '[' {
+                        end += 1;
+                    }
+                    let name: String = chars[start..end].iter().collect();
+                    match name.as_str() {
+                        "" => return Err(invalid("empty field name")),
+                        "*" => segments.push(PathSegment::Wildcard),
+                        _ => segments.push(PathSegment::Key(name)),
+                    }
+                    i = end;
+                }
+                '[' => {
+                    let close = chars[i..].iter().position(|c| *c == ']')
+                        .map(|offset| i + offset)
+                        .ok_or_else(|| invalid("unclosed '['"))?;
+                    let inner: String = chars[i + 1..close].iter().collect();
+                    let inner = inner.trim();
+                    let segment = if inner == "*" {
+                        PathSegment::Wildcard
+                    } else if let Some(quoted) = inner.strip_prefix('\'').and_then(|s| s.strip_suffix('\''))
+                        .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+                    {
+                        PathSegment::Key(quoted.to_string())
+                    } else {
+                        PathSegment::Index(inner.parse().map_err(|_| invalid("expected an index, '*' or a quoted name"))?)
+                    };
+                    segments.push(segment);
+                    i = close + 1;
+                }
+                _ => return Err(invalid("expected '.' or '['")),
+            }
+        }
+
+        Ok(Self { segments })
+    }
+
+    pub fn select<'a>(&self, root: &'a Value) -> Vec<&'a Value> {
+        let mut current = vec![root];
+        for segment in &self.segments {
+            current = current.into_iter()
+                .flat_map(|value| -> Vec<&'a Value> {
+                    match (segment, value) {
+                        (PathSegment::Key(key), Value::Object(object)) => object.get(key).into_iter().collect(),
+                        (PathSegment::Index(index), Value::Array(items)) => items.get(*index).into_iter().collect(),
+                        (PathSegment::Wildcard, Value::Array(items)) => items.iter().collect(),
+                        (PathSegment::Wildcard, Value::Object(object)) => object.values().collect(),
+                        _ => Vec::new(),
+                    }
+                })
+                .collect();
+        }
+        current
+    }
+
+    /// Records selected from a polled response; a path that lands on a
+    /// single array yields its elements.
+    pub fn records(&self, root: &Value) -> Vec<Value> {
+        match self.select(root).as_slice() {
+            [Value::Array(items)] => items.clone(),
+            selected => selected.iter().map(|value| (*value).clone()).collect(),
+        }
+    }
+
+    /// The first selected scalar as a string, for pagination cursors.
+    pub fn first_string(&self, root: &Value) -> Option<String> {
+        self.select(root).into_iter().find_map(|value| match value {
+            Value::String(s) if !s.is_empty() => Some(s.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::io::Write;
+
+    #[test]
+    fn test_split_and_decode_body() {
+        assert_eq!(split_body(b"[{\"a\":1},{\"a\":2}]").len(), 2);
+        assert_eq!(split_body(b"{\"a\":1}\n\n{\"a\":2}\nplain text\r\n"), vec![
+            json!({"a": 1}),
+            json!({"a": 2}),
+            json!("plain text"),
+        ]);
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(b"{\"a\":1}\n").unwrap();
+        let gzipped = gz.finish().unwrap();
+        assert_eq!(decode_body(&gzipped, Some("gzip"), 1024).unwrap(), b"{\"a\":1}\n");
+        assert!(decode_body(&gzipped, Some("gzip"), 4).is_err());
+
+        let zstd = zstd::stream::encode_all(&b"hello"[..], 3).unwrap();
+        assert_eq!(decode_body(&zstd, Some("zstd"), 1024).unwrap(), b"hello");
+        assert!(decode_body(b"x", Some("br"), 1024).is_err());
+    }
+
+    #[test]
+    fn test_request_auth() {
+        let bearer = RequestAuth::new(Some(&HttpSourceAuth::Bearer { tokens: vec!["t1".into(), "t2".into()] }));
+        let mut headers = HeaderMap::new();
+        assert!(bearer.verify(&headers, b"").is_err());
+        headers.insert(header::AUTHORIZATION, "Bearer t2".parse().unwrap());
+        assert!(bearer.verify(&headers, b"").is_ok());
+        headers.insert(header::AUTHORIZATION, "Bearer t3".parse().unwrap());
+        assert!(bearer.verify(&headers, b"").is_err());
+
+        let hmac_auth = RequestAuth::new(Some(&HttpSourceAuth::Hmac { secret: "key".into(), header: None }));
+        let mut headers = HeaderMap::new();
+        // HMAC-SHA256("key", "The quick brown fox jumps over the lazy dog")
+        headers.insert(
+            "x-signature",
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8".parse().unwrap(),
+        );
+        assert!(hmac_auth.verify(&headers, b"The quick brown fox jumps over the lazy dog").is_ok());
+        assert!(hmac_auth.verify(&headers, b"tampered").is_err());
+    }
+
+    #[test]
+    fn test_json_path() {
+        let response = json!({
+            "data": {"items": [{"id": 1}, {"id": 2}]},
+            "meta": {"next": "abc", "pages": [{"cursor": 7}]}
+        });
+
+        let items = JsonPath::parse("$.data.items").unwrap();
+        assert_eq!(items.records(&response), vec![json!({"id": 1}), json!({"id": 2})]);
+        assert_eq!(JsonPath::parse("$.data.items[*].id").unwrap().records(&response), vec![json!(1), json!(2)]);
+        assert_eq!(JsonPath::parse("$['meta'].next").unwrap().first_string(&response), Some("abc".to_string()));
+        assert_eq!(JsonPath::parse("$.meta.pages[0].cursor").unwrap().first_string(&response), Some("7".to_string()));
+        assert!(JsonPath::parse("$.missing").unwrap().first_string(&response).is_none());
+        assert!(JsonPath::parse("data.items").is_err());
+        assert!(JsonPath::parse("$.data[").is_err());
+    }
+}
@@ -1,22 +1,48 @@
 use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
 use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock};
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::time::{interval, Duration};
-use tokio::fs::File;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::net::UdpSocket;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio_rustls::TlsAcceptor;
+use notify::{RecursiveMode, Watcher};
 use tracing::{info, warn, error, debug};
 use uuid::Uuid;
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
+use axum::body::Bytes;
+use axum::extract::{ConnectInfo, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
 use rdkafka::consumer::{Consumer, StreamConsumer, CommitMode};
 use rdkafka::config::ClientConfig;
 use rdkafka::message::Message;
 
-use crate::config::{PipelineConfig, DataSource, SourceType};
+use crate::config::{PipelineConfig, DataSource, SourceType, TlsConfig};
 use crate::error::{Result, PipelineError};
 use crate::pipeline::{PipelineEvent, ProcessingStage};
+use crate::syslog::{Frame, FrameDecoder};
+use crate::beats::{self, BeatsEvent, LumberjackDecoder};
+use crate::fluentd::{self, ForwardEntry, SharedKeyAuth};
+use crate::file_tail::{CheckpointStore, FileTailer};
+use crate::database_source::{DatabasePoller, DEFAULT_BATCH_SIZE};
+use crate::http_source::{self, JsonPath, RequestAuth};
+use crate::event_queue::EventSender;
+#[cfg(feature = "aws")]
+use crate::s3_source::{self, ObjectLedger, S3ObjectSource};
+
+const DEFAULT_SYSLOG_MAX_MESSAGE_SIZE: usize = 64 * 1024;
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const BEATS_MAX_PAYLOAD_SIZE: usize = 16 * 1024 * 1024;
+const FLUENTD_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+const FILE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const FILE_CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+// Bound per-peer bookkeeping when many distinct senders appear
+const MAX_TRACKED_PEERS: usize = 10_000;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IngestionStats {
@@ -28,6 +54,20 @@ pub struct IngestionStats {
     pub connection_status: ConnectionStatus,
     pub throughput_events_per_sec: f64,
     pub throughput_bytes_per_sec: f64,
+    /// Per-sender counters for network sources, keyed by peer IP
+    #[serde(default)]
+    pub peers: HashMap<String, PeerStats>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerStats {
+    pub connections: u64,
+    pub active_connections: u64,
+    pub events_received: u64,
+    pub bytes_received: u64,
+    pub errors: u64,
+    pub oversized_messages: u64,
+    pub last_seen: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -51,6 +91,15 @@ struct SourceHandle {
     task_handle: tokio::task::JoinHandle<()>,
 }
 
+// Shared by the request handlers of a receive-mode HTTP source
+struct HttpReceiver {
+    source_name: String,
+    auth: RequestAuth,
+    max_body_size: usize,
+    queue: mpsc::Sender<PipelineEvent>,
+    stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
+}
+
 impl IngestionManager {
     pub async fn new(config: &PipelineConfig) -> Result<Self> {
         info!("Initializing ingestion manager");
@@ -72,7 +121,7 @@ impl IngestionManager {
         &self,
         source_name: &str,
         source_config: &DataSource,
-        event_tx: mpsc::UnboundedSender<PipelineEvent>,
+        event_tx: EventSender,
     ) -> Result<()> {
         info!("Starting ingestion source: {}", source_name);
         
@@ -88,6 +137,7 @@ impl IngestionManager {
                 connection_status: ConnectionStatus::Connecting,
                 throughput_events_per_sec: 0.0,
                 throughput_bytes_per_sec: 0.0,
+                peers: HashMap::new(),
             });
         }
         
@@ -102,10 +152,12 @@ impl IngestionManager {
         let task_handle = match source_config.source_type {
             SourceType::Syslog { .. } => {
                 let source_name = source_name.clone();
+                let tls_config = self.config.security.tls.clone();
                 tokio::spawn(async move {
                     if let Err(e) = Self::run_syslog_source(
                         &source_name,
                         &source_config,
+                        tls_config,
                         event_tx,
                         stats,
                         shutdown_rx,
@@ -188,13 +240,29 @@ impl IngestionManager {
             SourceType::Beats { .. } => {
                 let source_name = source_name.clone();
                 tokio::spawn(async move {
-                    error!("Beats source not yet implemented: {}", source_name);
+                    if let Err(e) = Self::run_beats_source(
+                        &source_name,
+                        &source_config,
+                        event_tx,
+                        stats,
+                        shutdown_rx,
+                    ).await {
+                        error!("Beats source {} failed: {}", source_name, e);
+                    }
                 })
             }
             SourceType::Fluentd { .. } => {
                 let source_name = source_name.clone();
                 tokio::spawn(async move {
-                    error!("Fluentd source not yet implemented: {}", source_name);
+                    if let Err(e) = Self::run_fluentd_source(
+                        &source_name,
+                        &source_config,
+                        event_tx,
+                        stats,
+                        shutdown_rx,
+                    ).await {
+                        error!("Fluentd source {} failed: {}", source_name, e);
+                    }
                 })
             }
             SourceType::Custom { .. } => {
@@ -221,16 +289,47 @@ impl IngestionManager {
     async fn run_syslog_source(
         source_name: &str,
         config: &DataSource,
-        event_tx: mpsc::UnboundedSender<PipelineEvent>,
+        tls_config: Option<TlsConfig>,
+        event_tx: EventSender,
         stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
-        mut shutdown_rx: mpsc::Receiver<()>,
+        shutdown_rx: mpsc::Receiver<()>,
     ) -> Result<()> {
         info!("Starting syslog source: {}", source_name);
         
-        let bind_addr = match &config.source_type {
-            SourceType::Syslog { port, .. } => format!("0.0.0.0:{}", port),
-            _ => "0.0.0.0:514".to_string(), // Default syslog port
+        let (bind_addr, protocol, max_message_size) = match &config.source_type {
+            SourceType::Syslog { port, protocol, max_message_size } => (
+                format!("0.0.0.0:{}", port),
+                protocol.to_lowercase(),
+                max_message_size.unwrap_or(DEFAULT_SYSLOG_MAX_MESSAGE_SIZE),
+            ),
+            _ => ("0.0.0.0:514".to_string(), "udp".to_string(), DEFAULT_SYSLOG_MAX_MESSAGE_SIZE), // Default syslog port
         };
+        
+        match protocol.as_str() {
+            "" | "udp" => Self::run_syslog_udp(source_name, &bind_addr, event_tx, stats, shutdown_rx).await,
+            "tcp" => {
+                Self::run_syslog_stream(source_name, &bind_addr, None, max_message_size, event_tx, stats, shutdown_rx).await
+            }
+            "tls" | "tcp+tls" => {
+                let tls_config = tls_config.ok_or_else(|| {
+                    PipelineError::config(format!("Syslog source '{}' uses TLS but security.tls is not configured", source_name))
+                })?;
+                let acceptor = crate::tls::build_acceptor(&tls_config)?;
+                Self::run_syslog_stream(source_name, &bind_addr, Some(acceptor), max_message_size, event_tx, stats, shutdown_rx).await
+            }
+            other => Err(PipelineError::config(format!(
+                "Unsupported syslog protocol '{}' for source '{}' (expected udp, tcp or tls)", other, source_name
+            ))),
+        }
+    }
+    
+    async fn run_syslog_udp(
+        source_name: &str,
+        bind_addr: &str,
+        event_tx: EventSender,
+        stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
+        mut shutdown_rx: mpsc::Receiver<()>,
+    ) -> Result<()> {
         let socket = UdpSocket::bind(bind_addr).await
             .map_err(|e| PipelineError::connection(format!("Failed to bind UDP socket: {}", e)))?;
         
@@ -247,33 +346,8 @@ impl IngestionManager {
                             let data = String::from_utf8_lossy(&buffer[..len]);
                             debug!("Received syslog message from {}: {}", addr, data);
                             
-                            let event = PipelineEvent {
-                                id: Uuid::new_v4(),
-                                timestamp: chrono::Utc::now(),
-                                source: source_name.to_string(),
-                                data: serde_json::json!({
-                                    "raw_message": data.to_string(),
-                                    "source_ip": addr.ip().to_string(),
-                                    "source_port": addr.port(),
-                                    "protocol": "syslog",
-                                    "transport": "udp"
-                                }),
-                                metadata: {
-                                    let mut meta = HashMap::new();
-                                    meta.insert("source_type".to_string(), "syslog".to_string());
-                                    meta.insert("source_ip".to_string(), addr.ip().to_string());
-                                    meta.insert("bytes_received".to_string(), len.to_string());
-                                    meta
-                                },
-                                processing_stage: ProcessingStage::Ingested,
-                            };
-                            
-                            if let Err(e) = event_tx.send(event) {
-                                error!("Failed to send syslog event: {}", e);
-                                Self::increment_error_count(&stats, source_name).await;
-                            } else {
-                                Self::update_stats(&stats, source_name, len as u64).await;
-                            }
+                            let event = Self::syslog_event(source_name, data.to_string(), addr, "udp", len);
+                            Self::deliver_network_event(event, source_name, addr.ip(), len, &event_tx, &stats).await;
                         }
                         Err(e) => {
                             error!("Syslog receive error: {}", e);
@@ -292,169 +366,964 @@ impl IngestionManager {
         Ok(())
     }
     
-    async fn run_file_source(
+    // TCP listener for syslog, optionally wrapped in TLS; each connection is
+    // read on its own task and aborted when the source shuts down
+    async fn run_syslog_stream(
         source_name: &str,
-        config: &DataSource,
-        event_tx: mpsc::UnboundedSender<PipelineEvent>,
+        bind_addr: &str,
+        acceptor: Option<TlsAcceptor>,
+        max_message_size: usize,
+        event_tx: EventSender,
         stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
         mut shutdown_rx: mpsc::Receiver<()>,
     ) -> Result<()> {
-        info!("Starting file source: {}", source_name);
+        let transport = if acceptor.is_some() { "tls" } else { "tcp" };
+        let listener = TcpListener::bind(bind_addr).await
+            .map_err(|e| PipelineError::connection(format!("Failed to bind {} listener on {}: {}", transport, bind_addr, e)))?;
         
-        let file_path = match &config.source_type {
-            SourceType::File { path, .. } => path.clone(),
-            _ => return Err(PipelineError::configuration("Invalid source type for file source")),
-        };
+        info!("Syslog source {} listening on {} ({})", source_name, bind_addr, transport);
+        Self::update_connection_status(&stats, source_name, ConnectionStatus::Connected).await;
         
-        if !Path::new(&file_path).exists() {
-            return Err(PipelineError::not_found(format!("File not found: {}", file_path)));
+        let mut connections = tokio::task::JoinSet::new();
+        
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, peer) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            error!("Failed to accept syslog connection: {}", e);
+                            Self::increment_error_count(&stats, source_name).await;
+                            continue;
+                        }
+                    };
+                    
+                    debug!("Accepted syslog {} connection from {}", transport, peer);
+                    let source_name = source_name.to_string();
+                    let acceptor = acceptor.clone();
+                    let event_tx = event_tx.clone();
+                    let stats = stats.clone();
+                    
+                    connections.spawn(async move {
+                        Self::update_peer_stats(&stats, &source_name, peer.ip(), |p| {
+                            p.connections += 1;
+                            p.active_connections += 1;
+                        }).await;
+                        
+                        let result = match acceptor {
+                            Some(acceptor) => match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                                Ok(Ok(tls_stream)) => {
+                                    Self::read_syslog_stream(tls_stream, peer, &source_name, transport, max_message_size, &event_tx, &stats).await
+                                }
+                                Ok(Err(e)) => Err(PipelineError::connection(format!("TLS handshake with {} failed: {}", peer, e))),
+                                Err(_) => Err(PipelineError::timeout(format!("TLS handshake with {} timed out", peer))),
+                            },
+                            None => Self::read_syslog_stream(stream, peer, &source_name, transport, max_message_size, &event_tx, &stats).await,
+                        };
+                        
+                        if let Err(e) = &result {
+                            warn!("Syslog connection from {} on source {} closed with error: {}", peer, source_name, e);
+                            Self::increment_error_count(&stats, &source_name).await;
+                        }
+                        Self::update_peer_stats(&stats, &source_name, peer.ip(), |p| {
+                            p.active_connections = p.active_connections.saturating_sub(1);
+                            if result.is_err() {
+                                p.errors += 1;
+                            }
+                        }).await;
+                    });
+                }
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                _ = shutdown_rx.recv() => {
+                    info!("Shutting down syslog source: {}", source_name);
+                    break;
+                }
+            }
         }
         
-        let file_path_clone = file_path.clone();
-        let file = File::open(file_path).await
-            .map_err(|e| PipelineError::io(format!("Failed to open file {}: {}", file_path_clone, e)))?;
+        connections.abort_all();
+        while connections.join_next().await.is_some() {}
         
-        let reader = BufReader::new(file);
-        let mut lines = reader.lines();
+        Self::update_connection_status(&stats, source_name, ConnectionStatus::Disconnected).await;
+        Ok(())
+    }
+    
+    async fn read_syslog_stream<S: AsyncRead + Unpin>(
+        mut stream: S,
+        peer: SocketAddr,
+        source_name: &str,
+        transport: &str,
+        max_message_size: usize,
+        event_tx: &EventSender,
+        stats: &Arc<RwLock<HashMap<String, IngestionStats>>>,
+    ) -> Result<()> {
+        let mut decoder = FrameDecoder::new(max_message_size);
+        let mut buffer = Vec::with_capacity(8192);
+        let mut chunk = vec![0u8; 16384];
+        
+        loop {
+            let read = stream.read(&mut chunk).await?;
+            let closed = read == 0;
+            buffer.extend_from_slice(&chunk[..read]);
+            
+            loop {
+                let frame = match decoder.decode(&mut buffer) {
+                    None if closed => decoder.finish(&mut buffer),
+                    frame => frame,
+                };
+                
+                match frame {
+                    Some(Frame::Message(bytes)) => {
+                        let len = bytes.len();
+                        let message = String::from_utf8_lossy(&bytes).into_owned();
+                        let event = Self::syslog_event(source_name, message, peer, transport, len);
+                        Self::deliver_network_event(event, source_name, peer.ip(), len, event_tx, stats).await;
+                    }
+                    Some(Frame::Oversized(len)) => {
+                        warn!("Discarded {} byte syslog message from {} (limit {} bytes)", len, peer, max_message_size);
+                        Self::increment_error_count(stats, source_name).await;
+                        Self::update_peer_stats(stats, source_name, peer.ip(), |p| p.oversized_messages += 1).await;
+                    }
+                    None => break,
+                }
+            }
+            
+            if closed {
+                debug!("Syslog {} connection from {} closed", transport, peer);
+                return Ok(());
+            }
+        }
+    }
+    
+    fn syslog_event(source_name: &str, message: String, peer: SocketAddr, transport: &str, len: usize) -> PipelineEvent {
+        PipelineEvent {
+            id: Uuid::new_v4(),
+            timestamp: chrono::Utc::now(),
+            source: source_name.to_string(),
+            data: serde_json::json!({
+                "raw_message": message,
+                "source_ip": peer.ip().to_string(),
+                "source_port": peer.port(),
+                "protocol": "syslog",
+                "transport": transport
+            }),
+            metadata: {
+                let mut meta = HashMap::new();
+                meta.insert("source_type".to_string(), "syslog".to_string());
+                meta.insert("source_ip".to_string(), peer.ip().to_string());
+                meta.insert("bytes_received".to_string(), len.to_string());
+                meta.insert("transport".to_string(), transport.to_string());
+                meta
+            },
+            processing_stage: ProcessingStage::Ingested,
+        }
+    }
+    
+    async fn deliver_network_event(
+        event: PipelineEvent,
+        source_name: &str,
+        peer: IpAddr,
+        len: usize,
+        event_tx: &EventSender,
+        stats: &Arc<RwLock<HashMap<String, IngestionStats>>>,
+    ) -> bool {
+        if let Err(e) = event_tx.send(event).await {
+            error!("Failed to send {} event: {}", source_name, e);
+            Self::increment_error_count(stats, source_name).await;
+            Self::update_peer_stats(stats, source_name, peer, |p| p.errors += 1).await;
+            false
+        } else {
+            Self::update_stats(stats, source_name, len as u64).await;
+            Self::update_peer_stats(stats, source_name, peer, |p| {
+                p.events_received += 1;
+                p.bytes_received += len as u64;
+            }).await;
+            true
+        }
+    }
+    
+    // Lumberjack v2 server for Filebeat, Winlogbeat and the other Elastic Beats
+    async fn run_beats_source(
+        source_name: &str,
+        config: &DataSource,
+        event_tx: EventSender,
+        stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
+        mut shutdown_rx: mpsc::Receiver<()>,
+    ) -> Result<()> {
+        info!("Starting Beats source: {}", source_name);
+        
+        let bind_addr = match &config.source_type {
+            SourceType::Beats { port } => format!("0.0.0.0:{}", port),
+            _ => return Err(PipelineError::configuration("Invalid source type for Beats source")),
+        };
+        
+        let listener = TcpListener::bind(&bind_addr).await
+            .map_err(|e| PipelineError::connection(format!("Failed to bind Beats listener on {}: {}", bind_addr, e)))?;
         
         Self::update_connection_status(&stats, source_name, ConnectionStatus::Connected).await;
         
+        let mut connections = tokio::task::JoinSet::new();
+        
         loop {
             tokio::select! {
-                line_result = lines.next_line() => {
-                    match line_result {
-                        Ok(Some(line)) => {
-                            debug!("Read line from file: {}", line);
-                            
-                            let event = PipelineEvent {
-                                id: Uuid::new_v4(),
-                                timestamp: chrono::Utc::now(),
-                                source: source_name.to_string(),
-                                data: serde_json::json!({
-                                    "raw_message": line,
-                                    "file_path": file_path_clone,
-                                    "protocol": "file"
-                                }),
-                                metadata: {
-                                    let mut meta = HashMap::new();
-                                    meta.insert("source_type".to_string(), "file".to_string());
-                                    meta.insert("file_path".to_string(), file_path_clone.to_string());
-                                    meta.insert("bytes_received".to_string(), line.len().to_string());
-                                    meta
-                                },
-                                processing_stage: ProcessingStage::Ingested,
-                            };
-                            
-                            if let Err(e) = event_tx.send(event) {
-                                error!("Failed to send file event: {}", e);
-                                Self::increment_error_count(&stats, source_name).await;
-                            } else {
-                                Self::update_stats(&stats, source_name, line.len() as u64).await;
-                            }
+                accepted = listener.accept() => {
+                    let (stream, peer) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            error!("Failed to accept Beats connection: {}", e);
+                            Self::increment_error_count(&stats, source_name).await;
+                            continue;
                         }
-                        Ok(None) => {
-                            // End of file reached, wait for new content or shutdown
-                            tokio::time::sleep(Duration::from_millis(100)).await;
+                    };
+                    
+                    debug!("Accepted Beats connection from {}", peer);
+                    let source_name = source_name.to_string();
+                    let event_tx = event_tx.clone();
+                    let stats = stats.clone();
+                    
+                    connections.spawn(async move {
+                        Self::update_peer_stats(&stats, &source_name, peer.ip(), |p| {
+                            p.connections += 1;
+                            p.active_connections += 1;
+                        }).await;
+                        
+                        let result = Self::handle_beats_connection(stream, peer, &source_name, &event_tx, &stats).await;
+                        
+                        if let Err(e) = &result {
+                            warn!("Beats connection from {} on source {} closed with error: {}", peer, source_name, e);
+                            Self::increment_error_count(&stats, &source_name).await;
                         }
+                        Self::update_peer_stats(&stats, &source_name, peer.ip(), |p| {
+                            p.active_connections = p.active_connections.saturating_sub(1);
+                            if result.is_err() {
+                                p.errors += 1;
+                            }
+                        }).await;
+                    });
+                }
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                _ = shutdown_rx.recv() => {
+                    info!("Shutting down Beats source: {}", source_name);
+                    break;
+                }
+            }
+        }
+        
+        connections.abort_all();
+        while connections.join_next().await.is_some() {}
+        
+        Self::update_connection_status(&stats, source_name, ConnectionStatus::Disconnected).await;
+        Ok(())
+    }
+    
+    async fn handle_beats_connection<S: AsyncRead + AsyncWrite + Unpin>(
+        mut stream: S,
+        peer: SocketAddr,
+        source_name: &str,
+        event_tx: &EventSender,
+        stats: &Arc<RwLock<HashMap<String, IngestionStats>>>,
+    ) -> Result<()> {
+        let mut decoder = LumberjackDecoder::new(BEATS_MAX_PAYLOAD_SIZE);
+        let mut buffer = Vec::with_capacity(8192);
+        let mut chunk = vec![0u8; 65536];
+        
+        loop {
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                if decoder.pending_events() > 0 {
+                    debug!("Beats connection from {} closed with {} unacknowledged events", peer, decoder.pending_events());
+                }
+                return Ok(());
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+            
+            while let Some(batch) = decoder.decode(&mut buffer)? {
+                // The ACK tells the beat it may drop these events, so only
+                // send it once every event has been handed to the pipeline
+                for beat_event in batch.events {
+                    let size = beat_event.size;
+                    let event = Self::beats_event(source_name, beat_event, peer);
+                    if !Self::deliver_network_event(event, source_name, peer.ip(), size, event_tx, stats).await {
+                        return Err(PipelineError::internal("Pipeline is not accepting events, Beats batch left unacknowledged"));
+                    }
+                }
+                stream.write_all(&beats::encode_ack(batch.last_sequence)).await?;
+                stream.flush().await?;
+            }
+        }
+    }
+    
+    fn beats_event(source_name: &str, beat_event: BeatsEvent, peer: SocketAddr) -> PipelineEvent {
+        let fields = beat_event.fields;
+        let timestamp = fields.get("@timestamp")
+            .and_then(|v| v.as_str())
+            .and_then(|ts| chrono::DateTime::parse_from_rfc3339(ts).ok())
+            .map(|ts| ts.with_timezone(&chrono::Utc))
+            .unwrap_or_else(chrono::Utc::now);
+        
+        let mut metadata = HashMap::new();
+        metadata.insert("source_type".to_string(), "beats".to_string());
+        metadata.insert("source_ip".to_string(), peer.ip().to_string());
+        metadata.insert("bytes_received".to_string(), beat_event.size.to_string());
+        metadata.insert("beats_sequence".to_string(), beat_event.sequence.to_string());
+        for (key, path) in [("beat", "/@metadata/beat"), ("beat_version", "/@metadata/version"), ("agent_type", "/agent/type")] {
+            if let Some(value) = fields.pointer(path).and_then(|v| v.as_str()) {
+                metadata.insert(key.to_string(), value.to_string());
+            }
+        }
+        
+        // Keep the beat document intact (agent, host, @metadata, ...) and add
+        // the fields downstream parsers look for
+        let data = match fields {
+            serde_json::Value::Object(mut object) => {
+                let raw_message = match object.get("message") {
+                    Some(serde_json::Value::String(message)) => message.clone(),
+                    _ => serde_json::Value::Object(object.clone()).to_string(),
+                };
+                object.entry("raw_message").or_insert(serde_json::Value::String(raw_message));
+                object.insert("source_ip".to_string(), serde_json::Value::String(peer.ip().to_string()));
+                object.insert("protocol".to_string(), serde_json::Value::String("beats".to_string()));
+                serde_json::Value::Object(object)
+            }
+            other => serde_json::json!({
+                "raw_message": other.to_string(),
+                "source_ip": peer.ip().to_string(),
+                "protocol": "beats"
+            }),
+        };
+        
+        PipelineEvent {
+            id: Uuid::new_v4(),
+            timestamp,
+            source: source_name.to_string(),
+            data,
+            metadata,
+            processing_stage: ProcessingStage::Ingested,
+        }
+    }
+    
+    // Fluent Forward protocol server, as spoken by Fluentd and Fluent Bit
+    async fn run_fluentd_source(
+        source_name: &str,
+        config: &DataSource,
+        event_tx: EventSender,
+        stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
+        mut shutdown_rx: mpsc::Receiver<()>,
+    ) -> Result<()> {
+        info!("Starting Fluentd source: {}", source_name);
+        
+        let (bind_addr, shared_key, self_hostname) = match &config.source_type {
+            SourceType::Fluentd { port, shared_key, self_hostname } => (
+                format!("0.0.0.0:{}", port),
+                shared_key.clone(),
+                self_hostname.clone()
+                    .or_else(|| std::env::var("HOSTNAME").ok())
+                    .unwrap_or_else(|| "siem-pipeline".to_string()),
+            ),
+            _ => return Err(PipelineError::configuration("Invalid source type for Fluentd source")),
+        };
+        
+        let listener = TcpListener::bind(&bind_addr).await
+            .map_err(|e| PipelineError::connection(format!("Failed to bind Fluentd listener on {}: {}", bind_addr, e)))?;
+        
+        Self::update_connection_status(&stats, source_name, ConnectionStatus::Connected).await;
+        
+        let mut connections = tokio::task::JoinSet::new();
+        
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, peer) = match accepted {
+                        Ok(accepted) => accepted,
                         Err(e) => {
-                            error!("File read error: {}", e);
+                            error!("Failed to accept Fluentd connection: {}", e);
                             Self::increment_error_count(&stats, source_name).await;
+                            continue;
                         }
+                    };
+                    
+                    debug!("Accepted Fluentd connection from {}", peer);
+                    let source_name = source_name.to_string();
+                    // Each connection gets its own handshake nonce
+                    let auth = shared_key.as_deref().map(|key| SharedKeyAuth::new(key, &self_hostname));
+                    let event_tx = event_tx.clone();
+                    let stats = stats.clone();
+                    
+                    connections.spawn(async move {
+                        Self::update_peer_stats(&stats, &source_name, peer.ip(), |p| {
+                            p.connections += 1;
+                            p.active_connections += 1;
+                        }).await;
+                        
+                        let result = Self::handle_fluentd_connection(stream, peer, &source_name, auth, &event_tx, &stats).await;
+                        
+                        if let Err(e) = &result {
+                            warn!("Fluentd connection from {} on source {} closed with error: {}", peer, source_name, e);
+                            Self::increment_error_count(&stats, &source_name).await;
+                        }
+                        Self::update_peer_stats(&stats, &source_name, peer.ip(), |p| {
+                            p.active_connections = p.active_connections.saturating_sub(1);
+                            if result.is_err() {
+                                p.errors += 1;
+                            }
+                        }).await;
+                    });
+                }
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+                _ = shutdown_rx.recv() => {
+                    info!("Shutting down Fluentd source: {}", source_name);
+                    break;
+                }
+            }
+        }
+        
+        connections.abort_all();
+        while connections.join_next().await.is_some() {}
+        
+        Self::update_connection_status(&stats, source_name, ConnectionStatus::Disconnected).await;
+        Ok(())
+    }
+    
+    async fn handle_fluentd_connection<S: AsyncRead + AsyncWrite + Unpin>(
+        mut stream: S,
+        peer: SocketAddr,
+        source_name: &str,
+        auth: Option<SharedKeyAuth>,
+        event_tx: &EventSender,
+        stats: &Arc<RwLock<HashMap<String, IngestionStats>>>,
+    ) -> Result<()> {
+        let mut pending_auth = auth;
+        if let Some(auth) = &pending_auth {
+            stream.write_all(&auth.helo()).await?;
+            stream.flush().await?;
+        }
+        
+        let mut buffer = Vec::with_capacity(8192);
+        let mut chunk = vec![0u8; 65536];
+        
+        loop {
+            let read = stream.read(&mut chunk).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            buffer.extend_from_slice(&chunk[..read]);
+            
+            while let Some((message, consumed)) = fluentd::decode_value(&buffer)? {
+                buffer.drain(..consumed);
+                
+                // Until the client answers HELO with a valid PING, nothing else is accepted
+                if let Some(auth) = pending_auth.take() {
+                    let (pong, authenticated) = auth.pong(&message);
+                    stream.write_all(&pong).await?;
+                    stream.flush().await?;
+                    if !authenticated {
+                        return Err(PipelineError::authentication(format!("Fluentd shared key handshake failed for {}", peer)));
+                    }
+                    continue;
+                }
+                
+                let forward = fluentd::parse_forward_message(&message, FLUENTD_MAX_MESSAGE_SIZE)?;
+                let count = forward.entries.len().max(1);
+                for (i, entry) in forward.entries.into_iter().enumerate() {
+                    // Wire bytes are shared out evenly across the message's entries
+                    let len = consumed / count + if i == 0 { consumed % count } else { 0 };
+                    let event = Self::fluentd_event(source_name, &forward.tag, entry, peer, len);
+                    if !Self::deliver_network_event(event, source_name, peer.ip(), len, event_tx, stats).await {
+                        return Err(PipelineError::internal("Pipeline is not accepting events, Fluentd chunk left unacknowledged"));
                     }
                 }
+                
+                if let Some(chunk_id) = forward.chunk {
+                    stream.write_all(&fluentd::encode_ack(&chunk_id)).await?;
+                    stream.flush().await?;
+                }
+            }
+            
+            if buffer.len() > FLUENTD_MAX_MESSAGE_SIZE {
+                return Err(PipelineError::bad_request(format!(
+                    "Fluentd message from {} exceeds limit of {} bytes", peer, FLUENTD_MAX_MESSAGE_SIZE
+                )));
+            }
+        }
+    }
+    
+    fn fluentd_event(source_name: &str, tag: &str, entry: ForwardEntry, peer: SocketAddr, len: usize) -> PipelineEvent {
+        let data = match entry.record {
+            serde_json::Value::Object(mut object) => {
+                let raw_message = ["log", "message"].iter()
+                    .find_map(|key| object.get(*key).and_then(|v| v.as_str()).map(str::to_string))
+                    .unwrap_or_else(|| serde_json::Value::Object(object.clone()).to_string());
+                object.entry("raw_message").or_insert(serde_json::Value::String(raw_message));
+                object.insert("source_ip".to_string(), serde_json::Value::String(peer.ip().to_string()));
+                object.insert("protocol".to_string(), serde_json::Value::String("fluentd".to_string()));
+                serde_json::Value::Object(object)
+            }
+            other => serde_json::json!({
+                "raw_message": other.to_string(),
+                "source_ip": peer.ip().to_string(),
+                "protocol": "fluentd"
+            }),
+        };
+        
+        PipelineEvent {
+            id: Uuid::new_v4(),
+            timestamp: entry.time,
+            source: source_name.to_string(),
+            data,
+            metadata: {
+                let mut meta = HashMap::new();
+                meta.insert("source_type".to_string(), "fluentd".to_string());
+                meta.insert("source_ip".to_string(), peer.ip().to_string());
+                meta.insert("bytes_received".to_string(), len.to_string());
+                meta.insert("tag".to_string(), tag.to_string());
+                meta
+            },
+            processing_stage: ProcessingStage::Ingested,
+        }
+    }
+    
+    async fn run_file_source(
+        source_name: &str,
+        config: &DataSource,
+        event_tx: EventSender,
+        stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
+        mut shutdown_rx: mpsc::Receiver<()>,
+    ) -> Result<()> {
+        info!("Starting file source: {}", source_name);
+        
+        let (pattern, watch, multiline_start, checkpoint_path) = match &config.source_type {
+            SourceType::File { path, watch, multiline_start, checkpoint_path } => (
+                path.clone(),
+                *watch,
+                multiline_start.clone(),
+                checkpoint_path.clone().unwrap_or_else(|| format!("data/checkpoints/{}.json", source_name)),
+            ),
+            _ => return Err(PipelineError::configuration("Invalid source type for file source")),
+        };
+        
+        let checkpoints = CheckpointStore::load(&checkpoint_path).await;
+        let mut tailer = FileTailer::new(&pattern, multiline_start.as_deref(), checkpoints)?;
+        
+        // Notifications only shorten the wait; polling still finds everything
+        let (wake_tx, mut wake_rx) = mpsc::unbounded_channel();
+        let _watcher = if watch {
+            match Self::watch_directory(tailer.base_dir(), tailer.is_recursive(), wake_tx) {
+                Ok(watcher) => Some(watcher),
+                Err(e) => {
+                    warn!("File source {} falling back to polling: {}", source_name, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        
+        Self::update_connection_status(&stats, source_name, ConnectionStatus::Connected).await;
+        
+        let mut poll_interval = interval(FILE_POLL_INTERVAL);
+        let mut last_checkpoint = std::time::Instant::now();
+        
+        loop {
+            tokio::select! {
+                _ = poll_interval.tick() => {}
+                Some(()) = wake_rx.recv() => {
+                    while wake_rx.try_recv().is_ok() {}
+                }
                 _ = shutdown_rx.recv() => {
                     info!("Shutting down file source: {}", source_name);
                     break;
                 }
             }
+            
+            for tailed in tailer.poll().await {
+                let len = tailed.message.len();
+                let event = PipelineEvent {
+                    id: Uuid::new_v4(),
+                    timestamp: chrono::Utc::now(),
+                    source: source_name.to_string(),
+                    data: serde_json::json!({
+                        "raw_message": tailed.message,
+                        "file_path": tailed.path,
+                        "file_offset": tailed.offset,
+                        "protocol": "file"
+                    }),
+                    metadata: {
+                        let mut meta = HashMap::new();
+                        meta.insert("source_type".to_string(), "file".to_string());
+                        meta.insert("file_path".to_string(), tailed.path);
+                        meta.insert("bytes_received".to_string(), len.to_string());
+                        meta
+                    },
+                    processing_stage: ProcessingStage::Ingested,
+                };
+                
+                // Offsets past this event must not be saved once it is lost
+                if let Err(e) = event_tx.send(event).await {
+                    Self::increment_error_count(&stats, source_name).await;
+                    Self::update_connection_status(&stats, source_name, ConnectionStatus::Error(e.to_string())).await;
+                    return Err(PipelineError::internal(format!("Failed to send file event: {}", e)));
+                }
+                Self::update_stats(&stats, source_name, len as u64).await;
+            }
+            
+            if last_checkpoint.elapsed() >= FILE_CHECKPOINT_INTERVAL {
+                if let Err(e) = tailer.save_checkpoints().await {
+                    error!("Failed to save checkpoints for file source {}: {}", source_name, e);
+                    Self::increment_error_count(&stats, source_name).await;
+                }
+                last_checkpoint = std::time::Instant::now();
+            }
         }
         
+        tailer.save_checkpoints().await?;
         Self::update_connection_status(&stats, source_name, ConnectionStatus::Disconnected).await;
         Ok(())
     }
     
+    fn watch_directory(
+        dir: &Path,
+        recursive: bool,
+        wake_tx: mpsc::UnboundedSender<()>,
+    ) -> notify::Result<notify::RecommendedWatcher> {
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+            if result.is_ok() {
+                let _ = wake_tx.send(());
+            }
+        })?;
+        let mode = if recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        watcher.watch(dir, mode)?;
+        Ok(watcher)
+    }
+    
     async fn run_http_source(
         source_name: &str,
         config: &DataSource,
-        event_tx: mpsc::UnboundedSender<PipelineEvent>,
+        event_tx: EventSender,
         stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
         http_client: Client,
-        mut shutdown_rx: mpsc::Receiver<()>,
+        shutdown_rx: mpsc::Receiver<()>,
     ) -> Result<()> {
         info!("Starting HTTP source: {}", source_name);
         
-        let url = match &config.source_type {
-            SourceType::Http { endpoint, .. } => endpoint.clone(),
+        let mode = match &config.source_type {
+            SourceType::Http { mode, .. } => mode.as_deref().unwrap_or("poll").to_lowercase(),
             _ => return Err(PipelineError::configuration("Invalid source type for HTTP source")),
         };
         
-        let poll_interval = Duration::from_secs(60); // Default poll interval
+        match mode.as_str() {
+            "poll" => Self::run_http_poll(source_name, config, event_tx, stats, http_client, shutdown_rx).await,
+            "receive" => Self::run_http_receiver(source_name, config, event_tx, stats, shutdown_rx).await,
+            other => Err(PipelineError::config(format!(
+                "Unsupported HTTP source mode '{}' for source '{}' (expected poll or receive)", other, source_name
+            ))),
+        }
+    }
+    
+    async fn run_http_poll(
+        source_name: &str,
+        config: &DataSource,
+        event_tx: EventSender,
+        stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
+        http_client: Client,
+        mut shutdown_rx: mpsc::Receiver<()>,
+    ) -> Result<()> {
+        let (url, method, poll_interval, headers, records_path, pagination) = match &config.source_type {
+            SourceType::Http { endpoint, method, poll_interval, headers, records_path, pagination, .. } => {
+                let method = match method.trim() {
+                    "" => reqwest::Method::GET,
+                    method => reqwest::Method::from_bytes(method.to_uppercase().as_bytes())
+                        .map_err(|_| PipelineError::config(format!("Invalid HTTP method '{}' for source '{}'", method, source_name)))?,
+                };
+                let poll_interval = match poll_interval {
+                    Some(value) => crate::utils::parse_duration(value)
+                        .ok_or_else(|| PipelineError::config(format!("Invalid poll_interval '{}' for source '{}'", value, source_name)))?,
+                    None => Duration::from_secs(60),
+                };
+                let url = reqwest::Url::parse(endpoint)
+                    .map_err(|e| PipelineError::config(format!("Invalid endpoint '{}' for source '{}': {}", endpoint, source_name, e)))?;
+                (
+                    url,
+                    method,
+                    poll_interval,
+                    headers.clone(),
+                    records_path.as_deref().map(JsonPath::parse).transpose()?,
+                    pagination.clone(),
+                )
+            }
+            _ => return Err(PipelineError::configuration("Invalid source type for HTTP source")),
+        };
+        let cursor_path = pagination.as_ref().map(|p| JsonPath::parse(&p.cursor_path)).transpose()?;
+        let max_pages = pagination.as_ref().and_then(|p| p.max_pages).unwrap_or(http_source::DEFAULT_MAX_PAGES);
+        
         let mut interval = interval(poll_interval);
         
         Self::update_connection_status(&stats, source_name, ConnectionStatus::Connected).await;
         
-        loop {
+        'polling: loop {
             tokio::select! {
-                _ = interval.tick() => {
-                    match http_client.get(&url).send().await {
-                        Ok(response) => {
-                            match response.text().await {
-                                Ok(body) => {
-                                    debug!("Received HTTP response: {} bytes", body.len());
-                                    
-                                    let event = PipelineEvent {
-                                        id: Uuid::new_v4(),
-                                        timestamp: chrono::Utc::now(),
-                                        source: source_name.to_string(),
-                                        data: serde_json::json!({
-                                            "raw_message": body,
-                                            "url": url,
-                                            "protocol": "http"
-                                        }),
-                                        metadata: {
-                                            let mut meta = HashMap::new();
-                                            meta.insert("source_type".to_string(), "http".to_string());
-                                            meta.insert("url".to_string(), url.to_string());
-                                            meta.insert("bytes_received".to_string(), body.len().to_string());
-                                            meta
-                                        },
-                                        processing_stage: ProcessingStage::Ingested,
-                                    };
-                                    
-                                    if let Err(e) = event_tx.send(event) {
-                                        error!("Failed to send HTTP event: {}", e);
-                                        Self::increment_error_count(&stats, source_name).await;
-                                    } else {
-                                        Self::update_stats(&stats, source_name, body.len() as u64).await;
-                                    }
-                                }
-                                Err(e) => {
-                                    error!("Failed to read HTTP response body: {}", e);
-                                    Self::increment_error_count(&stats, source_name).await;
-                                }
-                            }
+                _ = interval.tick() => {}
+                _ = shutdown_rx.recv() => break,
+            }
+            
+            // Each poll starts from the configured endpoint and follows cursors until none is returned
+            let mut cursor: Option<String> = None;
+            for _ in 0..max_pages {
+                let mut page_url = url.clone();
+                if let Some(cursor) = &cursor {
+                    match pagination.as_ref().and_then(|p| p.cursor_param.as_deref()) {
+                        Some(param) => {
+                            page_url.query_pairs_mut().append_pair(param, cursor);
                         }
+                        None => match url.join(cursor) {
+                            Ok(next) => page_url = next,
+                            Err(e) => {
+                                error!("HTTP source {} got an invalid next page URL '{}': {}", source_name, cursor, e);
+                                Self::increment_error_count(&stats, source_name).await;
+                                break;
+                            }
+                        },
+                    }
+                }
+                
+                let mut request = http_client.request(method.clone(), page_url.clone());
+                for (name, value) in &headers {
+                    request = request.header(name, value);
+                }
+                let body = match request.send().await.and_then(|response| response.error_for_status()) {
+                    Ok(response) => response.bytes().await,
+                    Err(e) => Err(e),
+                };
+                let body = match body {
+                    Ok(body) => body,
+                    Err(e) => {
+                        error!("HTTP source {} request to {} failed: {}", source_name, page_url, e);
+                        Self::increment_error_count(&stats, source_name).await;
+                        Self::update_connection_status(&stats, source_name, ConnectionStatus::Error(e.to_string())).await;
+                        break;
+                    }
+                };
+                Self::update_connection_status(&stats, source_name, ConnectionStatus::Connected).await;
+                debug!("Received HTTP response: {} bytes", body.len());
+                
+                let parsed = if records_path.is_some() || cursor_path.is_some() {
+                    match serde_json::from_slice::<serde_json::Value>(&body) {
+                        Ok(parsed) => Some(parsed),
                         Err(e) => {
-                            error!("HTTP request failed: {}", e);
+                            error!("HTTP source {} expected a JSON response from {}: {}", source_name, page_url, e);
                             Self::increment_error_count(&stats, source_name).await;
+                            break;
                         }
                     }
+                } else {
+                    None
+                };
+                let records = match (&records_path, &parsed) {
+                    (Some(path), Some(parsed)) => path.records(parsed),
+                    _ => http_source::split_body(&body),
+                };
+                
+                for record in records {
+                    let (event, len) = Self::http_event(source_name, record, None, Some(page_url.as_str()));
+                    if let Err(e) = event_tx.send(event).await {
+                        Self::increment_error_count(&stats, source_name).await;
+                        return Err(PipelineError::internal(format!("Failed to send HTTP event: {}", e)));
+                    }
+                    Self::update_stats(&stats, source_name, len as u64).await;
                 }
-                _ = shutdown_rx.recv() => {
-                    info!("Shutting down HTTP source: {}", source_name);
+                
+                cursor = match (&cursor_path, &parsed) {
+                    (Some(path), Some(parsed)) => path.first_string(parsed),
+                    _ => None,
+                };
+                if cursor.is_none() {
                     break;
                 }
+                if shutdown_rx.try_recv().is_ok() {
+                    break 'polling;
+                }
             }
         }
         
+        info!("Shutting down HTTP source: {}", source_name);
         Self::update_connection_status(&stats, source_name, ConnectionStatus::Disconnected).await;
         Ok(())
     }
     
+    // Push endpoint: senders POST NDJSON, JSON arrays or text, optionally compressed
+    async fn run_http_receiver(
+        source_name: &str,
+        config: &DataSource,
+        event_tx: EventSender,
+        stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
+        mut shutdown_rx: mpsc::Receiver<()>,
+    ) -> Result<()> {
+        let (bind_addr, path, method, auth, max_body_size, queue_size) = match &config.source_type {
+            SourceType::Http { endpoint, method, path, auth, max_body_size, queue_size, .. } => {
+                let method = match method.trim() {
+                    "" => axum::http::Method::POST,
+                    method => axum::http::Method::from_bytes(method.to_uppercase().as_bytes())
+                        .map_err(|_| PipelineError::config(format!("Invalid HTTP method '{}' for source '{}'", method, source_name)))?,
+                };
+                (
+                    endpoint.clone(),
+                    path.clone().unwrap_or_else(|| "/".to_string()),
+                    method,
+                    RequestAuth::new(auth.as_ref()),
+                    max_body_size.unwrap_or(http_source::DEFAULT_MAX_BODY_SIZE),
+                    queue_size.unwrap_or(http_source::DEFAULT_QUEUE_SIZE).max(1),
+                )
+            }
+            _ => return Err(PipelineError::configuration("Invalid source type for HTTP source")),
+        };
+        let method_filter = axum::routing::MethodFilter::try_from(method.clone())
+            .map_err(|e| PipelineError::config(format!("Unsupported HTTP method '{}' for source '{}': {}", method, source_name, e)))?;
+        
+        // Requests are admitted only while this queue has room, so a stalled
+        // pipeline turns into 429s for senders rather than unbounded memory
+        let (queue_tx, mut queue_rx) = mpsc::channel::<PipelineEvent>(queue_size);
+        let forwarder = tokio::spawn(async move {
+            while let Some(event) = queue_rx.recv().await {
+                if event_tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        });
+        
+        let receiver = Arc::new(HttpReceiver {
+            source_name: source_name.to_string(),
+            auth,
+            max_body_size,
+            queue: queue_tx,
+            stats: stats.clone(),
+        });
+        let router = axum::Router::new()
+            .route(&path, axum::routing::on(method_filter, Self::handle_http_push))
+            .layer(axum::extract::DefaultBodyLimit::max(max_body_size))
+            .with_state(receiver);
+        
+        let listener = TcpListener::bind(&bind_addr).await
+            .map_err(|e| PipelineError::connection(format!("Failed to bind HTTP listener on {}: {}", bind_addr, e)))?;
+        info!("HTTP source {} accepting {} {} on {}", source_name, method, path, bind_addr);
+        Self::update_connection_status(&stats, source_name, ConnectionStatus::Connected).await;
+        
+        let served = axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(async move {
+                let _ = shutdown_rx.recv().await;
+            })
+            .await;
+        // The router and its queue sender are gone once serving stops; let queued events drain
+        let _ = forwarder.await;
+        
+        info!("Shutting down HTTP source: {}", source_name);
+        Self::update_connection_status(&stats, source_name, ConnectionStatus::Disconnected).await;
+        served.map_err(|e| PipelineError::connection(format!("HTTP source {} failed: {}", source_name, e)))
+    }
+    
+    async fn handle_http_push(
+        State(receiver): State<Arc<HttpReceiver>>,
+        ConnectInfo(peer): ConnectInfo<SocketAddr>,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Response {
+        match Self::accept_http_push(&receiver, peer, &headers, &body).await {
+            Ok(accepted) => (StatusCode::ACCEPTED, Json(serde_json::json!({ "accepted": accepted }))).into_response(),
+            Err(e) => {
+                debug!("HTTP source {} rejected a request from {}: {}", receiver.source_name, peer, e);
+                Self::increment_error_count(&receiver.stats, &receiver.source_name).await;
+                Self::update_peer_stats(&receiver.stats, &receiver.source_name, peer.ip(), |p| p.errors += 1).await;
+                match e {
+                    PipelineError::RateLimitError(_) => ([(header::RETRY_AFTER, "1")], e).into_response(),
+                    e => e.into_response(),
+                }
+            }
+        }
+    }
+    
+    async fn accept_http_push(receiver: &HttpReceiver, peer: SocketAddr, headers: &HeaderMap, body: &[u8]) -> Result<usize> {
+        receiver.auth.verify(headers, body)?;
+        
+        let content_encoding = headers.get(header::CONTENT_ENCODING).and_then(|value| value.to_str().ok());
+        let body = http_source::decode_body(body, content_encoding, receiver.max_body_size)?;
+        let records = http_source::split_body(&body);
+        if records.len() > receiver.queue.max_capacity() {
+            return Err(PipelineError::bad_request(format!(
+                "Request holds {} records, more than the source accepts at once ({})",
+                records.len(),
+                receiver.queue.max_capacity()
+            )));
+        }
+        
+        // Admit the whole request or none of it, so a sender retrying after
+        // a 429 does not duplicate the part that got through
+        let permits = receiver.queue.try_reserve_many(records.len()).map_err(|e| match e {
+            TrySendError::Full(_) => PipelineError::rate_limit(format!("HTTP source {} queue is full", receiver.source_name)),
+            TrySendError::Closed(_) => PipelineError::service_unavailable(format!("HTTP source {} is shutting down", receiver.source_name)),
+        })?;
+        let mut sizes = Vec::with_capacity(records.len());
+        for (permit, record) in permits.zip(records) {
+            let (event, len) = Self::http_event(&receiver.source_name, record, Some(peer), None);
+            permit.send(event);
+            sizes.push(len);
+        }
+        
+        Self::update_peer_stats(&receiver.stats, &receiver.source_name, peer.ip(), |p| {
+            p.events_received += sizes.len() as u64;
+            p.bytes_received += body.len() as u64;
+        }).await;
+        for len in &sizes {
+            Self::update_stats(&receiver.stats, &receiver.source_name, *len as u64).await;
+        }
+        Ok(sizes.len())
+    }
+    
+    fn http_event(source_name: &str, record: serde_json::Value, peer: Option<SocketAddr>, url: Option<&str>) -> (PipelineEvent, usize) {
+        let mut data = match record {
+            serde_json::Value::Object(mut object) => {
+                let raw_message = serde_json::Value::Object(object.clone()).to_string();
+                object.entry("raw_message").or_insert(serde_json::Value::String(raw_message));
+                object
+            }
+            serde_json::Value::String(raw_message) => {
+                let mut object = serde_json::Map::new();
+                object.insert("raw_message".to_string(), serde_json::Value::String(raw_message));
+                object
+            }
+            other => {
+                let mut object = serde_json::Map::new();
+                object.insert("raw_message".to_string(), serde_json::Value::String(other.to_string()));
+                object
+            }
+        };
+        let len = data.get("raw_message").and_then(|v| v.as_str()).map(str::len).unwrap_or_default();
+        
+        let mut meta = HashMap::new();
+        meta.insert("source_type".to_string(), "http".to_string());
+        meta.insert("bytes_received".to_string(), len.to_string());
+        if let Some(peer) = peer {
+            data.insert("source_ip".to_string(), serde_json::Value::String(peer.ip().to_string()));
+            meta.insert("source_ip".to_string(), peer.ip().to_string());
+        }
+        if let Some(url) = url {
+            data.insert("url".to_string(), serde_json::Value::String(url.to_string()));
+            meta.insert("url".to_string(), url.to_string());
+        }
+        data.insert("protocol".to_string(), serde_json::Value::String("http".to_string()));
+        
+        let event = PipelineEvent {
+            id: Uuid::new_v4(),
+            timestamp: chrono::Utc::now(),
+            source: source_name.to_string(),
+            data: serde_json::Value::Object(data),
+            metadata: meta,
+            processing_stage: ProcessingStage::Ingested,
+        };
+        (event, len)
+    }
+    
     async fn run_kafka_source(
         source_name: &str,
         config: &DataSource,
-        event_tx: mpsc::UnboundedSender<PipelineEvent>,
+        event_tx: EventSender,
         stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
         mut shutdown_rx: mpsc::Receiver<()>,
     ) -> Result<()> {
@@ -542,17 +1411,43 @@ impl IngestionManager {
                                     
                                     // Send all events in batch
                                     let mut batch_bytes = 0u64;
+                                    let mut paused = None;
                                     for event in batch_events.drain(..) {
                                         if let Some(bytes) = event.metadata.get("bytes_received")
                                             .and_then(|s| s.parse::<u64>().ok()) {
                                             batch_bytes += bytes;
                                         }
                                         
-                                        if let Err(e) = event_tx.send(event) {
+                                        let sent = match event_tx.try_send(event) {
+                                            Ok(()) => Ok(()),
+                                            Err(TrySendError::Full(event)) => {
+                                                // Stop fetching while the pipeline catches up instead of
+                                                // letting librdkafka prefetch into memory
+                                                if paused.is_none() {
+                                                    match consumer.assignment().and_then(|assignment| {
+                                                        consumer.pause(&assignment).map(|_| assignment)
+                                                    }) {
+                                                        Ok(assignment) => {
+                                                            warn!("Pausing Kafka source {} until the event queue has room", source_name);
+                                                            paused = Some(assignment);
+                                                        }
+                                                        Err(e) => warn!("Failed to pause Kafka source {}: {}", source_name, e),
+                                                    }
+                                                }
+                                                event_tx.send(event).await.map_err(|e| e.to_string())
+                                            }
+                                            Err(e) => Err(e.to_string()),
+                                        };
+                                        if let Err(e) = sent {
                                             error!("Failed to send Kafka event: {}", e);
                                             Self::increment_error_count(&stats, source_name).await;
                                         }
                                     }
+                                    if let Some(assignment) = paused {
+                                        if let Err(e) = consumer.resume(&assignment) {
+                                            error!("Failed to resume Kafka source {}: {}", source_name, e);
+                                        }
+                                    }
                                     
                                     // Commit offsets for exactly-once delivery
                                     if !pending_messages.is_empty() {
@@ -589,7 +1484,7 @@ impl IngestionManager {
                                 .and_then(|s| s.parse::<u64>().ok()) {
                                 batch_bytes += bytes;
                             }
-                            let _ = event_tx.send(event);
+                            let _ = event_tx.send(event).await;
                         }
                         
                         // Final commit
@@ -611,71 +1506,248 @@ impl IngestionManager {
     async fn run_database_source(
         source_name: &str,
         config: &DataSource,
-        event_tx: mpsc::UnboundedSender<PipelineEvent>,
+        event_tx: EventSender,
         stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
         mut shutdown_rx: mpsc::Receiver<()>,
     ) -> Result<()> {
         info!("Starting database source: {}", source_name);
         
-        let connection_string = match &config.source_type {
-            SourceType::Database { connection_string, .. } => connection_string.clone(),
+        let (connection_string, query, tracking_column, poll_interval, batch_size, checkpoint_path) = match &config.source_type {
+            SourceType::Database { connection_string, query, tracking_column, poll_interval, batch_size, checkpoint_path } => {
+                let poll_interval = match poll_interval {
+                    Some(value) => crate::utils::parse_duration(value)
+                        .ok_or_else(|| PipelineError::config(format!("Invalid poll_interval '{}' for source '{}'", value, source_name)))?,
+                    None => Duration::from_secs(60),
+                };
+                (
+                    connection_string.clone(),
+                    query.clone(),
+                    tracking_column.clone(),
+                    poll_interval,
+                    batch_size.unwrap_or(DEFAULT_BATCH_SIZE),
+                    checkpoint_path.clone().unwrap_or_else(|| format!("data/checkpoints/{}.watermark.json", source_name)),
+                )
+            }
             _ => return Err(PipelineError::configuration("Invalid source type for database source")),
         };
-        // Database source implementation placeholder
+        
+        Self::update_connection_status(&stats, source_name, ConnectionStatus::Connecting).await;
+        let mut poller = DatabasePoller::connect(
+            &connection_string,
+            &query,
+            tracking_column,
+            batch_size,
+            checkpoint_path.into(),
+        ).await?;
         Self::update_connection_status(&stats, source_name, ConnectionStatus::Connected).await;
         
-        loop {
+        let mut interval = interval(poll_interval);
+        
+        'polling: loop {
             tokio::select! {
-                _ = tokio::time::sleep(Duration::from_secs(60)) => {
-                    // Database polling logic would go here
-                    info!("Database source {} polling (not implemented)", source_name);
+                _ = interval.tick() => {}
+                _ = shutdown_rx.recv() => break,
+            }
+            
+            // Page until caught up, checking for shutdown between pages
+            loop {
+                let rows = match poller.next_page().await {
+                    Ok(rows) => rows,
+                    Err(e) => {
+                        error!("Database source {} query failed: {}", source_name, e);
+                        Self::increment_error_count(&stats, source_name).await;
+                        Self::update_connection_status(&stats, source_name, ConnectionStatus::Error(e.to_string())).await;
+                        break;
+                    }
+                };
+                
+                for row in &rows {
+                    let raw_message = serde_json::Value::Object(row.clone()).to_string();
+                    let len = raw_message.len();
+                    let mut data = row.clone();
+                    data.insert("raw_message".to_string(), serde_json::Value::String(raw_message));
+                    data.insert("protocol".to_string(), serde_json::Value::String("database".to_string()));
+                    
+                    let event = PipelineEvent {
+                        id: Uuid::new_v4(),
+                        timestamp: chrono::Utc::now(),
+                        source: source_name.to_string(),
+                        data: serde_json::Value::Object(data),
+                        metadata: {
+                            let mut meta = HashMap::new();
+                            meta.insert("source_type".to_string(), "database".to_string());
+                            meta.insert("bytes_received".to_string(), len.to_string());
+                            meta
+                        },
+                        processing_stage: ProcessingStage::Ingested,
+                    };
+                    
+                    // Leave the watermark where it was so the page is fetched again next time
+                    if let Err(e) = event_tx.send(event).await {
+                        Self::increment_error_count(&stats, source_name).await;
+                        return Err(PipelineError::internal(format!("Failed to send database event: {}", e)));
+                    }
+                    Self::update_stats(&stats, source_name, len as u64).await;
                 }
-                _ = shutdown_rx.recv() => {
-                    info!("Shutting down database source: {}", source_name);
+                
+                if let Err(e) = poller.commit(&rows).await {
+                    error!("Database source {} failed to record its watermark: {}", source_name, e);
+                    Self::increment_error_count(&stats, source_name).await;
+                }
+                Self::update_connection_status(&stats, source_name, ConnectionStatus::Connected).await;
+                
+                if rows.len() < poller.batch_size() {
                     break;
                 }
+                if shutdown_rx.try_recv().is_ok() {
+                    break 'polling;
+                }
             }
+            poller.finish_poll();
         }
         
+        info!("Shutting down database source: {}", source_name);
         Self::update_connection_status(&stats, source_name, ConnectionStatus::Disconnected).await;
         Ok(())
     }
     
+    #[cfg(feature = "aws")]
     async fn run_s3_source(
         source_name: &str,
         config: &DataSource,
-        event_tx: mpsc::UnboundedSender<PipelineEvent>,
+        event_tx: EventSender,
         stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
         mut shutdown_rx: mpsc::Receiver<()>,
     ) -> Result<()> {
         info!("Starting S3 source: {}", source_name);
         
-        let (bucket, prefix): (String, String) = match &config.source_type {
-            SourceType::S3 { bucket, prefix, .. } => (bucket.clone(), prefix.clone()),
+        let (bucket, prefix, region, endpoint, poll_interval, checkpoint_path) = match &config.source_type {
+            SourceType::S3 { bucket, prefix, region, endpoint, poll_interval, checkpoint_path } => {
+                let poll_interval = match poll_interval {
+                    Some(value) => crate::utils::parse_duration(value)
+                        .ok_or_else(|| PipelineError::config(format!("Invalid poll_interval '{}' for source '{}'", value, source_name)))?,
+                    None => Duration::from_secs(300),
+                };
+                (
+                    bucket.clone(),
+                    prefix.clone(),
+                    region.clone(),
+                    endpoint.clone(),
+                    poll_interval,
+                    checkpoint_path.clone().unwrap_or_else(|| format!("data/checkpoints/{}.s3.json", source_name)),
+                )
+            }
             _ => return Err(PipelineError::configuration("Invalid source type for S3 source")),
         };
         
-        // S3 source implementation placeholder
-        Self::update_connection_status(&stats, source_name, ConnectionStatus::Connected).await;
+        Self::update_connection_status(&stats, source_name, ConnectionStatus::Connecting).await;
+        let objects = S3ObjectSource::connect(&bucket, &prefix, &region, endpoint.as_deref()).await;
+        let mut ledger = ObjectLedger::load(&checkpoint_path).await;
+        let compression = config.config.compression.as_ref();
         
-        loop {
+        let mut interval = interval(poll_interval);
+        
+        'polling: loop {
             tokio::select! {
-                _ = tokio::time::sleep(Duration::from_secs(300)) => {
-                    // S3 polling logic would go here
-                    info!("S3 source {} polling bucket {} (not implemented)", source_name, bucket);
+                _ = interval.tick() => {}
+                _ = shutdown_rx.recv() => break,
+            }
+            
+            let listed = match objects.list().await {
+                Ok(listed) => listed,
+                Err(e) => {
+                    error!("S3 source {} failed to list objects: {}", source_name, e);
+                    Self::increment_error_count(&stats, source_name).await;
+                    Self::update_connection_status(&stats, source_name, ConnectionStatus::Error(e.to_string())).await;
+                    continue;
                 }
-                _ = shutdown_rx.recv() => {
-                    info!("Shutting down S3 source: {}", source_name);
-                    break;
+            };
+            Self::update_connection_status(&stats, source_name, ConnectionStatus::Connected).await;
+            
+            ledger.retain_listed(&listed.iter().map(|object| object.key.clone()).collect());
+            let pending: Vec<_> = listed.into_iter()
+                .filter(|object| !ledger.is_processed(&object.key, &object.etag))
+                .collect();
+            
+            for object in &pending {
+                if shutdown_rx.try_recv().is_ok() {
+                    break 'polling;
+                }
+                
+                let records = match objects.fetch(&object.key).await
+                    .and_then(|data| s3_source::decompress(data, compression, &object.key))
+                {
+                    Ok(content) => s3_source::split_records(&content),
+                    Err(e) => {
+                        // Left unrecorded so the object is retried on the next poll
+                        error!("S3 source {} failed to read {}: {}", source_name, object.key, e);
+                        Self::increment_error_count(&stats, source_name).await;
+                        continue;
+                    }
+                };
+                debug!("S3 source {} read {} records from {} ({} bytes)", source_name, records.len(), object.key, object.size);
+                
+                for raw_message in records {
+                    let len = raw_message.len();
+                    let event = PipelineEvent {
+                        id: Uuid::new_v4(),
+                        timestamp: chrono::Utc::now(),
+                        source: source_name.to_string(),
+                        data: serde_json::json!({
+                            "raw_message": raw_message,
+                            "bucket": bucket,
+                            "key": object.key,
+                            "protocol": "s3"
+                        }),
+                        metadata: {
+                            let mut meta = HashMap::new();
+                            meta.insert("source_type".to_string(), "s3".to_string());
+                            meta.insert("bucket".to_string(), bucket.clone());
+                            meta.insert("key".to_string(), object.key.clone());
+                            meta.insert("etag".to_string(), object.etag.clone());
+                            meta.insert("bytes_received".to_string(), len.to_string());
+                            meta
+                        },
+                        processing_stage: ProcessingStage::Ingested,
+                    };
+                    
+                    if let Err(e) = event_tx.send(event).await {
+                        Self::increment_error_count(&stats, source_name).await;
+                        return Err(PipelineError::internal(format!("Failed to send S3 event: {}", e)));
+                    }
+                    Self::update_stats(&stats, source_name, len as u64).await;
+                }
+                
+                ledger.record(&object.key, &object.etag);
+                if let Err(e) = ledger.save().await {
+                    error!("S3 source {} failed to record processed objects: {}", source_name, e);
+                    Self::increment_error_count(&stats, source_name).await;
                 }
             }
+            
+            if let Err(e) = ledger.save().await {
+                error!("S3 source {} failed to record processed objects: {}", source_name, e);
+            }
         }
         
+        info!("Shutting down S3 source: {}", source_name);
         Self::update_connection_status(&stats, source_name, ConnectionStatus::Disconnected).await;
         Ok(())
     }
     
-
+    #[cfg(not(feature = "aws"))]
+    async fn run_s3_source(
+        source_name: &str,
+        _config: &DataSource,
+        _event_tx: EventSender,
+        _stats: Arc<RwLock<HashMap<String, IngestionStats>>>,
+        _shutdown_rx: mpsc::Receiver<()>,
+    ) -> Result<()> {
+        Err(PipelineError::config(format!(
+            "S3 source '{}' requires the pipeline to be built with the aws feature",
+            source_name
+        )))
+    }
     
     async fn update_stats(stats: &Arc<RwLock<HashMap<String, IngestionStats>>>, source_name: &str, bytes: u64) {
         let mut stats_guard = stats.write().await;
@@ -686,6 +1758,24 @@ impl IngestionManager {
         }
     }
     
+    async fn update_peer_stats<F: FnOnce(&mut PeerStats)>(
+        stats: &Arc<RwLock<HashMap<String, IngestionStats>>>,
+        source_name: &str,
+        peer: IpAddr,
+        update: F,
+    ) {
+        let mut stats_guard = stats.write().await;
+        if let Some(source_stats) = stats_guard.get_mut(source_name) {
+            let key = peer.to_string();
+            if !source_stats.peers.contains_key(&key) && source_stats.peers.len() >= MAX_TRACKED_PEERS {
+                return;
+            }
+            let peer_stats = source_stats.peers.entry(key).or_default();
+            update(peer_stats);
+            peer_stats.last_seen = Some(chrono::Utc::now());
+        }
+    }
+    
     async fn increment_error_count(stats: &Arc<RwLock<HashMap<String, IngestionStats>>>, source_name: &str) {
         let mut stats_guard = stats.write().await;
         if let Some(source_stats) = stats_guard.get_mut(source_name) {
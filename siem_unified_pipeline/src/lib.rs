@@ -83,10 +83,33 @@
 //!
 //! - [`config`] - Configuration management and validation
 //! - [`pipeline`] - Core pipeline orchestration and workflow
+//! - [`event_queue`] - Bounded event queue with disk spill between sources and processing
+//! - [`wal`] - Write-ahead log giving ingested events at-least-once delivery
 //! - [`ingestion`] - Data source management and collection
 //! - [`transformation`] - Event parsing, enrichment, and normalization
+//! - [`filter`] - Filter condition expressions for transformation steps
+//! - [`mapping`] - Field mapping, type coercion and validation for map steps
+//! - [`aggregation`] - Tumbling and sliding window roll-ups for aggregate steps
+//! - [`geoip`] - Offline MaxMind City and ASN lookups for the geoip enricher
+//! - [`cef`] - CEF and LEEF record parsing
+//! - [`syslog`] - RFC 5424 and RFC 3164 syslog parsing and stream framing
+//! - [`beats`] - Lumberjack v2 framing for Elastic Beats
+//! - [`fluentd`] - Fluent Forward protocol and MessagePack decoding
+//! - [`file_tail`] - Glob-based file tailing with rotation handling and checkpoints
+//! - [`database_source`] - Incremental polling of PostgreSQL and SQLite queries
+//! - [`http_source`] - HTTP push request handling and JSONPath record selection for polled APIs
+//! - [`s3_source`] - S3 object listing, decompression and record splitting
+//! - [`tls`] - TLS listener setup for network sources
 //! - [`routing`] - Intelligent event routing and distribution
+//! - [`balancer`] - Member selection for load-balanced and failover destination groups
+//! - [`detection`] - Streaming detection rules and alert generation
+//! - [`correlation`] - Windowed correlation and statistical rules
+//! - [`sigma`] - Sigma rule compilation and matching
 //! - [`storage`] - Multi-backend storage management
+//! - [`sink`] - Per-destination batching, compression and retry for storage writes
+//! - [`forwarder`] - Splunk HEC and templated webhook forwarding
+//! - [`file_sink`] - Templated, rotating NDJSON files with compression and retention
+//! - [`archive`] - Partitioned, rolling Parquet parts for file and S3 archives
 //! - [`metrics`] - Performance monitoring and observability
 //! - [`handlers`] - REST API endpoints and web interface
 //! - [`middleware`] - HTTP middleware for security and monitoring
@@ -97,10 +120,33 @@
 
 pub mod config;
 pub mod pipeline;
+pub mod event_queue;
+pub mod wal;
 pub mod ingestion;
 pub mod transformation;
+pub mod filter;
+pub mod mapping;
+pub mod aggregation;
+pub mod geoip;
+pub mod cef;
+pub mod syslog;
+pub mod beats;
+pub mod fluentd;
+pub mod file_tail;
+pub mod database_source;
+pub mod http_source;
+pub mod s3_source;
+pub mod tls;
 pub mod routing;
+pub mod balancer;
+pub mod detection;
+pub mod correlation;
+pub mod sigma;
 pub mod storage;
+pub mod sink;
+pub mod forwarder;
+pub mod file_sink;
+pub mod archive;
 pub mod metrics;
 pub mod handlers;
 pub mod middleware;
@@ -279,11 +325,79 @@ pub mod utils {
         }
     }
     
+    /// Parse a short duration such as `30s`, `5m`, `1h` or `7d`
+    ///
+    /// A bare number is taken as seconds. Values too large to represent are
+    /// rejected.
+    pub fn parse_duration(input: &str) -> Option<std::time::Duration> {
+        let input = input.trim();
+        let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+        let (value, unit) = input.split_at(split);
+        let value: u64 = value.parse().ok()?;
+
+        let seconds = match unit.trim() {
+            "" | "s" => value,
+            "ms" => return Some(std::time::Duration::from_millis(value)),
+            "m" => value.checked_mul(60)?,
+            "h" => value.checked_mul(3600)?,
+            "d" => value.checked_mul(86400)?,
+            "w" => value.checked_mul(604800)?,
+            _ => return None,
+        };
+        Some(std::time::Duration::from_secs(seconds))
+    }
+
     /// Validate IP address
     pub fn is_valid_ip(ip: &str) -> bool {
         ip.parse::<std::net::IpAddr>().is_ok()
     }
-    
+
+    /// Parse a CIDR block such as `10.0.0.0/8` or `fd00::/8` into its network
+    /// address and prefix length. A bare address is a single-host block.
+    pub fn parse_cidr(cidr: &str) -> Result<(std::net::IpAddr, u32), PipelineError> {
+        let invalid = || PipelineError::validation(format!("Invalid CIDR '{}'", cidr));
+        let block = cidr.trim();
+        let (network, prefix) = match block.split_once('/') {
+            Some((network, prefix)) => (network, Some(prefix.parse::<u32>().map_err(|_| invalid())?)),
+            None => (block, None),
+        };
+        let network: std::net::IpAddr = network.parse().map_err(|_| invalid())?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        match prefix.unwrap_or(max) {
+            prefix if prefix <= max => Ok((network, prefix)),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Check whether an IP address falls inside a CIDR block.
+    ///
+    /// A bare address is a single-host block, and IPv4-mapped IPv6 addresses
+    /// (`::ffff:a.b.c.d`) match IPv4 blocks. Returns `None` if the CIDR itself
+    /// is malformed; an unparsable IP or an address family mismatch is simply
+    /// not a match.
+    pub fn ip_in_cidr(ip: &str, cidr: &str) -> Option<bool> {
+        use std::net::IpAddr;
+
+        let (network, prefix) = parse_cidr(cidr).ok()?;
+        let ip = match ip.trim().parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) if network.is_ipv4() => ip.to_ipv4_mapped().map(IpAddr::V4),
+            Ok(ip) => Some(ip),
+            Err(_) => None,
+        };
+
+        Some(match (ip, network) {
+            (Some(IpAddr::V4(ip)), IpAddr::V4(net)) => {
+                let mask = u32::MAX.checked_shl(32 - prefix).unwrap_or(0);
+                u32::from(ip) & mask == u32::from(net) & mask
+            }
+            (Some(IpAddr::V6(ip)), IpAddr::V6(net)) => {
+                let mask = u128::MAX.checked_shl(128 - prefix).unwrap_or(0);
+                u128::from(ip) & mask == u128::from(net) & mask
+            }
+            _ => false,
+        })
+    }
+
     /// Validate hostname
     pub fn is_valid_hostname(hostname: &str) -> bool {
         if hostname.is_empty() || hostname.len() > 253 {
@@ -355,6 +469,17 @@ pub mod utils {
         
         Ok(decompressed)
     }
+    
+    /// Replace `path` with `content` through a temporary file and a rename,
+    /// creating parent directories, so a crash never leaves a partial file
+    pub async fn write_file_atomic(path: &std::path::Path, content: &[u8]) -> std::io::Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, content).await?;
+        tokio::fs::rename(&tmp_path, path).await
+    }
 }
 
 /// Prelude module for convenient imports
@@ -1071,6 +1196,30 @@ mod tests {
         assert!(is_valid_ip("::1"));
         assert!(!is_valid_ip("invalid"));
         
+        // Test duration parsing
+        assert_eq!(parse_duration("90"), Some(std::time::Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Some(std::time::Duration::from_secs(300)));
+        assert_eq!(parse_duration("2w"), Some(std::time::Duration::from_secs(1_209_600)));
+        assert_eq!(parse_duration("99999999999999999w"), None);
+        assert_eq!(parse_duration("99999999999999999999"), None);
+        assert_eq!(parse_duration("5y"), None);
+        
+        // Test CIDR matching
+        assert_eq!(parse_cidr(" 10.0.0.0/8 ").unwrap(), ("10.0.0.0".parse().unwrap(), 8));
+        assert_eq!(parse_cidr("fd00::1").unwrap(), ("fd00::1".parse().unwrap(), 128));
+        assert!(parse_cidr("10.0.0.0/").is_err());
+        assert_eq!(ip_in_cidr("10.1.200.3", "10.1.0.0/16"), Some(true));
+        assert_eq!(ip_in_cidr("10.2.0.1", "10.1.0.0/16"), Some(false));
+        assert_eq!(ip_in_cidr("::ffff:10.1.0.9", "10.1.0.0/16"), Some(true));
+        assert_eq!(ip_in_cidr("192.0.2.1", "0.0.0.0/0"), Some(true));
+        assert_eq!(ip_in_cidr("fd12::1", "fd00::/8"), Some(true));
+        assert_eq!(ip_in_cidr("fe80::1", "fd00::/8"), Some(false));
+        assert_eq!(ip_in_cidr("10.0.0.1", "fd00::/8"), Some(false));
+        assert_eq!(ip_in_cidr("192.0.2.7", "192.0.2.7"), Some(true));
+        assert_eq!(ip_in_cidr("not-an-ip", "10.0.0.0/8"), Some(false));
+        assert_eq!(ip_in_cidr("10.0.0.1", "10.0.0.0/33"), None);
+        assert_eq!(ip_in_cidr("10.0.0.1", "not-an-ip/8"), None);
+        
         // Test hostname validation
         assert!(is_valid_hostname("example.com"));
         assert!(is_valid_hostname("sub.example.com"));
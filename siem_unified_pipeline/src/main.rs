@@ -342,7 +342,17 @@ async fn run_server(
     // Initialize pipeline
     let pipeline = Arc::new(Pipeline::new(config.clone()).await?);
     let metrics = Arc::new(MetricsCollector::new(&config)?);
-    
+
+    // Alerts and stored detection rules live in Postgres; detections still run without it
+    match siem_unified_pipeline::database::DatabaseManager::new(config.database.clone()).await {
+        Ok(database) => {
+            if let Err(e) = pipeline.attach_database(Arc::new(database)).await {
+                warn!("Failed to load detection rules from database: {}", e);
+            }
+        }
+        Err(e) => warn!("Alert database unavailable, alerts will not be persisted: {}", e),
+    }
+
     // Create application state
     let app_state = handlers::AppState {
         pipeline: pipeline.clone(),
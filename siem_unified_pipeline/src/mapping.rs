@@ -0,0 +1,436 @@
+use std::collections::HashMap;
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::config::{FieldConfig, FieldType, ValidationRule};
+use crate::error::{Result, PipelineError};
+
+/// Look up a dotted path (`user.name`, `tags.0`) in a JSON value.
+pub fn get_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = value;
+    for part in path.split('.') {
+        current = match current {
+            Value::Object(map) => map.get(part)?,
+            Value::Array(items) => items.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(current)
+}
+
+/// Set a dotted path, creating intermediate objects and replacing non-object parents.
+pub fn set_path(value: &mut Value, path: &str, new_value: Value) {
+    let mut current = value;
+    let mut parts = path.split('.').peekable();
+    while let Some(part) = parts.next() {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        let map = current.as_object_mut().expect("just ensured object");
+        if parts.peek().is_none() {
+            map.insert(part.to_string(), new_value);
+            return;
+        }
+        current = map.entry(part.to_string()).or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+/// Remove a dotted path, returning the removed value.
+pub fn remove_path(value: &mut Value, path: &str) -> Option<Value> {
+    match path.rsplit_once('.') {
+        Some((parent, last)) => {
+            let mut current = value;
+            for part in parent.split('.') {
+                current = current.as_object_mut()?.get_mut(part)?;
+            }
+            current.as_object_mut()?.remove(last)
+        }
+        None => value.as_object_mut()?.remove(path),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum MappingOp {
+    Copy(String),
+    Move(String),
+    Delete,
+}
+
+/// Field operations for a `map` step.
+///
+/// Each entry maps a source path to `target` (copy), `copy:target`, `move:target`,
+/// `rename:target` or `delete`. Copies run first, then moves, then deletes, each in
+/// source-path order, so the result does not depend on map iteration order.
+#[derive(Debug, Clone)]
+pub struct FieldMappings {
+    operations: Vec<(String, MappingOp)>,
+}
+
+impl FieldMappings {
+    pub fn compile(field_mappings: &HashMap<String, String>) -> Result<Self> {
+        let mut operations = Vec::new();
+
+        for (source, target) in field_mappings {
+            if source.trim().is_empty() {
+                return Err(PipelineError::validation("Field mapping has an empty source path"));
+            }
+
+            let target = target.trim();
+            let op = if target == "delete" {
+                MappingOp::Delete
+            } else if let Some(path) = target.strip_prefix("copy:") {
+                MappingOp::Copy(path.trim().to_string())
+            } else if let Some(path) = target.strip_prefix("move:").or_else(|| target.strip_prefix("rename:")) {
+                MappingOp::Move(path.trim().to_string())
+            } else {
+                MappingOp::Copy(target.to_string())
+            };
+
+            if let MappingOp::Copy(path) | MappingOp::Move(path) = &op {
+                if path.is_empty() {
+                    return Err(PipelineError::validation(format!("Field mapping for '{}' has an empty target", source)));
+                }
+            }
+            operations.push((source.clone(), op));
+        }
+
+        let rank = |op: &MappingOp| match op {
+            MappingOp::Copy(_) => 0,
+            MappingOp::Move(_) => 1,
+            MappingOp::Delete => 2,
+        };
+        operations.sort_by(|(a_src, a_op), (b_src, b_op)| rank(a_op).cmp(&rank(b_op)).then(a_src.cmp(b_src)));
+
+        Ok(FieldMappings { operations })
+    }
+
+    /// Apply the mappings to `data`; `metadata.<key>` sources read from, and are
+    /// moved or deleted out of, event metadata.
+    pub fn apply(&self, data: &mut Value, metadata: &mut HashMap<String, String>) {
+        for (source, op) in &self.operations {
+            let metadata_key = source.strip_prefix("metadata.");
+            let value = match metadata_key {
+                Some(key) => metadata.get(key).map(|v| Value::String(v.clone())),
+                None => get_path(data, source).cloned(),
+            };
+            let mut remove_source = |data: &mut Value| match metadata_key {
+                Some(key) => { metadata.remove(key); }
+                None => { remove_path(data, source); }
+            };
+
+            match op {
+                MappingOp::Copy(target) => {
+                    if let Some(value) = value {
+                        set_path(data, target, value);
+                    }
+                }
+                MappingOp::Move(target) => {
+                    if let Some(value) = value {
+                        remove_source(data);
+                        set_path(data, target, value);
+                    }
+                }
+                MappingOp::Delete => remove_source(data),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Typed field definitions from `SourceConfig.fields`, with regexes compiled once.
+#[derive(Debug, Clone)]
+pub struct FieldSchema {
+    fields: Vec<(String, FieldConfig, Option<Regex>)>,
+    timestamp_format: Option<String>,
+}
+
+impl FieldSchema {
+    pub fn compile(fields: &HashMap<String, FieldConfig>, timestamp_format: Option<String>) -> Result<Self> {
+        let mut compiled = Vec::new();
+        for (name, field) in fields {
+            let regex = match &field.validation {
+                Some(ValidationRule::Regex(pattern)) => Some(Regex::new(pattern).map_err(|e| {
+                    PipelineError::config(format!("Invalid validation regex for field '{}': {}", name, e))
+                })?),
+                _ => None,
+            };
+            compiled.push((name.clone(), field.clone(), regex));
+        }
+        compiled.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(FieldSchema { fields: compiled, timestamp_format })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
+    /// Coerce and validate fields in place, returning one error per offending field.
+    pub fn apply(&self, data: &mut Value) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+
+        for (name, field, regex) in &self.fields {
+            let current = get_path(data, name).filter(|v| !v.is_null()).cloned();
+            let value = match (current, &field.default_value) {
+                (Some(value), _) => value,
+                (None, Some(default)) => Value::String(default.clone()),
+                (None, None) => {
+                    if field.required {
+                        errors.push(FieldError { field: name.clone(), message: "required field is missing".to_string() });
+                    }
+                    continue;
+                }
+            };
+
+            let coerced = match coerce(&value, &field.field_type, self.timestamp_format.as_deref()) {
+                Ok(coerced) => coerced,
+                Err(message) => {
+                    errors.push(FieldError { field: name.clone(), message });
+                    continue;
+                }
+            };
+
+            if let Some(rule) = &field.validation {
+                if let Err(message) = validate(&coerced, rule, regex.as_ref()) {
+                    errors.push(FieldError { field: name.clone(), message });
+                    continue;
+                }
+            }
+
+            set_path(data, name, coerced);
+        }
+
+        errors
+    }
+}
+
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.trim().to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn coerce(value: &Value, field_type: &FieldType, timestamp_format: Option<&str>) -> std::result::Result<Value, String> {
+    match field_type {
+        FieldType::String => Ok(Value::String(text_of(value))),
+        FieldType::Integer => match value {
+            Value::Number(n) if n.is_i64() || n.is_u64() => Ok(value.clone()),
+            Value::Number(n) => match n.as_f64() {
+                Some(f) if f.fract() == 0.0 => Ok(Value::from(f as i64)),
+                _ => Err(format!("{} is not an integer", n)),
+            },
+            _ => text_of(value).parse::<i64>()
+                .map(Value::from)
+                .map_err(|_| format!("'{}' is not an integer", text_of(value))),
+        },
+        FieldType::Float => match value {
+            Value::Number(n) => n.as_f64().map(Value::from).ok_or_else(|| format!("{} is not a float", n)),
+            _ => text_of(value).parse::<f64>()
+                .map(Value::from)
+                .map_err(|_| format!("'{}' is not a number", text_of(value))),
+        },
+        FieldType::Boolean => match value {
+            Value::Bool(_) => Ok(value.clone()),
+            _ => match text_of(value).to_lowercase().as_str() {
+                "true" | "yes" | "y" | "on" | "1" => Ok(Value::Bool(true)),
+                "false" | "no" | "n" | "off" | "0" => Ok(Value::Bool(false)),
+                other => Err(format!("'{}' is not a boolean", other)),
+            },
+        },
+        FieldType::Timestamp => parse_timestamp(value, timestamp_format)
+            .map(|ts| Value::String(ts.to_rfc3339()))
+            .ok_or_else(|| format!("'{}' is not a recognised timestamp", text_of(value))),
+        FieldType::IpAddress => {
+            let text = text_of(value);
+            let bare = text.trim_start_matches('[').trim_end_matches(']');
+            bare.parse::<std::net::IpAddr>()
+                .map(|ip| Value::String(ip.to_string()))
+                .map_err(|_| format!("'{}' is not an IP address", text))
+        }
+        FieldType::Url => {
+            let text = text_of(value);
+            match text.split_once("://") {
+                Some((scheme, rest)) if !scheme.is_empty()
+                    && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+                    && !rest.is_empty() => Ok(Value::String(text)),
+                _ => Err(format!("'{}' is not a URL", text)),
+            }
+        }
+        FieldType::Email => {
+            let text = text_of(value);
+            match text.split_once('@') {
+                Some((local, domain)) if !local.is_empty() && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.') => {
+                    Ok(Value::String(text.to_lowercase()))
+                }
+                _ => Err(format!("'{}' is not an email address", text)),
+            }
+        }
+        FieldType::Json => match value {
+            Value::String(s) => serde_json::from_str(s).map_err(|e| format!("invalid JSON: {}", e)),
+            other => Ok(other.clone()),
+        },
+    }
+}
+
+/// Parse epoch seconds/milliseconds, RFC 3339, or a configured chrono format (assumed UTC).
+pub fn parse_timestamp(value: &Value, format: Option<&str>) -> Option<DateTime<Utc>> {
+    let epoch = |n: f64| {
+        // Anything past the year 33658 in seconds is really milliseconds
+        if n.abs() >= 1e12 {
+            Utc.timestamp_millis_opt(n as i64).single()
+        } else {
+            Utc.timestamp_opt(n.trunc() as i64, (n.fract() * 1e9) as u32).single()
+        }
+    };
+
+    if let Value::Number(n) = value {
+        return n.as_f64().and_then(epoch);
+    }
+
+    let text = text_of(value);
+    if let Some(format) = format {
+        if let Ok(ts) = DateTime::parse_from_str(&text, format) {
+            return Some(ts.with_timezone(&Utc));
+        }
+        if let Ok(naive) = NaiveDateTime::parse_from_str(&text, format) {
+            return Some(Utc.from_utc_datetime(&naive));
+        }
+    }
+    if let Ok(ts) = DateTime::parse_from_rfc3339(&text) {
+        return Some(ts.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%d/%b/%Y:%H:%M:%S"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(&text, format) {
+            return Some(Utc.from_utc_datetime(&naive));
+        }
+    }
+    if let Ok(ts) = DateTime::parse_from_str(&text, "%d/%b/%Y:%H:%M:%S %z") {
+        return Some(ts.with_timezone(&Utc));
+    }
+    text.parse::<f64>().ok().and_then(epoch)
+}
+
+fn validate(value: &Value, rule: &ValidationRule, regex: Option<&Regex>) -> std::result::Result<(), String> {
+    match rule {
+        ValidationRule::Regex(pattern) => {
+            let text = text_of(value);
+            if regex.map(|r| r.is_match(&text)).unwrap_or(false) {
+                Ok(())
+            } else {
+                Err(format!("'{}' does not match {}", text, pattern))
+            }
+        }
+        ValidationRule::Range { min, max } => {
+            let number = match value {
+                Value::Number(n) => n.as_f64(),
+                other => text_of(other).parse().ok(),
+            };
+            match number {
+                Some(n) if n >= *min && n <= *max => Ok(()),
+                Some(n) => Err(format!("{} is outside {}..={}", n, min, max)),
+                None => Err(format!("'{}' is not numeric", text_of(value))),
+            }
+        }
+        ValidationRule::Length { min, max } => {
+            let length = text_of(value).chars().count();
+            if length >= *min && length <= *max {
+                Ok(())
+            } else {
+                Err(format!("length {} is outside {}..={}", length, min, max))
+            }
+        }
+        ValidationRule::OneOf(options) => {
+            let text = text_of(value);
+            if options.contains(&text) {
+                Ok(())
+            } else {
+                Err(format!("'{}' is not one of {:?}", text, options))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(field_type: FieldType, required: bool, validation: Option<ValidationRule>) -> FieldConfig {
+        FieldConfig { field_type, required, default_value: None, validation }
+    }
+
+    #[test]
+    fn test_copy_move_delete() {
+        let mappings = FieldMappings::compile(&HashMap::from([
+            ("parsed.host".to_string(), "host.name".to_string()),
+            ("parsed.src".to_string(), "move:source.ip".to_string()),
+            ("parsed.junk".to_string(), "delete".to_string()),
+            ("metadata.source_type".to_string(), "event.module".to_string()),
+        ])).unwrap();
+
+        let mut data = serde_json::json!({"parsed": {"host": "web01", "src": "10.0.0.1", "junk": 1}});
+        let mut metadata = HashMap::from([("source_type".to_string(), "syslog".to_string())]);
+        mappings.apply(&mut data, &mut metadata);
+
+        assert_eq!(data, serde_json::json!({
+            "parsed": {"host": "web01"},
+            "host": {"name": "web01"},
+            "source": {"ip": "10.0.0.1"},
+            "event": {"module": "syslog"}
+        }));
+    }
+
+    #[test]
+    fn test_move_and_delete_from_metadata() {
+        let mappings = FieldMappings::compile(&HashMap::from([
+            ("metadata.collector".to_string(), "move:observer.name".to_string()),
+            ("metadata.trace".to_string(), "delete".to_string()),
+        ])).unwrap();
+
+        // A data field shaped like the metadata path is left alone
+        let mut data = serde_json::json!({"metadata": {"collector": "from-data", "trace": "kept"}});
+        let mut metadata = HashMap::from([
+            ("collector".to_string(), "edge-1".to_string()),
+            ("trace".to_string(), "abc".to_string()),
+            ("source_type".to_string(), "syslog".to_string()),
+        ]);
+        mappings.apply(&mut data, &mut metadata);
+
+        assert_eq!(data, serde_json::json!({
+            "metadata": {"collector": "from-data", "trace": "kept"},
+            "observer": {"name": "edge-1"}
+        }));
+        assert_eq!(metadata, HashMap::from([("source_type".to_string(), "syslog".to_string())]));
+    }
+
+    #[test]
+    fn test_coercion_and_validation() {
+        let schema = FieldSchema::compile(&HashMap::from([
+            ("port".to_string(), field(FieldType::Integer, true, Some(ValidationRule::Range { min: 1.0, max: 65535.0 }))),
+            ("src".to_string(), field(FieldType::IpAddress, false, None)),
+            ("ts".to_string(), field(FieldType::Timestamp, false, None)),
+            ("ok".to_string(), field(FieldType::Boolean, false, None)),
+            ("user".to_string(), field(FieldType::String, true, None)),
+            ("action".to_string(), field(FieldType::String, false, Some(ValidationRule::OneOf(vec!["allow".into(), "deny".into()])))),
+        ]), None).unwrap();
+
+        let mut data = serde_json::json!({
+            "port": "443", "src": " 192.168.1.1 ", "ts": 1700000000, "ok": "yes", "action": "drop"
+        });
+        let errors = schema.apply(&mut data);
+
+        assert_eq!(data["port"], serde_json::json!(443));
+        assert_eq!(data["src"], serde_json::json!("192.168.1.1"));
+        assert_eq!(data["ts"], serde_json::json!("2023-11-14T22:13:20+00:00"));
+        assert_eq!(data["ok"], serde_json::json!(true));
+
+        let mut failed: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        failed.sort();
+        assert_eq!(failed, vec!["action", "user"]);
+    }
+}
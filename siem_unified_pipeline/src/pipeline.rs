@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio::time::{interval, Duration};
 use tracing::{info, warn, error, debug};
 use uuid::Uuid;
@@ -12,6 +12,11 @@ use crate::transformation::TransformationManager;
 use crate::routing::RoutingManager;
 use crate::storage::StorageManager;
 use crate::metrics::MetricsCollector;
+use crate::detection::DetectionEngine;
+use crate::database::DatabaseManager;
+use crate::event_queue::{event_queue, EventSender};
+use crate::wal::WriteAheadLog;
+use crate::sink::Delivery;
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct PipelineEvent {
@@ -23,18 +28,60 @@ pub struct PipelineEvent {
     pub processing_stage: ProcessingStage,
 }
 
+impl PipelineEvent {
+    /// Look up a field by name in `data` (exact key first, then dot notation),
+    /// falling back to `metadata`.
+    pub fn get_field(&self, path: &str) -> Option<std::borrow::Cow<'_, serde_json::Value>> {
+        if let Some(value) = self.data.get(path) {
+            return Some(std::borrow::Cow::Borrowed(value));
+        }
+
+        if path.contains('.') {
+            let mut current = &self.data;
+            let mut found = true;
+            for part in path.split('.') {
+                match current {
+                    serde_json::Value::Object(map) => match map.get(part) {
+                        Some(next) => current = next,
+                        None => { found = false; break; }
+                    },
+                    serde_json::Value::Array(items) => match part.parse::<usize>().ok().and_then(|i| items.get(i)) {
+                        Some(next) => current = next,
+                        None => { found = false; break; }
+                    },
+                    _ => { found = false; break; }
+                }
+            }
+            if found {
+                return Some(std::borrow::Cow::Borrowed(current));
+            }
+        }
+
+        self.metadata.get(path)
+            .map(|v| std::borrow::Cow::Owned(serde_json::Value::String(v.clone())))
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum ProcessingStage {
     Ingested,
     Parsed,
     Enriched,
     Filtered,
+    Aggregated,
     Normalized,
     Routed,
     Stored,
     Failed(String),
 }
 
+impl ProcessingStage {
+    /// Whether a transformation step took the event out of the stream on purpose
+    pub fn is_consumed(&self) -> bool {
+        matches!(self, ProcessingStage::Filtered | ProcessingStage::Aggregated)
+    }
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct PipelineStats {
     pub events_ingested: u64,
@@ -53,10 +100,12 @@ pub struct Pipeline {
     transformation_manager: Arc<TransformationManager>,
     routing_manager: Arc<RoutingManager>,
     storage_manager: Arc<StorageManager>,
+    detection_engine: Arc<DetectionEngine>,
     metrics_collector: Arc<MetricsCollector>,
     stats: Arc<RwLock<PipelineStats>>,
-    event_tx: mpsc::UnboundedSender<PipelineEvent>,
-    event_rx: Arc<RwLock<Option<mpsc::UnboundedReceiver<PipelineEvent>>>>,
+    event_tx: EventSender,
+    event_rx: Arc<RwLock<Option<mpsc::Receiver<PipelineEvent>>>>,
+    acks: Option<Acknowledger>,
     shutdown_tx: mpsc::Sender<()>,
     shutdown_rx: Arc<RwLock<Option<mpsc::Receiver<()>>>>,
     start_time: chrono::DateTime<chrono::Utc>,
@@ -71,12 +120,52 @@ impl Pipeline {
         let transformation_manager = Arc::new(TransformationManager::new(&config).await?);
         let routing_manager = Arc::new(RoutingManager::new(&config).await?);
         let storage_manager = Arc::new(StorageManager::new(&config).await?);
+        let detection_engine = Arc::new(DetectionEngine::new(&config).await?);
         let metrics_collector = Arc::new(MetricsCollector::new(&config)?);
         
-        // Create event channel
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        // Health probes, circuit breakers and failed writes mark destinations
+        // unhealthy for routing, which fails destination groups over on it
+        let mut health_updates = storage_manager.subscribe_health();
+        for (destination, health) in storage_manager.probed_health().await {
+            routing_manager.update_destination_health(&destination, health).await;
+        }
+        let health_routing = routing_manager.clone();
+        tokio::spawn(async move {
+            loop {
+                match health_updates.recv().await {
+                    Ok((destination, health)) => health_routing.update_destination_health(&destination, health).await,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Missed {} destination health updates", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        
+        // Open the write-ahead log, if enabled, and the bounded event queue in front of it
+        let (wal, recovered) = if config.wal.enabled {
+            let (wal, recovered) = WriteAheadLog::open(&config.wal)?;
+            (Some(wal), recovered)
+        } else {
+            (None, Vec::new())
+        };
+        let (event_tx, event_rx) = event_queue(&config.performance.buffers, wal.clone())?;
+        let acks = wal.map(|wal| Acknowledger::spawn(wal, config.performance.buffers.event_buffer_size));
         let (shutdown_tx, shutdown_rx) = mpsc::channel(1);
         
+        // Events that were never acknowledged go ahead of anything new from the sources
+        if !recovered.is_empty() {
+            info!("Replaying {} events from the write-ahead log", recovered.len());
+            let event_tx = event_tx.clone();
+            tokio::spawn(async move {
+                for event in recovered {
+                    if event_tx.replay(event).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        
         // Initialize stats
         let stats = Arc::new(RwLock::new(PipelineStats {
             events_ingested: 0,
@@ -97,10 +186,12 @@ impl Pipeline {
             transformation_manager,
             routing_manager,
             storage_manager,
+            detection_engine,
             metrics_collector,
             stats,
             event_tx,
             event_rx: Arc::new(RwLock::new(Some(event_rx))),
+            acks,
             shutdown_tx,
             shutdown_rx: Arc::new(RwLock::new(Some(shutdown_rx))),
             start_time,
@@ -135,8 +226,10 @@ impl Pipeline {
         let transformation_manager = self.transformation_manager.clone();
         let routing_manager = self.routing_manager.clone();
         let storage_manager = self.storage_manager.clone();
+        let detection_engine = self.detection_engine.clone();
         let stats = self.stats.clone();
         let metrics_collector = self.metrics_collector.clone();
+        let acks = self.acks.clone();
         
         tokio::spawn(async move {
             Self::process_events(
@@ -144,8 +237,10 @@ impl Pipeline {
                 transformation_manager,
                 routing_manager,
                 storage_manager,
+                detection_engine,
                 stats,
                 metrics_collector,
+                acks,
             ).await;
         });
         
@@ -162,6 +257,11 @@ impl Pipeline {
             }
         });
         
+        self.start_detection_maintenance();
+        self.start_aggregation_flush();
+        self.start_threat_intel_refresh();
+        self.start_queue_depth_reporting();
+        
         // Start health check
         let stats_clone = self.stats.clone();
         let start_time = self.start_time;
@@ -213,8 +313,11 @@ impl Pipeline {
         let transformation_manager = self.transformation_manager.clone();
         let routing_manager = self.routing_manager.clone();
         let storage_manager = self.storage_manager.clone();
+        let detection_engine = self.detection_engine.clone();
         let stats = self.stats.clone();
         let metrics_collector = self.metrics_collector.clone();
+        let batch_buffer_size = self.config.performance.buffers.batch_buffer_size;
+        let acks = self.acks.clone();
         
         tokio::spawn(async move {
             Self::process_events_parallel(
@@ -222,10 +325,13 @@ impl Pipeline {
                 transformation_manager,
                 routing_manager,
                 storage_manager,
+                detection_engine,
                 metrics_collector,
                 stats,
                 shutdown_rx,
                 worker_count,
+                batch_buffer_size,
+                acks,
             ).await;
         });
         
@@ -242,6 +348,11 @@ impl Pipeline {
             }
         });
         
+        self.start_detection_maintenance();
+        self.start_aggregation_flush();
+        self.start_threat_intel_refresh();
+        self.start_queue_depth_reporting();
+        
         // Start health check
         let stats_clone = self.stats.clone();
         let start_time = self.start_time;
@@ -257,13 +368,91 @@ impl Pipeline {
         Ok(())
     }
     
+    // Periodically expire idle correlation windows and snapshot the rest to disk
+    fn start_detection_maintenance(&self) {
+        let detection_engine = self.detection_engine.clone();
+        let period = Duration::from_secs(self.config.detection.correlation_snapshot_interval_secs.max(1));
+        tokio::spawn(async move {
+            let mut interval = interval(period);
+            loop {
+                interval.tick().await;
+                detection_engine.run_maintenance().await;
+            }
+        });
+    }
+    
+    fn start_queue_depth_reporting(&self) {
+        let event_tx = self.event_tx.clone();
+        let metrics_collector = self.metrics_collector.clone();
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(1));
+            while !event_tx.is_closed() {
+                interval.tick().await;
+                metrics_collector.update_queue_depth(event_tx.depth() as u64);
+            }
+        });
+    }
+    
+    // Emit summaries for closed aggregation windows back into the event stream;
+    // they resume the transformation pipeline after the step that produced them
+    fn start_aggregation_flush(&self) {
+        let transformation_manager = self.transformation_manager.clone();
+        let event_tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                for summary in transformation_manager.flush_aggregations(false).await {
+                    if event_tx.send(summary).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+    }
+    
+    // Keep the threat_intel enricher's indicators current: rows added since
+    // the last load on every tick, and a full reload now and then so deleted
+    // indicators stop matching
+    fn start_threat_intel_refresh(&self) {
+        let settings = &self.config.threat_intel;
+        if !settings.enabled {
+            return;
+        }
+        let transformation_manager = self.transformation_manager.clone();
+        let refresh_every = Duration::from_secs(settings.refresh_interval_secs.max(1));
+        let full_reload_every = Duration::from_secs(settings.full_reload_interval_secs);
+        tokio::spawn(async move {
+            let mut interval = interval(refresh_every);
+            let mut last_full_reload: Option<std::time::Instant> = None;
+            loop {
+                interval.tick().await;
+                let full = !matches!(last_full_reload, Some(at) if at.elapsed() < full_reload_every);
+                match transformation_manager.refresh_threat_intel(full).await {
+                    Ok(applied) => {
+                        if full {
+                            last_full_reload = Some(std::time::Instant::now());
+                            info!("Loaded {} threat intelligence indicators", applied);
+                        } else {
+                            debug!("Applied {} new threat intelligence indicators", applied);
+                        }
+                    }
+                    Err(e) => warn!("Failed to refresh threat intelligence: {}", e),
+                }
+            }
+        });
+    }
+    
+    #[allow(clippy::too_many_arguments)]
     async fn process_events(
-        mut event_rx: mpsc::UnboundedReceiver<PipelineEvent>,
+        mut event_rx: mpsc::Receiver<PipelineEvent>,
         transformation_manager: Arc<TransformationManager>,
         routing_manager: Arc<RoutingManager>,
         storage_manager: Arc<StorageManager>,
+        detection_engine: Arc<DetectionEngine>,
         stats: Arc<RwLock<PipelineStats>>,
         metrics_collector: Arc<MetricsCollector>,
+        acks: Option<Acknowledger>,
     ) {
         info!("Starting event processing loop");
         
@@ -283,9 +472,19 @@ impl Pipeline {
                     debug!("Event {} transformed successfully", event_id);
                     event.processing_stage = ProcessingStage::Normalized;
                 }
+                Err(_) if event.processing_stage.is_consumed() => {
+                    debug!("Event {} dropped by filter or folded into an aggregate", event_id);
+                    acknowledge(&acks, event_id);
+                    
+                    let mut stats_guard = stats.write().await;
+                    stats_guard.events_dropped += 1;
+                    continue;
+                }
                 Err(e) => {
                     error!("Transformation failed for event {}: {}", event_id, e);
                     event.processing_stage = ProcessingStage::Failed(e.to_string());
+                    // Replaying it would fail the same way
+                    acknowledge(&acks, event_id);
                     
                     let mut stats_guard = stats.write().await;
                     stats_guard.events_failed += 1;
@@ -293,21 +492,34 @@ impl Pipeline {
                 }
             }
             
+            // Run streaming detections on the normalized event
+            if let Err(e) = detection_engine.evaluate(&event).await {
+                warn!("Detection failed for event {}: {}", event_id, e);
+            }
+            
             // Route event to destinations
             match routing_manager.route_event(&event).await {
                 Ok(destinations) => {
                     debug!("Event {} routed to {} destinations", event_id, destinations.len());
                     event.processing_stage = ProcessingStage::Routed;
                     
-                    // Store in each destination
+                    // Queue for each destination's next batch
+                    let mut deliveries = Vec::new();
+                    let mut storage_success = true;
                     for destination in destinations {
-                        if let Err(e) = storage_manager.store_event(&event, &destination).await {
-                            error!("Storage failed for event {} in destination {}: {}", event_id, destination, e);
-                        } else {
-                            debug!("Event {} stored in destination {}", event_id, destination);
+                        match storage_manager.submit_event(&event, &destination).await {
+                            Ok(delivery) => deliveries.push(delivery),
+                            Err(e) => {
+                                error!("Storage failed for event {} in destination {}: {}", event_id, destination, e);
+                                storage_success = false;
+                            }
                         }
                     }
                     
+                    // Left unacknowledged, the event is replayed to every destination on restart
+                    if storage_success {
+                        acknowledge_when_stored(&acks, event_id, deliveries).await;
+                    }
                     event.processing_stage = ProcessingStage::Stored;
                     
                     let mut stats_guard = stats.write().await;
@@ -316,6 +528,7 @@ impl Pipeline {
                 Err(e) => {
                     error!("Routing failed for event {}: {}", event_id, e);
                     event.processing_stage = ProcessingStage::Failed(e.to_string());
+                    acknowledge(&acks, event_id);
                     
                     let mut stats_guard = stats.write().await;
                     stats_guard.events_failed += 1;
@@ -331,20 +544,25 @@ impl Pipeline {
     }
     
     // Enhanced parallel event processing for high-throughput (500k eps)
+    #[allow(clippy::too_many_arguments)]
     async fn process_events_parallel(
-        mut event_rx: mpsc::UnboundedReceiver<PipelineEvent>,
+        mut event_rx: mpsc::Receiver<PipelineEvent>,
         transformation_manager: Arc<TransformationManager>,
         routing_manager: Arc<RoutingManager>,
         storage_manager: Arc<StorageManager>,
+        detection_engine: Arc<DetectionEngine>,
         metrics_collector: Arc<MetricsCollector>,
         stats: Arc<RwLock<PipelineStats>>,
         mut shutdown_rx: mpsc::Receiver<()>,
         worker_count: usize,
+        batch_buffer_size: usize,
+        acks: Option<Acknowledger>,
     ) {
         info!("Starting parallel event processing with {} workers", worker_count);
         
-        let (batch_tx, batch_rx) = mpsc::unbounded_channel::<Vec<PipelineEvent>>();
         let batch_size = 1000;
+        // batch_buffer_size counts events; the channel holds whole batches
+        let (batch_tx, batch_rx) = mpsc::channel::<Vec<PipelineEvent>>((batch_buffer_size / batch_size).max(1));
         let batch_timeout = Duration::from_millis(100);
         
         // Batching task
@@ -362,7 +580,7 @@ impl Pipeline {
                                 
                                 // Send batch if full or timeout reached
                                 if (current_batch.len() >= batch_size || last_batch_time.elapsed() >= batch_timeout) && !current_batch.is_empty() {
-                                    if batch_tx_clone.send(current_batch.clone()).is_err() {
+                                    if batch_tx_clone.send(current_batch.clone()).await.is_err() {
                                         break;
                                     }
                                     current_batch.clear();
@@ -372,7 +590,7 @@ impl Pipeline {
                             None => {
                                 // Send remaining events
                                 if !current_batch.is_empty() {
-                                    let _ = batch_tx_clone.send(current_batch);
+                                    let _ = batch_tx_clone.send(current_batch).await;
                                 }
                                 break;
                             }
@@ -380,7 +598,7 @@ impl Pipeline {
                     }
                     _ = tokio::time::sleep(batch_timeout) => {
                         if !current_batch.is_empty() && last_batch_time.elapsed() >= batch_timeout {
-                            if batch_tx_clone.send(current_batch.clone()).is_err() {
+                            if batch_tx_clone.send(current_batch.clone()).await.is_err() {
                                 break;
                             }
                             current_batch.clear();
@@ -399,8 +617,10 @@ impl Pipeline {
             let transformation_manager = transformation_manager.clone();
             let routing_manager = routing_manager.clone();
             let storage_manager = storage_manager.clone();
+            let detection_engine = detection_engine.clone();
             let metrics_collector = metrics_collector.clone();
             let stats = stats.clone();
+            let acks = acks.clone();
             
             let worker_handle = tokio::spawn(async move {
                 info!("Worker {} started", worker_id);
@@ -418,6 +638,7 @@ impl Pipeline {
                     let batch_start = std::time::Instant::now();
                     let mut processed_count = 0u64;
                     let mut failed_count = 0u64;
+                    let mut dropped_count = 0u64;
                     
                     for mut event in batch {
                         let event_id = event.id;
@@ -427,36 +648,51 @@ impl Pipeline {
                             Ok(_) => {
                                 event.processing_stage = ProcessingStage::Normalized;
                             }
+                            Err(_) if event.processing_stage.is_consumed() => {
+                                acknowledge(&acks, event_id);
+                                dropped_count += 1;
+                                continue;
+                            }
                             Err(e) => {
                                 error!("Transformation failed for event {}: {}", event_id, e);
                                 event.processing_stage = ProcessingStage::Failed(e.to_string());
+                                acknowledge(&acks, event_id);
                                 failed_count += 1;
                                 continue;
                             }
                         }
                         
+                        if let Err(e) = detection_engine.evaluate(&event).await {
+                            warn!("Detection failed for event {}: {}", event_id, e);
+                        }
+                        
                         // Route and store event
                         match routing_manager.route_event(&event).await {
                             Ok(destinations) => {
                                 event.processing_stage = ProcessingStage::Routed;
                                 
-                                // Store in parallel to all destinations
+                                // Queue for every destination's next batch
                                 let storage_futures: Vec<_> = destinations.iter()
-                                    .map(|dest| storage_manager.store_event(&event, dest))
+                                    .map(|dest| storage_manager.submit_event(&event, dest))
                                     .collect();
                                 
                                 let storage_results = futures::future::join_all(storage_futures).await;
                                 let mut storage_success = true;
+                                let mut deliveries = Vec::with_capacity(destinations.len());
                                 
                                 for (i, result) in storage_results.into_iter().enumerate() {
-                                    if let Err(e) = result {
-                                        error!("Storage failed for event {} in destination {}: {}", 
-                                               event_id, destinations[i], e);
-                                        storage_success = false;
+                                    match result {
+                                        Ok(delivery) => deliveries.push(delivery),
+                                        Err(e) => {
+                                            error!("Storage failed for event {} in destination {}: {}", 
+                                                   event_id, destinations[i], e);
+                                            storage_success = false;
+                                        }
                                     }
                                 }
                                 
                                 if storage_success {
+                                    acknowledge_when_stored(&acks, event_id, deliveries).await;
                                     event.processing_stage = ProcessingStage::Stored;
                                     processed_count += 1;
                                 } else {
@@ -466,6 +702,7 @@ impl Pipeline {
                             Err(e) => {
                                 error!("Routing failed for event {}: {}", event_id, e);
                                 event.processing_stage = ProcessingStage::Failed(e.to_string());
+                                acknowledge(&acks, event_id);
                                 failed_count += 1;
                             }
                         }
@@ -480,6 +717,7 @@ impl Pipeline {
                         let mut stats_guard = stats.write().await;
                         stats_guard.events_processed += processed_count;
                         stats_guard.events_failed += failed_count;
+                        stats_guard.events_dropped += dropped_count;
                     }
                     
                     let batch_duration = batch_start.elapsed();
@@ -499,6 +737,7 @@ impl Pipeline {
         
         // Stop batching task
         batching_task.abort();
+        drop(batch_tx);
         
         // Wait for all workers to complete
         for handle in worker_handles {
@@ -550,20 +789,59 @@ impl Pipeline {
         self.routing_manager.clone()
     }
     
+    /// Get access to the detection engine
+    pub fn get_detection_engine(&self) -> Arc<DetectionEngine> {
+        self.detection_engine.clone()
+    }
+    
+    /// Attach the alert store used for raised alerts and stored detection rules
+    pub async fn attach_database(&self, database: Arc<DatabaseManager>) -> Result<()> {
+        self.detection_engine.set_database(database).await
+    }
+    
     pub async fn process_event(&self, event: &mut PipelineEvent) -> Result<()> {
-        // Transform the event
-        self.transformation_manager.process_event(event).await?;
+        // Transform the event; filtered and aggregated events stop here without being treated as failures
+        if let Err(e) = self.transformation_manager.process_event(event).await {
+            if event.processing_stage.is_consumed() {
+                let mut stats_guard = self.stats.write().await;
+                stats_guard.events_dropped += 1;
+                return Ok(());
+            }
+            return Err(e);
+        }
+        
+        // Run streaming detections
+        if let Err(e) = self.detection_engine.evaluate(event).await {
+            warn!("Detection failed for event {}: {}", event.id, e);
+        }
         
-        // Route and store the event
+        // Route and store the event, waiting for every destination's batch to be written
         let destinations = self.routing_manager.route_event(event).await?;
         
+        let mut deliveries = Vec::with_capacity(destinations.len());
+        let mut stored = Ok(());
         for destination in destinations {
-            if let Err(e) = self.storage_manager.store_event(event, &destination).await {
-                error!("Failed to store event to destination {}: {}", destination, e);
-                event.processing_stage = ProcessingStage::Failed(e.to_string());
-                return Err(e);
+            match self.storage_manager.submit_event(event, &destination).await {
+                Ok(delivery) => deliveries.push((destination, delivery)),
+                Err(e) => {
+                    stored = Err((destination, e));
+                    break;
+                }
+            }
+        }
+        if stored.is_ok() {
+            for (destination, delivery) in deliveries {
+                if let Err(e) = delivery.wait().await {
+                    stored = Err((destination, e));
+                    break;
+                }
             }
         }
+        if let Err((destination, e)) = stored {
+            error!("Failed to store event to destination {}: {}", destination, e);
+            event.processing_stage = ProcessingStage::Failed(e.to_string());
+            return Err(e);
+        }
         
         event.processing_stage = ProcessingStage::Stored;
         
@@ -592,6 +870,7 @@ impl Pipeline {
         let transformation_health = self.transformation_manager.get_health().await;
         let routing_health = self.routing_manager.get_health().await;
         let storage_health = self.storage_manager.get_health().await;
+        let detection_health = self.detection_engine.get_health().await;
         
         Ok(serde_json::json!({
             "status": health_status,
@@ -606,7 +885,8 @@ impl Pipeline {
                 "ingestion": ingestion_health,
                 "transformation": transformation_health,
                 "routing": routing_health,
-                "storage": storage_health
+                "storage": storage_health,
+                "detection": detection_health
             }
         }))
     }
@@ -649,6 +929,13 @@ impl Pipeline {
     pub async fn shutdown(&self) -> Result<()> {
         info!("Shutting down pipeline");
         
+        // Hand partially filled aggregation windows to the processing loop before it stops
+        for summary in self.transformation_manager.flush_aggregations(true).await {
+            if self.event_tx.send(summary).await.is_err() {
+                break;
+            }
+        }
+        
         if let Err(e) = self.shutdown_tx.send(()).await {
             warn!("Failed to send shutdown signal: {}", e);
         }
@@ -658,6 +945,7 @@ impl Pipeline {
         self.transformation_manager.shutdown().await?;
         self.routing_manager.shutdown().await?;
         self.storage_manager.shutdown().await?;
+        self.detection_engine.shutdown().await?;
         
         info!("Pipeline shutdown complete");
         Ok(())
@@ -674,12 +962,64 @@ impl Pipeline {
         self.transformation_manager.reload_config(&new_config).await?;
         self.routing_manager.reload_config(&new_config).await?;
         self.storage_manager.reload_config(&new_config).await?;
+        self.detection_engine.reload_config(&new_config).await?;
         
         info!("Configuration reloaded successfully");
         Ok(())
     }
 }
 
+/// Releases events from the write-ahead log. Events waiting on destination
+/// writes are acknowledged by a single task in the order they were submitted;
+/// its bounded queue makes workers wait when writes fall behind rather than
+/// leaving a task per in-flight event.
+#[derive(Clone)]
+struct Acknowledger {
+    wal: Arc<WriteAheadLog>,
+    stored_tx: mpsc::Sender<(Uuid, Vec<Delivery>)>,
+}
+
+impl Acknowledger {
+    fn spawn(wal: Arc<WriteAheadLog>, capacity: usize) -> Self {
+        let (stored_tx, mut stored_rx) = mpsc::channel::<(Uuid, Vec<Delivery>)>(capacity.max(1));
+        let task_wal = wal.clone();
+        tokio::spawn(async move {
+            while let Some((event_id, deliveries)) = stored_rx.recv().await {
+                if all_stored(deliveries).await {
+                    task_wal.ack(event_id);
+                }
+            }
+        });
+        Acknowledger { wal, stored_tx }
+    }
+}
+
+// Failed batches are logged by the destination's batcher, once per batch
+async fn all_stored(deliveries: Vec<Delivery>) -> bool {
+    for delivery in deliveries {
+        if delivery.wait().await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+// Release an event from the write-ahead log once nothing more will be done with it
+fn acknowledge(acks: &Option<Acknowledger>, event_id: Uuid) {
+    if let Some(acks) = acks {
+        acks.wal.ack(event_id);
+    }
+}
+
+// Acknowledge an event once every batch it was queued in has been written.
+// Left unacknowledged, it is replayed on restart.
+async fn acknowledge_when_stored(acks: &Option<Acknowledger>, event_id: Uuid, deliveries: Vec<Delivery>) {
+    let Some(acks) = acks else { return };
+    if acks.stored_tx.send((event_id, deliveries)).await.is_err() {
+        warn!("Write-ahead log acknowledgements stopped; event {} will be replayed", event_id);
+    }
+}
+
 impl Clone for PipelineStats {
     fn clone(&self) -> Self {
         Self {
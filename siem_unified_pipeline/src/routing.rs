@@ -1,14 +1,24 @@
 use std::collections::HashMap;
+use std::fmt;
 use std::sync::Arc;
+use futures::future::BoxFuture;
 use tokio::sync::RwLock;
 use tracing::{info, warn, debug};
 use serde::{Deserialize, Serialize};
 use regex::Regex;
 use chrono::{DateTime, Utc};
 
+use crate::balancer::GroupBalancer;
 use crate::config::PipelineConfig;
 use crate::error::{Result, PipelineError};
 use crate::pipeline::PipelineEvent;
+use crate::utils;
+
+/// Metadata key holding comma-separated destinations forced by a transformation filter
+pub const ROUTE_TO_METADATA_KEY: &str = "route_to";
+
+/// Deepest `all`/`any`/`not` nesting accepted from the API
+pub const MAX_CONDITION_DEPTH: usize = 16;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoutingRule {
@@ -18,11 +28,31 @@ pub struct RoutingRule {
     pub enabled: bool,
     pub priority: u32,
     pub conditions: Vec<RoutingCondition>,
+    /// Nested condition tree, ANDed with `conditions` when both are set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition_group: Option<ConditionGroup>,
     pub destinations: Vec<String>,
+    /// Skip lower-priority rules once this rule has routed an event
+    #[serde(default)]
+    pub stop_processing: bool,
+    /// Share of matching events (0-100) this rule routes; `None` routes all
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_percent: Option<f64>,
     pub tags: Vec<String>,
     pub metadata: HashMap<String, String>,
 }
 
+/// Boolean combination of routing conditions, written as `{"all": [...]}`,
+/// `{"any": [...]}`, `{"not": {...}}` or `{"condition": {...}}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionGroup {
+    All(Vec<ConditionGroup>),
+    Any(Vec<ConditionGroup>),
+    Not(Box<ConditionGroup>),
+    Condition(RoutingCondition),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct RoutingCondition {
@@ -50,6 +80,8 @@ pub enum ConditionOperator {
     NotIn,
     Exists,
     NotExists,
+    /// Field is an IP address inside one of the CIDR blocks in `value`
+    Cidr,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,6 +92,9 @@ pub struct RoutingStats {
     pub events_routed: u64,
     pub last_match: Option<DateTime<Utc>>,
     pub destinations_used: HashMap<String, u64>,
+    /// Matches dropped by the rule's `sample_percent`
+    #[serde(default)]
+    pub sampled_out: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,7 +107,7 @@ pub struct DestinationStats {
     pub health_status: DestinationHealth,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum DestinationHealth {
     Healthy,
     Degraded,
@@ -80,12 +115,66 @@ pub enum DestinationHealth {
     Unknown,
 }
 
+/// Result of one condition or condition group in a dry run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConditionTrace {
+    pub description: String,
+    pub passed: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// Value `extract_field_value` produced for `field`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual_value: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ConditionTrace>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleOutcome {
+    Routed,
+    /// Routed, and `stop_processing` ended evaluation
+    RoutedAndStopped,
+    NoMatch,
+    SampledOut,
+    Disabled,
+    /// An earlier rule stopped processing before this one was reached
+    NotReached,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleTrace {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub priority: u32,
+    pub outcome: RuleOutcome,
+    pub conditions: Vec<ConditionTrace>,
+    pub destinations: Vec<String>,
+}
+
+/// Step-by-step account of how `route_event` would route an event
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoutingTrace {
+    pub event_id: uuid::Uuid,
+    /// Destinations forced through the `route_to` metadata key
+    pub forced_destinations: Vec<String>,
+    pub rules: Vec<RuleTrace>,
+    pub used_default: bool,
+    /// Member each destination group resolved to
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub group_selections: HashMap<String, String>,
+    pub destinations: Vec<String>,
+    /// Destinations named by rules but missing from the configuration
+    pub unknown_destinations: Vec<String>,
+}
+
 pub struct RoutingManager {
     config: PipelineConfig,
     rules: Arc<RwLock<Vec<RoutingRule>>>,
     routing_stats: Arc<RwLock<HashMap<String, RoutingStats>>>,
     destination_stats: Arc<RwLock<HashMap<String, DestinationStats>>>,
     compiled_regexes: Arc<RwLock<HashMap<String, Regex>>>,
+    balancer: GroupBalancer,
 }
 
 impl RoutingManager {
@@ -98,6 +187,9 @@ impl RoutingManager {
             routing_stats: Arc::new(RwLock::new(HashMap::new())),
             destination_stats: Arc::new(RwLock::new(HashMap::new())),
             compiled_regexes: Arc::new(RwLock::new(HashMap::new())),
+            balancer: GroupBalancer::new(&config.routing, |member| {
+                config.destinations.get(member).is_some_and(|dest| dest.enabled)
+            }),
         };
         
         // Initialize destination stats
@@ -143,7 +235,10 @@ impl RoutingManager {
                         case_sensitive: false,
                     },
                 ],
+                condition_group: None,
                 destinations: vec!["clickhouse_security".to_string()],
+                stop_processing: false,
+                sample_percent: None,
                 tags: vec!["security".to_string(), "high_priority".to_string()],
                 metadata: HashMap::new(),
             },
@@ -161,7 +256,10 @@ impl RoutingManager {
                         case_sensitive: false,
                     },
                 ],
+                condition_group: None,
                 destinations: vec!["clickhouse_logs".to_string()],
+                stop_processing: false,
+                sample_percent: None,
                 tags: vec!["application".to_string()],
                 metadata: HashMap::new(),
             },
@@ -179,7 +277,10 @@ impl RoutingManager {
                         case_sensitive: false,
                     },
                 ],
+                condition_group: None,
                 destinations: vec!["clickhouse_network".to_string(), "kafka_network".to_string()],
+                stop_processing: false,
+                sample_percent: None,
                 tags: vec!["network".to_string()],
                 metadata: HashMap::new(),
             },
@@ -190,7 +291,10 @@ impl RoutingManager {
                 enabled: true,
                 priority: 1,
                 conditions: vec![], // No conditions = matches all
+                condition_group: None,
                 destinations: vec!["clickhouse_default".to_string()],
+                stop_processing: false,
+                sample_percent: None,
                 tags: vec!["default".to_string()],
                 metadata: HashMap::new(),
             },
@@ -209,6 +313,7 @@ impl RoutingManager {
                 events_routed: 0,
                 last_match: None,
                 destinations_used: HashMap::new(),
+                sampled_out: 0,
             });
         }
         
@@ -223,7 +328,17 @@ impl RoutingManager {
         let mut matched_destinations = Vec::new();
         let mut rule_matched = false;
         
-        // Sort rules by priority (higher priority first)
+        // Destinations chosen by a filter `route` action are always included
+        if let Some(routes) = event.metadata.get(ROUTE_TO_METADATA_KEY) {
+            for dest in routes.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+                if !matched_destinations.iter().any(|d: &String| d == dest) {
+                    matched_destinations.push(dest.to_string());
+                }
+            }
+            rule_matched = !matched_destinations.is_empty();
+        }
+        
+        // Sort rules by priority (higher priority first); ties keep definition order
         let mut sorted_rules: Vec<_> = rules.iter().collect();
         sorted_rules.sort_by(|a, b| b.priority.cmp(&a.priority));
         
@@ -233,6 +348,12 @@ impl RoutingManager {
             }
             
             if self.evaluate_rule(rule, event).await? {
+                if !sampled_in(rule, event) {
+                    debug!("Event {} matched rule {} but was sampled out", event.id, rule.name);
+                    self.record_sampled_out(&rule.id).await;
+                    continue;
+                }
+                
                 debug!("Event {} matched rule: {}", event.id, rule.name);
                 
                 // Add destinations from this rule
@@ -247,8 +368,10 @@ impl RoutingManager {
                 
                 rule_matched = true;
                 
-                // If this is a high-priority rule, we might want to stop here
-                // For now, we continue to allow multiple rules to match
+                if rule.stop_processing {
+                    debug!("Rule {} stops further routing for event {}", rule.name, event.id);
+                    break;
+                }
             }
         }
         
@@ -260,6 +383,8 @@ impl RoutingManager {
             }
         }
         
+        let (matched_destinations, _) = self.resolve_groups(matched_destinations, event, true).await?;
+        
         // Validate destinations exist
         let valid_destinations = self.validate_destinations(&matched_destinations).await?;
         
@@ -269,18 +394,217 @@ impl RoutingManager {
         Ok(valid_destinations)
     }
     
-    async fn evaluate_rule(&self, rule: &RoutingRule, event: &PipelineEvent) -> Result<bool> {
-        if rule.conditions.is_empty() {
-            return Ok(true); // No conditions = always match
+    /// Routes `event` against `rules` (the live rule set when `None`) without
+    /// touching statistics, recording why each rule did or did not fire.
+    /// Conditions of disabled and unreached rules are still evaluated so the
+    /// trace shows what they would have done.
+    pub async fn trace_event(&self, event: &PipelineEvent, rules: Option<&[RoutingRule]>) -> Result<RoutingTrace> {
+        let mut sorted_rules = match rules {
+            Some(rules) => rules.to_vec(),
+            None => self.get_rules().await,
+        };
+        sorted_rules.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+        
+        let forced_destinations: Vec<String> = event.metadata.get(ROUTE_TO_METADATA_KEY)
+            .map(|routes| routes.split(',').map(str::trim).filter(|d| !d.is_empty()).map(String::from).collect())
+            .unwrap_or_default();
+        let mut matched_destinations: Vec<String> = Vec::new();
+        for dest in &forced_destinations {
+            if !matched_destinations.contains(dest) {
+                matched_destinations.push(dest.clone());
+            }
         }
+        let mut rule_matched = !matched_destinations.is_empty();
+        let mut stopped = false;
+        let mut rule_traces = Vec::with_capacity(sorted_rules.len());
         
+        for rule in &sorted_rules {
+            let mut conditions = Vec::with_capacity(rule.conditions.len() + 1);
+            for condition in &rule.conditions {
+                conditions.push(self.trace_condition(condition, event).await?);
+            }
+            if let Some(group) = &rule.condition_group {
+                conditions.push(self.trace_group(group, event).await?);
+            }
+            let matches = conditions.iter().all(|c| c.passed);
+            
+            let outcome = if stopped {
+                RuleOutcome::NotReached
+            } else if !rule.enabled {
+                RuleOutcome::Disabled
+            } else if !matches {
+                RuleOutcome::NoMatch
+            } else if !sampled_in(rule, event) {
+                RuleOutcome::SampledOut
+            } else {
+                for dest in &rule.destinations {
+                    if !matched_destinations.contains(dest) {
+                        matched_destinations.push(dest.clone());
+                    }
+                }
+                rule_matched = true;
+                if rule.stop_processing {
+                    stopped = true;
+                    RuleOutcome::RoutedAndStopped
+                } else {
+                    RuleOutcome::Routed
+                }
+            };
+            
+            rule_traces.push(RuleTrace {
+                rule_id: rule.id.clone(),
+                rule_name: rule.name.clone(),
+                priority: rule.priority,
+                outcome,
+                conditions,
+                destinations: rule.destinations.clone(),
+            });
+        }
+        
+        let used_default = !rule_matched;
+        if used_default {
+            if let Some(default_dest) = self.get_default_destination().await {
+                matched_destinations.push(default_dest);
+            }
+        }
+        
+        let (matched_destinations, group_selections) = self.resolve_groups(matched_destinations, event, false).await?;
+        let destinations = self.validate_destinations(&matched_destinations).await?;
+        let unknown_destinations = matched_destinations.into_iter()
+            .filter(|dest| !destinations.contains(dest))
+            .collect();
+        
+        Ok(RoutingTrace {
+            event_id: event.id,
+            forced_destinations,
+            rules: rule_traces,
+            used_default,
+            group_selections,
+            destinations,
+            unknown_destinations,
+        })
+    }
+    
+    /// Replaces destination group names with the member each group picks for
+    /// this event, skipping members whose health is `Unhealthy`
+    async fn resolve_groups(
+        &self,
+        destinations: Vec<String>,
+        event: &PipelineEvent,
+        advance: bool,
+    ) -> Result<(Vec<String>, HashMap<String, String>)> {
+        let mut selections = HashMap::new();
+        if !destinations.iter().any(|dest| self.balancer.contains(dest)) {
+            return Ok((destinations, selections));
+        }
+        
+        let health = self.destination_stats.read().await;
+        let available = |member: &str| !matches!(
+            health.get(member),
+            Some(stats) if stats.health_status == DestinationHealth::Unhealthy
+        );
+        
+        let mut resolved: Vec<String> = Vec::with_capacity(destinations.len());
+        for dest in destinations {
+            let key = match self.balancer.hash_field(&dest) {
+                Some(field) => match self.extract_field_value(field, event)? {
+                    serde_json::Value::String(value) => Some(value),
+                    serde_json::Value::Null => None,
+                    other => Some(other.to_string()),
+                },
+                None => None,
+            };
+            let target = match self.balancer.select(&dest, key.as_deref(), available, advance) {
+                Some(member) => {
+                    selections.insert(dest, member.to_string());
+                    member.to_string()
+                }
+                None => dest,
+            };
+            if !resolved.contains(&target) {
+                resolved.push(target);
+            }
+        }
+        
+        Ok((resolved, selections))
+    }
+    
+    async fn trace_condition(&self, condition: &RoutingCondition, event: &PipelineEvent) -> Result<ConditionTrace> {
+        Ok(ConditionTrace {
+            description: condition.to_string(),
+            passed: self.evaluate_condition(condition, event).await?,
+            field: Some(condition.field.clone()),
+            actual_value: Some(self.extract_field_value(&condition.field, event)?),
+            children: Vec::new(),
+        })
+    }
+    
+    /// Unlike `evaluate_group` this visits every child so the trace is complete
+    fn trace_group<'a>(&'a self, group: &'a ConditionGroup, event: &'a PipelineEvent) -> BoxFuture<'a, Result<ConditionTrace>> {
+        Box::pin(async move {
+            let children = match group {
+                ConditionGroup::All(children) | ConditionGroup::Any(children) => children.iter().collect(),
+                ConditionGroup::Not(inner) => vec![inner.as_ref()],
+                ConditionGroup::Condition(condition) => return self.trace_condition(condition, event).await,
+            };
+            
+            let mut traces = Vec::with_capacity(children.len());
+            for child in children {
+                traces.push(self.trace_group(child, event).await?);
+            }
+            let passed = match group {
+                ConditionGroup::All(_) => traces.iter().all(|t| t.passed),
+                ConditionGroup::Any(_) => traces.iter().any(|t| t.passed),
+                _ => !traces[0].passed,
+            };
+            
+            Ok(ConditionTrace {
+                description: group.to_string(),
+                passed,
+                field: None,
+                actual_value: None,
+                children: traces,
+            })
+        })
+    }
+    
+    async fn evaluate_rule(&self, rule: &RoutingRule, event: &PipelineEvent) -> Result<bool> {
+        // No conditions = always match
         for condition in &rule.conditions {
             if !self.evaluate_condition(condition, event).await? {
                 return Ok(false); // All conditions must match (AND logic)
             }
         }
         
-        Ok(true)
+        match &rule.condition_group {
+            Some(group) => self.evaluate_group(group, event).await,
+            None => Ok(true),
+        }
+    }
+    
+    fn evaluate_group<'a>(&'a self, group: &'a ConditionGroup, event: &'a PipelineEvent) -> BoxFuture<'a, Result<bool>> {
+        Box::pin(async move {
+            match group {
+                ConditionGroup::All(children) => {
+                    for child in children {
+                        if !self.evaluate_group(child, event).await? {
+                            return Ok(false);
+                        }
+                    }
+                    Ok(true)
+                }
+                ConditionGroup::Any(children) => {
+                    for child in children {
+                        if self.evaluate_group(child, event).await? {
+                            return Ok(true);
+                        }
+                    }
+                    Ok(false)
+                }
+                ConditionGroup::Not(inner) => Ok(!self.evaluate_group(inner, event).await?),
+                ConditionGroup::Condition(condition) => self.evaluate_condition(condition, event).await,
+            }
+        })
     }
     
     async fn evaluate_condition(&self, condition: &RoutingCondition, event: &PipelineEvent) -> Result<bool> {
@@ -371,6 +695,11 @@ impl RoutingManager {
             ConditionOperator::NotExists => {
                 Ok(field_value.is_null())
             }
+            ConditionOperator::Cidr => {
+                let Some(addr) = field_value.as_str() else { return Ok(false) };
+                Ok(cidr_values(&condition.value)
+                    .any(|block| utils::ip_in_cidr(addr, block).unwrap_or(false)))
+            }
         }
     }
     
@@ -479,6 +808,13 @@ impl RoutingManager {
         self.config.destinations.keys().next().cloned()
     }
     
+    async fn record_sampled_out(&self, rule_id: &str) {
+        let mut stats_guard = self.routing_stats.write().await;
+        if let Some(stats) = stats_guard.get_mut(rule_id) {
+            stats.sampled_out += 1;
+        }
+    }
+    
     async fn update_rule_stats(&self, rule_id: &str, destinations: &[String]) {
         let mut stats_guard = self.routing_stats.write().await;
         if let Some(stats) = stats_guard.get_mut(rule_id) {
@@ -515,6 +851,7 @@ impl RoutingManager {
                 events_routed: 0,
                 last_match: None,
                 destinations_used: HashMap::new(),
+                sampled_out: 0,
             });
         }
         
@@ -562,7 +899,7 @@ impl RoutingManager {
         }
     }
     
-    fn validate_rule(&self, rule: &RoutingRule) -> Result<()> {
+    pub fn validate_rule(&self, rule: &RoutingRule) -> Result<()> {
         if rule.id.is_empty() {
             return Err(PipelineError::validation("Rule ID cannot be empty"));
         }
@@ -577,24 +914,35 @@ impl RoutingManager {
         
         // Validate destinations exist
         for dest in &rule.destinations {
-            if !self.config.destinations.contains_key(dest) {
+            if !self.config.destinations.contains_key(dest) && !self.balancer.contains(dest) {
                 return Err(PipelineError::validation(format!("Destination '{}' does not exist", dest)));
             }
         }
         
-        // Validate regex patterns in conditions
-        for condition in &rule.conditions {
-            if condition.operator == ConditionOperator::Regex {
-                if let Some(pattern) = condition.value.as_str() {
-                    Regex::new(pattern)
-                        .map_err(|e| PipelineError::validation(format!("Invalid regex pattern '{}': {}", pattern, e)))?;
-                }
+        if let Some(percent) = rule.sample_percent {
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(PipelineError::validation(format!("Sample percent must be between 0 and 100, got {}", percent)));
             }
         }
         
+        for condition in &rule.conditions {
+            validate_condition(condition)?;
+        }
+        
+        if let Some(group) = &rule.condition_group {
+            validate_group(group, 1)?;
+        }
+        
         Ok(())
     }
     
+    /// Rules in the order `route_event` evaluates them
+    pub async fn get_rules_in_evaluation_order(&self) -> Vec<RoutingRule> {
+        let mut rules = self.get_rules().await;
+        rules.sort_by_key(|rule| std::cmp::Reverse(rule.priority));
+        rules
+    }
+    
     pub async fn load_rules(&self, rules_path: &str) -> Result<()> {
         info!("Loading routing rules from: {}", rules_path);
         
@@ -755,4 +1103,336 @@ impl PartialEq for ConditionOperator {
     fn eq(&self, other: &Self) -> bool {
         std::mem::discriminant(self) == std::mem::discriminant(other)
     }
+}
+
+fn validate_condition(condition: &RoutingCondition) -> Result<()> {
+    if condition.field.is_empty() {
+        return Err(PipelineError::validation("Condition field cannot be empty"));
+    }
+    
+    match condition.operator {
+        ConditionOperator::Regex => {
+            if let Some(pattern) = condition.value.as_str() {
+                Regex::new(pattern)
+                    .map_err(|e| PipelineError::validation(format!("Invalid regex pattern '{}': {}", pattern, e)))?;
+            }
+        }
+        ConditionOperator::In | ConditionOperator::NotIn if !condition.value.is_array() => {
+            return Err(PipelineError::validation(format!("Operator {:?} on '{}' needs an array value", condition.operator, condition.field)));
+        }
+        ConditionOperator::Cidr => {
+            let mut blocks = cidr_values(&condition.value).peekable();
+            if blocks.peek().is_none() {
+                return Err(PipelineError::validation(format!("CIDR condition on '{}' needs a block or list of blocks", condition.field)));
+            }
+            for block in blocks {
+                utils::parse_cidr(block)?;
+            }
+        }
+        _ => {}
+    }
+    
+    Ok(())
+}
+
+fn validate_group(group: &ConditionGroup, depth: usize) -> Result<()> {
+    if depth > MAX_CONDITION_DEPTH {
+        return Err(PipelineError::validation(format!("Condition groups nest deeper than {} levels", MAX_CONDITION_DEPTH)));
+    }
+    
+    match group {
+        ConditionGroup::All(children) | ConditionGroup::Any(children) => {
+            // An empty `any` never matches, which is almost certainly a mistake
+            if children.is_empty() && matches!(group, ConditionGroup::Any(_)) {
+                return Err(PipelineError::validation("'any' group must contain at least one condition"));
+            }
+            for child in children {
+                validate_group(child, depth + 1)?;
+            }
+            Ok(())
+        }
+        ConditionGroup::Not(inner) => validate_group(inner, depth + 1),
+        ConditionGroup::Condition(condition) => validate_condition(condition),
+    }
+}
+
+/// Hashes rule and event IDs so a given event always gets the same sampling
+/// decision from a rule, while different rules sample independently
+fn sampled_in(rule: &RoutingRule, event: &PipelineEvent) -> bool {
+    match rule.sample_percent {
+        None => true,
+        Some(percent) if percent >= 100.0 => true,
+        Some(percent) if percent <= 0.0 => false,
+        Some(percent) => {
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(rule.id.as_bytes());
+            hasher.update(event.id.as_bytes());
+            f64::from(hasher.finalize() % 10_000) < percent * 100.0
+        }
+    }
+}
+
+fn cidr_values(value: &serde_json::Value) -> impl Iterator<Item = &str> {
+    let values: Vec<&str> = match value {
+        serde_json::Value::String(s) => vec![s.as_str()],
+        serde_json::Value::Array(items) => items.iter().filter_map(|v| v.as_str()).collect(),
+        _ => Vec::new(),
+    };
+    values.into_iter()
+}
+
+impl fmt::Display for RoutingCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.operator {
+            ConditionOperator::Equals => "==",
+            ConditionOperator::NotEquals => "!=",
+            ConditionOperator::Contains => "contains",
+            ConditionOperator::NotContains => "does not contain",
+            ConditionOperator::StartsWith => "starts with",
+            ConditionOperator::EndsWith => "ends with",
+            ConditionOperator::Regex => "matches",
+            ConditionOperator::GreaterThan => ">",
+            ConditionOperator::LessThan => "<",
+            ConditionOperator::GreaterThanOrEqual => ">=",
+            ConditionOperator::LessThanOrEqual => "<=",
+            ConditionOperator::In => "in",
+            ConditionOperator::NotIn => "not in",
+            ConditionOperator::Exists => return write!(f, "{} exists", self.field),
+            ConditionOperator::NotExists => return write!(f, "{} does not exist", self.field),
+            ConditionOperator::Cidr => "in network",
+        };
+        write!(f, "{} {} {}", self.field, op, self.value)?;
+        if self.case_sensitive {
+            write!(f, " (case-sensitive)")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConditionGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (children, joiner) = match self {
+            ConditionGroup::All(children) if children.is_empty() => return write!(f, "true"),
+            ConditionGroup::Any(children) if children.is_empty() => return write!(f, "false"),
+            ConditionGroup::All(children) => (children, " AND "),
+            ConditionGroup::Any(children) => (children, " OR "),
+            ConditionGroup::Not(inner) => return write!(f, "NOT ({})", inner),
+            ConditionGroup::Condition(condition) => return write!(f, "{}", condition),
+        };
+        if children.len() == 1 {
+            return write!(f, "{}", children[0]);
+        }
+        for (i, child) in children.iter().enumerate() {
+            if i > 0 {
+                f.write_str(joiner)?;
+            }
+            match child {
+                ConditionGroup::All(c) | ConditionGroup::Any(c) if c.len() > 1 => write!(f, "({})", child)?,
+                _ => write!(f, "{}", child)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RoutingRule {
+    /// Human-readable summary of when and where this rule routes events
+    pub fn explain(&self) -> String {
+        let mut clauses: Vec<ConditionGroup> = self.conditions.iter()
+            .cloned()
+            .map(ConditionGroup::Condition)
+            .collect();
+        if let Some(group) = &self.condition_group {
+            clauses.push(group.clone());
+        }
+        
+        let mut explanation = if clauses.is_empty() {
+            "Matches every event".to_string()
+        } else {
+            format!("Matches when {}", ConditionGroup::All(clauses))
+        };
+        
+        match self.sample_percent {
+            Some(percent) if percent < 100.0 => {
+                explanation.push_str(&format!("; routes {}% of matches", percent));
+            }
+            _ => {}
+        }
+        explanation.push_str(&format!(" to [{}]", self.destinations.join(", ")));
+        if self.stop_processing {
+            explanation.push_str(", then skips lower-priority rules");
+        }
+        if !self.enabled {
+            explanation.push_str(" (disabled)");
+        }
+        explanation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn condition(field: &str, operator: ConditionOperator, value: serde_json::Value) -> ConditionGroup {
+        ConditionGroup::Condition(RoutingCondition {
+            field: field.to_string(),
+            operator,
+            value,
+            case_sensitive: false,
+        })
+    }
+    
+    fn rule(condition_group: ConditionGroup) -> RoutingRule {
+        RoutingRule {
+            id: "r1".to_string(),
+            name: "r1".to_string(),
+            description: String::new(),
+            enabled: true,
+            priority: 10,
+            conditions: vec![],
+            condition_group: Some(condition_group),
+            destinations: vec!["clickhouse".to_string()],
+            stop_processing: false,
+            sample_percent: None,
+            tags: vec![],
+            metadata: HashMap::new(),
+        }
+    }
+    
+    fn event(data: serde_json::Value) -> PipelineEvent {
+        PipelineEvent {
+            id: uuid::Uuid::new_v4(),
+            timestamp: Utc::now(),
+            source: "test".to_string(),
+            data,
+            metadata: HashMap::new(),
+            processing_stage: crate::pipeline::ProcessingStage::Parsed,
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_nested_condition_groups() {
+        let manager = RoutingManager::new(&PipelineConfig::default()).await.unwrap();
+        let group = ConditionGroup::All(vec![
+            condition("src_ip", ConditionOperator::Cidr, serde_json::json!(["10.0.0.0/8", "192.168.0.0/16"])),
+            ConditionGroup::Any(vec![
+                condition("severity", ConditionOperator::Equals, serde_json::json!("critical")),
+                condition("protocol", ConditionOperator::In, serde_json::json!(["ssh", "rdp"])),
+            ]),
+            ConditionGroup::Not(Box::new(condition("user", ConditionOperator::Equals, serde_json::json!("scanner")))),
+        ]);
+        let rule = rule(group);
+        let hit = event(serde_json::json!({"src_ip": "10.4.4.4", "protocol": "SSH", "user": "alice"}));
+        let wrong_net = event(serde_json::json!({"src_ip": "172.16.0.1", "protocol": "ssh", "user": "alice"}));
+        let negated = event(serde_json::json!({"src_ip": "192.168.1.1", "severity": "critical", "user": "scanner"}));
+        assert!(manager.evaluate_rule(&rule, &hit).await.unwrap());
+        assert!(!manager.evaluate_rule(&rule, &wrong_net).await.unwrap());
+        assert!(!manager.evaluate_rule(&rule, &negated).await.unwrap());
+        
+        assert_eq!(
+            rule.explain(),
+            "Matches when src_ip in network [\"10.0.0.0/8\",\"192.168.0.0/16\"] AND (severity == \"critical\" OR protocol in [\"ssh\",\"rdp\"]) AND NOT (user == \"scanner\") to [clickhouse]"
+        );
+    }
+    
+    #[tokio::test]
+    async fn test_trace_matches_route_event() {
+        let destination: crate::config::DataDestination = serde_json::from_value(serde_json::json!({
+            "destination_type": { "type": "http", "endpoint": "http://localhost:9", "method": "POST", "headers": {} },
+            "config": { "format": "json", "compression": null, "partitioning": null },
+            "enabled": true,
+            "batch_size": 1,
+            "flush_interval": 1,
+            "retry_attempts": 0,
+        })).unwrap();
+        let mut config = PipelineConfig::default();
+        config.destinations.insert("archive".to_string(), destination.clone());
+        config.destinations.insert("network".to_string(), destination);
+        let manager = RoutingManager::new(&config).await.unwrap();
+        
+        let mut stop = rule(condition("src_ip", ConditionOperator::Cidr, serde_json::json!("10.0.0.0/8")));
+        stop.id = "internal".to_string();
+        stop.priority = 500;
+        stop.stop_processing = true;
+        stop.destinations = vec!["network".to_string()];
+        let mut all = rule(ConditionGroup::All(vec![]));
+        all.id = "everything".to_string();
+        all.destinations = vec!["archive".to_string(), "missing".to_string()];
+        let proposed = vec![all, stop];
+        
+        let internal = event(serde_json::json!({"src_ip": "10.9.8.7"}));
+        let trace = manager.trace_event(&internal, Some(&proposed)).await.unwrap();
+        assert_eq!(trace.destinations, vec!["network".to_string()]);
+        assert_eq!(trace.rules[0].outcome, RuleOutcome::RoutedAndStopped);
+        assert_eq!(trace.rules[0].conditions[0].actual_value, Some(serde_json::json!("10.9.8.7")));
+        assert_eq!(trace.rules[1].outcome, RuleOutcome::NotReached);
+        
+        let external = event(serde_json::json!({"src_ip": "203.0.113.5"}));
+        let trace = manager.trace_event(&external, Some(&proposed)).await.unwrap();
+        assert_eq!(trace.rules[0].outcome, RuleOutcome::NoMatch);
+        assert_eq!(trace.destinations, vec!["archive".to_string()]);
+        assert_eq!(trace.unknown_destinations, vec!["missing".to_string()]);
+        
+        // With no proposal the live rules are traced and agree with route_event
+        let trace = manager.trace_event(&external, None).await.unwrap();
+        assert_eq!(trace.destinations, manager.route_event(&external).await.unwrap());
+    }
+    
+    #[tokio::test]
+    async fn test_destination_group_failover() {
+        let destination: crate::config::DataDestination = serde_json::from_value(serde_json::json!({
+            "destination_type": { "type": "http", "endpoint": "http://localhost:9", "method": "POST", "headers": {} },
+            "config": { "format": "json", "compression": null, "partitioning": null },
+            "enabled": true,
+            "batch_size": 1,
+            "flush_interval": 1,
+            "retry_attempts": 0,
+        })).unwrap();
+        let mut config = PipelineConfig::default();
+        config.destinations.insert("ch_primary".to_string(), destination.clone());
+        config.destinations.insert("ch_standby".to_string(), destination);
+        config.routing.destination_groups.insert("clickhouse".to_string(), crate::config::DestinationGroup {
+            members: vec!["ch_primary".to_string(), "ch_standby".to_string()],
+            strategy: Some(crate::config::LoadBalancingStrategy::Failover),
+        });
+        let manager = RoutingManager::new(&config).await.unwrap();
+        // Rules name the group like any other destination
+        let rule = rule(ConditionGroup::All(vec![]));
+        manager.validate_rule(&rule).unwrap();
+        let rules = vec![rule];
+        
+        let e = event(serde_json::json!({}));
+        let trace = manager.trace_event(&e, Some(&rules)).await.unwrap();
+        assert_eq!(trace.destinations, vec!["ch_primary".to_string()]);
+        assert_eq!(trace.group_selections.get("clickhouse").map(String::as_str), Some("ch_primary"));
+        
+        manager.update_destination_health("ch_primary", DestinationHealth::Unhealthy).await;
+        let trace = manager.trace_event(&e, Some(&rules)).await.unwrap();
+        assert_eq!(trace.destinations, vec!["ch_standby".to_string()]);
+        
+        manager.update_destination_health("ch_primary", DestinationHealth::Healthy).await;
+        let trace = manager.trace_event(&e, Some(&rules)).await.unwrap();
+        assert_eq!(trace.destinations, vec!["ch_primary".to_string()]);
+    }
+    
+    #[test]
+    fn test_group_validation_and_sampling() {
+        let bad_cidr = condition("ip", ConditionOperator::Cidr, serde_json::json!("10.0.0.0/40"));
+        assert!(validate_group(&ConditionGroup::Not(Box::new(bad_cidr)), 1).is_err());
+        assert!(validate_group(&ConditionGroup::Any(vec![]), 1).is_err());
+        
+        let mut deep = condition("a", ConditionOperator::Exists, serde_json::Value::Null);
+        for _ in 0..MAX_CONDITION_DEPTH {
+            deep = ConditionGroup::Not(Box::new(deep));
+        }
+        assert!(validate_group(&deep, 1).is_err());
+        
+        let mut sampled = rule(ConditionGroup::All(vec![]));
+        sampled.sample_percent = Some(25.0);
+        let events: Vec<_> = (0..2000).map(|_| event(serde_json::json!({}))).collect();
+        let kept = events.iter().filter(|e| sampled_in(&sampled, e)).count();
+        assert!((350..650).contains(&kept), "kept {} of 2000", kept);
+        // Decisions are stable per event
+        assert!(events.iter().all(|e| sampled_in(&sampled, e) == sampled_in(&sampled, e)));
+    }
 }
\ No newline at end of file
@@ -0,0 +1,270 @@
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{info, warn};
+
+use crate::config::CompressionType;
+use crate::error::{Result, PipelineError};
+use crate::utils;
+
+// Decompressed objects larger than this are rejected rather than held in memory
+const MAX_OBJECT_SIZE: usize = 512 * 1024 * 1024;
+
+/// Keys and ETags of objects that have been fully ingested.
+pub struct ObjectLedger {
+    path: PathBuf,
+    objects: HashMap<String, String>,
+    dirty: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LedgerFile {
+    objects: HashMap<String, String>,
+}
+
+impl ObjectLedger {
+    pub async fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let objects = match tokio::fs::read(&path).await {
+            Ok(content) => match serde_json::from_slice::<LedgerFile>(&content) {
+                Ok(ledger) => {
+                    info!("Loaded {} processed S3 objects from {}", ledger.objects.len(), path.display());
+                    ledger.objects
+                }
+                Err(e) => {
+                    warn!("Ignoring unreadable S3 checkpoint file {}: {}", path.display(), e);
+                    HashMap::new()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => {
+                warn!("Failed to read S3 checkpoint file {}: {}", path.display(), e);
+                HashMap::new()
+            }
+        };
+
+        Self { path, objects, dirty: false }
+    }
+
+    /// True when this exact version of the object was already ingested; a
+    /// rewritten object gets a new ETag and is read again.
+    pub fn is_processed(&self, key: &str, etag: &str) -> bool {
+        self.objects.get(key).map(|seen| seen == etag).unwrap_or(false)
+    }
+
+    pub fn record(&mut self, key: &str, etag: &str) {
+        self.objects.insert(key.to_string(), etag.to_string());
+        self.dirty = true;
+    }
+
+    /// Forget objects that are no longer listed, e.g. after lifecycle expiry.
+    pub fn retain_listed(&mut self, listed: &HashSet<String>) {
+        let before = self.objects.len();
+        self.objects.retain(|key, _| listed.contains(key));
+        self.dirty |= self.objects.len() != before;
+    }
+
+    pub async fn save(&mut self) -> Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let content = serde_json::to_vec(&LedgerFile { objects: self.objects.clone() })?;
+        utils::write_file_atomic(&self.path, &content).await?;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+/// Undo the object's compression. The configured type wins; otherwise it is
+/// inferred from the key's extension.
+pub fn decompress(data: Vec<u8>, compression: Option<&CompressionType>, key: &str) -> Result<Vec<u8>> {
+    let compression = compression.cloned().or_else(|| {
+        let lower = key.to_lowercase();
+        if lower.ends_with(".gz") || lower.ends_with(".gzip") {
+            Some(CompressionType::Gzip)
+        } else if lower.ends_with(".zst") || lower.ends_with(".zstd") {
+            Some(CompressionType::Zstd)
+        } else if lower.ends_with(".lz4") {
+            Some(CompressionType::Lz4)
+        } else {
+            None
+        }
+    });
+
+    let limit = MAX_OBJECT_SIZE as u64 + 1;
+    let mut output = Vec::new();
+    let result = match compression {
+        None => return Ok(data),
+        Some(CompressionType::Gzip) => {
+            flate2::read::MultiGzDecoder::new(data.as_slice()).take(limit).read_to_end(&mut output)
+        }
+        Some(CompressionType::Zstd) => zstd::stream::read::Decoder::new(data.as_slice())
+            .and_then(|decoder| decoder.take(limit).read_to_end(&mut output)),
+        Some(CompressionType::Lz4) => {
+            lz4_flex::frame::FrameDecoder::new(data.as_slice()).take(limit).read_to_end(&mut output)
+        }
+        Some(CompressionType::Snappy) => {
+            return Err(PipelineError::compression("Snappy compressed objects are not supported"));
+        }
+    };
+
+    result.map_err(|e| PipelineError::compression(format!("Failed to decompress {}: {}", key, e)))?;
+    if output.len() > MAX_OBJECT_SIZE {
+        return Err(PipelineError::compression(format!(
+            "{} decompresses to more than {} bytes",
+            key, MAX_OBJECT_SIZE
+        )));
+    }
+    Ok(output)
+}
+
+/// Split an object into raw event messages: the elements of a JSON array,
+/// the `Records` array of a CloudTrail-style document, or non-empty lines.
+pub fn split_records(content: &[u8]) -> Vec<String> {
+    let text = String::from_utf8_lossy(content);
+    let trimmed = text.trim_start_matches('\u{feff}').trim();
+
+    if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        match serde_json::from_str::<Value>(trimmed) {
+            Ok(Value::Array(items)) => return items.iter().map(Value::to_string).collect(),
+            Ok(Value::Object(mut object)) => {
+                if let Some(Value::Array(records)) = object.remove("Records") {
+                    return records.iter().map(Value::to_string).collect();
+                }
+                return vec![Value::Object(object).to_string()];
+            }
+            // Not a single document, e.g. newline-delimited JSON
+            _ => {}
+        }
+    }
+
+    trimmed.lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim().is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Object listed under the source prefix.
+#[derive(Debug, Clone)]
+pub struct S3Object {
+    pub key: String,
+    pub etag: String,
+    pub size: i64,
+}
+
+/// Lists and downloads objects under one bucket prefix.
+#[cfg(feature = "aws")]
+pub struct S3ObjectSource {
+    client: aws_sdk_s3::Client,
+    bucket: String,
+    prefix: String,
+}
+
+#[cfg(feature = "aws")]
+impl S3ObjectSource {
+    /// Credentials come from the default AWS chain. An `endpoint` switches to
+    /// path-style addressing, which S3 stand-ins such as MinIO expect.
+    pub async fn connect(bucket: &str, prefix: &str, region: &str, endpoint: Option<&str>) -> Self {
+        let shared = aws_config::defaults(aws_config::BehaviorVersion::latest())
+            .region(aws_sdk_s3::config::Region::new(region.to_string()))
+            .load()
+            .await;
+
+        let mut builder = aws_sdk_s3::config::Builder::from(&shared);
+        if let Some(endpoint) = endpoint {
+            builder = builder.endpoint_url(endpoint).force_path_style(true);
+        }
+
+        Self {
+            client: aws_sdk_s3::Client::from_conf(builder.build()),
+            bucket: bucket.to_string(),
+            prefix: prefix.to_string(),
+        }
+    }
+
+    pub async fn list(&self) -> Result<Vec<S3Object>> {
+        let mut objects = Vec::new();
+        let mut pages = self.client.list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(&self.prefix)
+            .into_paginator()
+            .send();
+
+        while let Some(page) = pages.next().await {
+            let page = page.map_err(|e| {
+                PipelineError::storage(format!("Failed to list s3://{}/{}: {}", self.bucket, self.prefix, e))
+            })?;
+            for object in page.contents() {
+                let (Some(key), Some(etag)) = (object.key(), object.e_tag()) else { continue };
+                // Directory placeholders created by consoles
+                if key.ends_with('/') {
+                    continue;
+                }
+                objects.push(S3Object {
+                    key: key.to_string(),
+                    etag: etag.trim_matches('"').to_string(),
+                    size: object.size().unwrap_or_default(),
+                });
+            }
+        }
+        Ok(objects)
+    }
+
+    pub async fn fetch(&self, key: &str) -> Result<Vec<u8>> {
+        let object = self.client.get_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(|e| PipelineError::storage(format!("Failed to get s3://{}/{}: {}", self.bucket, key, e)))?;
+
+        let body = object.body.collect().await
+            .map_err(|e| PipelineError::storage(format!("Failed to read s3://{}/{}: {}", self.bucket, key, e)))?;
+        Ok(body.into_bytes().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_decompress_by_config_and_extension() {
+        let content = b"line one\nline two\n".to_vec();
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&content).unwrap();
+        let gzipped = gz.finish().unwrap();
+        assert_eq!(decompress(gzipped.clone(), None, "logs/a.json.gz").unwrap(), content);
+        assert_eq!(decompress(gzipped, Some(&CompressionType::Gzip), "logs/a").unwrap(), content);
+
+        let zstd = zstd::stream::encode_all(content.as_slice(), 3).unwrap();
+        assert_eq!(decompress(zstd, None, "a.log.zst").unwrap(), content);
+
+        let mut lz4 = lz4_flex::frame::FrameEncoder::new(Vec::new());
+        lz4.write_all(&content).unwrap();
+        assert_eq!(decompress(lz4.finish().unwrap(), Some(&CompressionType::Lz4), "a").unwrap(), content);
+
+        assert_eq!(decompress(content.clone(), None, "plain.log").unwrap(), content);
+        assert!(decompress(b"not gzip".to_vec(), None, "a.gz").is_err());
+    }
+
+    #[test]
+    fn test_split_records() {
+        let cloudtrail = br#"{"Records": [{"eventName": "ConsoleLogin"}, {"eventName": "GetObject"}]}"#;
+        assert_eq!(split_records(cloudtrail), vec![
+            r#"{"eventName":"ConsoleLogin"}"#.to_string(),
+            r#"{"eventName":"GetObject"}"#.to_string(),
+        ]);
+
+        assert_eq!(split_records(b"[1, {\"a\": 2}]"), vec!["1".to_string(), r#"{"a":2}"#.to_string()]);
+        assert_eq!(split_records(b"{\"a\":1}\n{\"a\":2}\n"), vec![r#"{"a":1}"#, r#"{"a":2}"#]);
+
+        let flow_logs = b"2 123456789010 eni-1 10.0.0.1 10.0.0.2 443 49152 6 10 840 1620000000 1620000060 ACCEPT OK\r\n\r\n";
+        assert_eq!(split_records(flow_logs).len(), 1);
+    }
+}
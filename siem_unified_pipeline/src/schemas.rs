@@ -83,6 +83,11 @@ pub struct CreateRoutingRuleRequest {
     #[validate(range(min = 0, max = 1000))]
     pub priority: Option<u32>,
     
+    pub stop_processing: Option<bool>,
+    
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub sample_percent: Option<f64>,
+    
     pub tags: Option<Vec<String>>,
 }
 
@@ -100,6 +105,11 @@ pub struct UpdateRoutingRuleRequest {
     #[validate(range(min = 0, max = 1000))]
     pub priority: Option<u32>,
     
+    pub stop_processing: Option<bool>,
+    
+    #[validate(range(min = 0.0, max = 100.0))]
+    pub sample_percent: Option<f64>,
+    
     pub tags: Option<Vec<String>>,
 }
 
@@ -110,9 +120,15 @@ pub struct RoutingRuleResponse {
     pub name: String,
     pub description: Option<String>,
     pub conditions: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub condition_group: Option<serde_json::Value>,
     pub actions: serde_json::Value,
     pub enabled: bool,
     pub priority: u32,
+    pub stop_processing: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_percent: Option<f64>,
+    pub explanation: String,
     pub tags: Vec<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -126,6 +142,20 @@ pub struct RoutingRulesListResponse {
     pub total: u64,
 }
 
+/// Sample events (full `PipelineEvent`s or bare `data` objects) and/or the
+/// most recent stored events, routed through the live rules and, when given,
+/// a proposed replacement rule set
+#[derive(Debug, Serialize, Deserialize, Validate)]
+#[serde(rename_all = "snake_case")]
+pub struct RoutingDryRunRequest {
+    pub events: Option<Vec<serde_json::Value>>,
+    
+    #[validate(range(min = 1, max = 500))]
+    pub last_n: Option<u32>,
+    
+    pub proposed_rules: Option<Vec<CreateRoutingRuleRequest>>,
+}
+
 // System Logs Schemas
 #[derive(Debug, Serialize, Deserialize, Validate)]
 #[serde(rename_all = "snake_case")]
@@ -228,14 +258,19 @@ impl From<crate::models::Event> for EventDetail {
 
 impl From<crate::routing::RoutingRule> for RoutingRuleResponse {
     fn from(rule: crate::routing::RoutingRule) -> Self {
+        let explanation = rule.explain();
         Self {
             id: rule.id,
             name: rule.name,
             description: Some(rule.description),
             conditions: serde_json::json!(rule.conditions),
+            condition_group: rule.condition_group.map(|group| serde_json::json!(group)),
             actions: serde_json::json!(rule.destinations),
             enabled: rule.enabled,
             priority: rule.priority,
+            stop_processing: rule.stop_processing,
+            sample_percent: rule.sample_percent,
+            explanation,
             tags: rule.tags,
             created_at: Utc::now(), // TODO: Add to RoutingRule struct
             updated_at: Utc::now(), // TODO: Add to RoutingRule struct
@@ -0,0 +1,667 @@
+use std::collections::HashMap;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value as YamlValue;
+
+use crate::error::{Result, PipelineError};
+use crate::models::AlertSeverity;
+use crate::pipeline::PipelineEvent;
+use crate::utils;
+
+/// Raw Sigma rule as it appears in YAML.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigmaRule {
+    pub title: String,
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub references: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub logsource: SigmaLogSource,
+    pub detection: HashMap<String, YamlValue>,
+    #[serde(default)]
+    pub falsepositives: Vec<String>,
+    #[serde(default)]
+    pub level: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SigmaLogSource {
+    pub category: Option<String>,
+    pub product: Option<String>,
+    pub service: Option<String>,
+}
+
+/// A Sigma rule compiled into matchers that can be evaluated per event.
+#[derive(Debug, Clone)]
+pub struct CompiledSigmaRule {
+    pub rule: SigmaRule,
+    selections: HashMap<String, Selection>,
+    condition: Condition,
+}
+
+#[derive(Debug, Clone)]
+enum Selection {
+    // List of field maps; the selection matches if any map matches
+    Fields(Vec<Vec<FieldMatcher>>),
+    // Keyword list, each matched as a substring anywhere in the event
+    Keywords(Vec<ValueMatcher>),
+}
+
+#[derive(Debug, Clone)]
+struct FieldMatcher {
+    field: String,
+    match_all: bool,
+    values: Vec<ValueMatcher>,
+}
+
+#[derive(Debug, Clone)]
+enum ValueMatcher {
+    Equals(String),
+    Contains(String),
+    StartsWith(String),
+    EndsWith(String),
+    Pattern(Regex),
+    Cidr(String),
+    Null,
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Selection(String),
+    OneOf(Vec<String>),
+    AllOf(Vec<String>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+impl CompiledSigmaRule {
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        let rule: SigmaRule = serde_yaml::from_str(yaml)
+            .map_err(|e| PipelineError::validation(format!("Invalid Sigma rule: {}", e)))?;
+        Self::compile(rule)
+    }
+
+    pub fn compile(rule: SigmaRule) -> Result<Self> {
+        let mut selections = HashMap::new();
+        let mut conditions = Vec::new();
+
+        for (name, definition) in &rule.detection {
+            match name.as_str() {
+                "condition" => match definition {
+                    YamlValue::String(s) => conditions.push(s.clone()),
+                    YamlValue::Sequence(items) => {
+                        for item in items {
+                            let s = item.as_str()
+                                .ok_or_else(|| PipelineError::validation("Sigma condition list must contain strings"))?;
+                            conditions.push(s.to_string());
+                        }
+                    }
+                    _ => return Err(PipelineError::validation("Sigma condition must be a string or list of strings")),
+                },
+                "timeframe" => {
+                    // Only meaningful for aggregations, which are handled by the correlation engine
+                }
+                _ => {
+                    selections.insert(name.clone(), compile_selection(name, definition)?);
+                }
+            }
+        }
+
+        if conditions.is_empty() {
+            return Err(PipelineError::validation(format!("Sigma rule '{}' has no condition", rule.title)));
+        }
+
+        let names: Vec<String> = selections.keys().cloned().collect();
+        let mut condition: Option<Condition> = None;
+        for text in &conditions {
+            let parsed = ConditionParser::new(text, &names)?.parse()?;
+            condition = Some(match condition {
+                Some(existing) => Condition::Or(Box::new(existing), Box::new(parsed)),
+                None => parsed,
+            });
+        }
+
+        Ok(CompiledSigmaRule {
+            rule,
+            selections,
+            condition: condition.expect("at least one condition"),
+        })
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.rule.id.as_deref()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.rule.title
+    }
+
+    pub fn matches(&self, event: &PipelineEvent) -> bool {
+        self.eval(&self.condition, event)
+    }
+
+    fn eval(&self, condition: &Condition, event: &PipelineEvent) -> bool {
+        match condition {
+            Condition::Selection(name) => self.selection_matches(name, event),
+            Condition::OneOf(names) => names.iter().any(|n| self.selection_matches(n, event)),
+            Condition::AllOf(names) => names.iter().all(|n| self.selection_matches(n, event)),
+            Condition::And(a, b) => self.eval(a, event) && self.eval(b, event),
+            Condition::Or(a, b) => self.eval(a, event) || self.eval(b, event),
+            Condition::Not(inner) => !self.eval(inner, event),
+        }
+    }
+
+    fn selection_matches(&self, name: &str, event: &PipelineEvent) -> bool {
+        match self.selections.get(name) {
+            Some(Selection::Fields(groups)) => groups.iter().any(|group| {
+                group.iter().all(|matcher| matcher.matches(event))
+            }),
+            Some(Selection::Keywords(keywords)) => {
+                let haystack = keyword_haystack(event);
+                keywords.iter().any(|k| k.matches_str(&haystack))
+            }
+            None => false,
+        }
+    }
+
+    pub fn severity(&self) -> AlertSeverity {
+        level_to_severity(self.rule.level.as_deref())
+    }
+
+    pub fn mitre_techniques(&self) -> Vec<String> {
+        mitre_techniques(&self.rule.tags)
+    }
+
+    pub fn mitre_tactics(&self) -> Vec<String> {
+        mitre_tactics(&self.rule.tags)
+    }
+}
+
+/// Map a Sigma `level` onto an alert severity.
+pub fn level_to_severity(level: Option<&str>) -> AlertSeverity {
+    match level.map(|l| l.to_lowercase()).as_deref() {
+        Some("critical") => AlertSeverity::Critical,
+        Some("high") => AlertSeverity::High,
+        Some("medium") => AlertSeverity::Medium,
+        Some("low") => AlertSeverity::Low,
+        _ => AlertSeverity::Info,
+    }
+}
+
+// ATT&CK tags are either `attack.<tactic>` or an ID such as `attack.t1059.001`, `attack.g0016`, `attack.s0002`
+fn is_attack_id(tag: &str) -> bool {
+    tag.starts_with(['t', 'T', 'g', 'G', 's', 'S']) && tag.chars().nth(1).is_some_and(|c| c.is_ascii_digit())
+}
+
+/// ATT&CK technique IDs from `attack.tNNNN` tags.
+pub fn mitre_techniques(tags: &[String]) -> Vec<String> {
+    tags.iter()
+        .filter_map(|t| t.strip_prefix("attack."))
+        .filter(|t| is_attack_id(t) && t.starts_with(['t', 'T']))
+        .map(|t| t.to_uppercase())
+        .collect()
+}
+
+/// ATT&CK tactic names from `attack.<tactic>` tags.
+pub fn mitre_tactics(tags: &[String]) -> Vec<String> {
+    tags.iter()
+        .filter_map(|t| t.strip_prefix("attack."))
+        .filter(|t| !is_attack_id(t))
+        .map(|t| t.replace('_', "-"))
+        .collect()
+}
+
+fn compile_selection(name: &str, definition: &YamlValue) -> Result<Selection> {
+    match definition {
+        YamlValue::Mapping(map) => Ok(Selection::Fields(vec![compile_field_map(name, map)?])),
+        YamlValue::Sequence(items) => {
+            if items.iter().all(|i| matches!(i, YamlValue::Mapping(_))) {
+                let groups = items.iter()
+                    .filter_map(|i| i.as_mapping())
+                    .map(|m| compile_field_map(name, m))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Selection::Fields(groups))
+            } else {
+                let keywords = items.iter()
+                    .map(|i| compile_value(i, &["contains"]))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Selection::Keywords(keywords))
+            }
+        }
+        YamlValue::String(_) | YamlValue::Number(_) => {
+            Ok(Selection::Keywords(vec![compile_value(definition, &["contains"])?]))
+        }
+        _ => Err(PipelineError::validation(format!("Unsupported definition for selection '{}'", name))),
+    }
+}
+
+fn compile_field_map(name: &str, map: &serde_yaml::Mapping) -> Result<Vec<FieldMatcher>> {
+    let mut matchers = Vec::new();
+
+    for (key, value) in map {
+        let key = key.as_str()
+            .ok_or_else(|| PipelineError::validation(format!("Non-string field name in selection '{}'", name)))?;
+        let mut parts = key.split('|');
+        let field = parts.next().unwrap_or_default().to_string();
+        let modifiers: Vec<&str> = parts.collect();
+
+        for modifier in &modifiers {
+            if !matches!(*modifier, "contains" | "startswith" | "endswith" | "re" | "cidr" | "all") {
+                return Err(PipelineError::validation(format!(
+                    "Unsupported Sigma modifier '{}' on field '{}'", modifier, field
+                )));
+            }
+        }
+
+        let values = match value {
+            YamlValue::Sequence(items) => items.iter()
+                .map(|v| compile_value(v, &modifiers))
+                .collect::<Result<Vec<_>>>()?,
+            other => vec![compile_value(other, &modifiers)?],
+        };
+
+        matchers.push(FieldMatcher {
+            field,
+            match_all: modifiers.contains(&"all"),
+            values,
+        });
+    }
+
+    Ok(matchers)
+}
+
+fn compile_value(value: &YamlValue, modifiers: &[&str]) -> Result<ValueMatcher> {
+    let text = match value {
+        YamlValue::Null => return Ok(ValueMatcher::Null),
+        YamlValue::String(s) => s.clone(),
+        YamlValue::Number(n) => n.to_string(),
+        YamlValue::Bool(b) => b.to_string(),
+        _ => return Err(PipelineError::validation("Sigma values must be scalars")),
+    };
+
+    if modifiers.contains(&"re") {
+        let regex = Regex::new(&text)
+            .map_err(|e| PipelineError::validation(format!("Invalid regex '{}': {}", text, e)))?;
+        return Ok(ValueMatcher::Pattern(regex));
+    }
+
+    if modifiers.contains(&"cidr") {
+        utils::parse_cidr(&text)?;
+        return Ok(ValueMatcher::Cidr(text));
+    }
+
+    let contains = modifiers.contains(&"contains");
+    let starts = modifiers.contains(&"startswith");
+    let ends = modifiers.contains(&"endswith");
+
+    if has_wildcards(&text) {
+        let mut pattern = String::from("(?s)");
+        if !contains && !ends {
+            pattern.push('^');
+        }
+        pattern.push_str(&wildcard_to_regex(&text));
+        if !contains && !starts {
+            pattern.push('$');
+        }
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|e| PipelineError::validation(format!("Invalid wildcard '{}': {}", text, e)))?;
+        return Ok(ValueMatcher::Pattern(regex));
+    }
+
+    let text = unescape_wildcards(&text).to_lowercase();
+    Ok(if contains {
+        ValueMatcher::Contains(text)
+    } else if starts {
+        ValueMatcher::StartsWith(text)
+    } else if ends {
+        ValueMatcher::EndsWith(text)
+    } else {
+        ValueMatcher::Equals(text)
+    })
+}
+
+fn has_wildcards(text: &str) -> bool {
+    let mut escaped = false;
+    for c in text.chars() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '*' | '?' if !escaped => return true,
+            _ => escaped = false,
+        }
+    }
+    false
+}
+
+fn wildcard_to_regex(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if matches!(chars.peek(), Some('*') | Some('?') | Some('\\')) => {
+                out.push_str(&regex::escape(&chars.next().unwrap().to_string()));
+            }
+            '*' => out.push_str(".*"),
+            '?' => out.push('.'),
+            _ => out.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    out
+}
+
+fn unescape_wildcards(text: &str) -> String {
+    text.replace("\\*", "*").replace("\\?", "?")
+}
+
+impl FieldMatcher {
+    fn matches(&self, event: &PipelineEvent) -> bool {
+        let value = event.get_field(&self.field);
+
+        let candidates: Vec<String> = match value.as_deref() {
+            None | Some(serde_json::Value::Null) => {
+                return self.values.iter().any(|v| matches!(v, ValueMatcher::Null));
+            }
+            Some(serde_json::Value::Array(items)) => items.iter().map(value_to_string).collect(),
+            Some(other) => vec![value_to_string(other)],
+        };
+
+        let check = |matcher: &ValueMatcher| candidates.iter().any(|c| matcher.matches_str(c));
+        if self.match_all {
+            self.values.iter().all(check)
+        } else {
+            self.values.iter().any(check)
+        }
+    }
+}
+
+impl ValueMatcher {
+    fn matches_str(&self, candidate: &str) -> bool {
+        match self {
+            ValueMatcher::Equals(v) => candidate.to_lowercase() == *v,
+            ValueMatcher::Contains(v) => candidate.to_lowercase().contains(v.as_str()),
+            ValueMatcher::StartsWith(v) => candidate.to_lowercase().starts_with(v.as_str()),
+            ValueMatcher::EndsWith(v) => candidate.to_lowercase().ends_with(v.as_str()),
+            ValueMatcher::Pattern(re) => re.is_match(candidate),
+            ValueMatcher::Cidr(cidr) => utils::ip_in_cidr(candidate, cidr).unwrap_or(false),
+            ValueMatcher::Null => false,
+        }
+    }
+}
+
+fn value_to_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Keyword selections search the raw message and every string value in `data`
+fn keyword_haystack(event: &PipelineEvent) -> String {
+    fn collect(value: &serde_json::Value, out: &mut String) {
+        match value {
+            serde_json::Value::String(s) => {
+                out.push_str(s);
+                out.push('\n');
+            }
+            serde_json::Value::Array(items) => items.iter().for_each(|v| collect(v, out)),
+            serde_json::Value::Object(map) => map.values().for_each(|v| collect(v, out)),
+            _ => {}
+        }
+    }
+
+    let mut out = String::new();
+    collect(&event.data, &mut out);
+    out
+}
+
+// Recursive-descent parser for Sigma condition expressions
+struct ConditionParser<'a> {
+    tokens: Vec<String>,
+    pos: usize,
+    selections: &'a [String],
+}
+
+impl<'a> ConditionParser<'a> {
+    fn new(text: &str, selections: &'a [String]) -> Result<Self> {
+        if text.contains('|') {
+            return Err(PipelineError::validation(
+                "Sigma aggregation conditions are not supported here; use a correlation rule instead",
+            ));
+        }
+
+        let mut tokens = Vec::new();
+        let mut current = String::new();
+        for c in text.chars() {
+            match c {
+                '(' | ')' => {
+                    if !current.is_empty() {
+                        tokens.push(std::mem::take(&mut current));
+                    }
+                    tokens.push(c.to_string());
+                }
+                c if c.is_whitespace() => {
+                    if !current.is_empty() {
+                        tokens.push(std::mem::take(&mut current));
+                    }
+                }
+                c => current.push(c),
+            }
+        }
+        if !current.is_empty() {
+            tokens.push(current);
+        }
+
+        Ok(ConditionParser { tokens, pos: 0, selections })
+    }
+
+    fn parse(mut self) -> Result<Condition> {
+        let condition = self.parse_or()?;
+        if self.pos < self.tokens.len() {
+            return Err(PipelineError::validation(format!(
+                "Unexpected token '{}' in Sigma condition", self.tokens[self.pos]
+            )));
+        }
+        Ok(condition)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|s| s.as_str())
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn parse_or(&mut self) -> Result<Condition> {
+        let mut left = self.parse_and()?;
+        while self.peek().map(|t| t.eq_ignore_ascii_case("or")).unwrap_or(false) {
+            self.pos += 1;
+            let right = self.parse_and()?;
+            left = Condition::Or(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Condition> {
+        let mut left = self.parse_not()?;
+        while self.peek().map(|t| t.eq_ignore_ascii_case("and")).unwrap_or(false) {
+            self.pos += 1;
+            let right = self.parse_not()?;
+            left = Condition::And(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Condition> {
+        if self.peek().map(|t| t.eq_ignore_ascii_case("not")).unwrap_or(false) {
+            self.pos += 1;
+            return Ok(Condition::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_primary()
+    }
+
+    fn parse_primary(&mut self) -> Result<Condition> {
+        let token = self.next()
+            .ok_or_else(|| PipelineError::validation("Unexpected end of Sigma condition"))?;
+
+        if token == "(" {
+            let inner = self.parse_or()?;
+            if self.next().as_deref() != Some(")") {
+                return Err(PipelineError::validation("Unbalanced parentheses in Sigma condition"));
+            }
+            return Ok(inner);
+        }
+
+        let quantifier = token.to_lowercase();
+        if (quantifier == "1" || quantifier == "all" || quantifier == "any")
+            && self.peek().map(|t| t.eq_ignore_ascii_case("of")).unwrap_or(false)
+        {
+            self.pos += 1;
+            let target = self.next()
+                .ok_or_else(|| PipelineError::validation("Expected selection pattern after 'of'"))?;
+            let names = self.resolve_pattern(&target)?;
+            return Ok(if quantifier == "all" {
+                Condition::AllOf(names)
+            } else {
+                Condition::OneOf(names)
+            });
+        }
+
+        if !self.selections.contains(&token) {
+            return Err(PipelineError::validation(format!("Unknown selection '{}' in Sigma condition", token)));
+        }
+        Ok(Condition::Selection(token))
+    }
+
+    fn resolve_pattern(&self, pattern: &str) -> Result<Vec<String>> {
+        let mut names: Vec<String> = if pattern.eq_ignore_ascii_case("them") {
+            self.selections.iter().filter(|n| !n.starts_with('_')).cloned().collect()
+        } else if let Some(prefix) = pattern.strip_suffix('*') {
+            self.selections.iter().filter(|n| n.starts_with(prefix)).cloned().collect()
+        } else {
+            self.selections.iter().filter(|n| *n == pattern).cloned().collect()
+        };
+
+        if names.is_empty() {
+            return Err(PipelineError::validation(format!("No selections match '{}' in Sigma condition", pattern)));
+        }
+        names.sort();
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ProcessingStage;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn event(data: serde_json::Value) -> PipelineEvent {
+        PipelineEvent {
+            id: Uuid::new_v4(),
+            timestamp: chrono::Utc::now(),
+            source: "test".to_string(),
+            data,
+            metadata: HashMap::new(),
+            processing_stage: ProcessingStage::Ingested,
+        }
+    }
+
+    #[test]
+    fn test_selection_with_modifiers_and_filter() {
+        let rule = CompiledSigmaRule::from_yaml(r#"
+title: Suspicious PowerShell
+level: high
+tags: [attack.execution, attack.t1059.001]
+detection:
+  selection:
+    Image|endswith: '\powershell.exe'
+    CommandLine|contains:
+      - '-enc'
+      - 'IEX'
+  filter:
+    src_ip|cidr: 10.0.0.0/8
+  condition: selection and not filter
+"#).unwrap();
+
+        assert!(rule.matches(&event(serde_json::json!({
+            "Image": "C:\\Windows\\System32\\PowerShell.exe",
+            "CommandLine": "powershell -enc AAAA",
+            "src_ip": "192.168.1.5"
+        }))));
+        assert!(!rule.matches(&event(serde_json::json!({
+            "Image": "C:\\Windows\\System32\\powershell.exe",
+            "CommandLine": "powershell -enc AAAA",
+            "src_ip": "10.1.2.3"
+        }))));
+        assert!(matches!(rule.severity(), AlertSeverity::High));
+        assert_eq!(rule.mitre_techniques(), vec!["T1059.001".to_string()]);
+        assert_eq!(rule.mitre_tactics(), vec!["execution".to_string()]);
+    }
+
+    #[test]
+    fn test_quantified_conditions_and_keywords() {
+        let rule = CompiledSigmaRule::from_yaml(r#"
+title: Quantifiers
+detection:
+  sel_user:
+    user: 'adm*'
+  sel_action:
+    action|re: '^(delete|drop)$'
+  keywords:
+    - 'mimikatz'
+  condition: all of sel_* or keywords
+"#).unwrap();
+
+        assert!(rule.matches(&event(serde_json::json!({"user": "Administrator", "action": "delete"}))));
+        assert!(!rule.matches(&event(serde_json::json!({"user": "Administrator", "action": "read"}))));
+        assert!(rule.matches(&event(serde_json::json!({"raw_message": "running Mimikatz now"}))));
+    }
+
+    #[test]
+    fn test_cidr_bare_address_and_mapped_ipv4() {
+        let rule = CompiledSigmaRule::from_yaml(r#"
+title: Known hosts
+detection:
+  sel:
+    src_ip|cidr:
+      - 192.0.2.7
+      - 10.0.0.0/8
+  condition: sel
+"#).unwrap();
+
+        assert!(rule.matches(&event(serde_json::json!({"src_ip": "192.0.2.7"}))));
+        assert!(!rule.matches(&event(serde_json::json!({"src_ip": "192.0.2.8"}))));
+        assert!(rule.matches(&event(serde_json::json!({"src_ip": "::ffff:10.1.2.3"}))));
+    }
+
+    #[test]
+    fn test_mitre_tags_with_non_ascii_characters() {
+        let tags = vec!["attack.tä".to_string(), "attack.é".to_string(), "attack.t1003".to_string()];
+        assert_eq!(mitre_techniques(&tags), vec!["T1003".to_string()]);
+        assert_eq!(mitre_tactics(&tags), vec!["tä".to_string(), "é".to_string()]);
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        assert!(CompiledSigmaRule::from_yaml("title: x\ndetection:\n  sel:\n    a: b\n  condition: missing\n").is_err());
+        assert!(CompiledSigmaRule::from_yaml("title: x\ndetection:\n  sel:\n    a|base64: b\n  condition: sel\n").is_err());
+        assert!(CompiledSigmaRule::from_yaml("title: x\ndetection:\n  sel:\n    a: b\n  condition: sel | count() > 5\n").is_err());
+        assert!(CompiledSigmaRule::from_yaml("title: x\ndetection:\n  sel:\n    a|cidr: 10.0.0.0/33\n  condition: sel\n").is_err());
+        assert!(CompiledSigmaRule::from_yaml("title: x\ndetection:\n  sel:\n    a|cidr: host/8\n  condition: sel\n").is_err());
+    }
+}
@@ -0,0 +1,443 @@
+use std::io::Write;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, warn};
+
+use crate::config::{CircuitBreakerConfig, CompressionType, DataDestination, DestinationType};
+use crate::error::{Result, PipelineError};
+use crate::pipeline::PipelineEvent;
+use crate::routing::DestinationHealth;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Destination health changes, as seen by each destination's batcher.
+pub type HealthSender = broadcast::Sender<(String, DestinationHealth)>;
+
+/// Writes a whole batch of events to one destination, returning the bytes written.
+#[async_trait::async_trait]
+pub trait BatchWriter: Send + 'static {
+    async fn write_batch(&mut self, events: &[PipelineEvent]) -> Result<u64>;
+
+    /// Called after a flush interval without events, for writers with
+    /// time-based work of their own such as rolling files.
+    async fn idle(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called once after the last batch, before the batcher stops.
+    async fn close(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchSettings {
+    pub batch_size: usize,
+    pub flush_interval: Duration,
+    pub retry_attempts: u32,
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+}
+
+impl BatchSettings {
+    /// `batch_size`, `flush_interval` (milliseconds), `retry_attempts` and
+    /// `circuit_breaker` of a destination.
+    pub fn from_destination(destination: &DataDestination) -> Self {
+        let forwards = matches!(destination.destination_type, DestinationType::SplunkHec { .. } | DestinationType::Webhook { .. });
+        Self {
+            batch_size: destination.batch_size.max(1),
+            flush_interval: Duration::from_millis(destination.flush_interval.max(1)),
+            retry_attempts: destination.retry_attempts,
+            circuit_breaker: destination.circuit_breaker.clone()
+                .or_else(|| forwards.then(CircuitBreakerConfig::default)),
+        }
+    }
+}
+
+/// Consecutive-failure circuit breaker for one destination.
+///
+/// Once `failure_threshold` writes in a row have failed the breaker opens
+/// and writes are refused without being attempted. After
+/// `reset_timeout_secs` one write is let through: success closes the
+/// breaker, failure keeps it open for another timeout.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &CircuitBreakerConfig) -> Self {
+        Self {
+            failure_threshold: config.failure_threshold.max(1),
+            reset_timeout: Duration::from_secs(config.reset_timeout_secs),
+            failures: 0,
+            opened_at: None,
+        }
+    }
+
+    /// Whether a write may be attempted now.
+    pub fn allow(&self) -> bool {
+        match self.opened_at {
+            Some(opened_at) => opened_at.elapsed() >= self.reset_timeout,
+            None => true,
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        self.failures = 0;
+        self.opened_at = None;
+    }
+
+    pub fn record_failure(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+        if self.failures >= self.failure_threshold {
+            self.opened_at = Some(Instant::now());
+        }
+    }
+
+    /// Open is unhealthy; failing but still closed, or waiting on a probe, is degraded.
+    pub fn health(&self) -> DestinationHealth {
+        match self.opened_at {
+            Some(_) if !self.allow() => DestinationHealth::Unhealthy,
+            Some(_) => DestinationHealth::Degraded,
+            None if self.failures > 0 => DestinationHealth::Degraded,
+            None => DestinationHealth::Healthy,
+        }
+    }
+}
+
+// Publishes a destination's health whenever it changes
+struct HealthReporter {
+    destination: String,
+    sender: Option<HealthSender>,
+    last: Option<DestinationHealth>,
+}
+
+impl HealthReporter {
+    fn report(&mut self, health: DestinationHealth) {
+        if self.last.as_ref() == Some(&health) {
+            return;
+        }
+        match health {
+            DestinationHealth::Healthy if self.last.is_some() => info!("Destination {} is healthy again", self.destination),
+            DestinationHealth::Unhealthy => warn!("Destination {} is unhealthy", self.destination),
+            _ => {}
+        }
+        if let Some(sender) = &self.sender {
+            // Nobody listening is fine; health is also visible in the logs
+            let _ = sender.send((self.destination.clone(), health.clone()));
+        }
+        self.last = Some(health);
+    }
+}
+
+struct Pending {
+    event: PipelineEvent,
+    done: oneshot::Sender<std::result::Result<(), String>>,
+}
+
+/// Resolves once the batch holding an event has been written, or has
+/// failed after every retry.
+pub struct Delivery {
+    destination: String,
+    rx: oneshot::Receiver<std::result::Result<(), String>>,
+}
+
+impl Delivery {
+    pub async fn wait(self) -> Result<()> {
+        match self.rx.await {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(e)) => Err(PipelineError::storage(e)),
+            Err(_) => Err(PipelineError::storage(format!(
+                "Destination '{}' stopped before the batch was written", self.destination
+            ))),
+        }
+    }
+}
+
+/// Collects events for one destination and hands them to its writer once
+/// `batch_size` events are waiting or `flush_interval` has passed since the
+/// first of them arrived. A failed batch is retried `retry_attempts` times
+/// with exponential backoff before its events are reported as failed.
+pub struct Batcher {
+    destination: String,
+    tx: mpsc::Sender<Pending>,
+    task: JoinHandle<()>,
+}
+
+impl Batcher {
+    pub fn spawn<W: BatchWriter>(destination: &str, settings: BatchSettings, writer: W, health: Option<HealthSender>) -> Self {
+        // Room for the batch being written plus the next one; beyond that submitters wait
+        let (tx, rx) = mpsc::channel(settings.batch_size.saturating_mul(2));
+        let health = HealthReporter { destination: destination.to_string(), sender: health, last: None };
+        let task = tokio::spawn(run_batcher(destination.to_string(), settings, writer, health, rx));
+        Self { destination: destination.to_string(), tx, task }
+    }
+
+    pub async fn submit(&self, event: PipelineEvent) -> Result<Delivery> {
+        let (done, rx) = oneshot::channel();
+        self.tx.send(Pending { event, done }).await
+            .map_err(|_| PipelineError::storage(format!("Destination '{}' is shut down", self.destination)))?;
+        Ok(Delivery { destination: self.destination.clone(), rx })
+    }
+
+    /// Write out whatever is still queued and stop.
+    pub async fn close(self) {
+        drop(self.tx);
+        if let Err(e) = self.task.await {
+            error!("Batcher for destination {} failed: {}", self.destination, e);
+        }
+    }
+}
+
+async fn run_batcher<W: BatchWriter>(
+    destination: String,
+    settings: BatchSettings,
+    mut writer: W,
+    mut health: HealthReporter,
+    mut rx: mpsc::Receiver<Pending>,
+) {
+    let mut breaker = settings.circuit_breaker.as_ref().map(CircuitBreaker::new);
+    let mut batch = Vec::with_capacity(settings.batch_size);
+    loop {
+        let first = match tokio::time::timeout(settings.flush_interval, rx.recv()).await {
+            Ok(Some(first)) => first,
+            Ok(None) => break,
+            Err(_) => {
+                if let Err(e) = writer.idle().await {
+                    warn!("Idle maintenance for destination {} failed: {}", destination, e);
+                }
+                continue;
+            }
+        };
+        batch.push(first);
+        let deadline = tokio::time::Instant::now() + settings.flush_interval;
+        while batch.len() < settings.batch_size {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(pending)) => batch.push(pending),
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        let (events, waiters): (Vec<_>, Vec<_>) = batch.drain(..)
+            .map(|pending| (pending.event, pending.done))
+            .unzip();
+        let outcome = write_with_retry(&destination, &settings, &mut writer, breaker.as_mut(), &events).await;
+        health.report(match &breaker {
+            Some(breaker) => breaker.health(),
+            None if outcome.is_ok() => DestinationHealth::Healthy,
+            None => DestinationHealth::Unhealthy,
+        });
+        for done in waiters {
+            let _ = done.send(outcome.clone());
+        }
+    }
+    if let Err(e) = writer.close().await {
+        error!("Failed to close destination {}: {}", destination, e);
+    }
+    debug!("Batcher for destination {} stopped", destination);
+}
+
+async fn write_with_retry<W: BatchWriter>(
+    destination: &str,
+    settings: &BatchSettings,
+    writer: &mut W,
+    mut breaker: Option<&mut CircuitBreaker>,
+    events: &[PipelineEvent],
+) -> std::result::Result<(), String> {
+    let mut attempt = 0;
+    loop {
+        if breaker.as_ref().is_some_and(|breaker| !breaker.allow()) {
+            debug!("Circuit open, failing batch of {} events to {}", events.len(), destination);
+            return Err(format!("Circuit breaker for destination '{}' is open", destination));
+        }
+        let result = writer.write_batch(events).await;
+        if let Some(breaker) = breaker.as_mut() {
+            match &result {
+                Ok(_) => breaker.record_success(),
+                Err(_) => breaker.record_failure(),
+            }
+        }
+        match result {
+            Ok(bytes) => {
+                debug!("Wrote batch of {} events ({} bytes) to {}", events.len(), bytes, destination);
+                return Ok(());
+            }
+            Err(e) if attempt < settings.retry_attempts => {
+                let delay = backoff(attempt);
+                warn!("Batch of {} events to {} failed (attempt {}), retrying in {:?}: {}",
+                      events.len(), destination, attempt + 1, delay, e);
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                error!("Batch of {} events to {} failed after {} attempts: {}",
+                       events.len(), destination, attempt + 1, e);
+                return Err(e.to_string());
+            }
+        }
+    }
+}
+
+/// 100ms doubling per attempt, capped at 30s.
+pub fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(100u64.saturating_mul(1u64 << attempt.min(20))).min(MAX_BACKOFF)
+}
+
+/// Compress a serialized batch. Each call produces a complete gzip member,
+/// zstd frame or LZ4 frame, so batches appended to one file still form a
+/// valid stream.
+pub fn compress(data: &[u8], compression: Option<&CompressionType>) -> Result<Vec<u8>> {
+    let compressed = match compression {
+        None => return Ok(data.to_vec()),
+        Some(CompressionType::Gzip) => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).and_then(|_| encoder.finish())
+        }
+        Some(CompressionType::Zstd) => zstd::stream::encode_all(data, 3),
+        Some(CompressionType::Lz4) => {
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+            encoder.write_all(data).and_then(|_| encoder.finish().map_err(std::io::Error::from))
+        }
+        Some(CompressionType::Snappy) => {
+            return Err(PipelineError::compression("Snappy is only supported for Kafka destinations"));
+        }
+    };
+    compressed.map_err(|e| PipelineError::compression(format!("Failed to compress batch: {}", e)))
+}
+
+/// File name suffix for objects written with `compression`.
+pub fn extension(compression: Option<&CompressionType>) -> &'static str {
+    match compression {
+        Some(CompressionType::Gzip) => ".gz",
+        Some(CompressionType::Zstd) => ".zst",
+        Some(CompressionType::Lz4) => ".lz4",
+        Some(CompressionType::Snappy) => ".snappy",
+        None => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ProcessingStage;
+    use std::collections::HashMap;
+    use std::io::Read;
+    use std::sync::{Arc, Mutex};
+
+    fn event(n: u64) -> PipelineEvent {
+        PipelineEvent {
+            id: uuid::Uuid::new_v4(),
+            timestamp: chrono::Utc::now(),
+            source: "test".to_string(),
+            data: serde_json::json!({ "n": n }),
+            metadata: HashMap::new(),
+            processing_stage: ProcessingStage::Routed,
+        }
+    }
+
+    // Records batch sizes and fails the first `failures` writes
+    struct TestWriter {
+        batches: Arc<Mutex<Vec<usize>>>,
+        failures: u32,
+    }
+
+    #[async_trait::async_trait]
+    impl BatchWriter for TestWriter {
+        async fn write_batch(&mut self, events: &[PipelineEvent]) -> Result<u64> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(PipelineError::storage("unavailable"));
+            }
+            self.batches.lock().unwrap().push(events.len());
+            Ok(events.len() as u64)
+        }
+    }
+
+    fn settings(batch_size: usize, retry_attempts: u32) -> BatchSettings {
+        BatchSettings { batch_size, flush_interval: Duration::from_millis(50), retry_attempts, circuit_breaker: None }
+    }
+
+    #[tokio::test]
+    async fn test_batches_by_size_and_interval() {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let batcher = Batcher::spawn("test", settings(3, 0), TestWriter { batches: batches.clone(), failures: 0 }, None);
+
+        let mut deliveries = Vec::new();
+        for n in 0..4 {
+            deliveries.push(batcher.submit(event(n)).await.unwrap());
+        }
+        for delivery in deliveries {
+            delivery.wait().await.unwrap();
+        }
+        // Three fill a batch; the fourth goes out when the interval passes
+        assert_eq!(*batches.lock().unwrap(), vec![3, 1]);
+
+        batcher.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_retries_with_backoff() {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let batcher = Batcher::spawn("test", settings(2, 2), TestWriter { batches: batches.clone(), failures: 2 }, None);
+        let first = batcher.submit(event(1)).await.unwrap();
+        let second = batcher.submit(event(2)).await.unwrap();
+        first.wait().await.unwrap();
+        second.wait().await.unwrap();
+        assert_eq!(*batches.lock().unwrap(), vec![2]);
+        batcher.close().await;
+
+        let batcher = Batcher::spawn("test", settings(1, 1), TestWriter { batches, failures: 2 }, None);
+        assert!(batcher.submit(event(3)).await.unwrap().wait().await.is_err());
+
+        assert_eq!(backoff(0), Duration::from_millis(100));
+        assert_eq!(backoff(3), Duration::from_millis(800));
+        assert_eq!(backoff(40), MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker_opens_and_probes() {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let mut settings = settings(1, 0);
+        settings.circuit_breaker = Some(CircuitBreakerConfig { failure_threshold: 2, reset_timeout_secs: 0 });
+        let (health, mut updates) = broadcast::channel(16);
+        let batcher = Batcher::spawn("hec", settings, TestWriter { batches: batches.clone(), failures: 2 }, Some(health));
+
+        for _ in 0..3 {
+            let _ = batcher.submit(event(1)).await.unwrap().wait().await;
+        }
+        // Degraded from the first failure, through the open breaker, until the probe succeeds
+        let mut seen = Vec::new();
+        while let Ok((_, health)) = updates.try_recv() {
+            seen.push(health);
+        }
+        assert_eq!(seen, vec![DestinationHealth::Degraded, DestinationHealth::Healthy]);
+        assert_eq!(*batches.lock().unwrap(), vec![1]);
+        batcher.close().await;
+
+        let mut breaker = CircuitBreaker::new(&CircuitBreakerConfig { failure_threshold: 1, reset_timeout_secs: 60 });
+        breaker.record_failure();
+        assert!(!breaker.allow());
+        assert_eq!(breaker.health(), DestinationHealth::Unhealthy);
+    }
+
+    #[test]
+    fn test_compressed_batches_concatenate() {
+        let mut stream = compress(b"{\"n\":1}\n", Some(&CompressionType::Gzip)).unwrap();
+        stream.extend(compress(b"{\"n\":2}\n", Some(&CompressionType::Gzip)).unwrap());
+        let mut text = String::new();
+        flate2::read::MultiGzDecoder::new(stream.as_slice()).read_to_string(&mut text).unwrap();
+        assert_eq!(text, "{\"n\":1}\n{\"n\":2}\n");
+
+        let zstd = compress(b"hello", Some(&CompressionType::Zstd)).unwrap();
+        assert_eq!(zstd::stream::decode_all(zstd.as_slice()).unwrap(), b"hello");
+        let lz4 = compress(b"hello", Some(&CompressionType::Lz4)).unwrap();
+        let mut decoded = Vec::new();
+        lz4_flex::frame::FrameDecoder::new(lz4.as_slice()).read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, b"hello");
+        assert!(compress(b"hello", Some(&CompressionType::Snappy)).is_err());
+    }
+}
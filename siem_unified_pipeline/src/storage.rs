@@ -1,15 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{info, warn, error, debug};
+use std::sync::atomic::AtomicU64;
+use std::time::Duration;
+use tokio::sync::{broadcast, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{info, warn, debug};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 use clickhouse::{Client as ClickHouseClient, Row};
-use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::producer::{FutureProducer, FutureRecord, Producer};
 use rdkafka::config::ClientConfig;
-use redis::{Client as RedisClient, Commands};
-use tokio::fs::OpenOptions;
-use tokio::io::AsyncWriteExt;
+use redis::Client as RedisClient;
 use reqwest::Client as HttpClient;
 use regex::Regex;
 
@@ -18,10 +20,16 @@ use aws_sdk_s3::Client as S3Client;
 #[cfg(feature = "aws")]
 use aws_config::BehaviorVersion;
 
-use crate::config::{PipelineConfig, DataDestination, DestinationType};
+use crate::archive::{DirectoryStore, ParquetArchive, RollPolicy};
+#[cfg(feature = "aws")]
+use crate::archive::PartStore;
+use crate::config::{PipelineConfig, DataDestination, DestinationType, CompressionType, DataFormat};
 use crate::error::{Result, PipelineError};
+use crate::file_sink::{self, PathTemplate, RotatingFiles};
+use crate::forwarder::{self, SplunkHecWriter, WebhookWriter};
 use crate::pipeline::PipelineEvent;
 use crate::routing::DestinationHealth;
+use crate::sink::{self, Batcher, BatchSettings, BatchWriter, Delivery, HealthSender};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -73,10 +81,16 @@ pub struct StorageManager {
     clickhouse_clients: Arc<RwLock<HashMap<String, ClickHouseClient>>>,
     kafka_producers: Arc<RwLock<HashMap<String, FutureProducer>>>,
     redis_clients: Arc<RwLock<HashMap<String, RedisClient>>>,
-    file_handles: Arc<RwLock<HashMap<String, tokio::fs::File>>>,
+    // Bumped on SIGHUP; file destinations reopen their paths when it changes
+    file_reopen: Arc<AtomicU64>,
     #[cfg(feature = "aws")]
     s3_clients: Arc<RwLock<HashMap<String, S3Client>>>,
     http_clients: Arc<RwLock<HashMap<String, HttpClient>>>,
+    batchers: RwLock<HashMap<String, Batcher>>,
+    health: HealthSender,
+    // Latest probe result per destination, for subscribers that join late
+    probed_health: Arc<RwLock<HashMap<String, DestinationHealth>>>,
+    health_probes: Option<JoinHandle<()>>,
 }
 
 #[async_trait::async_trait]
@@ -110,25 +124,63 @@ impl StorageManager {
     pub async fn new(config: &PipelineConfig) -> Result<Self> {
         info!("Initializing storage manager");
         
-        let manager = StorageManager {
+        let mut manager = StorageManager {
             config: config.clone(),
             stats: Arc::new(RwLock::new(HashMap::new())),
             clickhouse_clients: Arc::new(RwLock::new(HashMap::new())),
             kafka_producers: Arc::new(RwLock::new(HashMap::new())),
             redis_clients: Arc::new(RwLock::new(HashMap::new())),
-            file_handles: Arc::new(RwLock::new(HashMap::new())),
+            file_reopen: Arc::new(AtomicU64::new(0)),
             #[cfg(feature = "aws")]
             s3_clients: Arc::new(RwLock::new(HashMap::new())),
             http_clients: Arc::new(RwLock::new(HashMap::new())),
+            batchers: RwLock::new(HashMap::new()),
+            health: broadcast::channel(256).0,
+            probed_health: Arc::new(RwLock::new(HashMap::new())),
+            health_probes: None,
         };
         
-        // Initialize connections for each destination
+        // Initialize connections for each destination. A group member that is
+        // down at startup does not stop the pipeline: the rest of its group
+        // takes its events until probes see it recover.
+        let group_members: HashSet<&str> = config.routing.destination_groups.values()
+            .flat_map(|group| group.members.iter().map(String::as_str))
+            .collect();
         for (dest_name, dest_config) in &config.destinations {
-            if dest_config.enabled {
-                manager.initialize_destination(dest_name, dest_config).await?;
+            if !dest_config.enabled {
+                continue;
+            }
+            match manager.initialize_destination(dest_name, dest_config).await {
+                Err(e) if group_members.contains(dest_name.as_str()) => {
+                    warn!("Destination group member {} is unavailable at startup: {}", dest_name, e);
+                    manager.update_connection_status(dest_name, ConnectionStatus::Error(e.to_string())).await;
+                    publish_health(&manager.probed_health, &manager.health, dest_name, DestinationHealth::Unhealthy).await;
+                }
+                result => result?,
+            }
+        }
+        manager.start_batchers(&group_members).await?;
+        
+        if config.routing.health_check_interval_secs > 0 {
+            let probes = manager.health_probes().await;
+            if !probes.is_empty() {
+                manager.health_probes = Some(spawn_health_probes(
+                    probes,
+                    Duration::from_secs(config.routing.health_check_interval_secs),
+                    manager.stats.clone(),
+                    manager.probed_health.clone(),
+                    manager.health.clone(),
+                ));
             }
         }
         
+        let writes_files = config.destinations.values().any(|dest| dest.enabled
+            && matches!(dest.destination_type, DestinationType::File { .. })
+            && dest.config.format != DataFormat::Parquet);
+        if writes_files {
+            file_sink::watch_hangup(manager.file_reopen.clone());
+        }
+        
         Ok(manager)
     }
     
@@ -167,9 +219,18 @@ impl StorageManager {
             DestinationType::S3 { .. } => {
                 self.initialize_s3(dest_name, dest_config).await?
             }
+            #[cfg(not(feature = "aws"))]
+            DestinationType::S3 { .. } => {
+                return Err(PipelineError::configuration(format!(
+                    "Destination '{}' needs S3 support, which requires the aws feature", dest_name
+                )));
+            }
             DestinationType::Http { .. } => {
                 self.initialize_http(dest_name, dest_config).await?
             }
+            DestinationType::SplunkHec { .. } | DestinationType::Webhook { .. } => {
+                self.initialize_forwarder(dest_name, dest_config).await?
+            }
             DestinationType::Custom { .. } => {
                 // TODO: Implement custom destination initialization
                 warn!("Custom destination type not yet implemented");
@@ -191,9 +252,27 @@ impl StorageManager {
             _ => return Err(PipelineError::configuration("Invalid destination type for ClickHouse")),
         };
         
+        // The native protocol only compresses inserts with LZ4
+        let compression = match &dest_config.config.compression {
+            None => clickhouse::Compression::None,
+            Some(CompressionType::Lz4) => clickhouse::Compression::Lz4,
+            Some(other) => {
+                warn!("ClickHouse destination {} supports lz4 compression only, using it instead of {:?}", dest_name, other);
+                clickhouse::Compression::Lz4
+            }
+        };
+        
         let client = ClickHouseClient::default()
             .with_url(connection_string)
-            .with_database(database);
+            .with_database(database)
+            .with_compression(compression);
+        
+        // Registered before connecting so a failover member that is down now
+        // still gets a batcher and health probes
+        {
+            let mut clients_guard = self.clickhouse_clients.write().await;
+            clients_guard.insert(dest_name.to_string(), client.clone());
+        }
         
         // Test connection
         client.query("SELECT 1").execute().await
@@ -206,11 +285,6 @@ impl StorageManager {
         };
         self.create_clickhouse_table(&client, table_name).await?;
         
-        {
-            let mut clients_guard = self.clickhouse_clients.write().await;
-            clients_guard.insert(dest_name.to_string(), client);
-        }
-        
         Ok(())
     }
     
@@ -289,6 +363,13 @@ impl StorageManager {
             DestinationType::Kafka { brokers, .. } => brokers.join(","),
             _ => return Err(PipelineError::configuration("Invalid destination type for Kafka")),
         };
+        let compression = match &dest_config.config.compression {
+            None => "none",
+            Some(CompressionType::Gzip) => "gzip",
+            Some(CompressionType::Zstd) => "zstd",
+            Some(CompressionType::Lz4) => "lz4",
+            Some(CompressionType::Snappy) => "snappy",
+        };
         
         // Enhanced Kafka producer with exactly-once delivery guarantees
         let producer: FutureProducer = ClientConfig::new()
@@ -297,7 +378,7 @@ impl StorageManager {
             .set("acks", "all")                       // Wait for all replicas
             .set("retries", "2147483647")             // Infinite retries
             .set("max.in.flight.requests.per.connection", "5")
-            .set("compression.type", "lz4")           // High performance compression
+            .set("compression.type", compression)
             .set("batch.size", "65536")              // 64KB batches
             .set("linger.ms", "5")                   // Low latency
             .set("queue.buffering.max.kbytes", "131072")  // 128MB buffer
@@ -324,17 +405,18 @@ impl StorageManager {
         let client = RedisClient::open(connection_string.as_str())
             .map_err(|e| PipelineError::configuration(format!("Failed to create Redis client: {}", e)))?;
         
+        // Opening a client does not connect, so register it before the ping
+        {
+            let mut clients_guard = self.redis_clients.write().await;
+            clients_guard.insert(dest_name.to_string(), client.clone());
+        }
+        
         // Test connection
         let mut conn = client.get_connection()
             .map_err(|e| PipelineError::connection(format!("Redis connection failed: {}", e)))?;
         let _: String = redis::cmd("PING").query(&mut conn)
             .map_err(|e| PipelineError::connection(format!("Redis ping failed: {}", e)))?;
         
-        {
-            let mut clients_guard = self.redis_clients.write().await;
-            clients_guard.insert(dest_name.to_string(), client);
-        }
-        
         info!("Redis client for '{}' initialized successfully", dest_name);
         Ok(())
     }
@@ -345,16 +427,17 @@ impl StorageManager {
             _ => return Err(PipelineError::configuration("Invalid destination type for File")),
         };
         
-        let file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(file_path)
-            .await
-            .map_err(|e| PipelineError::io(format!("Failed to open file {}: {}", file_path, e)))?;
+        // Parquet destinations write parts under `path`; their batcher opens them
+        if dest_config.config.format == DataFormat::Parquet {
+            return Ok(());
+        }
         
-        {
-            let mut files_guard = self.file_handles.write().await;
-            files_guard.insert(dest_name.to_string(), file);
+        file_sink::validate_compression(dest_config.config.compression.as_ref())
+            .map_err(|e| PipelineError::configuration(format!("Destination '{}': {}", dest_name, e)))?;
+        let root = PathTemplate::new(file_path).root();
+        if !root.as_os_str().is_empty() {
+            tokio::fs::create_dir_all(&root).await
+                .map_err(|e| PipelineError::io(format!("Failed to create directory {}: {}", root.display(), e)))?;
         }
         
         Ok(())
@@ -362,10 +445,16 @@ impl StorageManager {
     
 
     
+    /// Queue an event for a destination and wait until the batch holding it has been written.
     pub async fn store_event(&self, event: &PipelineEvent, destination: &str) -> Result<()> {
-        let start_time = std::time::Instant::now();
-        
-        debug!("Storing event {} to destination: {}", event.id, destination);
+        self.submit_event(event, destination).await?.wait().await
+    }
+    
+    /// Queue an event for a destination's next batch. The returned delivery
+    /// resolves once that batch is written, so callers can carry on with
+    /// other events in the meantime.
+    pub async fn submit_event(&self, event: &PipelineEvent, destination: &str) -> Result<Delivery> {
+        debug!("Queueing event {} for destination: {}", event.id, destination);
         
         let dest_config = self.config.destinations.get(destination)
             .ok_or_else(|| PipelineError::not_found(format!("Destination '{}' not found", destination)))?;
@@ -374,162 +463,154 @@ impl StorageManager {
             return Err(PipelineError::bad_request(format!("Destination '{}' is disabled", destination)));
         }
         
-        let result = match &dest_config.destination_type {
-            DestinationType::ClickHouse { .. } => {
-                self.store_to_clickhouse(event, destination, dest_config).await
-            }
-            DestinationType::Kafka { .. } => {
-                self.store_to_kafka(event, destination, dest_config).await
-            }
-            DestinationType::Redis { .. } => {
-                self.store_to_redis(event, destination, dest_config).await
-            }
-            DestinationType::File { .. } => {
-                self.store_to_file(event, destination, dest_config).await
+        let batchers_guard = self.batchers.read().await;
+        let batcher = batchers_guard.get(destination)
+            .ok_or_else(|| PipelineError::not_found(format!("Destination '{}' is not accepting events", destination)))?;
+        batcher.submit(event.clone()).await
+    }
+    
+    async fn start_batchers(&self, group_members: &HashSet<&str>) -> Result<()> {
+        let mut batchers_guard = self.batchers.write().await;
+        for (dest_name, dest_config) in &self.config.destinations {
+            if !dest_config.enabled {
+                continue;
             }
-            DestinationType::S3 { .. } => {
-                self.store_to_s3(event, destination, dest_config).await
+            let writer = match self.sink_writer(dest_name, dest_config).await {
+                Ok(Some(writer)) => writer,
+                Ok(None) => continue,
+                Err(e) if group_members.contains(dest_name.as_str()) => {
+                    warn!("Destination group member {} will not accept events: {}", dest_name, e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            let sink = DestinationSink {
+                destination: dest_name.clone(),
+                writer,
+                compression: dest_config.config.compression.clone(),
+                stats: self.stats.clone(),
+            };
+            batchers_guard.insert(dest_name.clone(), Batcher::spawn(
+                dest_name,
+                BatchSettings::from_destination(dest_config),
+                sink,
+                Some(self.health.clone()),
+            ));
+        }
+        Ok(())
+    }
+    
+    // What a destination's batcher writes with; custom destinations have nothing yet
+    async fn sink_writer(&self, dest_name: &str, dest_config: &DataDestination) -> Result<Option<SinkWriter>> {
+        let compression = dest_config.config.compression.as_ref();
+        let missing = |kind: &str| PipelineError::not_found(format!("{} for '{}' not found", kind, dest_name));
+        
+        let writer = match &dest_config.destination_type {
+            DestinationType::ClickHouse { table, .. } => SinkWriter::ClickHouse {
+                client: self.clickhouse_clients.read().await.get(dest_name).cloned()
+                    .ok_or_else(|| missing("ClickHouse client"))?,
+                table: table.clone(),
+            },
+            DestinationType::Kafka { topic, .. } => SinkWriter::Kafka {
+                producer: self.kafka_producers.read().await.get(dest_name).cloned()
+                    .ok_or_else(|| missing("Kafka producer"))?,
+                topic: topic.clone(),
+            },
+            DestinationType::Redis { key_pattern, ttl, .. } => {
+                if compression.is_some() {
+                    warn!("Redis destination {} stores events uncompressed", dest_name);
+                }
+                SinkWriter::Redis {
+                    client: self.redis_clients.read().await.get(dest_name).cloned()
+                        .ok_or_else(|| missing("Redis client"))?,
+                    key_pattern: key_pattern.clone(),
+                    ttl: *ttl,
+                }
             }
-            DestinationType::Http { .. } => {
-                self.store_to_http(event, destination, dest_config).await
+            DestinationType::File { path, rotation } if dest_config.config.format == DataFormat::Parquet => {
+                SinkWriter::ParquetFile {
+                    archive: ParquetArchive::new(
+                        DirectoryStore::open(path)?,
+                        dest_config.config.partitioning.clone(),
+                        RollPolicy::from_rotation(rotation),
+                        compression,
+                    ),
+                }
             }
-            DestinationType::Custom { .. } => {
-                // TODO: Implement custom storage
-                Err(PipelineError::internal("Custom storage not yet implemented"))
+            DestinationType::File { path, rotation } => SinkWriter::File {
+                files: RotatingFiles::new(path, rotation, compression, self.file_reopen.clone())?,
+            },
+            #[cfg(feature = "aws")]
+            DestinationType::S3 { bucket, prefix, rotation, .. } if dest_config.config.format == DataFormat::Parquet => {
+                let store = S3PartStore {
+                    client: self.s3_clients.read().await.get(dest_name).cloned()
+                        .ok_or_else(|| missing("S3 client"))?,
+                    bucket: bucket.clone(),
+                    prefix: prefix.trim_end_matches('/').to_string(),
+                };
+                SinkWriter::ParquetS3 {
+                    archive: ParquetArchive::new(
+                        store,
+                        dest_config.config.partitioning.clone(),
+                        rotation.as_ref().map(RollPolicy::from_rotation).unwrap_or_default(),
+                        compression,
+                    ),
+                }
             }
-        };
-        
-        let storage_time = start_time.elapsed().as_millis() as f64;
-        
-        match result {
-            Ok(bytes_stored) => {
-                self.update_storage_stats(destination, bytes_stored, storage_time).await;
-                debug!("Event {} stored successfully to {} in {:.2}ms", 
-                       event.id, destination, storage_time);
-                Ok(())
+            #[cfg(feature = "aws")]
+            DestinationType::S3 { bucket, prefix, .. } => {
+                sink::compress(b"", compression)
+                    .map_err(|e| PipelineError::configuration(format!("Destination '{}': {}", dest_name, e)))?;
+                SinkWriter::S3 {
+                    client: self.s3_clients.read().await.get(dest_name).cloned()
+                        .ok_or_else(|| missing("S3 client"))?,
+                    bucket: bucket.clone(),
+                    prefix: prefix.trim_end_matches('/').to_string(),
+                }
             }
-            Err(e) => {
-                error!("Failed to store event {} to {}: {}", event.id, destination, e);
-                self.increment_error_count(destination).await;
-                Err(e)
+            #[cfg(not(feature = "aws"))]
+            DestinationType::S3 { .. } => return Ok(None),
+            DestinationType::Http { endpoint, method, headers } => {
+                let content_encoding = match compression {
+                    None => None,
+                    Some(CompressionType::Gzip) => Some("gzip"),
+                    Some(CompressionType::Zstd) => Some("zstd"),
+                    Some(other) => {
+                        return Err(PipelineError::configuration(format!(
+                            "HTTP destination '{}' supports gzip or zstd compression, not {:?}", dest_name, other
+                        )));
+                    }
+                };
+                let method = match method.to_uppercase().as_str() {
+                    "POST" => reqwest::Method::POST,
+                    "PUT" => reqwest::Method::PUT,
+                    "PATCH" => reqwest::Method::PATCH,
+                    _ => return Err(PipelineError::configuration(format!("Unsupported HTTP method: {}", method))),
+                };
+                SinkWriter::Http {
+                    client: self.http_clients.read().await.get(dest_name).cloned()
+                        .ok_or_else(|| missing("HTTP client"))?,
+                    endpoint: endpoint.clone(),
+                    method,
+                    headers: headers.clone(),
+                    content_encoding,
+                }
             }
-        }
-    }
-    
-    async fn store_to_clickhouse(&self, event: &PipelineEvent, destination: &str, dest_config: &DataDestination) -> Result<u64> {
-        let clients_guard = self.clickhouse_clients.read().await;
-        let client = clients_guard.get(destination)
-            .ok_or_else(|| PipelineError::not_found(format!("ClickHouse client for '{}' not found", destination)))?;
-        
-        let table_name = match &dest_config.destination_type {
-            DestinationType::ClickHouse { table, .. } => table,
-            _ => return Err(PipelineError::configuration("Invalid destination type for ClickHouse")),
-        };
-        
-        // Convert PipelineEvent to SiemEvent
-        let siem_event = self.convert_to_siem_event(event)?;
-        
-        let mut insert = client.insert(table_name)?;
-        insert.write(&siem_event).await
-            .map_err(|e| PipelineError::database(format!("ClickHouse insert failed: {}", e)))?;
-        insert.end().await
-            .map_err(|e| PipelineError::database(format!("ClickHouse insert commit failed: {}", e)))?;
-        
-        // Estimate bytes stored (rough calculation)
-        let bytes_stored = siem_event.message.len() + siem_event.raw_message.len() + 200; // Approximate overhead
-        
-        Ok(bytes_stored as u64)
-    }
-    
-    async fn store_to_kafka(&self, event: &PipelineEvent, destination: &str, dest_config: &DataDestination) -> Result<u64> {
-        let producers_guard = self.kafka_producers.read().await;
-        let producer = producers_guard.get(destination)
-            .ok_or_else(|| PipelineError::not_found(format!("Kafka producer for '{}' not found", destination)))?;
-        
-        let topic = match &dest_config.destination_type {
-            DestinationType::Kafka { topic, .. } => topic,
-            _ => return Err(PipelineError::configuration("Invalid destination type for Kafka")),
-        };
-        
-        // Serialize event to JSON
-        let event_json = serde_json::to_string(event)
-            .map_err(|e| PipelineError::serialization(format!("Failed to serialize event: {}", e)))?;
-        
-        let event_id = event.id.to_string();
-        let record = FutureRecord::to(topic)
-            .key(&event_id)
-            .payload(&event_json);
-        
-        producer.send(record, tokio::time::Duration::from_secs(5)).await
-            .map_err(|e| PipelineError::kafka(format!("Kafka send failed: {:?}", e)))?;
-        
-        Ok(event_json.len() as u64)
-    }
-    
-    async fn store_to_redis(&self, event: &PipelineEvent, destination: &str, dest_config: &DataDestination) -> Result<u64> {
-        let clients_guard = self.redis_clients.read().await;
-        let client = clients_guard.get(destination)
-            .ok_or_else(|| PipelineError::not_found(format!("Redis client for '{}' not found", destination)))?;
-        
-        let mut conn = client.get_connection()
-            .map_err(|e| PipelineError::connection(format!("Redis connection failed: {}", e)))?;
-        
-        // Get configuration parameters from Redis destination type
-        let (key_pattern, ttl) = match &dest_config.destination_type {
-            DestinationType::Redis { key_pattern, ttl, .. } => (key_pattern.as_str(), *ttl),
-            _ => return Err(PipelineError::configuration("Invalid destination type for Redis")),
+            DestinationType::SplunkHec { .. } => SinkWriter::SplunkHec {
+                writer: SplunkHecWriter::from_destination(
+                    self.http_clients.read().await.get(dest_name).cloned().ok_or_else(|| missing("HTTP client"))?,
+                    &dest_config.destination_type,
+                )?,
+            },
+            DestinationType::Webhook { .. } => SinkWriter::Webhook {
+                writer: WebhookWriter::from_destination(
+                    self.http_clients.read().await.get(dest_name).cloned().ok_or_else(|| missing("HTTP client"))?,
+                    &dest_config.destination_type,
+                )?,
+            },
+            DestinationType::Custom { .. } => return Ok(None),
         };
-        
-        // Generate Redis key
-        let event_id = event.id.to_string();
-        let timestamp_str = event.timestamp.timestamp().to_string();
-        let key = key_pattern
-            .replace("{timestamp}", &timestamp_str)
-            .replace("{source}", &event.source)
-            .replace("{id}", &event_id);
-        
-        // Serialize event
-        let event_json = serde_json::to_string(event)
-            .map_err(|e| PipelineError::serialization(format!("Failed to serialize event: {}", e)))?;
-        
-        // Store in Redis with optional TTL
-        if let Some(ttl_seconds) = ttl {
-            conn.set_ex::<_, _, ()>(&key, &event_json, ttl_seconds)
-                .map_err(|e| PipelineError::connection(format!("Redis SET with TTL failed: {}", e)))?;
-        } else {
-            conn.set::<_, _, ()>(&key, &event_json)
-                .map_err(|e| PipelineError::connection(format!("Redis SET failed: {}", e)))?;
-        }
-        
-        // Add to real-time stream for UI
-        let stream_key = format!("siem:stream:{}", event.source);
-        conn.xadd::<_, _, _, _, ()>(&stream_key, "*", &[("event", &event_json)])
-            .map_err(|e| PipelineError::connection(format!("Redis XADD failed: {}", e)))?;
-        
-        // Trim stream to keep only recent events (last 10000)
-        let _: std::result::Result<i32, redis::RedisError> = conn.xtrim(&stream_key, redis::streams::StreamMaxlen::Approx(10000));
-        
-        debug!("Event {} stored to Redis with key: {}", event.id, key);
-        Ok(event_json.len() as u64)
-    }
-    
-    async fn store_to_file(&self, event: &PipelineEvent, destination: &str, _dest_config: &DataDestination) -> Result<u64> {
-        let mut files_guard = self.file_handles.write().await;
-        let file = files_guard.get_mut(destination)
-            .ok_or_else(|| PipelineError::not_found(format!("File handle for '{}' not found", destination)))?;
-        
-        // Serialize event to JSON and write to file
-        let event_json = serde_json::to_string(event)
-            .map_err(|e| PipelineError::serialization(format!("Failed to serialize event: {}", e)))?;
-        
-        let line = format!("{}\n", event_json);
-        file.write_all(line.as_bytes()).await
-            .map_err(|e| PipelineError::io(format!("Failed to write to file: {}", e)))?;
-        
-        file.flush().await
-            .map_err(|e| PipelineError::io(format!("Failed to flush file: {}", e)))?;
-        
-        Ok(line.len() as u64)
+        Ok(Some(writer))
     }
 
     #[cfg(feature = "aws")]
@@ -589,91 +670,34 @@ impl StorageManager {
         Ok(())
     }
 
-    #[cfg(feature = "aws")]
-    async fn store_to_s3(&self, event: &PipelineEvent, destination: &str, dest_config: &DataDestination) -> Result<u64> {
-        let clients_guard = self.s3_clients.read().await;
-        let client = clients_guard.get(destination)
-            .ok_or_else(|| PipelineError::not_found(format!("S3 client for '{}' not found", destination)))?;
-        
-        let (bucket, key_prefix) = match &dest_config.destination_type {
-            DestinationType::S3 { bucket, prefix, .. } => (bucket, prefix.as_str()),
-            _ => return Err(PipelineError::configuration("Invalid destination type for S3")),
-        };
-        
-        // Convert event to JSON
-        let event_json = serde_json::to_string(&event)
-            .map_err(|e| PipelineError::serialization(format!("Failed to serialize event: {}", e)))?;
-        
-        // Generate S3 key with timestamp and event ID
-        let timestamp = event.timestamp.format("%Y/%m/%d/%H");
-        let key = format!("{}/{}/{}.json", key_prefix, timestamp, event.id);
-        
-        // Upload to S3
-        client.put_object()
-            .bucket(bucket)
-            .key(&key)
-            .body(aws_sdk_s3::primitives::ByteStream::from(event_json.clone().into_bytes()))
-            .content_type("application/json")
-            .send()
-            .await
-            .map_err(|e| PipelineError::storage(format!("S3 upload failed: {}", e)))?;
-        
-        debug!("Event {} stored to S3 bucket '{}' with key '{}'", event.id, bucket, key);
+    async fn initialize_forwarder(&self, dest_name: &str, dest_config: &DataDestination) -> Result<()> {
+        info!("Initializing forwarding destination: {}", dest_name);
         
-        Ok(event_json.len() as u64)
-    }
-
-    async fn store_to_http(&self, event: &PipelineEvent, destination: &str, dest_config: &DataDestination) -> Result<u64> {
-        let clients_guard = self.http_clients.read().await;
-        let client = clients_guard.get(destination)
-            .ok_or_else(|| PipelineError::not_found(format!("HTTP client for '{}' not found", destination)))?;
+        let client = forwarder::http_client()?;
         
-        let (endpoint_url, method, headers) = match &dest_config.destination_type {
-            DestinationType::Http { endpoint, method, headers, .. } => {
-                (endpoint, method.as_str(), headers)
+        // Splunk exposes an unauthenticated health endpoint; webhooks have nothing to probe
+        if let DestinationType::SplunkHec { endpoint, .. } = &dest_config.destination_type {
+            let health_url = format!("{}/services/collector/health", endpoint.trim_end_matches('/'));
+            match client.get(&health_url).send().await {
+                Ok(response) if response.status().is_success() => {
+                    info!("Splunk HEC '{}' is accessible", endpoint);
+                }
+                Ok(response) => {
+                    warn!("Splunk HEC '{}' returned status: {}", endpoint, response.status());
+                    self.update_connection_status(dest_name, ConnectionStatus::Error(format!("HTTP {}", response.status()))).await;
+                }
+                Err(e) => {
+                    warn!("Splunk HEC '{}' health check failed: {}", endpoint, e);
+                    self.update_connection_status(dest_name, ConnectionStatus::Error(e.to_string())).await;
+                }
             }
-            _ => return Err(PipelineError::configuration("Invalid destination type for HTTP")),
-        };
-        
-        // Convert event to JSON
-        let event_json = serde_json::to_string(&event)
-            .map_err(|e| PipelineError::serialization(format!("Failed to serialize event: {}", e)))?;
-        
-        // Build HTTP request
-        let mut request = match method.to_uppercase().as_str() {
-            "POST" => client.post(endpoint_url),
-            "PUT" => client.put(endpoint_url),
-            "PATCH" => client.patch(endpoint_url),
-            _ => return Err(PipelineError::configuration(format!("Unsupported HTTP method: {}", method))),
-        };
-        
-        // Add headers if specified
-        for (key, value) in headers {
-            request = request.header(key, value);
         }
         
-        // Set content type and send request
-        let response = request
-            .header("Content-Type", "application/json")
-            .body(event_json.clone())
-            .send()
-            .await
-            .map_err(|e| PipelineError::http(format!("HTTP request failed: {}", e)))?;
-        
-        if !response.status().is_success() {
-            return Err(PipelineError::storage(format!(
-                "HTTP endpoint returned error status: {} - {}", 
-                response.status(),
-                response.text().await.unwrap_or_default()
-            )));
-        }
-        
-        debug!("Event {} sent to HTTP endpoint '{}' with status {}", event.id, endpoint_url, response.status());
-        
-        Ok(event_json.len() as u64)
+        self.http_clients.write().await.insert(dest_name.to_string(), client);
+        Ok(())
     }
 
-    fn convert_to_siem_event(&self, event: &PipelineEvent) -> Result<SiemEvent> {
+    fn convert_to_siem_event(event: &PipelineEvent) -> Result<SiemEvent> {
         let source_ip = event.metadata.get("source_ip").unwrap_or(&"0.0.0.0".to_string()).clone();
         let source_port: u16 = event.metadata.get("source_port")
             .and_then(|p| p.parse().ok())
@@ -715,34 +739,59 @@ impl StorageManager {
     
 
     
-    async fn update_storage_stats(&self, destination: &str, bytes_stored: u64, storage_time_ms: f64) {
+    async fn update_connection_status(&self, destination: &str, status: ConnectionStatus) {
         let mut stats_guard = self.stats.write().await;
         if let Some(stats) = stats_guard.get_mut(destination) {
-            stats.events_stored += 1;
-            stats.bytes_stored += bytes_stored;
-            stats.last_storage_time = Some(Utc::now());
-            
-            // Update moving average of storage time
-            if stats.avg_storage_time_ms == 0.0 {
-                stats.avg_storage_time_ms = storage_time_ms;
-            } else {
-                stats.avg_storage_time_ms = (stats.avg_storage_time_ms + storage_time_ms) / 2.0;
-            }
+            stats.connection_status = status;
         }
     }
     
-    async fn increment_error_count(&self, destination: &str) {
-        let mut stats_guard = self.stats.write().await;
-        if let Some(stats) = stats_guard.get_mut(destination) {
-            stats.errors += 1;
-        }
+    /// Health changes of each destination, from its batch writes, circuit
+    /// breaker and periodic health probes.
+    pub fn subscribe_health(&self) -> broadcast::Receiver<(String, DestinationHealth)> {
+        self.health.subscribe()
     }
     
-    async fn update_connection_status(&self, destination: &str, status: ConnectionStatus) {
-        let mut stats_guard = self.stats.write().await;
-        if let Some(stats) = stats_guard.get_mut(destination) {
-            stats.connection_status = status;
+    /// Last probe result per destination, to seed a new subscriber
+    pub async fn probed_health(&self) -> HashMap<String, DestinationHealth> {
+        self.probed_health.read().await.clone()
+    }
+    
+    // Connection-level checks for destinations that have something to probe;
+    // HTTP and webhook destinations rely on their writes and circuit breaker
+    async fn health_probes(&self) -> Vec<(String, HealthProbe)> {
+        let mut probes = Vec::new();
+        for (dest_name, dest_config) in &self.config.destinations {
+            if !dest_config.enabled {
+                continue;
+            }
+            let probe = match &dest_config.destination_type {
+                DestinationType::ClickHouse { .. } => self.clickhouse_clients.read().await.get(dest_name).cloned()
+                    .map(HealthProbe::ClickHouse),
+                DestinationType::Kafka { .. } => self.kafka_producers.read().await.get(dest_name).cloned()
+                    .map(HealthProbe::Kafka),
+                DestinationType::Redis { .. } => self.redis_clients.read().await.get(dest_name).cloned()
+                    .map(HealthProbe::Redis),
+                DestinationType::File { path, .. } => {
+                    let root = if dest_config.config.format == DataFormat::Parquet {
+                        PathBuf::from(path)
+                    } else {
+                        PathTemplate::new(path).root()
+                    };
+                    (!root.as_os_str().is_empty()).then_some(HealthProbe::Directory(root))
+                }
+                DestinationType::SplunkHec { endpoint, .. } => self.http_clients.read().await.get(dest_name).cloned()
+                    .map(|client| HealthProbe::Url(client, format!("{}/services/collector/health", endpoint.trim_end_matches('/')))),
+                #[cfg(feature = "aws")]
+                DestinationType::S3 { bucket, .. } => self.s3_clients.read().await.get(dest_name).cloned()
+                    .map(|client| HealthProbe::S3 { client, bucket: bucket.clone() }),
+                _ => None,
+            };
+            if let Some(probe) = probe {
+                probes.push((dest_name.clone(), probe));
+            }
         }
+        probes
     }
     
     pub async fn get_stats(&self) -> HashMap<String, StorageStats> {
@@ -917,14 +966,14 @@ impl StorageManager {
     pub async fn shutdown(&self) -> Result<()> {
         info!("Shutting down storage manager");
         
-        // Close file handles
-        {
-            let mut files_guard = self.file_handles.write().await;
-            for (dest_name, mut file) in files_guard.drain() {
-                if let Err(e) = file.flush().await {
-                    warn!("Failed to flush file for destination {}: {}", dest_name, e);
-                }
-            }
+        if let Some(probes) = &self.health_probes {
+            probes.abort();
+        }
+        
+        // Write out queued batches; each destination flushes its files as its batcher stops
+        let batchers: Vec<Batcher> = self.batchers.write().await.drain().map(|(_, batcher)| batcher).collect();
+        for batcher in batchers {
+            batcher.close().await;
         }
         
         info!("Storage manager shutdown complete");
@@ -932,6 +981,363 @@ impl StorageManager {
     }
 }
 
+// Upper bound on a single probe, so one hung backend cannot stall a round
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+enum HealthProbe {
+    ClickHouse(ClickHouseClient),
+    Kafka(FutureProducer),
+    Redis(RedisClient),
+    Directory(PathBuf),
+    Url(HttpClient, String),
+    #[cfg(feature = "aws")]
+    S3 { client: S3Client, bucket: String },
+}
+
+impl HealthProbe {
+    async fn check(&self) -> std::result::Result<(), String> {
+        match self {
+            HealthProbe::ClickHouse(client) => client.query("SELECT 1").execute().await
+                .map_err(|e| e.to_string()),
+            // Both client libraries only offer blocking calls for these
+            HealthProbe::Kafka(producer) => {
+                let producer = producer.clone();
+                tokio::task::spawn_blocking(move || {
+                    producer.client().fetch_metadata(None, PROBE_TIMEOUT).map(|_| ()).map_err(|e| e.to_string())
+                }).await.map_err(|e| e.to_string())?
+            }
+            HealthProbe::Redis(client) => {
+                let client = client.clone();
+                tokio::task::spawn_blocking(move || {
+                    let mut conn = client.get_connection().map_err(|e| e.to_string())?;
+                    redis::cmd("PING").query::<String>(&mut conn).map(|_| ()).map_err(|e| e.to_string())
+                }).await.map_err(|e| e.to_string())?
+            }
+            HealthProbe::Directory(path) => {
+                let metadata = tokio::fs::metadata(path).await.map_err(|e| format!("{}: {}", path.display(), e))?;
+                if metadata.permissions().readonly() {
+                    return Err(format!("{} is read-only", path.display()));
+                }
+                Ok(())
+            }
+            HealthProbe::Url(client, url) => match client.get(url).send().await {
+                Ok(response) if response.status().is_success() => Ok(()),
+                Ok(response) => Err(format!("HTTP {}", response.status())),
+                Err(e) => Err(e.to_string()),
+            },
+            #[cfg(feature = "aws")]
+            HealthProbe::S3 { client, bucket } => client.head_bucket().bucket(bucket).send().await
+                .map(|_| ()).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+// Records a probe result and announces it if it changed. Batchers publish
+// their own changes on the same channel, so only changes are sent here too.
+async fn publish_health(
+    states: &RwLock<HashMap<String, DestinationHealth>>,
+    sender: &HealthSender,
+    destination: &str,
+    health: DestinationHealth,
+) {
+    let mut states = states.write().await;
+    if states.get(destination) != Some(&health) {
+        states.insert(destination.to_string(), health.clone());
+        let _ = sender.send((destination.to_string(), health));
+    }
+}
+
+fn spawn_health_probes(
+    probes: Vec<(String, HealthProbe)>,
+    interval: Duration,
+    stats: Arc<RwLock<HashMap<String, StorageStats>>>,
+    states: Arc<RwLock<HashMap<String, DestinationHealth>>>,
+    sender: HealthSender,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let results = futures::future::join_all(probes.iter().map(|(name, probe)| async move {
+                let result = tokio::time::timeout(PROBE_TIMEOUT, probe.check()).await
+                    .unwrap_or_else(|_| Err("timed out".to_string()));
+                (name, result)
+            })).await;
+            
+            for (name, result) in results {
+                let (health, status) = match result {
+                    Ok(()) => (DestinationHealth::Healthy, ConnectionStatus::Connected),
+                    Err(e) => {
+                        debug!("Health probe for destination {} failed: {}", name, e);
+                        (DestinationHealth::Unhealthy, ConnectionStatus::Error(e))
+                    }
+                };
+                if states.read().await.get(name) != Some(&health) {
+                    match &status {
+                        ConnectionStatus::Error(e) => warn!("Destination {} is unhealthy: {}", name, e),
+                        _ => info!("Destination {} is healthy", name),
+                    }
+                }
+                if let Some(dest_stats) = stats.write().await.get_mut(name) {
+                    dest_stats.connection_status = status;
+                }
+                publish_health(&states, &sender, name, health).await;
+            }
+        }
+    })
+}
+
+// A destination's connection, owned by its batcher task
+enum SinkWriter {
+    ClickHouse { client: ClickHouseClient, table: String },
+    Kafka { producer: FutureProducer, topic: String },
+    Redis { client: RedisClient, key_pattern: String, ttl: Option<u64> },
+    File { files: RotatingFiles },
+    ParquetFile { archive: ParquetArchive<DirectoryStore> },
+    #[cfg(feature = "aws")]
+    S3 { client: S3Client, bucket: String, prefix: String },
+    #[cfg(feature = "aws")]
+    ParquetS3 { archive: ParquetArchive<S3PartStore> },
+    Http {
+        client: HttpClient,
+        endpoint: String,
+        method: reqwest::Method,
+        headers: HashMap<String, String>,
+        content_encoding: Option<&'static str>,
+    },
+    SplunkHec { writer: SplunkHecWriter },
+    Webhook { writer: WebhookWriter },
+}
+
+impl SinkWriter {
+    async fn write(&mut self, events: &[PipelineEvent], compression: Option<&CompressionType>) -> Result<u64> {
+        match self {
+            SinkWriter::ClickHouse { client, table } => {
+                let mut insert = client.insert(table)?;
+                let mut bytes_stored = 0;
+                for event in events {
+                    let siem_event = StorageManager::convert_to_siem_event(event)?;
+                    // Estimate bytes stored (rough calculation)
+                    bytes_stored += siem_event.message.len() + siem_event.raw_message.len() + 200;
+                    insert.write(&siem_event).await
+                        .map_err(|e| PipelineError::database(format!("ClickHouse insert failed: {}", e)))?;
+                }
+                insert.end().await
+                    .map_err(|e| PipelineError::database(format!("ClickHouse insert commit failed: {}", e)))?;
+                Ok(bytes_stored as u64)
+            }
+            SinkWriter::Kafka { producer, topic } => {
+                let records = events.iter()
+                    .map(|event| Ok((event.id.to_string(), serde_json::to_string(event)?)))
+                    .collect::<Result<Vec<_>>>()?;
+                
+                // The producer batches and compresses; wait for every delivery report
+                let sends = records.iter().map(|(key, payload)| {
+                    producer.send(FutureRecord::to(topic).key(key).payload(payload), tokio::time::Duration::from_secs(5))
+                });
+                for result in futures::future::join_all(sends).await {
+                    result.map_err(|(e, _)| PipelineError::kafka(format!("Kafka send failed: {}", e)))?;
+                }
+                Ok(records.iter().map(|(_, payload)| payload.len() as u64).sum())
+            }
+            SinkWriter::Redis { client, key_pattern, ttl } => {
+                let mut conn = client.get_connection()
+                    .map_err(|e| PipelineError::connection(format!("Redis connection failed: {}", e)))?;
+                
+                let mut pipe = redis::pipe();
+                let mut stream_keys = HashSet::new();
+                let mut bytes_stored = 0;
+                for event in events {
+                    let key = key_pattern
+                        .replace("{timestamp}", &event.timestamp.timestamp().to_string())
+                        .replace("{source}", &event.source)
+                        .replace("{id}", &event.id.to_string());
+                    let event_json = serde_json::to_string(event)?;
+                    
+                    match ttl {
+                        Some(ttl_seconds) => pipe.set_ex(&key, &event_json, *ttl_seconds).ignore(),
+                        None => pipe.set(&key, &event_json).ignore(),
+                    };
+                    
+                    // Add to real-time stream for UI
+                    let stream_key = format!("siem:stream:{}", event.source);
+                    pipe.xadd(&stream_key, "*", &[("event", &event_json)]).ignore();
+                    stream_keys.insert(stream_key);
+                    bytes_stored += event_json.len() as u64;
+                }
+                
+                // Trim streams to keep only recent events (last 10000)
+                for stream_key in &stream_keys {
+                    pipe.xtrim(stream_key, redis::streams::StreamMaxlen::Approx(10000)).ignore();
+                }
+                pipe.query::<()>(&mut conn)
+                    .map_err(|e| PipelineError::connection(format!("Redis pipeline failed: {}", e)))?;
+                Ok(bytes_stored)
+            }
+            SinkWriter::File { files } => files.write(events).await,
+            SinkWriter::ParquetFile { archive } => archive.write(events).await,
+            SinkWriter::SplunkHec { writer } => writer.write(events).await,
+            SinkWriter::Webhook { writer } => writer.write(events).await,
+            #[cfg(feature = "aws")]
+            SinkWriter::ParquetS3 { archive } => archive.write(events).await,
+            #[cfg(feature = "aws")]
+            SinkWriter::S3 { client, bucket, prefix } => {
+                // One object per hour present in the batch, named after its first
+                // event so a retried batch overwrites rather than duplicates
+                let mut partitions: BTreeMap<String, Vec<&PipelineEvent>> = BTreeMap::new();
+                for event in events {
+                    partitions.entry(event.timestamp.format("%Y/%m/%d/%H").to_string()).or_default().push(event);
+                }
+                
+                let mut bytes_stored = 0;
+                for (partition, partition_events) in partitions {
+                    let body = sink::compress(&to_ndjson(partition_events.iter().copied())?, compression)?;
+                    let key = format!("{}/{}/{}.jsonl{}", prefix, partition, partition_events[0].id, sink::extension(compression));
+                    bytes_stored += body.len() as u64;
+                    
+                    client.put_object()
+                        .bucket(bucket.as_str())
+                        .key(&key)
+                        .body(aws_sdk_s3::primitives::ByteStream::from(body))
+                        .content_type("application/x-ndjson")
+                        .send()
+                        .await
+                        .map_err(|e| PipelineError::storage(format!("S3 upload failed: {}", e)))?;
+                    debug!("Stored {} events to S3 bucket '{}' with key '{}'", partition_events.len(), bucket, key);
+                }
+                Ok(bytes_stored)
+            }
+            SinkWriter::Http { client, endpoint, method, headers, content_encoding } => {
+                let body = serde_json::to_vec(events)?;
+                let body = match content_encoding {
+                    Some(_) => sink::compress(&body, compression)?,
+                    None => body,
+                };
+                let bytes_stored = body.len() as u64;
+                
+                let mut request = client.request(method.clone(), endpoint.as_str())
+                    .header("Content-Type", "application/json");
+                if let Some(encoding) = content_encoding {
+                    request = request.header("Content-Encoding", *encoding);
+                }
+                for (key, value) in headers.iter() {
+                    request = request.header(key, value);
+                }
+                
+                let response = request.body(body).send().await
+                    .map_err(|e| PipelineError::http(format!("HTTP request failed: {}", e)))?;
+                if !response.status().is_success() {
+                    return Err(PipelineError::storage(format!(
+                        "HTTP endpoint returned error status: {} - {}",
+                        response.status(),
+                        response.text().await.unwrap_or_default()
+                    )));
+                }
+                Ok(bytes_stored)
+            }
+        }
+    }
+}
+
+impl SinkWriter {
+    // Files and Parquet parts roll on age even while no events arrive, and
+    // are flushed or finished on shutdown
+    async fn roll(&mut self, all: bool) -> Result<()> {
+        match self {
+            SinkWriter::File { files } => files.rotate(all).await,
+            SinkWriter::ParquetFile { archive } => archive.roll(all).await,
+            #[cfg(feature = "aws")]
+            SinkWriter::ParquetS3 { archive } => archive.roll(all).await,
+            _ => Ok(()),
+        }
+    }
+}
+
+// Parquet parts are built in memory and uploaded whole once finished
+#[cfg(feature = "aws")]
+struct S3PartStore {
+    client: S3Client,
+    bucket: String,
+    prefix: String,
+}
+
+#[cfg(feature = "aws")]
+#[async_trait::async_trait]
+impl PartStore for S3PartStore {
+    type Writer = Vec<u8>;
+
+    fn create(&mut self, _partition: &str, _name: &str) -> Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    async fn publish(&mut self, partition: &str, name: &str, body: Vec<u8>) -> Result<()> {
+        let key = format!("{}/{}/{}", self.prefix, partition, name);
+        self.client.put_object()
+            .bucket(self.bucket.as_str())
+            .key(&key)
+            .body(aws_sdk_s3::primitives::ByteStream::from(body))
+            .content_type("application/vnd.apache.parquet")
+            .send()
+            .await
+            .map_err(|e| PipelineError::storage(format!("S3 upload of {} failed: {}", key, e)))?;
+        Ok(())
+    }
+}
+
+fn to_ndjson<'a>(events: impl Iterator<Item = &'a PipelineEvent>) -> Result<Vec<u8>> {
+    let mut lines = Vec::new();
+    for event in events {
+        serde_json::to_writer(&mut lines, event)?;
+        lines.push(b'\n');
+    }
+    Ok(lines)
+}
+
+struct DestinationSink {
+    destination: String,
+    writer: SinkWriter,
+    compression: Option<CompressionType>,
+    stats: Arc<RwLock<HashMap<String, StorageStats>>>,
+}
+
+#[async_trait::async_trait]
+impl BatchWriter for DestinationSink {
+    async fn write_batch(&mut self, events: &[PipelineEvent]) -> Result<u64> {
+        let start_time = std::time::Instant::now();
+        let result = self.writer.write(events, self.compression.as_ref()).await;
+        let storage_time = start_time.elapsed().as_millis() as f64;
+        
+        let mut stats_guard = self.stats.write().await;
+        if let Some(stats) = stats_guard.get_mut(&self.destination) {
+            match &result {
+                Ok(bytes_stored) => {
+                    stats.events_stored += events.len() as u64;
+                    stats.bytes_stored += bytes_stored;
+                    stats.last_storage_time = Some(Utc::now());
+                    
+                    // Update moving average of batch storage time
+                    if stats.avg_storage_time_ms == 0.0 {
+                        stats.avg_storage_time_ms = storage_time;
+                    } else {
+                        stats.avg_storage_time_ms = (stats.avg_storage_time_ms + storage_time) / 2.0;
+                    }
+                }
+                Err(_) => stats.errors += 1,
+            }
+        }
+        result
+    }
+    
+    async fn idle(&mut self) -> Result<()> {
+        self.writer.roll(false).await
+    }
+    
+    async fn close(&mut self) -> Result<()> {
+        self.writer.roll(true).await
+    }
+}
+
 impl DestinationType {
     pub fn to_string(&self) -> &'static str {
         match self {
@@ -941,6 +1347,8 @@ impl DestinationType {
             DestinationType::S3 { .. } => "s3",
             DestinationType::File { .. } => "file",
             DestinationType::Http { .. } => "http",
+            DestinationType::SplunkHec { .. } => "splunk_hec",
+            DestinationType::Webhook { .. } => "webhook",
             DestinationType::Custom { .. } => "custom",
         }
     }
@@ -0,0 +1,564 @@
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDateTime, TimeZone, Utc};
+
+use crate::error::{Result, PipelineError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyslogFormat {
+    Rfc5424,
+    Rfc3164,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuredDataElement {
+    pub id: String,
+    pub params: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyslogMessage {
+    pub format: SyslogFormat,
+    pub priority: Option<u8>,
+    pub version: Option<u8>,
+    /// Device time with the offset it was sent (or assumed) in
+    pub timestamp: Option<DateTime<FixedOffset>>,
+    /// Timestamp text exactly as it appeared on the wire
+    pub raw_timestamp: Option<String>,
+    pub hostname: Option<String>,
+    pub app_name: Option<String>,
+    pub procid: Option<String>,
+    pub msgid: Option<String>,
+    pub structured_data: Vec<StructuredDataElement>,
+    pub message: String,
+}
+
+impl SyslogMessage {
+    pub fn facility(&self) -> Option<u8> {
+        self.priority.map(|p| p >> 3)
+    }
+
+    pub fn severity(&self) -> Option<u8> {
+        self.priority.map(|p| p & 7)
+    }
+}
+
+pub fn severity_name(severity: u8) -> &'static str {
+    match severity {
+        0 => "emergency",
+        1 => "alert",
+        2 => "critical",
+        3 => "error",
+        4 => "warning",
+        5 => "notice",
+        6 => "info",
+        7 => "debug",
+        _ => "unknown",
+    }
+}
+
+pub fn facility_name(facility: u8) -> &'static str {
+    const NAMES: [&str; 24] = [
+        "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news",
+        "uucp", "cron", "authpriv", "ftp", "ntp", "security", "console", "solaris-cron",
+        "local0", "local1", "local2", "local3", "local4", "local5", "local6", "local7",
+    ];
+    NAMES.get(facility as usize).copied().unwrap_or("unknown")
+}
+
+/// Parse a fixed UTC offset such as `UTC`, `Z`, `+05:30` or `-0800`.
+pub fn parse_utc_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("utc") || value.eq_ignore_ascii_case("gmt") || value == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    let value = value.strip_prefix("UTC").or_else(|| value.strip_prefix("GMT")).unwrap_or(value);
+    let (sign, digits) = match value.chars().next()? {
+        '+' => (1, &value[1..]),
+        '-' => (-1, &value[1..]),
+        _ => return None,
+    };
+    let digits = digits.replace(':', "");
+    let (hours, minutes) = match digits.len() {
+        1 | 2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (digits[..2].parse::<i32>().ok()?, digits[2..].parse::<i32>().ok()?),
+        _ => return None,
+    };
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Parse an RFC 5424 message, falling back to lenient RFC 3164 parsing.
+///
+/// `timezone` applies to RFC 3164 timestamps, which carry no offset, and `now`
+/// is used to infer their missing year.
+pub fn parse_syslog(line: &str, timezone: FixedOffset, now: DateTime<Utc>) -> Result<SyslogMessage> {
+    let line = line.trim_end_matches(['\r', '\n', '\0']);
+    if line.trim().is_empty() {
+        return Err(PipelineError::parsing("Empty syslog message"));
+    }
+
+    let (priority, rest) = match parse_priority(line) {
+        Some((priority, rest)) => (Some(priority), rest),
+        None => (None, line),
+    };
+
+    // RFC 5424: "<PRI>1 " followed by the header
+    if priority.is_some() {
+        if let Some(rest) = rest.strip_prefix("1 ") {
+            return parse_rfc5424(priority, rest);
+        }
+    }
+
+    Ok(parse_rfc3164(priority, rest, timezone, now))
+}
+
+fn parse_priority(line: &str) -> Option<(u8, &str)> {
+    let rest = line.strip_prefix('<')?;
+    let end = rest.find('>')?;
+    if end == 0 || end > 3 {
+        return None;
+    }
+    let priority = rest[..end].parse::<u8>().ok().filter(|p| *p <= 191)?;
+    Some((priority, &rest[end + 1..]))
+}
+
+fn nil(value: &str) -> Option<String> {
+    if value == "-" || value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+fn next_token(input: &str) -> (&str, &str) {
+    match input.find(' ') {
+        Some(index) => (&input[..index], &input[index + 1..]),
+        None => (input, ""),
+    }
+}
+
+fn parse_rfc5424(priority: Option<u8>, rest: &str) -> Result<SyslogMessage> {
+    let (timestamp_text, rest) = next_token(rest);
+    let (hostname, rest) = next_token(rest);
+    let (app_name, rest) = next_token(rest);
+    let (procid, rest) = next_token(rest);
+    let (msgid, rest) = next_token(rest);
+
+    let timestamp = match timestamp_text {
+        "-" => None,
+        text => Some(DateTime::parse_from_rfc3339(text).map_err(|e| {
+            PipelineError::parsing(format!("Invalid RFC 5424 timestamp '{}': {}", text, e))
+        })?),
+    };
+
+    let (structured_data, message) = parse_structured_data(rest)?;
+    let message = message.strip_prefix('\u{feff}').unwrap_or(message).to_string();
+
+    Ok(SyslogMessage {
+        format: SyslogFormat::Rfc5424,
+        priority,
+        version: Some(1),
+        timestamp,
+        raw_timestamp: nil(timestamp_text),
+        hostname: nil(hostname),
+        app_name: nil(app_name),
+        procid: nil(procid),
+        msgid: nil(msgid),
+        structured_data,
+        message,
+    })
+}
+
+/// Parse `-` or a run of `[id name="value" ...]` elements, returning the remaining message.
+fn parse_structured_data(input: &str) -> Result<(Vec<StructuredDataElement>, &str)> {
+    if let Some(rest) = input.strip_prefix('-') {
+        return Ok((Vec::new(), rest.strip_prefix(' ').unwrap_or(rest)));
+    }
+
+    let mut elements = Vec::new();
+    let mut rest = input;
+    while let Some(body) = rest.strip_prefix('[') {
+        let (element, remaining) = parse_sd_element(body)?;
+        elements.push(element);
+        rest = remaining;
+    }
+
+    if elements.is_empty() && !input.is_empty() {
+        return Err(PipelineError::parsing("RFC 5424 structured data must be '-' or start with '['"));
+    }
+    Ok((elements, rest.strip_prefix(' ').unwrap_or(rest)))
+}
+
+fn parse_sd_element(input: &str) -> Result<(StructuredDataElement, &str)> {
+    let id_end = input.find([' ', ']'])
+        .ok_or_else(|| PipelineError::parsing("Unterminated structured data element"))?;
+    let id = input[..id_end].to_string();
+    if id.is_empty() {
+        return Err(PipelineError::parsing("Structured data element has no SD-ID"));
+    }
+
+    let mut params = Vec::new();
+    let mut rest = &input[id_end..];
+    loop {
+        rest = rest.trim_start_matches(' ');
+        if let Some(after) = rest.strip_prefix(']') {
+            return Ok((StructuredDataElement { id, params }, after));
+        }
+
+        let eq = rest.find("=\"")
+            .ok_or_else(|| PipelineError::parsing(format!("Malformed parameter in structured data element '{}'", id)))?;
+        let name = rest[..eq].to_string();
+
+        // Values escape '"', '\' and ']' with a backslash
+        let mut value = String::new();
+        let mut chars = rest[eq + 2..].char_indices();
+        let mut end = None;
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, escaped @ ('"' | '\\' | ']'))) => value.push(escaped),
+                    Some((_, other)) => {
+                        value.push('\\');
+                        value.push(other);
+                    }
+                    None => break,
+                },
+                '"' => {
+                    end = Some(eq + 2 + index + 1);
+                    break;
+                }
+                other => value.push(other),
+            }
+        }
+
+        let end = end.ok_or_else(|| PipelineError::parsing(format!("Unterminated parameter value in '{}'", id)))?;
+        params.push((name, value));
+        rest = &rest[end..];
+    }
+}
+
+const BSD_TIMESTAMP_FORMATS: [&str; 2] = ["%b %d %H:%M:%S%.f", "%b %d %H:%M:%S"];
+const BSD_TIMESTAMP_WITH_YEAR_FORMATS: [&str; 2] = ["%b %d %Y %H:%M:%S%.f", "%b %d %Y %H:%M:%S"];
+
+/// Lenient RFC 3164: every part after the priority is optional, and anything
+/// unrecognised is kept as the message rather than rejected.
+fn parse_rfc3164(priority: Option<u8>, rest: &str, timezone: FixedOffset, now: DateTime<Utc>) -> SyslogMessage {
+    let rest = rest.trim_start();
+    let (timestamp, raw_timestamp, rest) = match parse_bsd_timestamp(rest, timezone, now) {
+        Some((timestamp, raw, rest)) => (Some(timestamp), Some(raw), rest),
+        None => (None, None, rest),
+    };
+
+    // HOSTNAME is present when the next token is followed by more text and is not the TAG
+    let (hostname, rest) = {
+        let (token, remaining) = next_token(rest);
+        let looks_like_tag = token.ends_with(':') || token.contains('[');
+        if timestamp.is_some() && !token.is_empty() && !remaining.is_empty() && !looks_like_tag {
+            (Some(token.to_string()), remaining)
+        } else {
+            (None, rest)
+        }
+    };
+
+    // TAG: "app[pid]: " or "app: "
+    let (app_name, procid, message) = match rest.find(": ") {
+        Some(colon) if !rest[..colon].contains(' ') && colon > 0 => {
+            let tag = &rest[..colon];
+            let message = &rest[colon + 2..];
+            match tag.find('[') {
+                Some(open) if tag.ends_with(']') => (
+                    Some(tag[..open].to_string()),
+                    Some(tag[open + 1..tag.len() - 1].to_string()),
+                    message,
+                ),
+                _ => (Some(tag.to_string()), None, message),
+            }
+        }
+        _ => (None, None, rest),
+    };
+
+    SyslogMessage {
+        format: SyslogFormat::Rfc3164,
+        priority,
+        version: None,
+        timestamp,
+        raw_timestamp,
+        hostname,
+        app_name: app_name.filter(|a| !a.is_empty()),
+        procid: procid.filter(|p| !p.is_empty()),
+        msgid: None,
+        structured_data: Vec::new(),
+        message: message.to_string(),
+    }
+}
+
+fn parse_bsd_timestamp(input: &str, timezone: FixedOffset, now: DateTime<Utc>) -> Option<(DateTime<FixedOffset>, String, &str)> {
+    // Some senders put an RFC 3339 timestamp in an otherwise BSD-style message
+    let (first, after_first) = next_token(input);
+    if first.len() >= 19 && first.as_bytes()[4] == b'-' {
+        if let Ok(timestamp) = DateTime::parse_from_rfc3339(first) {
+            return Some((timestamp, first.to_string(), after_first));
+        }
+        if let Ok(naive) = NaiveDateTime::parse_from_str(first, "%Y-%m-%dT%H:%M:%S%.f") {
+            let timestamp = timezone.from_local_datetime(&naive).single()?;
+            return Some((timestamp, first.to_string(), after_first));
+        }
+    }
+
+    // "Mmm dd hh:mm:ss" with a space-padded day, optionally with a year before the time
+    let tokens: Vec<&str> = input.split(' ').filter(|t| !t.is_empty()).take(4).collect();
+    if tokens.len() < 3 || tokens[0].len() != 3 {
+        return None;
+    }
+
+    let with_year = tokens.len() == 4 && tokens[2].len() == 4 && tokens[2].bytes().all(|b| b.is_ascii_digit());
+    let used = if with_year { 4 } else { 3 };
+    let text = tokens[..used].join(" ");
+
+    let naive = if with_year {
+        BSD_TIMESTAMP_WITH_YEAR_FORMATS.iter().find_map(|f| NaiveDateTime::parse_from_str(&text, f).ok())?
+    } else {
+        infer_year(&text, timezone, now)?
+    };
+    let timestamp = timezone.from_local_datetime(&naive).earliest()?;
+
+    // Skip past the tokens we consumed, allowing for the double space of padded days
+    let mut remaining = input;
+    for _ in 0..used {
+        remaining = remaining.trim_start_matches(' ');
+        remaining = next_token(remaining).1;
+    }
+    let raw = input[..input.len() - remaining.len()].trim_end().to_string();
+    Some((timestamp, raw, remaining.trim_start_matches(' ')))
+}
+
+/// Pick the year that puts a year-less timestamp closest to, and not well after, `now`:
+/// a December message read in early January belongs to last year.
+fn infer_year(text: &str, timezone: FixedOffset, now: DateTime<Utc>) -> Option<NaiveDateTime> {
+    let local_now = now.with_timezone(&timezone).naive_local();
+    let current_year = local_now.year();
+    let with_year = |year: i32| BSD_TIMESTAMP_FORMATS.iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(&format!("{} {}", year, text), &format!("%Y {}", f)).ok());
+
+    if let Some(naive) = with_year(current_year) {
+        if naive <= local_now + chrono::Duration::days(1) {
+            return Some(naive);
+        }
+    }
+    // Otherwise the most recent earlier year the date exists in (Feb 29 needs a leap year)
+    (1..=4).find_map(|back| with_year(current_year - back))
+}
+
+/// One unit pulled off a stream transport by [`FrameDecoder`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    Message(Vec<u8>),
+    /// A message over the size limit was discarded; carries its (known or buffered) length
+    Oversized(usize),
+}
+
+/// RFC 6587 framing for syslog over TCP/TLS.
+///
+/// Each frame is octet-counted (`LEN SP MSG`) when it starts with a digit, and
+/// newline-terminated otherwise, so senders can mix the two on one connection.
+/// Oversized frames are skipped rather than closing the connection.
+#[derive(Debug)]
+pub struct FrameDecoder {
+    max_message_size: usize,
+    // Bytes of an oversized octet-counted frame still to skip
+    skip_bytes: usize,
+    // Skipping an oversized newline-terminated frame up to its LF
+    skip_line: bool,
+}
+
+impl FrameDecoder {
+    pub fn new(max_message_size: usize) -> Self {
+        FrameDecoder { max_message_size, skip_bytes: 0, skip_line: false }
+    }
+
+    /// Take the next frame from the front of `buffer`, or `None` until more data arrives.
+    pub fn decode(&mut self, buffer: &mut Vec<u8>) -> Option<Frame> {
+        if self.skip_bytes > 0 {
+            let skipped = self.skip_bytes.min(buffer.len());
+            buffer.drain(..skipped);
+            self.skip_bytes -= skipped;
+            if self.skip_bytes > 0 {
+                return None;
+            }
+        }
+
+        if self.skip_line {
+            match buffer.iter().position(|b| *b == b'\n') {
+                Some(lf) => {
+                    buffer.drain(..=lf);
+                    self.skip_line = false;
+                }
+                None => {
+                    buffer.clear();
+                    return None;
+                }
+            }
+        }
+
+        // Stray terminators between octet-counted frames
+        let leading = buffer.iter().take_while(|b| matches!(b, b'\n' | b'\r' | 0)).count();
+        buffer.drain(..leading);
+        if buffer.is_empty() {
+            return None;
+        }
+
+        if matches!(buffer[0], b'1'..=b'9') {
+            let digits = buffer.iter().take(10).take_while(|b| b.is_ascii_digit()).count();
+            match buffer.get(digits) {
+                Some(b' ') => {
+                    let length: usize = std::str::from_utf8(&buffer[..digits]).ok()
+                        .and_then(|d| d.parse().ok())
+                        .unwrap_or(usize::MAX);
+                    let header = digits + 1;
+
+                    if length > self.max_message_size {
+                        buffer.drain(..header);
+                        self.skip_bytes = length;
+                        return Some(Frame::Oversized(length));
+                    }
+                    if buffer.len() < header + length {
+                        return None;
+                    }
+                    let message = buffer[header..header + length].to_vec();
+                    buffer.drain(..header + length);
+                    return Some(Frame::Message(message));
+                }
+                // Length prefix not complete yet
+                None if digits == buffer.len() && digits < 10 => return None,
+                _ => {}
+            }
+        }
+
+        match buffer.iter().position(|b| *b == b'\n') {
+            Some(lf) => {
+                let mut message: Vec<u8> = buffer.drain(..=lf).collect();
+                message.pop();
+                if message.last() == Some(&b'\r') {
+                    message.pop();
+                }
+                if message.len() > self.max_message_size {
+                    Some(Frame::Oversized(message.len()))
+                } else {
+                    Some(Frame::Message(message))
+                }
+            }
+            None if buffer.len() > self.max_message_size => {
+                let buffered = buffer.len();
+                buffer.clear();
+                self.skip_line = true;
+                Some(Frame::Oversized(buffered))
+            }
+            None => None,
+        }
+    }
+
+    /// Flush an unterminated final message when the peer closes the connection.
+    pub fn finish(&mut self, buffer: &mut Vec<u8>) -> Option<Frame> {
+        if self.skip_bytes > 0 || self.skip_line {
+            buffer.clear();
+            return None;
+        }
+        let message: Vec<u8> = std::mem::take(buffer);
+        let trimmed = message.iter().rposition(|b| !matches!(b, b'\n' | b'\r' | 0)).map(|end| message[..=end].to_vec())?;
+        Some(Frame::Message(trimmed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc() -> FixedOffset {
+        FixedOffset::east_opt(0).unwrap()
+    }
+
+    #[test]
+    fn test_rfc5424_with_structured_data() {
+        let message = parse_syslog(
+            "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 [exampleSDID@32473 iut=\"3\" eventSource=\"Application\" eventID=\"1011\"][examplePriority@32473 class=\"high \\\"x\\\" \\]\"] \u{feff}An application event log entry",
+            utc(),
+            Utc::now(),
+        ).unwrap();
+
+        assert_eq!(message.format, SyslogFormat::Rfc5424);
+        assert_eq!(message.facility(), Some(20));
+        assert_eq!(message.severity(), Some(5));
+        assert_eq!(message.hostname.as_deref(), Some("mymachine.example.com"));
+        assert_eq!(message.app_name.as_deref(), Some("evntslog"));
+        assert_eq!(message.procid, None);
+        assert_eq!(message.msgid.as_deref(), Some("ID47"));
+        assert_eq!(message.timestamp.unwrap().to_rfc3339(), "2003-10-11T22:14:15.003+00:00");
+        assert_eq!(message.structured_data.len(), 2);
+        assert_eq!(message.structured_data[0].params[2], ("eventID".to_string(), "1011".to_string()));
+        assert_eq!(message.structured_data[1].params[0].1, "high \"x\" ]");
+        assert_eq!(message.message, "An application event log entry");
+
+        let message = parse_syslog("<34>1 2003-08-24T05:14:15.000003-07:00 192.0.2.1 myproc 8710 - - %% It's time to make the do-nuts.", utc(), Utc::now()).unwrap();
+        assert_eq!(message.timestamp.unwrap().with_timezone(&Utc).to_rfc3339(), "2003-08-24T12:14:15.000003+00:00");
+        assert_eq!(message.procid.as_deref(), Some("8710"));
+        assert!(message.structured_data.is_empty());
+
+        assert!(parse_syslog("<34>1 yesterday host app - - - msg", utc(), Utc::now()).is_err());
+    }
+
+    #[test]
+    fn test_rfc3164_year_inference_and_timezone() {
+        let now = Utc.with_ymd_and_hms(2024, 1, 2, 3, 0, 0).unwrap();
+        let plus_two = parse_utc_offset("+02:00").unwrap();
+
+        let message = parse_syslog("<34>Dec 31 23:59:58 mymachine su[123]: 'su root' failed for lonvick on /dev/pts/8", plus_two, now).unwrap();
+        assert_eq!(message.format, SyslogFormat::Rfc3164);
+        assert_eq!(message.timestamp.unwrap().with_timezone(&Utc).to_rfc3339(), "2023-12-31T21:59:58+00:00");
+        assert_eq!(message.raw_timestamp.as_deref(), Some("Dec 31 23:59:58"));
+        assert_eq!(message.hostname.as_deref(), Some("mymachine"));
+        assert_eq!(message.app_name.as_deref(), Some("su"));
+        assert_eq!(message.procid.as_deref(), Some("123"));
+        assert_eq!(message.message, "'su root' failed for lonvick on /dev/pts/8");
+
+        let message = parse_syslog("<13>Jan  2 01:02:03 host sshd: Accepted password", utc(), now).unwrap();
+        assert_eq!(message.timestamp.unwrap().to_rfc3339(), "2024-01-02T01:02:03+00:00");
+        assert_eq!(message.app_name.as_deref(), Some("sshd"));
+
+        let message = parse_syslog("just some text", utc(), now).unwrap();
+        assert_eq!(message.timestamp, None);
+        assert_eq!(message.message, "just some text");
+
+        assert_eq!(parse_utc_offset("-0800").unwrap().local_minus_utc(), -8 * 3600);
+        assert!(parse_utc_offset("Europe/Paris").is_none());
+    }
+
+    #[test]
+    fn test_octet_counted_and_newline_framing() {
+        let mut decoder = FrameDecoder::new(32);
+        let mut buffer = b"11 <13>1 - msg10 <13>hello\n".to_vec();
+
+        assert_eq!(decoder.decode(&mut buffer), Some(Frame::Message(b"<13>1 - msg".to_vec())));
+        // Only part of the second frame has arrived
+        buffer.truncate(9);
+        assert_eq!(decoder.decode(&mut buffer), None);
+        buffer.extend_from_slice(b"llo\n<14>plain line\r\n");
+        assert_eq!(decoder.decode(&mut buffer), Some(Frame::Message(b"<13>hello\n".to_vec())));
+        assert_eq!(decoder.decode(&mut buffer), Some(Frame::Message(b"<14>plain line".to_vec())));
+        assert_eq!(decoder.decode(&mut buffer), None);
+
+        // Oversized frames are skipped without losing the next one
+        buffer.extend_from_slice(b"40 ");
+        buffer.extend_from_slice(&[b'x'; 40]);
+        buffer.extend_from_slice(b"<15>after\n");
+        assert_eq!(decoder.decode(&mut buffer), Some(Frame::Oversized(40)));
+        assert_eq!(decoder.decode(&mut buffer), Some(Frame::Message(b"<15>after".to_vec())));
+
+        buffer.extend_from_slice(&[b'y'; 33]);
+        assert_eq!(decoder.decode(&mut buffer), Some(Frame::Oversized(33)));
+        buffer.extend_from_slice(b"yyy\n<16>tail");
+        assert_eq!(decoder.decode(&mut buffer), None);
+        assert_eq!(decoder.finish(&mut buffer), Some(Frame::Message(b"<16>tail".to_vec())));
+    }
+}
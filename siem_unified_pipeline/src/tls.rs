@@ -0,0 +1,67 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
+use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+use crate::config::TlsConfig;
+use crate::error::{Result, PipelineError};
+
+/// Build a server-side TLS acceptor from `SecurityConfig.tls`.
+///
+/// When `ca_file` is set, clients must present a certificate that chains to one
+/// of its CAs (mutual TLS); otherwise any client may connect.
+pub fn build_acceptor(tls: &TlsConfig) -> Result<TlsAcceptor> {
+    let certs = load_certs(&tls.cert_file)?;
+    let key = load_private_key(&tls.key_file)?;
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match &tls.ca_file {
+        Some(ca_file) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_file)? {
+                roots.add(&cert).map_err(|e| {
+                    PipelineError::config(format!("Invalid CA certificate in {}: {}", ca_file, e))
+                })?;
+            }
+            builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots).boxed())
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let config = builder.with_single_cert(certs, key)
+        .map_err(|e| PipelineError::config(format!("Invalid TLS certificate or key: {}", e)))?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+fn open_pem(path: &str) -> Result<BufReader<File>> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| PipelineError::config(format!("Failed to open {}: {}", path, e)))
+}
+
+fn load_certs(path: &str) -> Result<Vec<Certificate>> {
+    let certs = rustls_pemfile::certs(&mut open_pem(path)?)
+        .map_err(|e| PipelineError::config(format!("Failed to read certificates from {}: {}", path, e)))?;
+
+    if certs.is_empty() {
+        return Err(PipelineError::config(format!("No certificates found in {}", path)));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_private_key(path: &str) -> Result<PrivateKey> {
+    let items = rustls_pemfile::read_all(&mut open_pem(path)?)
+        .map_err(|e| PipelineError::config(format!("Failed to read private key from {}: {}", path, e)))?;
+
+    items.into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| PipelineError::config(format!("No private key found in {}", path)))
+}
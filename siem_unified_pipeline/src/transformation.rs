@@ -1,14 +1,25 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
 use std::collections::HashMap;
+use std::net::IpAddr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{info, warn, error, debug};
 use serde::{Deserialize, Serialize};
 use regex::Regex;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, FixedOffset, Utc};
 
-use crate::config::{PipelineConfig, TransformationStep};
+use crate::aggregation::{Aggregator, AGGREGATED_BY_METADATA_KEY};
+use crate::cef::{self, CefRecord, LeefRecord};
+use crate::config::{FilterAction, GeoIpConfig, PipelineConfig, ThreatIntelConfig, TransformationStep};
 use crate::error::{Result, PipelineError};
+use crate::filter::FilterExpression;
+use crate::geoip::{GeoIpResolver, UNKNOWN_LOCATION};
+use crate::mapping::{FieldMappings, FieldSchema};
 use crate::pipeline::{PipelineEvent, ProcessingStage};
+use crate::routing::ROUTE_TO_METADATA_KEY;
+use crate::syslog;
+use siem_ioc::{ClickHouseFeed, IocStore};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -17,8 +28,12 @@ pub struct TransformationStats {
     pub events_processed: u64,
     pub events_failed: u64,
     pub events_dropped: u64,
+    #[serde(default)]
+    pub events_aggregated: u64,
     pub processing_time_ms: f64,
     pub last_processed: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub field_errors: HashMap<String, u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -30,11 +45,16 @@ pub struct ParsedEvent {
     pub process: String,
     pub message: String,
     pub fields: HashMap<String, serde_json::Value>,
+    /// Filled in by the `geoip` enricher
+    pub source_country: Option<String>,
+    pub destination_country: Option<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct EnrichmentData {
+    /// Location of the event's source address
     pub geo_location: Option<GeoLocation>,
+    pub destination_geo_location: Option<GeoLocation>,
     pub threat_intel: Option<ThreatIntel>,
     pub asset_info: Option<AssetInfo>,
     pub user_info: Option<UserInfo>,
@@ -59,6 +79,9 @@ pub struct ThreatIntel {
     pub confidence: f64,
     pub source: String,
     pub last_seen: Option<DateTime<Utc>>,
+    /// Indicator of compromise the event matched
+    #[serde(default)]
+    pub indicator: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -88,6 +111,18 @@ pub struct TransformationManager {
     enrichers: HashMap<String, Box<dyn EventEnricher + Send + Sync>>,
     filters: HashMap<String, Box<dyn EventFilter + Send + Sync>>,
     normalizers: HashMap<String, Box<dyn EventNormalizer + Send + Sync>>,
+    // Filter conditions compiled up front, keyed by pipeline name and step index
+    compiled_filters: HashMap<String, HashMap<usize, FilterExpression>>,
+    // Map step operations, keyed the same way as the filters
+    compiled_mappings: HashMap<String, HashMap<usize, FieldMappings>>,
+    // Typed field definitions keyed by source name
+    source_schemas: HashMap<String, FieldSchema>,
+    // Open aggregation windows, keyed by pipeline name and step index
+    aggregators: Arc<RwLock<HashMap<String, HashMap<usize, Aggregator>>>>,
+    // Indicators shared with the threat_intel enricher, and the table they
+    // are refreshed from when threat intelligence is enabled
+    ioc_store: Arc<IocStore>,
+    ioc_feed: Option<ClickHouseFeed>,
 }
 
 #[async_trait::async_trait]
@@ -116,24 +151,28 @@ pub trait EventNormalizer: Send + Sync {
 
 // Built-in parsers
 pub struct SyslogParser {
-    regex: Regex,
+    // Offsets for RFC 3164 timestamps, keyed by source name
+    timezones: HashMap<String, FixedOffset>,
 }
 
 pub struct JsonParser;
 
-pub struct CefParser {
-    regex: Regex,
-}
+pub struct CefParser;
+
+pub struct LeefParser;
 
 pub struct WindowsEventParser;
 
 // Built-in enrichers
 pub struct GeoIpEnricher {
-    // In a real implementation, this would contain GeoIP database
+    resolver: GeoIpResolver,
+    source_ip_fields: Vec<String>,
+    destination_ip_fields: Vec<String>,
 }
 
 pub struct ThreatIntelEnricher {
-    // In a real implementation, this would connect to threat intel feeds
+    store: Arc<IocStore>,
+    fields: Vec<String>,
 }
 
 pub struct AssetEnricher {
@@ -167,6 +206,7 @@ impl TransformationManager {
     pub async fn new(config: &PipelineConfig) -> Result<Self> {
         info!("Initializing transformation manager");
         
+        let ioc_store = Arc::new(IocStore::new());
         let mut manager = TransformationManager {
             config: config.clone(),
             stats: Arc::new(RwLock::new(HashMap::new())),
@@ -174,17 +214,24 @@ impl TransformationManager {
             enrichers: HashMap::new(),
             filters: HashMap::new(),
             normalizers: HashMap::new(),
+            compiled_filters: Self::compile_filters(config)?,
+            compiled_mappings: Self::compile_mappings(config)?,
+            source_schemas: Self::compile_schemas(config)?,
+            aggregators: Arc::new(RwLock::new(Self::build_aggregators(config)?)),
+            ioc_store: ioc_store.clone(),
+            ioc_feed: config.threat_intel.enabled.then(|| Self::ioc_feed(&config.threat_intel)),
         };
         
         // Register built-in parsers
-        manager.register_parser(Box::new(SyslogParser::new()?));
+        manager.register_parser(Box::new(SyslogParser::new(config)?));
         manager.register_parser(Box::new(JsonParser));
-        manager.register_parser(Box::new(CefParser::new()?));
+        manager.register_parser(Box::new(CefParser));
+        manager.register_parser(Box::new(LeefParser));
         manager.register_parser(Box::new(WindowsEventParser));
         
         // Register built-in enrichers
-        manager.register_enricher(Box::new(GeoIpEnricher::new()));
-        manager.register_enricher(Box::new(ThreatIntelEnricher::new()));
+        manager.register_enricher(Box::new(GeoIpEnricher::new(&config.geoip)));
+        manager.register_enricher(Box::new(ThreatIntelEnricher::new(&config.threat_intel, ioc_store)));
         manager.register_enricher(Box::new(AssetEnricher::new()));
         manager.register_enricher(Box::new(UserEnricher::new()));
         
@@ -205,8 +252,10 @@ impl TransformationManager {
                     events_processed: 0,
                     events_failed: 0,
                     events_dropped: 0,
+                    events_aggregated: 0,
                     processing_time_ms: 0.0,
                     last_processed: None,
+                    field_errors: HashMap::new(),
                 });
             }
         }
@@ -214,6 +263,86 @@ impl TransformationManager {
         Ok(manager)
     }
     
+    fn compile_filters(config: &PipelineConfig) -> Result<HashMap<String, HashMap<usize, FilterExpression>>> {
+        let mut compiled = HashMap::new();
+        
+        for (pipeline_name, pipeline) in &config.transformations {
+            let mut filters = HashMap::new();
+            for (index, step) in pipeline.steps.iter().enumerate() {
+                if let TransformationStep::Filter { condition, .. } = step {
+                    let expression = FilterExpression::compile(condition).map_err(|e| {
+                        PipelineError::config(format!(
+                            "Invalid filter condition in pipeline '{}' step {}: {}", pipeline_name, index, e
+                        ))
+                    })?;
+                    filters.insert(index, expression);
+                }
+            }
+            compiled.insert(pipeline_name.clone(), filters);
+        }
+        
+        Ok(compiled)
+    }
+    
+    fn compile_mappings(config: &PipelineConfig) -> Result<HashMap<String, HashMap<usize, FieldMappings>>> {
+        let mut compiled = HashMap::new();
+        
+        for (pipeline_name, pipeline) in &config.transformations {
+            let mut mappings = HashMap::new();
+            for (index, step) in pipeline.steps.iter().enumerate() {
+                if let TransformationStep::Map { field_mappings } = step {
+                    let mapping = FieldMappings::compile(field_mappings).map_err(|e| {
+                        PipelineError::config(format!(
+                            "Invalid field mapping in pipeline '{}' step {}: {}", pipeline_name, index, e
+                        ))
+                    })?;
+                    mappings.insert(index, mapping);
+                }
+            }
+            compiled.insert(pipeline_name.clone(), mappings);
+        }
+        
+        Ok(compiled)
+    }
+    
+    fn build_aggregators(config: &PipelineConfig) -> Result<HashMap<String, HashMap<usize, Aggregator>>> {
+        let mut aggregators = HashMap::new();
+        
+        for (pipeline_name, pipeline) in &config.transformations {
+            let mut steps = HashMap::new();
+            for (index, step) in pipeline.steps.iter().enumerate() {
+                if let TransformationStep::Aggregate { window, functions, group_by, slide } = step {
+                    let name = format!("{}#{}", pipeline_name, index);
+                    let aggregator = Aggregator::new(&name, window, slide.as_deref(), group_by, functions).map_err(|e| {
+                        PipelineError::config(format!(
+                            "Invalid aggregation in pipeline '{}' step {}: {}", pipeline_name, index, e
+                        ))
+                    })?;
+                    steps.insert(index, aggregator);
+                }
+            }
+            if !steps.is_empty() {
+                aggregators.insert(pipeline_name.clone(), steps);
+            }
+        }
+        
+        Ok(aggregators)
+    }
+    
+    fn compile_schemas(config: &PipelineConfig) -> Result<HashMap<String, FieldSchema>> {
+        let mut schemas = HashMap::new();
+        
+        for (source_name, source) in &config.sources {
+            let schema = FieldSchema::compile(&source.config.fields, source.config.timestamp_format.clone())
+                .map_err(|e| PipelineError::config(format!("Invalid field definitions for source '{}': {}", source_name, e)))?;
+            if !schema.is_empty() {
+                schemas.insert(source_name.clone(), schema);
+            }
+        }
+        
+        Ok(schemas)
+    }
+    
     pub fn register_parser(&mut self, parser: Box<dyn EventParser + Send + Sync>) {
         let name = parser.name().to_string();
         self.parsers.insert(name, parser);
@@ -248,12 +377,23 @@ impl TransformationManager {
         let mut _parsed_event = None;
         let mut enrichment_data = EnrichmentData {
             geo_location: None,
+            destination_geo_location: None,
             threat_intel: None,
             asset_info: None,
             user_info: None,
         };
         
-        for step in &pipeline_config.steps {
+        // Aggregate summaries skip the steps that ran before they were produced
+        let resume_after = event.metadata.get(AGGREGATED_BY_METADATA_KEY)
+            .and_then(|marker| marker.rsplit_once('#'))
+            .filter(|(name, _)| *name == pipeline_name)
+            .and_then(|(_, index)| index.parse::<usize>().ok());
+        
+        for (step_index, step) in pipeline_config.steps.iter().enumerate() {
+            if resume_after.is_some_and(|after| step_index <= after) {
+                continue;
+            }
+            
             match step {
                 TransformationStep::Parse { parser, .. } => {
                     if let Some(parser_impl) = self.parsers.get(parser) {
@@ -274,7 +414,7 @@ impl TransformationManager {
                     }
                 }
                 TransformationStep::Enrich { enricher, .. } => {
-                    if let Some(ref _parsed) = _parsed_event {
+                    if let Some(ref mut _parsed) = _parsed_event {
                         if let Some(enricher_impl) = self.enrichers.get(enricher) {
                             match enricher_impl.enrich(event, _parsed).await {
                                 Ok(enrichment) => {
@@ -283,8 +423,13 @@ impl TransformationManager {
                                     
                                     // Merge enrichment data
                                     if enrichment.geo_location.is_some() {
+                                        _parsed.source_country = known_country(enrichment.geo_location.as_ref());
                                         enrichment_data.geo_location = enrichment.geo_location;
                                     }
+                                    if enrichment.destination_geo_location.is_some() {
+                                        _parsed.destination_country = known_country(enrichment.destination_geo_location.as_ref());
+                                        enrichment_data.destination_geo_location = enrichment.destination_geo_location;
+                                    }
                                     if enrichment.threat_intel.is_some() {
                                         enrichment_data.threat_intel = enrichment.threat_intel;
                                     }
@@ -303,28 +448,59 @@ impl TransformationManager {
                         }
                     }
                 }
-                TransformationStep::Filter { condition, action: _ } => {
-                    if let Some(ref _parsed) = _parsed_event {
-                        // For now, implement a simple condition check
-                        // In a real implementation, this would parse and evaluate the condition
-                        let should_process = true; // Placeholder logic
-                        
-                        if !should_process {
-                            debug!("Event {} filtered out by condition: {}", event.id, condition);
+                TransformationStep::Filter { condition, action } => {
+                    let expression = self.compiled_filters.get(&pipeline_name)
+                        .and_then(|filters| filters.get(&step_index))
+                        .ok_or_else(|| PipelineError::internal(format!("Filter condition '{}' was not compiled", condition)))?;
+                    
+                    if !expression.matches(event, _parsed_event.as_ref()) {
+                        debug!("Event {} did not match filter condition: {}", event.id, condition);
+                        continue;
+                    }
+                    
+                    match action {
+                        FilterAction::Drop => {
+                            debug!("Event {} dropped by filter condition: {}", event.id, condition);
                             event.processing_stage = ProcessingStage::Filtered;
                             self.increment_dropped_count(&pipeline_name).await;
                             return Err(PipelineError::validation("Event filtered out"));
                         }
-                        debug!("Event {} passed filter condition: {}", event.id, condition);
+                        FilterAction::Route(destination) => {
+                            debug!("Event {} routed to {} by filter condition: {}", event.id, destination, condition);
+                            let routes = event.metadata.entry(ROUTE_TO_METADATA_KEY.to_string()).or_default();
+                            if !routes.split(',').any(|r| r == destination) {
+                                if !routes.is_empty() {
+                                    routes.push(',');
+                                }
+                                routes.push_str(destination);
+                            }
+                        }
+                        FilterAction::Tag(tag) => {
+                            debug!("Event {} tagged {} by filter condition: {}", event.id, tag, condition);
+                            Self::apply_tag(event, tag);
+                        }
                     }
                 }
                 TransformationStep::Map { field_mappings } => {
-                    debug!("Applying field mappings: {:?}", field_mappings);
-                    // Field mapping implementation would go here
-                    // For now, just log that we're applying mappings
-                    for (source_field, target_field) in field_mappings {
-                        debug!("Mapping field {} to {}", source_field, target_field);
-                        // In a real implementation, this would copy/transform field values
+                    debug!("Applying {} field mappings to event {}", field_mappings.len(), event.id);
+                    Self::ensure_object_data(event);
+                    
+                    if let Some(mappings) = self.compiled_mappings.get(&pipeline_name).and_then(|m| m.get(&step_index)) {
+                        mappings.apply(&mut event.data, &mut event.metadata);
+                    }
+                    
+                    // Field errors are recorded but never fail the event; the
+                    // offending value is left as it was
+                    if let Some(schema) = self.source_schemas.get(&event.source) {
+                        let errors = schema.apply(&mut event.data);
+                        if !errors.is_empty() {
+                            for error in &errors {
+                                debug!("Field '{}' of event {} failed validation: {}", error.field, event.id, error.message);
+                            }
+                            let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+                            event.metadata.insert("field_errors".to_string(), fields.join(","));
+                            self.record_field_errors(&pipeline_name, &fields).await;
+                        }
                     }
                 }
                 TransformationStep::Normalize { schema, .. } => {
@@ -345,9 +521,18 @@ impl TransformationManager {
                     }
                 }
                 TransformationStep::Aggregate { window, .. } => {
-                    debug!("Applying aggregation with window: {}", window);
-                    // Aggregation implementation would go here
-                    // For now, just log that we're applying aggregation
+                    {
+                        let mut aggregators = self.aggregators.write().await;
+                        let aggregator = aggregators.get_mut(&pipeline_name)
+                            .and_then(|steps| steps.get_mut(&step_index))
+                            .ok_or_else(|| PipelineError::internal(format!("Aggregation over '{}' was not initialized", window)))?;
+                        aggregator.add(event);
+                    }
+                    
+                    debug!("Event {} folded into {} aggregation window", event.id, window);
+                    event.processing_stage = ProcessingStage::Aggregated;
+                    self.increment_aggregated_count(&pipeline_name).await;
+                    return Err(PipelineError::validation("Event folded into aggregate"));
                 }
                 TransformationStep::Custom { plugin, .. } => {
                     debug!("Applying custom plugin: {}", plugin);
@@ -364,6 +549,32 @@ impl TransformationManager {
         Ok(())
     }
     
+    // Tags are collected in `data.tags` only, so a tag never clobbers an
+    // event field of the same name; conditions test them with `tags in [...]`
+    fn apply_tag(event: &mut PipelineEvent, tag: &str) {
+        Self::ensure_object_data(event);
+        
+        if let Some(data) = event.data.as_object_mut() {
+            let tags = data.entry("tags").or_insert_with(|| serde_json::json!([]));
+            if !tags.is_array() {
+                let existing = std::mem::take(tags);
+                *tags = serde_json::json!([existing]);
+            }
+            if let Some(tags) = tags.as_array_mut() {
+                if !tags.iter().any(|t| t.as_str() == Some(tag)) {
+                    tags.push(serde_json::Value::String(tag.to_string()));
+                }
+            }
+        }
+    }
+    
+    fn ensure_object_data(event: &mut PipelineEvent) {
+        if !event.data.is_object() {
+            let original = std::mem::take(&mut event.data);
+            event.data = serde_json::json!({ "raw_message": original });
+        }
+    }
+    
     fn determine_pipeline(&self, event: &PipelineEvent) -> Result<String> {
         // Simple pipeline selection based on source
         // In a real implementation, this could be more sophisticated
@@ -401,6 +612,41 @@ impl TransformationManager {
         }
     }
     
+    async fn increment_aggregated_count(&self, pipeline_name: &str) {
+        let mut stats_guard = self.stats.write().await;
+        if let Some(stats) = stats_guard.get_mut(pipeline_name) {
+            stats.events_aggregated += 1;
+        }
+    }
+    
+    /// Collect summary events for aggregation windows that have closed, or for
+    /// every open window when `force` is set (used on shutdown).
+    pub async fn flush_aggregations(&self, force: bool) -> Vec<PipelineEvent> {
+        let now = Utc::now();
+        let mut aggregators = self.aggregators.write().await;
+        
+        let mut summaries = Vec::new();
+        for steps in aggregators.values_mut() {
+            for aggregator in steps.values_mut() {
+                summaries.extend(aggregator.flush(now, force));
+            }
+        }
+        
+        if !summaries.is_empty() {
+            debug!("Emitting {} aggregation summaries", summaries.len());
+        }
+        summaries
+    }
+    
+    async fn record_field_errors(&self, pipeline_name: &str, fields: &[&str]) {
+        let mut stats_guard = self.stats.write().await;
+        if let Some(stats) = stats_guard.get_mut(pipeline_name) {
+            for field in fields {
+                *stats.field_errors.entry(field.to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+    
     pub async fn get_stats(&self) -> HashMap<String, TransformationStats> {
         let stats_guard = self.stats.read().await;
         stats_guard.clone()
@@ -459,6 +705,26 @@ impl TransformationManager {
         Ok(())
     }
     
+    /// Loads indicators added to the threat_intel table since the last call,
+    /// or the whole table when `full`. Returns the number applied.
+    pub async fn refresh_threat_intel(&self, full: bool) -> Result<usize> {
+        let Some(feed) = &self.ioc_feed else { return Ok(0) };
+        let applied = if full {
+            self.ioc_store.reload(feed).await
+        } else {
+            self.ioc_store.refresh(feed).await
+        };
+        applied.map_err(|e| PipelineError::database(format!("Failed to load threat intelligence: {}", e)))
+    }
+    
+    fn ioc_feed(config: &ThreatIntelConfig) -> ClickHouseFeed {
+        let feed = ClickHouseFeed::new(reqwest::Client::new(), config.url.clone(), config.table.clone());
+        match &config.username {
+            Some(username) => feed.with_credentials(username.clone(), config.password.clone()),
+            None => feed,
+        }
+    }
+    
     pub async fn shutdown(&self) -> Result<()> {
         info!("Shutting down transformation manager");
         Ok(())
@@ -467,11 +733,19 @@ impl TransformationManager {
 
 // Parser implementations
 impl SyslogParser {
-    pub fn new() -> Result<Self> {
-        let regex = Regex::new(r"^<(\d+)>(\w{3}\s+\d{1,2}\s+\d{2}:\d{2}:\d{2})\s+(\S+)\s+(\S+):\s*(.*)$")
-            .map_err(|e| PipelineError::parsing(format!("Invalid syslog regex: {}", e)))?;
+    pub fn new(config: &PipelineConfig) -> Result<Self> {
+        let mut timezones = HashMap::new();
+        
+        for (source_name, source) in &config.sources {
+            if let Some(timezone) = &source.config.timezone {
+                let offset = syslog::parse_utc_offset(timezone).ok_or_else(|| {
+                    PipelineError::config(format!("Invalid timezone '{}' for source '{}'", timezone, source_name))
+                })?;
+                timezones.insert(source_name.clone(), offset);
+            }
+        }
         
-        Ok(SyslogParser { regex })
+        Ok(SyslogParser { timezones })
     }
 }
 
@@ -481,47 +755,60 @@ impl EventParser for SyslogParser {
         let raw_message = event.data["raw_message"].as_str()
             .ok_or_else(|| PipelineError::parsing("No raw_message field found"))?;
         
-        if let Some(captures) = self.regex.captures(raw_message) {
-            let priority = captures.get(1).unwrap().as_str().parse::<u32>()
-                .map_err(|e| PipelineError::parsing(format!("Invalid priority: {}", e)))?;
-            
-            let facility = (priority >> 3).to_string();
-            let severity = match priority & 7 {
-                0 => "emergency",
-                1 => "alert",
-                2 => "critical",
-                3 => "error",
-                4 => "warning",
-                5 => "notice",
-                6 => "info",
-                7 => "debug",
-                _ => "unknown",
-            }.to_string();
-            
-            let timestamp_str = captures.get(2).unwrap().as_str();
-            let hostname = captures.get(3).unwrap().as_str().to_string();
-            let process = captures.get(4).unwrap().as_str().to_string();
-            let message = captures.get(5).unwrap().as_str().to_string();
-            
-            // Parse timestamp (simplified)
-            let timestamp = Utc::now(); // In real implementation, parse the actual timestamp
-            
-            let mut fields = HashMap::new();
+        let timezone = self.timezones.get(&event.source).copied()
+            .unwrap_or_else(|| FixedOffset::east_opt(0).expect("zero offset is valid"));
+        let message = syslog::parse_syslog(raw_message, timezone, Utc::now())?;
+        
+        let mut fields = HashMap::new();
+        if let Some(priority) = message.priority {
             fields.insert("priority".to_string(), serde_json::Value::Number(priority.into()));
-            fields.insert("raw_timestamp".to_string(), serde_json::Value::String(timestamp_str.to_string()));
-            
-            Ok(ParsedEvent {
-                timestamp,
-                severity,
-                facility,
-                hostname,
-                process,
-                message,
-                fields,
-            })
-        } else {
-            Err(PipelineError::parsing("Failed to parse syslog message"))
         }
+        if let Some(raw_timestamp) = &message.raw_timestamp {
+            fields.insert("raw_timestamp".to_string(), serde_json::Value::String(raw_timestamp.clone()));
+        }
+        if let Some(version) = message.version {
+            fields.insert("syslog.version".to_string(), serde_json::Value::Number(version.into()));
+        }
+        if let Some(procid) = &message.procid {
+            fields.insert("syslog.procid".to_string(), serde_json::Value::String(procid.clone()));
+            if let Ok(pid) = procid.parse::<u32>() {
+                fields.insert("process.pid".to_string(), serde_json::Value::Number(pid.into()));
+            }
+        }
+        if let Some(msgid) = &message.msgid {
+            fields.insert("syslog.msgid".to_string(), serde_json::Value::String(msgid.clone()));
+        }
+        // Structured data becomes "sd.<SD-ID>.<PARAM-NAME>"
+        for element in &message.structured_data {
+            for (name, value) in &element.params {
+                fields.insert(format!("sd.{}.{}", element.id, name), serde_json::Value::String(value.clone()));
+            }
+        }
+        
+        // Device time replaces the ingest time on the event so time-range
+        // searches see when it happened; the ingest time is kept in metadata
+        let timestamp = match message.timestamp {
+            Some(device_time) => {
+                let device_time = device_time.with_timezone(&Utc);
+                event.metadata.entry("received_at".to_string())
+                    .or_insert_with(|| event.timestamp.to_rfc3339());
+                event.timestamp = device_time;
+                device_time
+            }
+            None => event.timestamp,
+        };
+        
+        Ok(ParsedEvent {
+            timestamp,
+            severity: message.severity().map(syslog::severity_name).unwrap_or("info").to_string(),
+            facility: message.facility().map(syslog::facility_name).unwrap_or("user").to_string(),
+            hostname: message.hostname.clone().unwrap_or_else(|| "unknown".to_string()),
+            process: message.app_name.clone().unwrap_or_else(|| "unknown".to_string()),
+            message: message.message,
+            fields,
+            source_country: None,
+            destination_country: None,
+        })
     }
     
     fn name(&self) -> &str {
@@ -559,6 +846,8 @@ impl EventParser for JsonParser {
             process,
             message,
             fields,
+            source_country: None,
+            destination_country: None,
         })
     }
     
@@ -570,28 +859,87 @@ impl EventParser for JsonParser {
 // Implement other parsers, enrichers, filters, and normalizers...
 // (Abbreviated for brevity - full implementations would follow similar patterns)
 
+// Canonical field map shared by the CEF and LEEF parsers: well-known keys take
+// their `parsed_event.rs` names (ports and pids as numbers), the rest keep their own
+fn vendor_format_fields<'a>(
+    header: &[(&str, &str)],
+    attributes: impl Iterator<Item = &'a (String, String)>,
+) -> HashMap<String, serde_json::Value> {
+    let mut fields = HashMap::new();
+    
+    for (name, value) in header {
+        if !value.is_empty() {
+            fields.insert(name.to_string(), serde_json::Value::String(value.to_string()));
+        }
+    }
+    
+    for (key, value) in attributes {
+        let name = cef::canonical_field(key).unwrap_or(key.as_str());
+        let value = match name {
+            "source.port" | "destination.port" | "process.pid" => value.trim().parse::<u64>()
+                .map(serde_json::Value::from)
+                .unwrap_or_else(|_| serde_json::Value::String(value.clone())),
+            _ => serde_json::Value::String(value.clone()),
+        };
+        // The first well-known alias wins, e.g. `src` over `sourceAddress`
+        fields.entry(name.to_string()).or_insert(value);
+    }
+    
+    fields
+}
+
+// Last token of a syslog header in front of the CEF/LEEF marker is the sending host
+fn prefix_hostname(prefix: &str) -> Option<String> {
+    prefix.split_whitespace().last()
+        .filter(|host| !host.ends_with(':') && !host.starts_with('<'))
+        .map(str::to_string)
+}
+
+fn string_field(fields: &HashMap<String, serde_json::Value>, name: &str) -> Option<String> {
+    fields.get(name).and_then(|v| v.as_str()).map(str::to_string)
+}
+
 impl CefParser {
-    pub fn new() -> Result<Self> {
-        let regex = Regex::new(r"^CEF:(\d+)\|([^|]*)\|([^|]*)\|([^|]*)\|([^|]*)\|([^|]*)\|([^|]*)\|(.*)$")
-            .map_err(|e| PipelineError::parsing(format!("Invalid CEF regex: {}", e)))?;
+    fn to_parsed_event(record: &CefRecord, received: DateTime<Utc>) -> ParsedEvent {
+        let header = [
+            ("cef.version", record.version.as_str()),
+            ("observer.vendor", record.device_vendor.as_str()),
+            ("observer.product", record.device_product.as_str()),
+            ("observer.version", record.device_version.as_str()),
+            ("event.id", record.signature_id.as_str()),
+            ("rule.name", record.name.as_str()),
+            ("log.level", record.severity.as_str()),
+        ];
+        let fields = vendor_format_fields(&header, record.extensions.iter());
+        
+        let timestamp = ["rt", "end", "start"].iter()
+            .find_map(|key| record.get(key).and_then(|value| cef::parse_device_time(value, None)))
+            .unwrap_or(received);
         
-        Ok(CefParser { regex })
+        ParsedEvent {
+            timestamp,
+            severity: cef::normalize_severity(&record.severity),
+            facility: "security".to_string(),
+            hostname: string_field(&fields, "host.name")
+                .or_else(|| prefix_hostname(&record.prefix))
+                .unwrap_or_else(|| "unknown".to_string()),
+            process: record.device_product.clone(),
+            message: string_field(&fields, "message").unwrap_or_else(|| record.name.clone()),
+            fields,
+            source_country: None,
+            destination_country: None,
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl EventParser for CefParser {
-    async fn parse(&self, _event: &mut PipelineEvent) -> Result<ParsedEvent> {
-        // CEF parsing implementation
-        Ok(ParsedEvent {
-            timestamp: Utc::now(),
-            severity: "info".to_string(),
-            facility: "security".to_string(),
-            hostname: "unknown".to_string(),
-            process: "cef".to_string(),
-            message: "CEF event".to_string(),
-            fields: HashMap::new(),
-        })
+    async fn parse(&self, event: &mut PipelineEvent) -> Result<ParsedEvent> {
+        let raw_message = event.data["raw_message"].as_str()
+            .ok_or_else(|| PipelineError::parsing("No raw_message field found"))?;
+        
+        let record = cef::parse_cef(raw_message)?;
+        Ok(Self::to_parsed_event(&record, event.timestamp))
     }
     
     fn name(&self) -> &str {
@@ -599,6 +947,58 @@ impl EventParser for CefParser {
     }
 }
 
+impl LeefParser {
+    fn to_parsed_event(record: &LeefRecord, received: DateTime<Utc>) -> ParsedEvent {
+        let header = [
+            ("leef.version", record.version.as_str()),
+            ("observer.vendor", record.vendor.as_str()),
+            ("observer.product", record.product.as_str()),
+            ("observer.version", record.product_version.as_str()),
+            ("event.id", record.event_id.as_str()),
+        ];
+        let mut fields = vendor_format_fields(&header, record.attributes.iter());
+        
+        let severity = record.get("sev").unwrap_or_default();
+        if !severity.is_empty() {
+            fields.insert("log.level".to_string(), serde_json::Value::String(severity.to_string()));
+        }
+        
+        let timestamp = record.get("devTime")
+            .and_then(|value| cef::parse_device_time(value, record.get("devTimeFormat")))
+            .unwrap_or(received);
+        
+        ParsedEvent {
+            timestamp,
+            severity: cef::normalize_severity(severity),
+            facility: "security".to_string(),
+            hostname: string_field(&fields, "host.name")
+                .or_else(|| prefix_hostname(&record.prefix))
+                .unwrap_or_else(|| "unknown".to_string()),
+            process: record.product.clone(),
+            message: string_field(&fields, "message")
+                .unwrap_or_else(|| format!("{} {} event {}", record.vendor, record.product, record.event_id)),
+            fields,
+            source_country: None,
+            destination_country: None,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EventParser for LeefParser {
+    async fn parse(&self, event: &mut PipelineEvent) -> Result<ParsedEvent> {
+        let raw_message = event.data["raw_message"].as_str()
+            .ok_or_else(|| PipelineError::parsing("No raw_message field found"))?;
+        
+        let record = cef::parse_leef(raw_message)?;
+        Ok(Self::to_parsed_event(&record, event.timestamp))
+    }
+    
+    fn name(&self) -> &str {
+        "leef"
+    }
+}
+
 #[async_trait::async_trait]
 impl EventParser for WindowsEventParser {
     async fn parse(&self, _event: &mut PipelineEvent) -> Result<ParsedEvent> {
@@ -611,6 +1011,8 @@ impl EventParser for WindowsEventParser {
             process: "windows".to_string(),
             message: "Windows event".to_string(),
             fields: HashMap::new(),
+            source_country: None,
+            destination_country: None,
         })
     }
     
@@ -622,29 +1024,53 @@ impl EventParser for WindowsEventParser {
 // Enricher implementations
 impl Default for GeoIpEnricher {
     fn default() -> Self {
-        Self::new()
+        Self::new(&GeoIpConfig::default())
     }
 }
 
 impl GeoIpEnricher {
-    pub fn new() -> Self {
-        GeoIpEnricher {}
+    pub fn new(config: &GeoIpConfig) -> Self {
+        GeoIpEnricher {
+            resolver: GeoIpResolver::new(config),
+            source_ip_fields: config.source_ip_fields.clone(),
+            destination_ip_fields: config.destination_ip_fields.clone(),
+        }
+    }
+
+    /// First of `fields` on the event or its parsed form holding an address
+    fn address(event: &PipelineEvent, parsed: &ParsedEvent, fields: &[String]) -> Option<IpAddr> {
+        fields.iter().find_map(|field| {
+            let value = event.get_field(field).or_else(|| parsed.fields.get(field).map(Cow::Borrowed))?;
+            value.as_str()?.trim().parse().ok()
+        })
     }
 }
 
+/// Country of a location, unless the databases did not know it
+fn known_country(location: Option<&GeoLocation>) -> Option<String> {
+    location.map(|location| location.country.clone()).filter(|country| country != UNKNOWN_LOCATION)
+}
+
 #[async_trait::async_trait]
 impl EventEnricher for GeoIpEnricher {
-    async fn enrich(&self, _event: &mut PipelineEvent, _parsed: &ParsedEvent) -> Result<EnrichmentData> {
-        // GeoIP enrichment implementation
+    async fn enrich(&self, event: &mut PipelineEvent, parsed: &ParsedEvent) -> Result<EnrichmentData> {
+        let source = Self::address(event, parsed, &self.source_ip_fields)
+            .and_then(|ip| self.resolver.lookup(ip));
+        let destination = Self::address(event, parsed, &self.destination_ip_fields)
+            .and_then(|ip| self.resolver.lookup(ip));
+        
+        // Carried on the event itself so the locations reach storage
+        if let Some(data) = event.data.as_object_mut() {
+            for (key, location) in [("source_geo", &source), ("destination_geo", &destination)] {
+                if let Some(location) = location {
+                    data.insert(key.to_string(), serde_json::to_value(location)?);
+                }
+            }
+        }
+        
         Ok(EnrichmentData {
-            geo_location: Some(GeoLocation {
-                country: "Unknown".to_string(),
-                city: "Unknown".to_string(),
-                latitude: 0.0,
-                longitude: 0.0,
-                asn: None,
-                organization: None,
-            }),
+            geo_location: source,
+            destination_geo_location: destination,
             threat_intel: None,
             asset_info: None,
             user_info: None,
@@ -661,28 +1087,65 @@ impl EventEnricher for GeoIpEnricher {
 
 impl Default for ThreatIntelEnricher {
     fn default() -> Self {
-        Self::new()
+        Self::new(&ThreatIntelConfig::default(), Arc::new(IocStore::new()))
     }
 }
 
 impl ThreatIntelEnricher {
-    pub fn new() -> Self {
-        ThreatIntelEnricher {}
+    pub fn new(config: &ThreatIntelConfig, store: Arc<IocStore>) -> Self {
+        ThreatIntelEnricher {
+            store,
+            fields: config.fields.clone(),
+        }
     }
 }
 
 #[async_trait::async_trait]
 impl EventEnricher for ThreatIntelEnricher {
-    async fn enrich(&self, _event: &mut PipelineEvent, _parsed: &ParsedEvent) -> Result<EnrichmentData> {
+    async fn enrich(&self, event: &mut PipelineEvent, parsed: &ParsedEvent) -> Result<EnrichmentData> {
+        // The most confident match across the configured fields; the first
+        // field wins a tie
+        let matched = self.store.read(|index| {
+            self.fields.iter()
+                .filter_map(|field| {
+                    let value = event.get_field(field).or_else(|| parsed.fields.get(field).map(Cow::Borrowed))?;
+                    let hit = match value.as_ref() {
+                        serde_json::Value::String(text) => index.match_value(text),
+                        serde_json::Value::Array(items) => items.iter()
+                            .filter_map(|item| index.match_value(item.as_str()?))
+                            .min_by_key(|hit| Reverse(hit.confidence)),
+                        _ => None,
+                    }?;
+                    Some((field, hit))
+                })
+                .min_by_key(|(_, hit)| Reverse(hit.confidence))
+        });
+        
+        let threat_intel = matched.map(|(field, hit)| {
+            debug!("Event {} field {} matched indicator {} from {}", event.id, field, hit.indicator, hit.source);
+            if let Some(data) = event.data.as_object_mut() {
+                data.insert("threat_intel".to_string(), serde_json::json!({
+                    "indicator": hit.indicator,
+                    "type": hit.ioc_type.as_str(),
+                    "source": hit.source,
+                    "confidence": hit.confidence,
+                    "field": field,
+                }));
+            }
+            ThreatIntel {
+                is_malicious: true,
+                threat_type: Some(hit.ioc_type.as_str().to_string()),
+                confidence: f64::from(hit.confidence),
+                source: hit.source,
+                last_seen: None,
+                indicator: Some(hit.indicator),
+            }
+        });
+        
         Ok(EnrichmentData {
             geo_location: None,
-            threat_intel: Some(ThreatIntel {
-                is_malicious: false,
-                threat_type: None,
-                confidence: 0.0,
-                source: "internal".to_string(),
-                last_seen: None,
-            }),
+            destination_geo_location: None,
+            threat_intel,
             asset_info: None,
             user_info: None,
         })
@@ -712,6 +1175,7 @@ impl EventEnricher for AssetEnricher {
     async fn enrich(&self, _event: &mut PipelineEvent, _parsed: &ParsedEvent) -> Result<EnrichmentData> {
         Ok(EnrichmentData {
             geo_location: None,
+            destination_geo_location: None,
             threat_intel: None,
             asset_info: None,
             user_info: None,
@@ -742,6 +1206,7 @@ impl EventEnricher for UserEnricher {
     async fn enrich(&self, _event: &mut PipelineEvent, _parsed: &ParsedEvent) -> Result<EnrichmentData> {
         Ok(EnrichmentData {
             geo_location: None,
+            destination_geo_location: None,
             threat_intel: None,
             asset_info: None,
             user_info: None,
@@ -818,4 +1283,30 @@ impl EventNormalizer for ElasticCommonSchemaNormalizer {
     fn name(&self) -> &str {
         "ecs"
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tags_do_not_overwrite_fields() {
+        let mut event = PipelineEvent {
+            id: uuid::Uuid::new_v4(),
+            timestamp: Utc::now(),
+            source: "test".to_string(),
+            data: serde_json::json!({"user": "alice", "tags": "legacy"}),
+            metadata: HashMap::new(),
+            processing_stage: ProcessingStage::Parsed,
+        };
+
+        TransformationManager::apply_tag(&mut event, "user");
+        TransformationManager::apply_tag(&mut event, "security_event");
+        TransformationManager::apply_tag(&mut event, "user");
+
+        assert_eq!(event.data["user"], "alice");
+        assert_eq!(event.data["tags"], serde_json::json!(["legacy", "user", "security_event"]));
+        assert!(event.data.get("security_event").is_none());
+        assert!(FilterExpression::compile("tags in ['security_event']").unwrap().matches(&event, None));
+    }
+}
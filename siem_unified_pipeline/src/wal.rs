@@ -0,0 +1,489 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::config::{FsyncPolicy, WalConfig};
+use crate::error::{Result, PipelineError};
+use crate::pipeline::PipelineEvent;
+
+const EVENT_RECORD: u8 = b'E';
+const ACK_RECORD: u8 = b'A';
+// Record kind, payload length and CRC32 of the payload
+const HEADER_LEN: usize = 9;
+
+enum Record {
+    Event(PipelineEvent),
+    Ack(Uuid),
+}
+
+struct Segment {
+    path: PathBuf,
+    size: u64,
+    // Events in this segment not yet acknowledged
+    pending: usize,
+}
+
+struct WalState {
+    segments: BTreeMap<u64, Segment>,
+    active_id: u64,
+    active: File,
+    locations: HashMap<Uuid, u64>,
+    total_size: u64,
+    unsynced: bool,
+}
+
+/// Segment-file write-ahead log of ingested events.
+///
+/// Events are appended as they enter the event queue and acknowledged once
+/// every destination they were routed to has stored them; whatever is still
+/// unacknowledged when the process starts is handed back for replay. Acks are
+/// records in the same log, so a segment can only be deleted once it is the
+/// oldest and holds no pending events. Compaction copies the stragglers of
+/// the oldest segment forward so one slow event does not pin the log.
+pub struct WriteAheadLog {
+    dir: PathBuf,
+    fsync: FsyncPolicy,
+    segment_bytes: u64,
+    max_bytes: u64,
+    state: Mutex<WalState>,
+    space_freed: Notify,
+}
+
+impl WriteAheadLog {
+    /// Open the log in `config.path`, returning it together with the events
+    /// that were never acknowledged, oldest first.
+    pub fn open(config: &WalConfig) -> Result<(Arc<Self>, Vec<PipelineEvent>)> {
+        let dir = PathBuf::from(&config.path);
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| PipelineError::io(format!("Failed to create WAL directory {}: {}", dir.display(), e)))?;
+
+        let mut segments = BTreeMap::new();
+        let mut events = Vec::new();
+        let mut acked = HashSet::new();
+        for id in list_segments(&dir)? {
+            let path = segment_path(&dir, id);
+            let (records, valid_len) = read_segment(&path)?;
+            let file_len = std::fs::metadata(&path)?.len();
+            if valid_len < file_len {
+                warn!("Truncating {} bytes of incomplete records from {}", file_len - valid_len, path.display());
+                OpenOptions::new().write(true).open(&path)?.set_len(valid_len)?;
+            }
+            for record in records {
+                match record {
+                    Record::Event(event) => events.push((id, event)),
+                    Record::Ack(event_id) => {
+                        acked.insert(event_id);
+                    }
+                }
+            }
+            segments.insert(id, Segment { path, size: valid_len, pending: 0 });
+        }
+
+        let mut locations = HashMap::new();
+        let mut recovered = Vec::new();
+        for (segment_id, event) in events {
+            // A copy left behind by an interrupted compaction is replayed once
+            if acked.contains(&event.id) || locations.contains_key(&event.id) {
+                continue;
+            }
+            locations.insert(event.id, segment_id);
+            if let Some(segment) = segments.get_mut(&segment_id) {
+                segment.pending += 1;
+            }
+            recovered.push(event);
+        }
+
+        let active_id = segments.keys().next_back().map(|id| id + 1).unwrap_or(0);
+        let active_path = segment_path(&dir, active_id);
+        let active = open_segment(&active_path)?;
+        segments.insert(active_id, Segment { path: active_path, size: 0, pending: 0 });
+        let total_size = segments.values().map(|segment| segment.size).sum();
+
+        let wal = Arc::new(Self {
+            dir,
+            fsync: config.fsync.clone(),
+            segment_bytes: config.segment_size_mb.max(1).saturating_mul(1024 * 1024),
+            max_bytes: config.max_size_mb.max(1).saturating_mul(1024 * 1024),
+            state: Mutex::new(WalState { segments, active_id, active, locations, total_size, unsynced: false }),
+            space_freed: Notify::new(),
+        });
+        {
+            let mut state = wal.lock()?;
+            wal.remove_acknowledged_head(&mut state);
+        }
+        info!("Opened write-ahead log in {} with {} unacknowledged events", wal.dir.display(), recovered.len());
+
+        let period = match config.fsync {
+            FsyncPolicy::Interval => Duration::from_millis(config.fsync_interval_ms.max(1)),
+            _ => Duration::from_secs(1),
+        };
+        let weak = Arc::downgrade(&wal);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let Some(wal) = weak.upgrade() else { break };
+                if let Err(e) = wal.sync() {
+                    error!("Failed to sync write-ahead log: {}", e);
+                }
+                if let Err(e) = wal.compact() {
+                    error!("Failed to compact write-ahead log: {}", e);
+                }
+            }
+        });
+
+        Ok((wal, recovered))
+    }
+
+    /// Log an event. Fails when the log has reached its size limit.
+    pub fn append(&self, event: &PipelineEvent) -> Result<()> {
+        let payload = serde_json::to_vec(event)?;
+        let mut state = self.lock()?;
+        if state.total_size + (HEADER_LEN + payload.len()) as u64 > self.max_bytes {
+            return Err(PipelineError::BufferOverflowError("Write-ahead log is full".to_string()));
+        }
+
+        let segment_id = self.write_record(&mut state, EVENT_RECORD, &payload)?;
+        state.locations.insert(event.id, segment_id);
+        if let Some(segment) = state.segments.get_mut(&segment_id) {
+            segment.pending += 1;
+        }
+        if self.fsync == FsyncPolicy::Always {
+            state.active.sync_data()?;
+            state.unsynced = false;
+        }
+        Ok(())
+    }
+
+    /// Mark an event as delivered. Acks are not synced on their own: losing
+    /// one only means the event is replayed again.
+    pub fn ack(&self, event_id: Uuid) {
+        let Ok(mut state) = self.lock() else { return };
+        let Some(segment_id) = state.locations.remove(&event_id) else { return };
+
+        if let Err(e) = self.write_record(&mut state, ACK_RECORD, event_id.as_bytes()) {
+            warn!("Failed to record acknowledgement for event {}: {}", event_id, e);
+        }
+        if let Some(segment) = state.segments.get_mut(&segment_id) {
+            segment.pending = segment.pending.saturating_sub(1);
+        }
+        self.remove_acknowledged_head(&mut state);
+    }
+
+    /// Wait until acknowledgements free some space, or a short while.
+    pub async fn wait_for_space(&self) {
+        let _ = tokio::time::timeout(Duration::from_secs(1), self.space_freed.notified()).await;
+    }
+
+    pub fn pending(&self) -> usize {
+        self.lock().map(|state| state.locations.len()).unwrap_or_default()
+    }
+
+    pub fn size(&self) -> u64 {
+        self.lock().map(|state| state.total_size).unwrap_or_default()
+    }
+
+    pub fn sync(&self) -> Result<()> {
+        if self.fsync == FsyncPolicy::Never {
+            return Ok(());
+        }
+        let mut state = self.lock()?;
+        if state.unsynced {
+            state.active.sync_data()?;
+            state.unsynced = false;
+        }
+        Ok(())
+    }
+
+    /// Once the log is over half its limit, move the pending events of the
+    /// oldest segments into the active one so those segments can be deleted.
+    pub fn compact(&self) -> Result<()> {
+        let mut state = self.lock()?;
+        while state.total_size > self.max_bytes / 2 {
+            let Some((&head_id, head)) = state.segments.first_key_value() else { break };
+            if head_id == state.active_id {
+                break;
+            }
+
+            let (records, _) = read_segment(&head.path)?;
+            let mut moved = 0;
+            for record in records {
+                let Record::Event(event) = record else { continue };
+                if state.locations.get(&event.id) != Some(&head_id) {
+                    continue;
+                }
+                let segment_id = self.write_record(&mut state, EVENT_RECORD, &serde_json::to_vec(&event)?)?;
+                state.locations.insert(event.id, segment_id);
+                if let Some(segment) = state.segments.get_mut(&segment_id) {
+                    segment.pending += 1;
+                }
+                if let Some(segment) = state.segments.get_mut(&head_id) {
+                    segment.pending -= 1;
+                }
+                moved += 1;
+            }
+            state.active.sync_data()?;
+            info!("Compacted write-ahead log segment {} ({} pending events moved)", head_id, moved);
+
+            self.remove_acknowledged_head(&mut state);
+            if state.segments.contains_key(&head_id) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, WalState>> {
+        self.state.lock().map_err(|_| PipelineError::internal("Write-ahead log lock poisoned"))
+    }
+
+    // Returns the segment the record went into, which may since have been rolled
+    fn write_record(&self, state: &mut WalState, kind: u8, payload: &[u8]) -> Result<u64> {
+        let mut record = Vec::with_capacity(HEADER_LEN + payload.len());
+        record.push(kind);
+        record.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        record.extend_from_slice(&crc32fast::hash(payload).to_be_bytes());
+        record.extend_from_slice(payload);
+
+        let active_id = state.active_id;
+        if let Err(e) = state.active.write_all(&record) {
+            // Cut off a partial record so later appends stay readable
+            if let Some(segment) = state.segments.get(&active_id) {
+                let _ = state.active.set_len(segment.size);
+            }
+            return Err(e.into());
+        }
+        state.total_size += record.len() as u64;
+        state.unsynced = true;
+
+        let size = match state.segments.get_mut(&active_id) {
+            Some(segment) => {
+                segment.size += record.len() as u64;
+                segment.size
+            }
+            None => 0,
+        };
+        if size >= self.segment_bytes {
+            self.roll(state)?;
+        }
+        Ok(active_id)
+    }
+
+    fn roll(&self, state: &mut WalState) -> Result<()> {
+        if self.fsync != FsyncPolicy::Never {
+            state.active.sync_data()?;
+        }
+        let active_id = state.active_id + 1;
+        let path = segment_path(&self.dir, active_id);
+        state.active = open_segment(&path)?;
+        state.active_id = active_id;
+        state.segments.insert(active_id, Segment { path, size: 0, pending: 0 });
+        state.unsynced = false;
+        Ok(())
+    }
+
+    fn remove_acknowledged_head(&self, state: &mut WalState) {
+        let mut freed = false;
+        while let Some((&id, segment)) = state.segments.first_key_value() {
+            if id == state.active_id || segment.pending > 0 {
+                break;
+            }
+            if let Err(e) = std::fs::remove_file(&segment.path) {
+                warn!("Failed to remove WAL segment {}: {}", segment.path.display(), e);
+                break;
+            }
+            state.total_size -= segment.size;
+            state.segments.remove(&id);
+            freed = true;
+        }
+        if freed {
+            self.space_freed.notify_waiters();
+        }
+    }
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("segment-{:010}.wal", id))
+}
+
+fn list_segments(dir: &Path) -> Result<Vec<u64>> {
+    let mut ids = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        let id = name.to_str()
+            .and_then(|name| name.strip_prefix("segment-"))
+            .and_then(|name| name.strip_suffix(".wal"))
+            .and_then(|id| id.parse().ok());
+        if let Some(id) = id {
+            ids.push(id);
+        }
+    }
+    ids.sort_unstable();
+    Ok(ids)
+}
+
+fn open_segment(path: &Path) -> Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+        .map_err(|e| PipelineError::io(format!("Failed to open WAL segment {}: {}", path.display(), e)))
+}
+
+// Read records up to the first incomplete or corrupt one, which can only be
+// the tail of a write interrupted by a crash
+fn read_segment(path: &Path) -> Result<(Vec<Record>, u64)> {
+    let file = File::open(path)?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let mut records = Vec::new();
+    let mut valid_len = 0u64;
+
+    loop {
+        let mut header = [0u8; HEADER_LEN];
+        if reader.read_exact(&mut header).is_err() {
+            break;
+        }
+        let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        let crc = u32::from_be_bytes([header[5], header[6], header[7], header[8]]);
+        // A torn header can claim any length; never allocate past the end of the file
+        if len as u64 > file_len.saturating_sub(valid_len + HEADER_LEN as u64) {
+            break;
+        }
+        let mut payload = vec![0u8; len];
+        if reader.read_exact(&mut payload).is_err() || crc32fast::hash(&payload) != crc {
+            break;
+        }
+
+        let record = match header[0] {
+            EVENT_RECORD => serde_json::from_slice(&payload).ok().map(Record::Event),
+            ACK_RECORD => Uuid::from_slice(&payload).ok().map(Record::Ack),
+            _ => None,
+        };
+        let Some(record) = record else { break };
+        records.push(record);
+        valid_len += (HEADER_LEN + len) as u64;
+    }
+
+    Ok((records, valid_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipeline::ProcessingStage;
+
+    fn event(n: u64) -> PipelineEvent {
+        PipelineEvent {
+            id: Uuid::new_v4(),
+            timestamp: chrono::Utc::now(),
+            source: "test".to_string(),
+            data: serde_json::json!({ "n": n, "padding": "x".repeat(200) }),
+            metadata: HashMap::new(),
+            processing_stage: ProcessingStage::Ingested,
+        }
+    }
+
+    fn config(dir: &Path, max_size_mb: u64) -> WalConfig {
+        WalConfig {
+            enabled: true,
+            path: dir.display().to_string(),
+            fsync: FsyncPolicy::Always,
+            fsync_interval_ms: 1000,
+            segment_size_mb: 1,
+            max_size_mb,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replays_unacknowledged_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let events: Vec<_> = (0..5).map(event).collect();
+        {
+            let (wal, recovered) = WriteAheadLog::open(&config(dir.path(), 64)).unwrap();
+            assert!(recovered.is_empty());
+            for event in &events {
+                wal.append(event).unwrap();
+            }
+            wal.ack(events[0].id);
+            wal.ack(events[3].id);
+            assert_eq!(wal.pending(), 3);
+        }
+
+        // A write torn by a crash is dropped on the next open
+        let last = list_segments(dir.path()).unwrap().into_iter().max().unwrap();
+        let mut file = OpenOptions::new().append(true).open(segment_path(dir.path(), last)).unwrap();
+        file.write_all(&[EVENT_RECORD, 0, 0, 1, 0, 1, 2]).unwrap();
+
+        let (wal, recovered) = WriteAheadLog::open(&config(dir.path(), 64)).unwrap();
+        let ids: Vec<_> = recovered.iter().map(|e| e.id).collect();
+        assert_eq!(ids, vec![events[1].id, events[2].id, events[4].id]);
+
+        for id in ids {
+            wal.ack(id);
+        }
+        drop(wal);
+        let (_, recovered) = WriteAheadLog::open(&config(dir.path(), 64)).unwrap();
+        assert!(recovered.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_length_stops_recovery() {
+        let dir = tempfile::tempdir().unwrap();
+        let kept = event(0);
+        {
+            let (wal, _) = WriteAheadLog::open(&config(dir.path(), 64)).unwrap();
+            wal.append(&kept).unwrap();
+        }
+
+        // A header claiming a 4 GiB payload is rejected before anything is allocated
+        let path = segment_path(dir.path(), list_segments(dir.path()).unwrap()[0]);
+        let valid_len = std::fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[EVENT_RECORD, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0, b'{']).unwrap();
+
+        let (records, len) = read_segment(&path).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(len, valid_len);
+
+        let (_, recovered) = WriteAheadLog::open(&config(dir.path(), 64)).unwrap();
+        assert_eq!(recovered.iter().map(|e| e.id).collect::<Vec<_>>(), vec![kept.id]);
+    }
+
+    #[tokio::test]
+    async fn test_size_cap_and_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let (wal, _) = WriteAheadLog::open(&config(dir.path(), 2)).unwrap();
+
+        // Fill past a segment, keeping one early straggler unacknowledged
+        let straggler = event(0);
+        wal.append(&straggler).unwrap();
+        let mut appended = 1;
+        while wal.size() < 1024 * 1024 + 4096 {
+            let event = event(appended);
+            wal.append(&event).unwrap();
+            wal.ack(event.id);
+            appended += 1;
+        }
+        assert!(list_segments(dir.path()).unwrap().len() >= 2);
+
+        let mut full = false;
+        for n in 0..10_000 {
+            if wal.append(&event(n)).is_err() {
+                full = true;
+                break;
+            }
+        }
+        assert!(full);
+
+        wal.compact().unwrap();
+        assert_eq!(list_segments(dir.path()).unwrap().len(), 1);
+
+        drop(wal);
+        let (_, recovered) = WriteAheadLog::open(&config(dir.path(), 2)).unwrap();
+        assert!(recovered.iter().any(|e| e.id == straggler.id));
+    }
+}